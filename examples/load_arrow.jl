@@ -0,0 +1,26 @@
+# Load the Arrow outputs of hcp-rs (built with `--features arrow`).
+#
+#   parameters file:  arrow_output: true
+#   ensemble:         hcp-rs ensemble --arrow membership.arrow data_configs.txt ...
+#
+# usage: julia load_arrow.jl <save directory> <saved_data_name> [membership.arrow]
+using Arrow, DataFrames
+
+dir, name = ARGS[1], ARGS[2]
+trace = DataFrame(Arrow.Table(joinpath(dir, "$(name)_trace.arrow")))
+println(describe(trace[:, filter(c -> eltype(trace[!, c]) <: Real, names(trace))]))
+
+if "configs" in names(trace)
+    # group memberships of every node in the last snapshot; group 0 is the root
+    last_config = trace.configs[end]
+    groups(bits) = [g for g in 0:63 if (bits >> g) & 1 == 1]
+    for (node, bits) in enumerate(last_config)
+        println(node - 1, ": ", groups(bits))
+    end
+end
+
+if length(ARGS) > 2
+    # rows are nodes, columns `group_0`, `group_1`, ... are consensus groups
+    membership = Matrix(DataFrame(Arrow.Table(ARGS[3])))
+    println(size(membership))
+end
@@ -0,0 +1,32 @@
+//! `cargo fuzz run network`: every network reader on the same input, then the model setup on
+//! whatever parses. Inputs declaring huge networks are skipped, they only run out of memory.
+#![no_main]
+
+use hcp_rs::network::NetworkData;
+use hcp_rs::parameters::Parameters;
+use hcp_rs::HierarchicalModel;
+use libfuzzer_sys::fuzz_target;
+use std::sync::Arc;
+
+fuzz_target!(|data: &[u8]| {
+    if data.split(|b| !b.is_ascii_digit()).any(|w| w.len() > 6) {
+        return;
+    }
+    let mut networks = vec![
+        NetworkData::read_edge_list(data),
+        NetworkData::read_mtx(data),
+        NetworkData::read_pajek(data),
+    ];
+    if let Ok(gml) = std::str::from_utf8(data) {
+        networks.push(NetworkData::parse_gml(gml));
+    }
+    let params = Parameters::load(&b"seed: 1\nmax_num_groups: 4\n"[..]).unwrap();
+    for network in networks.into_iter().flatten() {
+        let _ = network.stats();
+        if let Ok(mut hcp) = HierarchicalModel::<f64>::with_network(&params, Arc::new(network)) {
+            for _ in 0..10 {
+                hcp.step();
+            }
+        }
+    }
+});
@@ -0,0 +1,11 @@
+//! `cargo fuzz run parameters`: malformed parameter files must be rejected with an error
+#![no_main]
+
+use hcp_rs::parameters::Parameters;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(p) = Parameters::load(data) {
+        let _ = p.fix_seed();
+    }
+});
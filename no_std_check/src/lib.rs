@@ -0,0 +1,7 @@
+//! `cargo build` here checks that the sampling core (`hcp_rs::core`) builds without std.
+//! It only includes the module's sources, so it needs none of the crate's dependencies.
+#![no_std]
+extern crate alloc;
+
+#[path = "../../src/core/mod.rs"]
+pub mod sampling_core;
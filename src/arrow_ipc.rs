@@ -0,0 +1,117 @@
+//! Arrow IPC (feather v2) tables, readable with `Arrow.Table` in Julia, `arrow::read_feather`
+//! in R and `pyarrow.feather.read_table` in Python.
+//!
+//! Traces (`{name}_trace.arrow`) have one row per snapshot, the column `iteration: uint64` and
+//! a column for each recorded quantity: `time: float64`, `log_like: float64`,
+//! `num_groups: uint64`, `entropy: float64`, `dl: float64`, the list columns
+//! `configs, group_size, edges, pairs: list<uint64>` and `stats, density: list<float64>`,
+//! following the `log_fields` and `save_timestamps` parameters. Matrices have one `float64`
+//! column per matrix column. See `examples/load_arrow.jl`.
+use arrow_array::builder::{Float64Builder, ListBuilder, UInt64Builder};
+use arrow_array::{ArrayRef, Float64Array, RecordBatch, UInt64Array};
+use arrow_ipc::writer::FileWriter;
+use arrow_schema::{Field, Schema};
+use std::io::Write;
+use std::sync::Arc;
+
+/// columns of equal length, written as a single record batch
+#[derive(Default)]
+pub struct Table {
+    fields: Vec<Field>,
+    columns: Vec<ArrayRef>,
+}
+
+impl Table {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn push(&mut self, name: &str, column: ArrayRef) {
+        self.fields
+            .push(Field::new(name, column.data_type().clone(), false));
+        self.columns.push(column);
+    }
+
+    pub fn f64s(&mut self, name: &str, values: impl IntoIterator<Item = f64>) {
+        let column = Float64Array::from_iter_values(values);
+        self.push(name, Arc::new(column));
+    }
+
+    pub fn u64s(&mut self, name: &str, values: impl IntoIterator<Item = u64>) {
+        let column = UInt64Array::from_iter_values(values);
+        self.push(name, Arc::new(column));
+    }
+
+    pub fn u64_lists<'a>(&mut self, name: &str, rows: impl IntoIterator<Item = &'a [u64]>) {
+        let mut builder = ListBuilder::new(UInt64Builder::new());
+        for row in rows {
+            builder.values().append_slice(row);
+            builder.append(true);
+        }
+        self.push(name, Arc::new(builder.finish()));
+    }
+
+    pub fn f64_lists<'a>(&mut self, name: &str, rows: impl IntoIterator<Item = &'a [f64]>) {
+        let mut builder = ListBuilder::new(Float64Builder::new());
+        for row in rows {
+            builder.values().append_slice(row);
+            builder.append(true);
+        }
+        self.push(name, Arc::new(builder.finish()));
+    }
+
+    /// a table of the rows of the row-major matrix `data`, with columns `names`
+    pub fn from_matrix(names: &[String], data: &[f64]) -> Self {
+        let mut table = Self::new();
+        for (j, name) in names.iter().enumerate() {
+            table.f64s(name, data.iter().skip(j).step_by(names.len()).copied());
+        }
+        table
+    }
+
+    pub fn write<W: Write>(self, w: W) -> Result<(), String> {
+        let schema = Arc::new(Schema::new(self.fields));
+        let batch =
+            RecordBatch::try_new(schema.clone(), self.columns).map_err(|e| e.to_string())?;
+        let mut writer = FileWriter::try_new(w, &schema).map_err(|e| e.to_string())?;
+        writer.write(&batch).map_err(|e| e.to_string())?;
+        writer.finish().map_err(|e| e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::Float64Type;
+    use arrow_ipc::reader::FileReader;
+    use std::io::Cursor;
+
+    #[test]
+    fn roundtrip() {
+        let mut table = Table::new();
+        table.u64s("iteration", [0, 1500]);
+        table.u64_lists("configs", [&[1, 3][..], &[1, 1][..]]);
+        let mut buf = Vec::new();
+        table.write(&mut buf).unwrap();
+
+        let batch = FileReader::try_new(Cursor::new(buf), None)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        assert_eq!(batch.num_rows(), 2);
+        assert_eq!(batch.schema().field(1).name(), "configs");
+
+        let matrix = Table::from_matrix(&[String::from("a"), String::from("b")], &[1., 2., 3., 4.]);
+        let mut buf = Vec::new();
+        matrix.write(&mut buf).unwrap();
+        let batch = FileReader::try_new(Cursor::new(buf), None)
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap();
+        let b = batch.column(1).as_primitive::<Float64Type>();
+        assert_eq!(b.values().to_vec(), [2., 4.]);
+    }
+}
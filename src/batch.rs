@@ -0,0 +1,394 @@
+//! batches of independent node moves (experimental, `batch_moves`): moves of distinct,
+//! non-adjacent nodes change the highest common groups of disjoint sets of edges, and of
+//! disjoint sets of pairs apart from the pairs within the batch. Their count deltas are
+//! evaluated against the final configuration, on a pool of worker threads kept for the whole
+//! run, and made exact by correcting only the pairs within the batch. The batch is then
+//! accepted or rejected as a whole by the Metropolis rule on its joint log-likelihood
+//! difference. On sparse graphs most draws are independent, so the O(n) delta of every move
+//! runs in parallel.
+//!
+//! Unlike `step`, which keeps the acceptance rule of the reference implementation, a batch
+//! is accepted with a Hastings correction: the probability of drawing each move, a node out of
+//! the members or non-members of its group, is compared with that of drawing the reverse move
+//! in the state after it.
+use crate::core::hcg;
+use crate::likelihood::Likelihood;
+use crate::multi_group_model::{Groups, Move, Node};
+use crate::network::NetworkData;
+#[cfg(feature = "profiling")]
+use crate::profiling;
+use crate::rng::SamplerRng;
+use crate::{Float, HierarchicalModel, Proposal};
+use std::mem;
+use std::sync::{mpsc, Arc};
+use std::thread;
+
+/// pair and edge deltas of a node move, see `hcg::node_move_deltas`
+type Deltas = ([isize; 64], [isize; 64]);
+
+/// moves of one batch for a worker to evaluate against the node groups after the batch
+struct Job {
+    groups: Arc<[Groups]>,
+    mask: Groups,
+    share: Vec<(Node, Groups)>,
+    done: mpsc::Sender<Vec<Deltas>>,
+}
+
+/// worker threads evaluating the moves of batches, shared between clones of the model.
+/// The workers exit once the last clone is dropped.
+pub(crate) struct BatchPool {
+    workers: Vec<mpsc::Sender<Job>>,
+}
+
+impl BatchPool {
+    fn new(network: &Arc<NetworkData>, threads: usize) -> Self {
+        let workers = (0..threads)
+            .map(|_| {
+                let (jobs, queue) = mpsc::channel::<Job>();
+                let network = Arc::clone(network);
+                thread::spawn(move || {
+                    for job in queue {
+                        let deltas = job
+                            .share
+                            .iter()
+                            .map(|&(u, old)| node_deltas(&network, &job.groups, job.mask, u, old))
+                            .collect();
+                        // the batch is gone if its thread panicked
+                        let _ = job.done.send(deltas);
+                    }
+                });
+                jobs
+            })
+            .collect();
+        Self { workers }
+    }
+
+    /// deltas of the moved `nodes` in the configuration `groups`, in order
+    fn deltas(&self, groups: Arc<[Groups]>, mask: Groups, nodes: &[(Node, Groups)]) -> Vec<Deltas> {
+        let results: Vec<_> = nodes
+            .chunks(nodes.len().div_ceil(self.workers.len()))
+            .zip(&self.workers)
+            .map(|(share, worker)| {
+                let (done, result) = mpsc::channel();
+                let job = Job {
+                    groups: Arc::clone(&groups),
+                    mask,
+                    share: share.to_vec(),
+                    done,
+                };
+                worker.send(job).expect("batch worker exited");
+                result
+            })
+            .collect();
+        results
+            .into_iter()
+            .flat_map(|r| r.recv().expect("batch worker panicked"))
+            .collect()
+    }
+}
+
+/// `hcg::node_move_deltas` of `node`, now in `groups[node]`, against a copy of the groups
+fn node_deltas(
+    network: &NetworkData,
+    groups: &[Groups],
+    mask: Groups,
+    node: Node,
+    old: Groups,
+) -> Deltas {
+    let new = groups[node as usize];
+    let (mut pairs, mut edges) = ([0; 64], [0; 64]);
+    for (v, &g) in groups.iter().enumerate() {
+        if v != node as usize {
+            pairs[highest_common(old, g, mask)] -= 1;
+            pairs[highest_common(new, g, mask)] += 1;
+        }
+    }
+    for &v in network.neighbors(node) {
+        let g = groups[v as usize];
+        edges[highest_common(old, g, mask)] -= 1;
+        edges[highest_common(new, g, mask)] += 1;
+    }
+    (pairs, edges)
+}
+
+/// node and groups before the move, for node moves
+fn node_move(m: Move) -> Option<(Node, Groups)> {
+    match m {
+        Move::AddNodeToGroup {
+            node, old_state, ..
+        }
+        | Move::RemoveNodeFromGroup {
+            node, old_state, ..
+        } => Some((node as Node, old_state)),
+        Move::AddGroup { .. } | Move::RemoveGroup { .. } => None,
+    }
+}
+
+/// highest common group of nodes in the groups `a` and `b`
+fn highest_common(a: Groups, b: Groups, mask: Groups) -> usize {
+    63 - (a & b & mask).leading_zeros() as usize
+}
+
+impl<F: Float, L: Likelihood, R: SamplerRng> HierarchicalModel<F, L, R> {
+    /// perform one Monte Carlo step that proposes up to `size` node moves and accepts or
+    /// rejects them together. Drawn moves of a node already in the batch, or adjacent to one,
+    /// are dropped; group moves are proposed alone, as in `step`. The deltas are evaluated on
+    /// up to `threads` threads. Returns the proposals in order, each with the joint decision
+    /// and its change of the log-likelihood after the moves before it, see `replay_batch`.
+    /// Rejected batches leave the model as it was. Pruning empty groups within a batch is not
+    /// supported: the empty group policy must be `OnSelection` or `Keep`.
+    pub fn batch_step(&mut self, size: usize, threads: usize) -> Vec<Proposal> {
+        self.pruned.clear();
+        let Some(first) = timed!(Proposal, self.uniform_groupsize()) else {
+            return Vec::new();
+        };
+        if size <= 1 || node_move(first).is_none() {
+            return vec![self.metropolis(first)];
+        }
+        let mut moves = vec![first];
+        let mut nodes = vec![node_move(first).unwrap()];
+        let mut log_hastings = self.log_proposal_ratio(first);
+        for _ in 1..size {
+            let Some(m) = timed!(Proposal, self.propose_node_move(false)) else {
+                continue;
+            };
+            match node_move(m) {
+                Some((u, old)) if nodes.iter().all(|&(v, _)| self.independent(u, v)) => {
+                    log_hastings += self.log_proposal_ratio(m);
+                    moves.push(m);
+                    nodes.push((u, old));
+                }
+                _ => self.model.undo_move(m),
+            }
+        }
+
+        let mut deltas = timed!(UpdateHcgProps, self.batch_deltas(&nodes, threads));
+        let num_groups = self.model.num_groups();
+        let mask = Groups::MAX >> (64 - num_groups);
+        // each delta counts the pairs within the batch with the other nodes moved already;
+        // count those after it as not moved yet
+        for (i, &(u, old_u)) in nodes.iter().enumerate() {
+            let new_u = self.model.groups_of(u as usize);
+            for &(v, old_v) in &nodes[i + 1..] {
+                let new_v = self.model.groups_of(v as usize);
+                let pairs = &mut deltas[i].0;
+                pairs[highest_common(old_u, old_v, mask)] -= 1;
+                pairs[highest_common(new_u, old_v, mask)] += 1;
+                pairs[highest_common(old_u, new_v, mask)] += 1;
+                pairs[highest_common(new_u, new_v, mask)] -= 1;
+            }
+        }
+
+        let (mut hcg_pairs, mut hcg_edges) = (self.hcg_pairs.clone(), self.hcg_edges.clone());
+        let mut log_likes: Vec<F> = Vec::with_capacity(moves.len());
+        for (pairs, edges) in &deltas {
+            for g in 0..num_groups {
+                hcg_pairs[g] = hcg_pairs[g].wrapping_add_signed(pairs[g]);
+                hcg_edges[g] = hcg_edges[g].wrapping_add_signed(edges[g]);
+            }
+            log_likes.push(timed!(
+                CalcLoglike,
+                self.likelihood.log_like(&hcg_edges, &hcg_pairs)
+            ));
+        }
+        let new_loglike = *log_likes.last().unwrap();
+        let alpha =
+            f64::exp(self.beta * (new_loglike - self.log_like).to_f64() + log_hastings).min(1.0);
+        let old_pairs = mem::replace(&mut self.hcg_pairs, hcg_pairs);
+        let old_edges = mem::replace(&mut self.hcg_edges, hcg_edges);
+        let accepted = timed!(Rng, self.rng.bernoulli(alpha)) && self.keeps_assortative(&moves);
+
+        let mut previous = self.log_like;
+        let proposals = moves
+            .iter()
+            .zip(log_likes)
+            .map(|(&m, log_like)| {
+                let delta_log_like = (log_like - previous).to_f64();
+                previous = log_like;
+                Proposal {
+                    m,
+                    delta_log_like,
+                    accepted,
+                }
+            })
+            .collect();
+        if accepted {
+            self.log_like = new_loglike;
+        } else {
+            self.hcg_pairs = old_pairs;
+            self.hcg_edges = old_edges;
+            for &m in moves.iter().rev() {
+                self.model.undo_move(m);
+            }
+        }
+        proposals
+    }
+
+    /// log of the probability of proposing the reverse of the applied node move `m` over that
+    /// of proposing `m`. Both pick the group alike, then a node out of its members to remove
+    /// or out of its non-members to add.
+    fn log_proposal_ratio(&self, m: Move) -> f64 {
+        let n = self.model.num_nodes() as f64;
+        match m {
+            Move::AddNodeToGroup { group, .. } => {
+                let size = self.model.group_size(group) as f64;
+                ((n - size + 1.0) / size).ln()
+            }
+            Move::RemoveNodeFromGroup { group, .. } => {
+                let size = self.model.group_size(group) as f64;
+                ((size + 1.0) / (n - size)).ln()
+            }
+            Move::AddGroup { .. } | Move::RemoveGroup { .. } => 0.0,
+        }
+    }
+
+    /// whether moves of `u` and `v` change the highest common groups of disjoint edge sets
+    fn independent(&self, u: Node, v: Node) -> bool {
+        u != v && !self.network.neighbors(u).contains(&v)
+    }
+
+    /// re-apply the recorded proposals of one `batch_step` with their joint decision. The
+    /// returned proposals carry the freshly computed log-likelihood differences. Fails
+    /// without changing the model if a move does not fit, see `replay_step`.
+    pub fn replay_batch(&mut self, batch: &[Proposal]) -> Result<Vec<Proposal>, String> {
+        let accepted = batch.iter().all(|p| p.accepted);
+        let (pairs, edges, log_like) = (
+            self.hcg_pairs.clone(),
+            self.hcg_edges.clone(),
+            self.log_like,
+        );
+        let (parents, violations) = (self.parents.clone(), self.violations);
+        let mut replayed = Vec::with_capacity(batch.len());
+        let result = batch.iter().try_for_each(|p| {
+            replayed.push(Proposal {
+                accepted,
+                ..self.replay_step(p.m, true)?
+            });
+            Ok::<_, String>(())
+        });
+        if !accepted || result.is_err() {
+            for p in replayed.iter().rev() {
+                self.model.undo_move(p.m);
+            }
+            (self.hcg_pairs, self.hcg_edges, self.log_like) = (pairs, edges, log_like);
+            (self.parents, self.violations) = (parents, violations);
+        }
+        result.map(|()| replayed)
+    }
+
+    /// pair and edge deltas of the moved `nodes` in the current configuration, each against
+    /// its groups before the move
+    fn batch_deltas(&mut self, nodes: &[(Node, Groups)], threads: usize) -> Vec<Deltas> {
+        if threads <= 1 {
+            let (network, model) = (&*self.network, &self.model);
+            return nodes
+                .iter()
+                .map(|&(u, old)| hcg::node_move_deltas(network, model, u, old))
+                .collect();
+        }
+        if self
+            .batch_pool
+            .as_ref()
+            .is_none_or(|pool| pool.workers.len() != threads)
+        {
+            self.batch_pool = Some(Arc::new(BatchPool::new(&self.network, threads)));
+        }
+        let mask = Groups::MAX >> (64 - self.model.num_groups());
+        // copying the groups costs less than evaluating a single move
+        let groups: Arc<[Groups]> = self.model.groups().into();
+        self.batch_pool
+            .as_ref()
+            .unwrap()
+            .deltas(groups, mask, nodes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::multi_group_model::Move;
+    use crate::tests::_example_model;
+
+    #[test]
+    fn batches() {
+        let mut hcp = _example_model(b"seed: 4\nmax_num_groups: 6\ninitial_num_groups: 4\n");
+        let initial = hcp.clone();
+        let mut threaded = hcp.clone();
+        let mut batches = Vec::new();
+        let mut batched = 0;
+        for _ in 0..2000 {
+            let batch = hcp.batch_step(4, 1);
+            assert_eq!(threaded.batch_step(4, 3), batch);
+            assert!(batch.iter().all(|p| p.accepted == batch[0].accepted));
+            if batch.len() > 1 {
+                batched += 1;
+            }
+            batches.push(batch);
+        }
+        assert!(batched > 100);
+        assert!(batches.iter().any(|b| b.len() > 1 && !b[0].accepted));
+        assert_eq!(hcp.model().groups(), threaded.model().groups());
+        assert!(hcp.clone().recompute().is_consistent());
+
+        // every batch, accepted or not, replays from the trace
+        let mut replayed = initial;
+        for batch in &batches {
+            let r = match batch.len() {
+                1 => vec![replayed.replay_step(batch[0].m, batch[0].accepted).unwrap()],
+                _ => replayed.replay_batch(batch).unwrap(),
+            };
+            let r: Vec<_> = r
+                .iter()
+                .map(|p| (p.m, p.delta_log_like, p.accepted))
+                .collect();
+            let b: Vec<_> = batch
+                .iter()
+                .map(|p| (p.m, p.delta_log_like, p.accepted))
+                .collect();
+            assert_eq!(r, b);
+        }
+        assert_eq!(replayed.model().groups(), hcp.model().groups());
+        assert_eq!(replayed.log_like(), hcp.log_like());
+    }
+
+    #[test]
+    fn hastings() {
+        let mut hcp = _example_model(b"initial_num_groups: 3\n");
+        let n = hcp.model.num_nodes();
+        let size = hcp.model.group_size(1usize);
+        // a move and its reverse cancel
+        let add = hcp.model.add_node_to_group_by_idx(1, 0);
+        let forward = hcp.log_proposal_ratio(add);
+        assert!((forward - ((n - size) as f64 / (size + 1) as f64).ln()).abs() < 1e-12);
+        let Move::AddNodeToGroup { node, .. } = add else {
+            unreachable!()
+        };
+        let idx = hcp
+            .model
+            .members(1)
+            .iter()
+            .position(|&u| u as usize == node)
+            .unwrap();
+        let remove = hcp.model.remove_node_from_group_by_idx(1, idx);
+        assert!((forward + hcp.log_proposal_ratio(remove)).abs() < 1e-12);
+        assert_eq!(hcp.model.group_size(1usize), size);
+    }
+
+    #[test]
+    fn invalid_replay() {
+        let mut hcp = _example_model(b"seed: 4\ninitial_num_groups: 4\n");
+        let initial = hcp.clone();
+        let bad = Move::RemoveGroup { group: 7 };
+        assert!(hcp.replay_step(bad, true).is_err());
+        // the moves before the one that does not fit are undone
+        let first = hcp.model.clone().add_node_to_group_by_idx(1, 0);
+        let batch = [first, bad].map(|m| crate::Proposal {
+            m,
+            delta_log_like: 0.0,
+            accepted: true,
+        });
+        assert!(hcp.replay_batch(&batch).is_err());
+        assert_eq!(hcp.model.groups(), initial.model.groups());
+        assert_eq!(hcp.log_like(), initial.log_like());
+        assert!(hcp.recompute().is_consistent());
+    }
+}
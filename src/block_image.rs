@@ -0,0 +1,197 @@
+//! the adjacency matrix as a grayscale heatmap, with the nodes sorted by the hierarchy so that
+//! nested groups are nested diagonal blocks, outlined in gray. Written as binary PGM or as PNG
+//! (uncompressed, so no dependency is needed).
+use crate::multi_group_model::{MultiGroupModel, Node};
+use crate::network::NetworkData;
+use crate::npy::crc32;
+use std::io::{self, Write};
+
+const BOUNDARY: u8 = 160;
+
+/// 8-bit grayscale image, row major
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+    pub side: usize,
+    pub pixels: Vec<u8>,
+}
+
+/// nodes in the order of the hierarchy of `MultiGroupModel::parent_groups`: the members of
+/// every group are contiguous if the groups are nested, members of a group precede those of
+/// its child groups, and groups are visited depth first
+pub fn hierarchy_order(model: &MultiGroupModel) -> Vec<Node> {
+    let parents = model.parent_groups();
+    // rank of every group in a depth-first traversal from the root
+    let mut rank = vec![0; parents.len()];
+    let mut stack = vec![0];
+    let mut next = 0;
+    while let Some(g) = stack.pop() {
+        rank[g] = next;
+        next += 1;
+        stack.extend((0..parents.len()).rev().filter(|&c| parents[c] == Some(g)));
+    }
+    let key = |u: &Node| {
+        let groups = model.groups_of(*u as usize);
+        let mut ranks: Vec<usize> = (0..parents.len())
+            .filter(|&g| (groups >> g) & 1 != 0)
+            .map(|g| rank[g])
+            .collect();
+        ranks.sort_unstable();
+        ranks
+    };
+    let mut order: Vec<Node> = (0..model.groups().len() as Node).collect();
+    order.sort_by_cached_key(key);
+    order
+}
+
+/// render the adjacency matrix of `network` in `hierarchy_order` on at most `max_side` pixels
+/// square. Each pixel is a square of nodes, black if all their pairs are edges, white if none;
+/// small networks are scaled up instead.
+pub fn render(network: &NetworkData, model: &MultiGroupModel, max_side: usize) -> Image {
+    let n = network.node_count();
+    let max_side = max_side.max(1);
+    let order = hierarchy_order(model);
+    let mut position = vec![0; n];
+    for (i, &u) in order.iter().enumerate() {
+        position[u as usize] = i;
+    }
+    let nodes_per_cell = n.div_ceil(max_side).max(1);
+    let cells = n.div_ceil(nodes_per_cell);
+    let scale = (max_side / cells.max(1)).max(1);
+    let side = cells * scale;
+
+    let mut edges = vec![0usize; cells * cells];
+    for (u, v) in network.edges() {
+        let (a, b) = (
+            position[u as usize] / nodes_per_cell,
+            position[v as usize] / nodes_per_cell,
+        );
+        edges[a * cells + b] += 1;
+        edges[b * cells + a] += 1;
+    }
+    let mut pixels = vec![0; side * side];
+    for (i, row) in pixels.chunks_mut(side).enumerate() {
+        for (j, p) in row.iter_mut().enumerate() {
+            let density = edges[(i / scale) * cells + j / scale] as f64
+                / (nodes_per_cell * nodes_per_cell) as f64;
+            *p = (255.0 * (1.0 - density.min(1.0))).round() as u8;
+        }
+    }
+
+    // outline the span of every non-root group on the diagonal
+    for g in 1..model.num_groups() {
+        let span = model.members(g).iter().map(|&u| position[u as usize]);
+        let (Some(first), Some(last)) = (span.clone().min(), span.max()) else {
+            continue;
+        };
+        let start = first / nodes_per_cell * scale;
+        let end = (last / nodes_per_cell + 1) * scale - 1;
+        for k in start..=end {
+            for (i, j) in [(start, k), (end, k), (k, start), (k, end)] {
+                pixels[i * side + j] = BOUNDARY;
+            }
+        }
+    }
+    Image { side, pixels }
+}
+
+impl Image {
+    /// binary PGM (`P5`)
+    pub fn write_pgm<W: Write>(&self, mut w: W) -> io::Result<()> {
+        write!(w, "P5\n{} {}\n255\n", self.side, self.side)?;
+        w.write_all(&self.pixels)?;
+        w.flush()
+    }
+
+    /// grayscale PNG with stored (uncompressed) deflate blocks. Fails for an empty image,
+    /// which PNG cannot represent.
+    pub fn write_png<W: Write>(&self, mut w: W) -> io::Result<()> {
+        fn chunk<W: Write>(w: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+            w.write_all(&(data.len() as u32).to_be_bytes())?;
+            let mut body = kind.to_vec();
+            body.extend_from_slice(data);
+            w.write_all(&body)?;
+            w.write_all(&crc32(&body).to_be_bytes())
+        }
+
+        if self.side == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "cannot write an empty image as PNG",
+            ));
+        }
+        let side = (self.side as u32).to_be_bytes();
+        // width, height, bit depth 8, grayscale, deflate, no filter, no interlace
+        let header = [&side[..], &side[..], &[8, 0, 0, 0, 0]].concat();
+        // every scanline starts with filter type 0
+        let raw: Vec<u8> = self
+            .pixels
+            .chunks(self.side)
+            .flat_map(|row| std::iter::once(0).chain(row.iter().copied()))
+            .collect();
+        let mut zlib = vec![0x78, 0x01];
+        let blocks: Vec<&[u8]> = raw.chunks(u16::MAX as usize).collect();
+        for (i, block) in blocks.iter().enumerate() {
+            zlib.push((i + 1 == blocks.len()) as u8);
+            let len = block.len() as u16;
+            zlib.extend(len.to_le_bytes());
+            zlib.extend((!len).to_le_bytes());
+            zlib.extend_from_slice(block);
+        }
+        let (mut a, mut b) = (1u32, 0u32);
+        for &byte in &raw {
+            a = (a + byte as u32) % 65521;
+            b = (b + a) % 65521;
+        }
+        zlib.extend(((b << 16) | a).to_be_bytes());
+
+        w.write_all(b"\x89PNG\r\n\x1a\n")?;
+        chunk(&mut w, b"IHDR", &header)?;
+        chunk(&mut w, b"IDAT", &zlib)?;
+        chunk(&mut w, b"IEND", &[])?;
+        w.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blocks() {
+        // group 1 = {1, 3} with child group 2 = {3}; edges inside group 1 and one to node 0
+        let model = MultiGroupModel::with_groups(vec![1, 3, 1, 7], 3, 64);
+        let network = NetworkData::from_edges(4, [(1, 3), (0, 2)]);
+        assert_eq!(hierarchy_order(&model), [0, 2, 1, 3]);
+
+        let image = render(&network, &model, 4);
+        assert_eq!(image.side, 4);
+        #[rustfmt::skip]
+        assert_eq!(image.pixels, [
+            255, 0, 255, 255,
+            0, 255, 255, 255,
+            255, 255, BOUNDARY, BOUNDARY,
+            255, 255, BOUNDARY, BOUNDARY,
+        ]);
+        // two nodes per pixel: half of the matrix entries of nodes 0, 2 are edges
+        let image = render(&network, &model, 2);
+        assert_eq!((image.side, image.pixels[0]), (2, 128));
+        assert_eq!(render(&network, &model, 9).side, 8);
+
+        let mut pgm = Vec::new();
+        image.write_pgm(&mut pgm).unwrap();
+        assert_eq!(pgm, b"P5\n2 2\n255\n\x80\xff\xff\xa0");
+        let mut png = Vec::new();
+        image.write_png(&mut png).unwrap();
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR"));
+        // the constant IEND chunk
+        assert!(png.ends_with(b"\x00\x00\x00\x00IEND\xae\x42\x60\x82"));
+
+        let empty = Image {
+            side: 0,
+            pixels: Vec::new(),
+        };
+        let mut png = Vec::new();
+        assert!(empty.write_png(&mut png).is_err());
+        assert!(png.is_empty());
+    }
+}
@@ -0,0 +1,182 @@
+//! Checkpoints for resuming a run.
+//!
+//! A checkpoint is binary, little endian: a header of `b"HCPC"` and the `u32` format version,
+//! followed by the sections of
+//! each part of the run state, written by its owner with an `Encoder` and read back in the
+//! same order with a `Decoder`. Slices are stored as their `u64` length followed by the
+//! elements.
+//!
+//! The random number generator state is not stored. Instead the generator is reseeded with
+//! `seeds::sub_seed(seed, Stream::Checkpoint, iteration)` at every checkpoint iteration, in
+//! uninterrupted and resumed runs alike, so both draw the same numbers from there on.
+use std::io::{self, Read, Write};
+
+const CHECKPOINT_MAGIC: &[u8; 4] = b"HCPC";
+/// bumped whenever a section changes layout, e.g. a new log field
+const CHECKPOINT_VERSION: u32 = 4;
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("invalid checkpoint: {}", msg),
+    )
+}
+
+pub struct Encoder<W: Write> {
+    w: W,
+}
+
+impl<W: Write> Encoder<W> {
+    /// write the header
+    pub fn new(mut w: W) -> io::Result<Self> {
+        w.write_all(CHECKPOINT_MAGIC)?;
+        w.write_all(&CHECKPOINT_VERSION.to_le_bytes())?;
+        Ok(Self { w })
+    }
+
+    pub fn u64(&mut self, x: u64) -> io::Result<()> {
+        self.w.write_all(&x.to_le_bytes())
+    }
+
+    pub fn usize(&mut self, x: usize) -> io::Result<()> {
+        self.u64(x as u64)
+    }
+
+    pub fn f64(&mut self, x: f64) -> io::Result<()> {
+        self.u64(x.to_bits())
+    }
+
+    pub fn u32s(&mut self, xs: &[u32]) -> io::Result<()> {
+        self.usize(xs.len())?;
+        xs.iter()
+            .try_for_each(|x| self.w.write_all(&x.to_le_bytes()))
+    }
+
+    pub fn u64s(&mut self, xs: &[u64]) -> io::Result<()> {
+        self.usize(xs.len())?;
+        xs.iter().try_for_each(|&x| self.u64(x))
+    }
+
+    pub fn usizes(&mut self, xs: &[usize]) -> io::Result<()> {
+        self.usize(xs.len())?;
+        xs.iter().try_for_each(|&x| self.usize(x))
+    }
+
+    pub fn f64s(&mut self, xs: &[f64]) -> io::Result<()> {
+        self.usize(xs.len())?;
+        xs.iter().try_for_each(|&x| self.f64(x))
+    }
+
+    pub fn into_inner(self) -> W {
+        self.w
+    }
+}
+
+pub struct Decoder<R: Read> {
+    r: R,
+}
+
+impl<R: Read> Decoder<R> {
+    /// read and check the header
+    pub fn new(mut r: R) -> io::Result<Self> {
+        let mut magic = [0; 4];
+        r.read_exact(&mut magic)?;
+        if &magic != CHECKPOINT_MAGIC {
+            return Err(invalid("not a checkpoint"));
+        }
+        let mut version = [0; 4];
+        r.read_exact(&mut version)?;
+        let version = u32::from_le_bytes(version);
+        if version != CHECKPOINT_VERSION {
+            return Err(invalid(&format!(
+                "unsupported checkpoint version {}, expected {}",
+                version, CHECKPOINT_VERSION
+            )));
+        }
+        Ok(Self { r })
+    }
+
+    pub fn u64(&mut self) -> io::Result<u64> {
+        let mut b = [0; 8];
+        self.r.read_exact(&mut b)?;
+        Ok(u64::from_le_bytes(b))
+    }
+
+    pub fn usize(&mut self) -> io::Result<usize> {
+        usize::try_from(self.u64()?).map_err(|_| invalid("length out of range"))
+    }
+
+    pub fn f64(&mut self) -> io::Result<f64> {
+        self.u64().map(f64::from_bits)
+    }
+
+    pub fn u32s(&mut self) -> io::Result<Vec<u32>> {
+        (0..self.usize()?)
+            .map(|_| {
+                let mut b = [0; 4];
+                self.r.read_exact(&mut b)?;
+                Ok(u32::from_le_bytes(b))
+            })
+            .collect()
+    }
+
+    pub fn u64s(&mut self) -> io::Result<Vec<u64>> {
+        (0..self.usize()?).map(|_| self.u64()).collect()
+    }
+
+    pub fn usizes(&mut self) -> io::Result<Vec<usize>> {
+        (0..self.usize()?).map(|_| self.usize()).collect()
+    }
+
+    pub fn f64s(&mut self) -> io::Result<Vec<f64>> {
+        (0..self.usize()?).map(|_| self.f64()).collect()
+    }
+
+    /// fail unless `actual == expected`
+    pub fn expect(&self, what: &str, actual: usize, expected: usize) -> io::Result<()> {
+        if actual == expected {
+            Ok(())
+        } else {
+            Err(invalid(&format!(
+                "{} is {}, expected {}",
+                what, actual, expected
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let mut e = Encoder::new(Vec::new()).unwrap();
+        e.u64(7).unwrap();
+        e.f64(-0.5).unwrap();
+        e.u32s(&[1, 2]).unwrap();
+        e.usizes(&[]).unwrap();
+        e.f64s(&[f64::NAN]).unwrap();
+        let buf = e.into_inner();
+        let mut d = Decoder::new(&buf[..]).unwrap();
+        assert_eq!(d.u64().unwrap(), 7);
+        assert_eq!(d.f64().unwrap(), -0.5);
+        assert_eq!(d.u32s().unwrap(), [1, 2]);
+        assert_eq!(d.usizes().unwrap(), []);
+        assert!(d.f64s().unwrap()[0].is_nan());
+        assert!(d.u64().is_err());
+        assert!(Decoder::new(&b"HCPT"[..]).is_err());
+    }
+
+    #[test]
+    fn version() {
+        let buf = Encoder::new(Vec::new()).unwrap().into_inner();
+        assert_eq!(buf.len(), 8);
+        assert!(Decoder::new(&buf[..]).is_ok());
+        let mut old = buf.clone();
+        old[4..].copy_from_slice(&1u32.to_le_bytes());
+        let err = Decoder::new(&old[..]).err().unwrap();
+        assert!(err.to_string().contains("unsupported checkpoint version 1"));
+        assert!(Decoder::new(&buf[..4]).is_err());
+    }
+}
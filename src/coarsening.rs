@@ -0,0 +1,181 @@
+//! coarsening of huge networks before sampling: nodes whose neighbor sets overlap by at least
+//! `coarsen_overlap` (Jaccard index; 1: structurally equivalent nodes only) are merged into
+//! supernodes. The hierarchy is fitted to the much smaller network of supernodes and projected
+//! back, every node joining the groups of its supernode, to initialize the fit of the network
+//! itself. This is a heuristic initializer: the coarse fit counts every supernode as a single
+//! node, not as the pairs and edges of its nodes, so it does not approximate the fit of the
+//! network and is only a starting point.
+use crate::multi_group_model::{Groups, Node};
+use crate::network::NetworkData;
+use crate::parameters::Parameters;
+use crate::seeds::{SeedStream, Stream};
+use crate::HierarchicalModel;
+use std::sync::Arc;
+
+/// supernode of every node
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Coarsening {
+    pub supernode: Vec<Node>,
+    /// number of nodes of every supernode
+    pub weights: Vec<usize>,
+}
+
+impl Coarsening {
+    /// merge every node, in order of id, with all later unmerged nodes overlapping it by at
+    /// least `min_overlap`. Nodes without edges all form one supernode.
+    /// Takes time proportional to the sum of the squared degrees.
+    pub fn new(network: &NetworkData, min_overlap: f64) -> Self {
+        let n = network.node_count();
+        let mut supernode = vec![Node::MAX; n];
+        let mut weights = Vec::new();
+        let mut isolated = None;
+        let mut shared = vec![0usize; n];
+        let mut candidates = Vec::new();
+        for u in 0..n as Node {
+            if supernode[u as usize] != Node::MAX {
+                continue;
+            }
+            if network.degree(u) == 0 {
+                let s = *isolated.get_or_insert_with(|| {
+                    weights.push(0);
+                    weights.len() - 1
+                });
+                supernode[u as usize] = s as Node;
+                weights[s] += 1;
+                continue;
+            }
+            let s = weights.len();
+            supernode[u as usize] = s as Node;
+            weights.push(1);
+            for &w in network.neighbors(u) {
+                for &v in network.neighbors(w) {
+                    if v > u && supernode[v as usize] == Node::MAX {
+                        if shared[v as usize] == 0 {
+                            candidates.push(v);
+                        }
+                        shared[v as usize] += 1;
+                    }
+                }
+            }
+            for v in candidates.drain(..) {
+                let common = std::mem::take(&mut shared[v as usize]);
+                let union = network.degree(u) + network.degree(v) - common;
+                if common as f64 >= min_overlap * union as f64 {
+                    supernode[v as usize] = s as Node;
+                    weights[s] += 1;
+                }
+            }
+        }
+        Self { supernode, weights }
+    }
+
+    /// the network of supernodes, adjacent if any of their nodes are
+    pub fn network(&self, network: &NetworkData) -> NetworkData {
+        let mut edges: Vec<(Node, Node)> = network
+            .edges()
+            .map(|(u, v)| {
+                let (a, b) = (self.supernode[u as usize], self.supernode[v as usize]);
+                (a.min(b), a.max(b))
+            })
+            .collect();
+        edges.sort_unstable();
+        edges.dedup();
+        NetworkData::from_edges(self.weights.len(), edges)
+    }
+
+    /// groups of every node from the groups of every supernode
+    pub fn project(&self, groups: &[Groups]) -> Vec<Groups> {
+        self.supernode.iter().map(|&s| groups[s as usize]).collect()
+    }
+}
+
+/// fit the network of supernodes of `network` for `params.max_itr` iterations, seeded with the
+/// `Stream::Stage` sub-seed 0 of `seeds`. Returns a model of `network` in the projection of
+/// the best configuration found.
+pub fn coarsened_fit(
+    params: &Parameters,
+    network: Arc<NetworkData>,
+    seeds: &mut SeedStream,
+) -> Result<HierarchicalModel, String> {
+    let coarsening = Coarsening::new(&network, params.coarsen_overlap);
+    let coarse = Arc::new(coarsening.network(&network));
+    if !params.quiet {
+        println!(
+            "coarsening: {} nodes into {} supernodes (largest {}), {} edges into {}",
+            network.node_count(),
+            coarse.node_count(),
+            coarsening.weights.iter().max().unwrap_or(&0),
+            network.edge_count(),
+            coarse.edge_count()
+        );
+    }
+    let coarse_params = Parameters {
+        seed: Some(seeds.derive(Stream::Stage, 0)),
+        ..params.clone()
+    };
+    let mut hcp: HierarchicalModel = HierarchicalModel::with_network(&coarse_params, coarse)?;
+    let mut best = (
+        hcp.log_like(),
+        hcp.model().groups().to_vec(),
+        hcp.model().num_groups(),
+    );
+    for i in 0..params.max_itr {
+        hcp.set_group_move_weight(params.move_schedule.weight(i));
+        if hcp.get_groups().is_some() && hcp.log_like() > best.0 {
+            best = (
+                hcp.log_like(),
+                hcp.model().groups().to_vec(),
+                hcp.model().num_groups(),
+            );
+        }
+    }
+    if !params.quiet {
+        println!("coarse fit: {} groups, best energy {:.4}", best.2, best.0);
+    }
+
+    let (_, groups, num_groups) = best;
+    HierarchicalModel::with_network(
+        &params.with_initial_config(coarsening.project(&groups), num_groups as u32),
+        network,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::_example_parameters;
+
+    #[test]
+    fn supernodes() {
+        // 0 and 1 are structurally equivalent, 2 and 3 overlap by 2/3, 0 and 4 by 1/2, 5 and 6
+        // are isolated
+        let network = NetworkData::from_edges(7, [(0, 2), (0, 3), (1, 2), (1, 3), (2, 4)]);
+        let exact = Coarsening::new(&network, 1.0);
+        assert_eq!(exact.supernode, [0, 0, 1, 2, 3, 4, 4]);
+        assert_eq!(exact.weights, [2, 1, 1, 1, 2]);
+
+        let loose = Coarsening::new(&network, 0.6);
+        assert_eq!(loose.supernode, [0, 0, 1, 1, 2, 3, 3]);
+        let coarse = loose.network(&network);
+        assert_eq!(coarse.edges().collect::<Vec<_>>(), [(0, 1), (1, 2)]);
+        assert_eq!(loose.project(&[3, 1, 7, 1]), [3, 3, 1, 1, 7, 1, 1]);
+    }
+
+    #[test]
+    fn fit() {
+        let params = _example_parameters(b"max_itr: 2000\ncoarsen_overlap: 0.5\n").unwrap();
+        let network = Arc::new(NetworkData::load(&params).unwrap());
+        let coarsening = Coarsening::new(&network, 0.5);
+        assert!(coarsening.weights.len() < network.node_count());
+        let hcp = coarsened_fit(&params, network.clone(), &mut SeedStream::new(1)).unwrap();
+        assert_eq!(hcp.model().groups().len(), network.node_count());
+        // nodes of a supernode share their groups
+        for u in 0..network.node_count() {
+            for v in 0..u {
+                if coarsening.supernode[u] == coarsening.supernode[v] {
+                    assert_eq!(hcp.model().groups_of(u), hcp.model().groups_of(v));
+                }
+            }
+        }
+    }
+}
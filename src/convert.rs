@@ -0,0 +1,78 @@
+//! conversion between the outputs of the C++ implementation and the snapshot stream, so both
+//! can be analysed with the same tools. The C++ code writes `{name}_configs.txt` with the
+//! group bits of every node, one snapshot per line, and `{name}_energies.txt` with the
+//! log-likelihood of each snapshot. It does not record iterations, so snapshots read from it
+//! are numbered by their position.
+use crate::multi_group_model::Groups;
+use crate::trace::Snapshot;
+use std::io::{self, BufRead, Write};
+
+/// number of groups used by `groups`, counting the root
+fn num_groups(groups: &[Groups]) -> u32 {
+    (64 - groups.iter().fold(1, |a, &g| a | g).leading_zeros()).max(1)
+}
+
+/// read the snapshots of a C++ run from its `configs` and `energies` files
+pub fn read_cpp(configs: impl BufRead, energies: impl BufRead) -> Result<Vec<Snapshot>, String> {
+    let mut energies = energies.lines();
+    configs
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            let line = line.map_err(|e| e.to_string())?;
+            let groups = line
+                .split_whitespace()
+                .map(|w| w.parse().or(Err(format!("not an integer: {}", w))))
+                .collect::<Result<Vec<Groups>, String>>()?;
+            let energy = energies
+                .next()
+                .ok_or(format!("no energy for configuration {}", i))?
+                .map_err(|e| e.to_string())?;
+            Ok(Snapshot {
+                iteration: i as u64,
+                log_like: energy
+                    .trim()
+                    .parse()
+                    .or(Err(format!("not a number: {}", energy)))?,
+                num_groups: num_groups(&groups),
+                groups,
+            })
+        })
+        .collect()
+}
+
+/// write `snapshots` as the `configs` and `energies` files of the C++ implementation
+pub fn write_cpp<'a>(
+    snapshots: impl IntoIterator<Item = &'a Snapshot>,
+    mut configs: impl Write,
+    mut energies: impl Write,
+) -> io::Result<()> {
+    for snapshot in snapshots {
+        let groups: Vec<String> = snapshot.groups.iter().map(|g| g.to_string()).collect();
+        writeln!(configs, "{}", groups.join(" "))?;
+        writeln!(energies, "{}", snapshot.log_like)?;
+    }
+    configs.flush()?;
+    energies.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cpp_roundtrip() {
+        let snapshots = read_cpp(&b"3 3 1 5\n1 1 1 1\n"[..], &b"-12.5\n-10\n"[..]).unwrap();
+        assert_eq!(snapshots.len(), 2);
+        assert_eq!(snapshots[0].num_groups, 3);
+        assert_eq!(snapshots[1].num_groups, 1);
+        assert_eq!(snapshots[1].iteration, 1);
+        assert_eq!(snapshots[1].log_like, -10.0);
+
+        let (mut configs, mut energies) = (Vec::new(), Vec::new());
+        write_cpp(&snapshots, &mut configs, &mut energies).unwrap();
+        assert_eq!(configs, b"3 3 1 5\n1 1 1 1\n");
+        assert_eq!(energies, b"-12.5\n-10\n");
+        assert!(read_cpp(&b"1 1\n"[..], &b""[..]).is_err());
+    }
+}
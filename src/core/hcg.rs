@@ -0,0 +1,91 @@
+//! highest common groups (HCG) of node pairs and the per-group edge and pair counts the
+//! likelihood is computed from
+use super::multi_group_model::{Groups, MultiGroupModel, Node};
+use super::network::NetworkData;
+use alloc::vec;
+use alloc::vec::Vec;
+
+pub trait HCG {
+    /// Highest Common Group
+    fn hcg(&self, u: Node, v: Node) -> usize;
+
+    fn hcg_node(&self, old_state: Groups, u: Node) -> usize;
+}
+
+impl HCG for MultiGroupModel {
+    fn hcg(&self, u: Node, v: Node) -> usize {
+        let group_mask = (1u64 << self.num_groups()) - 1;
+        let masked_u = self.groups_of(u as usize) & group_mask;
+        let masked_v = self.groups_of(v as usize) & group_mask;
+
+        let common_bits = masked_u & masked_v;
+        let common_bits = common_bits | (common_bits >> 1u64);
+        let common_bits = common_bits | (common_bits >> 2u64);
+        let common_bits = common_bits | (common_bits >> 4u64);
+        let common_bits = common_bits | (common_bits >> 8u64);
+        let common_bits = common_bits | (common_bits >> 16u64);
+        let common_bits = common_bits | (common_bits >> 32u64);
+
+        (63u64 - ((common_bits - (common_bits >> 1u64)).leading_zeros() as u64)) as usize
+    }
+
+    fn hcg_node(&self, old_state: Groups, u: Node) -> usize {
+        let group_mask = (1u64 << self.num_groups()) - 1;
+        let masked_u = old_state & group_mask;
+        let masked_v = self.groups_of(u as usize) & group_mask;
+
+        let common_bits = masked_u & masked_v;
+        let common_bits = common_bits | (common_bits >> 1u64);
+        let common_bits = common_bits | (common_bits >> 2u64);
+        let common_bits = common_bits | (common_bits >> 4u64);
+        let common_bits = common_bits | (common_bits >> 8u64);
+        let common_bits = common_bits | (common_bits >> 16u64);
+        let common_bits = common_bits | (common_bits >> 32u64);
+
+        (63u64 - (common_bits - (common_bits >> 1u64)).leading_zeros() as u64) as usize
+    }
+}
+
+/// number of edges for which each group is the highest common group
+pub fn hcg_edges(network: &NetworkData, model: &MultiGroupModel) -> Vec<usize> {
+    // void hierarchical_model::set_hcg_edges()
+    let mut hcg_edges = vec![0; model.num_groups()];
+    for (u, v) in network.edges() {
+        hcg_edges[model.hcg(u, v)] += 1;
+    }
+    hcg_edges
+}
+
+/// number of node pairs for which each group is the highest common group
+pub fn hcg_pairs(model: &MultiGroupModel) -> Vec<usize> {
+    // void hierarchical_model::set_hcg_pairs()
+    let n = model.groups().len() as Node;
+    let mut hcg_pairs = vec![0; model.num_groups()];
+    for u in 0..n {
+        for v in u + 1..n {
+            hcg_pairs[model.hcg(u, v)] += 1;
+        }
+    }
+    hcg_pairs
+}
+
+/// change of the pair and edge counts of every group after `node` changed groups from
+/// `old_state` to its current groups
+pub fn node_move_deltas(
+    network: &NetworkData,
+    model: &MultiGroupModel,
+    node: Node,
+    old_state: Groups,
+) -> ([isize; 64], [isize; 64]) {
+    let mut pairs = [0; 64];
+    let mut edges = [0; 64];
+    for v in (0..network.node_count() as Node).filter(|&v| v != node) {
+        pairs[model.hcg_node(old_state, v)] -= 1;
+        pairs[model.hcg(node, v)] += 1;
+    }
+    for &v in network.neighbors(node) {
+        edges[model.hcg_node(old_state, v)] -= 1;
+        edges[model.hcg(node, v)] += 1;
+    }
+    (pairs, edges)
+}
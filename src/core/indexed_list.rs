@@ -0,0 +1,384 @@
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// indexed list is a strange name for a 2d array
+#[derive(Clone, PartialEq, Eq)]
+pub struct IndexedList<T> {
+    data: Vec<T>,
+    n_cols: usize,
+}
+
+impl<T> IndexedList<T> {
+    pub fn new(n_cols: usize) -> Self {
+        Self {
+            data: Vec::new(),
+            n_cols,
+        }
+    }
+
+    /// empty list with room for `n_rows` rows
+    pub fn with_rows(n_rows: usize, n_cols: usize) -> Self {
+        Self {
+            data: Vec::with_capacity(n_rows * n_cols),
+            n_cols,
+        }
+    }
+
+    /// list with the rows stored back to back in `data`, as returned by `flat`. Errors if
+    /// `data` is not a whole number of rows.
+    pub fn from_flat(data: Vec<T>, n_cols: usize) -> Result<Self, String> {
+        if !data.len().is_multiple_of(n_cols) {
+            return Err(format!("{} entries are not rows of {}", data.len(), n_cols));
+        }
+        Ok(Self { data, n_cols })
+    }
+
+    /// number of entries in each row
+    pub fn row_len(&self) -> usize {
+        self.n_cols
+    }
+
+    pub fn n_rows(&self) -> usize {
+        self.data.len().checked_div(self.n_cols).unwrap_or(0)
+    }
+
+    pub fn rows(&self) -> core::slice::Chunks<'_, T> {
+        // a list without columns has no data, any chunk size gives no rows
+        self.data.chunks(self.n_cols.max(1))
+    }
+
+    pub fn iter_row(&self, row: usize) -> core::slice::Iter<'_, T> {
+        self[row].iter()
+    }
+
+    pub fn row(&self, row: usize) -> Option<&[T]> {
+        self.data.get(row * self.n_cols..(row + 1) * self.n_cols)
+    }
+
+    pub fn row_mut(&mut self, row: usize) -> Option<&mut [T]> {
+        self.data
+            .get_mut(row * self.n_cols..(row + 1) * self.n_cols)
+    }
+
+    pub fn swap_rows(&mut self, a: usize, b: usize) {
+        if a == b {
+            return;
+        }
+        let (lo, hi) = (a.min(b), a.max(b));
+        let (head, tail) = self.data.split_at_mut(hi * self.n_cols);
+        head[lo * self.n_cols..(lo + 1) * self.n_cols].swap_with_slice(&mut tail[..self.n_cols]);
+    }
+
+    pub fn flat(&self) -> &Vec<T> {
+        &self.data
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> Option<&T> {
+        self.data.get(row * self.n_cols + col)
+    }
+
+    pub fn get_mut(&mut self, row: usize, col: usize) -> Option<&mut T> {
+        self.data.get_mut(row * self.n_cols + col)
+    }
+
+    pub fn remove_row(&mut self, index: usize) {
+        let pos = index * self.n_cols;
+        self.data.drain(pos..(pos + self.n_cols));
+    }
+
+    pub fn clear(&mut self) {
+        self.data.clear();
+    }
+}
+
+impl<T: Clone> IndexedList<T> {
+    pub fn insert_row(&mut self, index: usize, element: &[T]) {
+        let pos = index * self.n_cols;
+        self.data.splice(pos..pos, element.iter().cloned());
+    }
+
+    pub fn push_row(&mut self, element: &[T]) {
+        self.data.extend_from_slice(element);
+    }
+
+    pub fn fill_row(&mut self, row: usize, value: T) {
+        self[row].fill(value);
+    }
+}
+
+impl<T> core::ops::Index<usize> for IndexedList<T> {
+    type Output = [T];
+    fn index(&self, row: usize) -> &Self::Output {
+        &self.data[row * self.n_cols..(row + 1) * self.n_cols]
+    }
+}
+
+impl<T> core::ops::IndexMut<usize> for IndexedList<T> {
+    fn index_mut(&mut self, row: usize) -> &mut Self::Output {
+        &mut self.data[row * self.n_cols..(row + 1) * self.n_cols]
+    }
+}
+
+impl<T> core::ops::Index<(usize, usize)> for IndexedList<T> {
+    type Output = T;
+    fn index(&self, (row, col): (usize, usize)) -> &Self::Output {
+        &self.data[row * self.n_cols + col]
+    }
+}
+impl<T> core::ops::IndexMut<(usize, usize)> for IndexedList<T> {
+    fn index_mut(&mut self, (row, col): (usize, usize)) -> &mut Self::Output {
+        &mut self.data[row * self.n_cols + col]
+    }
+}
+
+impl<'a, T> FromIterator<&'a [T]> for IndexedList<T>
+where
+    T: Clone + 'a,
+{
+    fn from_iter<I: IntoIterator<Item = &'a [T]>>(iter: I) -> Self {
+        let mut iter = iter.into_iter().peekable();
+        let n_cols = iter.peek().map_or(0, |row| row.len());
+        Self {
+            data: iter.flatten().cloned().collect(),
+            n_cols,
+        }
+    }
+}
+
+impl<'a, T> IntoIterator for &'a IndexedList<T> {
+    type Item = &'a [T];
+    type IntoIter = core::slice::Chunks<'a, T>;
+    fn into_iter(self) -> Self::IntoIter {
+        self.rows()
+    }
+}
+
+impl<T: core::fmt::Debug> core::fmt::Debug for IndexedList<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> Result<(), core::fmt::Error> {
+        writeln!(f, "IndexedList::from([")?;
+        for row in self {
+            writeln!(f, "    {:?},", row)?;
+        }
+        writeln!(f, "])")?;
+        Ok(())
+    }
+}
+
+/// an `IndexedList` of rows allocated once, up to a fixed capacity, in one contiguous block.
+/// Logical rows map to storage rows through an index, so inserting or removing a row rotates
+/// the index, O(capacity), instead of moving the data of every following row.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct FixedIndexedList<T> {
+    data: IndexedList<T>,
+    /// storage row of every logical row; entries from `len` on are the free rows
+    rows: Vec<usize>,
+    len: usize,
+}
+
+impl<T: Clone> FixedIndexedList<T> {
+    /// `capacity` rows of `n_cols` entries set to `value`, with `len` of them in use
+    pub fn new(capacity: usize, n_cols: usize, len: usize, value: T) -> Self {
+        assert!(len <= capacity);
+        let mut data = IndexedList::with_rows(capacity, n_cols);
+        for _ in 0..capacity {
+            data.push_row(&vec![value.clone(); n_cols]);
+        }
+        Self {
+            data,
+            rows: (0..capacity).collect(),
+            len,
+        }
+    }
+
+    /// insert a row at `index`, taking the first free storage row, and return it.
+    /// The row keeps the contents it had when it was freed.
+    pub fn insert_row(&mut self, index: usize) -> &mut [T] {
+        assert!(index <= self.len && self.len < self.capacity());
+        self.rows[index..=self.len].rotate_right(1);
+        self.len += 1;
+        &mut self[index]
+    }
+}
+
+impl<T> FixedIndexedList<T> {
+    /// a list with the storage `data` and the storage row of every logical row, see
+    /// `storage` and `row_index`
+    pub fn from_parts(data: IndexedList<T>, rows: Vec<usize>, len: usize) -> Result<Self, String> {
+        let mut seen = vec![false; rows.len()];
+        for &r in &rows {
+            if r >= rows.len() || core::mem::replace(&mut seen[r], true) {
+                return Err(String::from("row index is not a permutation"));
+            }
+        }
+        if data.n_rows() != rows.len() || len > rows.len() {
+            return Err(format!(
+                "{} of {} index rows in use for {} storage rows",
+                len,
+                rows.len(),
+                data.n_rows()
+            ));
+        }
+        Ok(Self { data, rows, len })
+    }
+
+    /// number of rows in use
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// remove the row at `index`, freeing its storage row for the next `insert_row`
+    pub fn remove_row(&mut self, index: usize) {
+        assert!(index < self.len);
+        self.rows[index..self.len].rotate_left(1);
+        self.len -= 1;
+    }
+
+    /// all rows in storage order, in use or not
+    pub fn storage(&self) -> &IndexedList<T> {
+        &self.data
+    }
+
+    /// storage row of every logical row, the free rows last
+    pub fn row_index(&self) -> &[usize] {
+        &self.rows
+    }
+}
+
+impl<T> core::ops::Index<usize> for FixedIndexedList<T> {
+    type Output = [T];
+    fn index(&self, row: usize) -> &Self::Output {
+        &self.data[self.rows[row]]
+    }
+}
+
+impl<T> core::ops::IndexMut<usize> for FixedIndexedList<T> {
+    fn index_mut(&mut self, row: usize) -> &mut Self::Output {
+        &mut self.data[self.rows[row]]
+    }
+}
+
+impl<T> core::ops::Index<(usize, usize)> for FixedIndexedList<T> {
+    type Output = T;
+    fn index(&self, (row, col): (usize, usize)) -> &Self::Output {
+        &self.data[(self.rows[row], col)]
+    }
+}
+
+impl<T> core::ops::IndexMut<(usize, usize)> for FixedIndexedList<T> {
+    fn index_mut(&mut self, (row, col): (usize, usize)) -> &mut Self::Output {
+        &mut self.data[(self.rows[row], col)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_iter() {
+        let v: IndexedList<i32> = [1, 2, 3, 4, 5, 6].chunks(3).collect();
+        assert_eq!(v[(0, 1)], 2);
+        assert_eq!(v[(1, 2)], 6);
+    }
+
+    #[test]
+    fn push() {
+        let mut v = IndexedList::new(3);
+        v.push_row(&[1, 2, 3]);
+        v.push_row(&[4, 5, 6]);
+        assert_eq!(v[(0, 1)], 2);
+        assert_eq!(v[(1, 2)], 6);
+    }
+    #[test]
+    fn insert() {
+        let mut v: IndexedList<i32> = [1, 2, 3, 4, 5, 6].chunks(3).collect();
+        v.insert_row(1, &[8, 8, 8]);
+        assert_eq!(v[(1, 2)], 8);
+    }
+    #[test]
+    fn remove() {
+        let mut v: IndexedList<i32> = [1, 2, 3, 4, 5, 6].chunks(3).collect();
+        v.remove_row(0);
+        assert_eq!(v[(0, 1)], 5);
+    }
+    #[test]
+    fn with_rows() {
+        let mut v = IndexedList::with_rows(2, 3);
+        assert_eq!(v.row_len(), 3);
+        assert_eq!(v.row(0), None);
+        v.push_row(&[1, 2, 3]);
+        v.push_row(&[4, 5, 6]);
+        assert!(v.flat().capacity() >= 6);
+        assert_eq!(v.row(1), Some(&[4, 5, 6][..]));
+        assert_eq!(v.row(2), None);
+        assert_eq!(IndexedList::from_flat(v.flat().clone(), 3), Ok(v));
+        assert!(IndexedList::from_flat(vec![1, 2, 3, 4], 3).is_err());
+        assert!(IndexedList::from_flat(vec![1], 0).is_err());
+        assert!(IndexedList::<i32>::from_flat(Vec::new(), 0).is_ok());
+    }
+    #[test]
+    fn rows() {
+        let v: IndexedList<i32> = [1, 2, 3, 4, 5, 6].chunks(3).collect();
+        assert_eq!(v.n_rows(), 2);
+        assert_eq!(v.rows().collect::<Vec<_>>(), [[1, 2, 3], [4, 5, 6]]);
+        assert_eq!(v.iter_row(1).sum::<i32>(), 15);
+        let mut n = 0;
+        for row in &v {
+            assert_eq!(row.len(), 3);
+            n += 1;
+        }
+        assert_eq!(n, 2);
+        assert_eq!(IndexedList::<i32>::new(0).rows().count(), 0);
+    }
+    #[test]
+    fn fill() {
+        let mut v: IndexedList<i32> = [1, 2, 3, 4, 5, 6].chunks(3).collect();
+        v.fill_row(1, 0);
+        assert_eq!(v.flat(), &[1, 2, 3, 0, 0, 0]);
+    }
+    #[test]
+    fn swap() {
+        let mut v: IndexedList<i32> = [1, 2, 3, 4, 5, 6, 7, 8, 9].chunks(3).collect();
+        v.swap_rows(2, 0);
+        assert_eq!(v.flat(), &[7, 8, 9, 4, 5, 6, 1, 2, 3]);
+        v.swap_rows(1, 1);
+        assert_eq!(&v[1], [4, 5, 6]);
+    }
+    #[test]
+    fn fixed_rows() {
+        let mut v = FixedIndexedList::new(4, 2, 2, 0);
+        v[0].copy_from_slice(&[1, 2]);
+        v[1].copy_from_slice(&[3, 4]);
+        v.insert_row(1).copy_from_slice(&[5, 6]);
+        assert_eq!((v.len(), v.capacity()), (3, 4));
+        assert_eq!(
+            (&v[0], &v[1], &v[2]),
+            (&[1, 2][..], &[5, 6][..], &[3, 4][..])
+        );
+        // the data stays in place, only the index moves
+        assert_eq!(v.storage().flat(), &[1, 2, 3, 4, 5, 6, 0, 0]);
+        assert_eq!(v.row_index(), [0, 2, 1, 3]);
+        v.remove_row(0);
+        assert_eq!(v[(0, 1)], 6);
+        assert_eq!(v.row_index(), [2, 1, 0, 3]);
+        // the freed row is the next one reused
+        assert_eq!(v.insert_row(2), [1, 2]);
+
+        let parts =
+            |rows: Vec<usize>, len| FixedIndexedList::from_parts(v.storage().clone(), rows, len);
+        assert_eq!(parts(v.row_index().to_vec(), 3), Ok(v.clone()));
+        assert!(parts(vec![0, 1, 1, 3], 3).is_err());
+        assert!(parts(vec![0, 1, 2], 3).is_err());
+        assert!(parts(vec![0, 1, 2, 3], 5).is_err());
+    }
+}
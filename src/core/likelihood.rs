@@ -0,0 +1,261 @@
+//! Likelihood of the network given the group configuration.
+//!
+//! The sampler only knows, for every group, the number of edges and the number of node pairs
+//! for which it is the highest common group. A `Likelihood` turns these counts into a
+//! log-likelihood as a sum of independent per-group terms, so alternative null models only
+//! need to provide `group_term`.
+use super::math::{self, CompensatedSum, Float, LnFactTable};
+use alloc::format;
+use alloc::string::{String, ToString};
+use core::fmt::Debug;
+use core::iter;
+
+/// settings of the likelihood, see `Parameters::likelihood_config`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LikelihoodConfig {
+    pub density_prior_a: f64,
+    pub density_prior_b: f64,
+    pub ln_fact_table_max_mb: usize,
+    pub ln_fact_overflow: LnFactOverflow,
+}
+
+/// what happens when the ln-factorial table would exceed `ln_fact_table_max_mb`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LnFactOverflow {
+    /// warn and evaluate ln-factorials on demand with `lgamma`, slower but without the table
+    #[default]
+    Lgamma,
+    /// fail before allocating
+    Error,
+}
+
+pub trait Likelihood: Clone + Debug + Send + Sync + 'static {
+    /// the likelihood selected by `config`
+    fn from_config(config: &LikelihoodConfig) -> Result<Self, String>;
+
+    /// called once with the number of nodes before any term is evaluated,
+    /// e.g. to fill lookup tables. Returns a warning for the user, if any.
+    fn prepare<F: Float>(&mut self, _num_nodes: usize) -> Result<Option<String>, String> {
+        Ok(None)
+    }
+
+    /// log-likelihood contribution of a group that is the highest common group of
+    /// `pairs` node pairs, `edges` of which are connected
+    fn group_term<F: Float>(&self, edges: usize, pairs: usize) -> F;
+
+    /// number of ln-factorials precomputed by `prepare` in precision `F`
+    fn ln_fact_table_len<F: Float>(&self) -> usize {
+        0
+    }
+
+    /// `group_term` in double precision without lookup tables, used to correct drift
+    fn group_term_exact(&self, edges: usize, pairs: usize) -> f64 {
+        self.group_term::<f64>(edges, pairs)
+    }
+
+    /// change of a group's term when its counts change from `old` to `new` (edges, pairs)
+    fn delta<F: Float>(&self, old: (usize, usize), new: (usize, usize)) -> F {
+        self.group_term::<F>(new.0, new.1) - self.group_term::<F>(old.0, old.1)
+    }
+
+    /// total log-likelihood for the per-group counts `edges` and `pairs`
+    fn log_like<F: Float>(&self, edges: &[usize], pairs: &[usize]) -> F {
+        iter::zip(edges, pairs)
+            .map(|(&e, &p)| self.group_term::<F>(e, p))
+            .sum::<CompensatedSum<F>>()
+            .value()
+    }
+
+    /// `log_like` in double precision from `group_term_exact`
+    fn log_like_exact(&self, edges: &[usize], pairs: &[usize]) -> f64 {
+        iter::zip(edges, pairs)
+            .map(|(&e, &p)| self.group_term_exact(e, p))
+            .sum::<CompensatedSum>()
+            .value()
+    }
+}
+
+/// edges of each group drawn with a group specific probability under a Beta(a, b) prior,
+/// integrated out: `ln(B(e + a, p - e + b) / B(a, b))`. The default uniform prior a = b = 1
+/// gives `ln(e! (p - e)! / (p + 1)!)`.
+#[derive(Debug, Clone)]
+pub struct BetaBinomial {
+    a: f64,
+    b: f64,
+    /// `ln B(a, b)`
+    ln_norm: f64,
+    /// a and b are whole numbers, so the gamma functions are ln-factorials from the table
+    integral: bool,
+    /// size limit of the ln-factorial table in bytes
+    max_table_bytes: usize,
+    on_overflow: LnFactOverflow,
+    ln_fact: LnFactTable,
+}
+
+impl BetaBinomial {
+    pub fn new(a: f64, b: f64) -> Result<Self, String> {
+        if !(a > 0.0 && b > 0.0 && a.is_finite() && b.is_finite()) {
+            return Err(format!(
+                "density prior parameters must be positive and finite: {} {}",
+                a, b
+            ));
+        }
+        Ok(Self {
+            a,
+            b,
+            ln_norm: math::ln_beta(a, b),
+            integral: a % 1.0 == 0.0 && b % 1.0 == 0.0,
+            max_table_bytes: 2048 << 20,
+            on_overflow: LnFactOverflow::default(),
+            ln_fact: LnFactTable::default(),
+        })
+    }
+
+    /// limit the ln-factorial table to `max_mb` MiB, see `LnFactOverflow`
+    pub fn with_table_limit(self, max_mb: usize, on_overflow: LnFactOverflow) -> Self {
+        Self {
+            max_table_bytes: max_mb.saturating_mul(1 << 20),
+            on_overflow,
+            ..self
+        }
+    }
+}
+
+impl Default for BetaBinomial {
+    fn default() -> Self {
+        Self::new(1.0, 1.0).unwrap()
+    }
+}
+
+impl Likelihood for BetaBinomial {
+    fn from_config(config: &LikelihoodConfig) -> Result<Self, String> {
+        Ok(Self::new(config.density_prior_a, config.density_prior_b)?
+            .with_table_limit(config.ln_fact_table_max_mb, config.ln_fact_overflow))
+    }
+
+    fn prepare<F: Float>(&mut self, num_nodes: usize) -> Result<Option<String>, String> {
+        // for a prior stronger than the data the table up to n² + a + b would mostly hold
+        // values that are never read, ln_beta is cheaper
+        if self.a + self.b > num_nodes as f64 * num_nodes as f64 {
+            self.integral = false;
+        }
+        if !self.integral {
+            return Ok(None);
+        }
+        let max = num_nodes
+            .checked_mul(num_nodes)
+            .and_then(|n2| n2.checked_add((self.a + self.b) as usize));
+        let bytes = max.and_then(|m| (m + 1).checked_mul(core::mem::size_of::<F>()));
+        let needed = || {
+            format!(
+                "the ln-factorial table for {} nodes needs {} MiB, more than \
+                 ln_fact_table_max_mb: {}",
+                num_nodes,
+                bytes.map_or(String::from("over usize::MAX bytes"), |b| (b >> 20)
+                    .to_string()),
+                self.max_table_bytes >> 20
+            )
+        };
+        match (max, bytes) {
+            (Some(max), Some(bytes)) if bytes <= self.max_table_bytes => {
+                self.ln_fact.precompute::<F>(max);
+                Ok(None)
+            }
+            _ if self.on_overflow == LnFactOverflow::Lgamma => {
+                let warning = format!("{}; evaluating them on demand, which is slower", needed());
+                self.integral = false;
+                Ok(Some(warning))
+            }
+            _ => Err(format!(
+                "{}; set ln_fact_overflow: lgamma to evaluate them on demand",
+                needed()
+            )),
+        }
+    }
+
+    #[inline]
+    fn group_term<F: Float>(&self, edges: usize, pairs: usize) -> F {
+        if !self.integral {
+            return F::from_f64(self.group_term_exact(edges, pairs));
+        }
+        // Γ(n) = (n - 1)!
+        let (a, b) = (self.a as usize, self.b as usize);
+        let ln_fact = |x| self.ln_fact.ln_fact::<F>(x);
+        ln_fact(edges + a - 1) + ln_fact(pairs - edges + b - 1)
+            - ln_fact(pairs + a + b - 1)
+            - F::from_f64(self.ln_norm)
+    }
+
+    fn ln_fact_table_len<F: Float>(&self) -> usize {
+        self.ln_fact.num_values::<F>()
+    }
+
+    fn group_term_exact(&self, edges: usize, pairs: usize) -> f64 {
+        math::ln_beta(edges as f64 + self.a, (pairs - edges) as f64 + self.b) - self.ln_norm
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn beta_binomial() {
+        let mut l = BetaBinomial::default();
+        l.prepare::<f64>(4).unwrap();
+        // 1 of 2 pairs connected: 1! 1! / 3! = 1/6
+        assert!((l.group_term::<f64>(1, 2) - (1.0f64 / 6.0).ln()).abs() < 1e-12);
+        assert_eq!(l.group_term::<f64>(0, 0), 0.0);
+        assert_eq!(l.group_term_exact(1, 2), l.group_term::<f64>(1, 2));
+        assert_eq!(
+            l.delta::<f64>((1, 2), (2, 2)),
+            l.group_term::<f64>(2, 2) - l.group_term::<f64>(1, 2)
+        );
+        assert_eq!(
+            l.log_like::<f64>(&[1, 2], &[2, 2]),
+            l.group_term::<f64>(1, 2) + l.group_term::<f64>(2, 2)
+        );
+    }
+
+    #[test]
+    fn density_prior() {
+        // Beta(1, 3) favours sparse groups, by a factor of 3 for an empty pair
+        let mut sparse = BetaBinomial::new(1.0, 3.0).unwrap();
+        sparse.prepare::<f64>(4).unwrap();
+        assert!((sparse.group_term::<f64>(0, 1) - 0.75f64.ln()).abs() < 1e-12);
+        assert!((sparse.group_term_exact(1, 1) - 0.25f64.ln()).abs() < 1e-12);
+        // non-integral parameters are evaluated with the gamma function
+        let half = BetaBinomial::new(0.5, 0.5).unwrap();
+        assert!((half.group_term::<f64>(1, 2) - (0.125f64).ln()).abs() < 1e-12);
+        assert!(BetaBinomial::new(0.0, 1.0).is_err());
+        // no table of a billion values for 4 nodes
+        let mut strong = BetaBinomial::new(1e9, 1.0).unwrap();
+        assert_eq!(strong.prepare::<f64>(4), Ok(None));
+        assert_eq!(strong.ln_fact_table_len::<f64>(), 0);
+        assert_eq!(
+            strong.group_term::<f64>(3, 6),
+            strong.group_term_exact(3, 6)
+        );
+    }
+
+    #[test]
+    fn table_limit() {
+        // 1000 nodes need a table of about 7.6 MiB
+        let exact = BetaBinomial::default();
+        let mut error = exact.clone().with_table_limit(4, LnFactOverflow::Error);
+        let err = error.prepare::<f64>(1000).unwrap_err();
+        assert!(err.contains("needs 7 MiB"), "{}", err);
+        assert!(error.prepare::<f64>(usize::MAX).is_err());
+
+        let mut lgamma = exact.clone().with_table_limit(4, LnFactOverflow::Lgamma);
+        let warning = lgamma.prepare::<f64>(1000).unwrap().unwrap();
+        assert!(
+            warning.ends_with("evaluating them on demand, which is slower"),
+            "{}",
+            warning
+        );
+        assert!(!lgamma.integral);
+        let (e, p) = (1000, 499_500);
+        assert!((lgamma.group_term::<f64>(e, p) - exact.group_term_exact(e, p)).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,300 @@
+use alloc::sync::Arc;
+use core::fmt::{Debug, Display};
+use core::ops::{Add, Neg, Sub};
+
+/// natural logarithm of `x`, in pure Rust as `core` has no floating point math (fdlibm's `log`)
+pub fn ln(x: f64) -> f64 {
+    const LN2_HI: f64 = 0.6931471803691238;
+    const LN2_LO: f64 = 1.9082149292705877e-10;
+    const LG: [f64; 7] = [
+        0.6666666666666735,
+        0.3999999999940942,
+        0.2857142874366239,
+        0.22222198432149784,
+        0.1818357216161805,
+        0.15313837699209373,
+        0.14798198605116586,
+    ];
+    if x.is_nan() || x < 0.0 {
+        return f64::NAN;
+    }
+    if x == 0.0 {
+        return f64::NEG_INFINITY;
+    }
+    if x == f64::INFINITY {
+        return x;
+    }
+    let (mut bits, mut k) = (x.to_bits(), 0i64);
+    if bits >> 52 == 0 {
+        // subnormal, scale by 2^54
+        bits = (x * f64::from_bits(0x4350_0000_0000_0000)).to_bits();
+        k = -54;
+    }
+    // reduce x to [sqrt(2)/2, sqrt(2)) * 2^k
+    let hx = (bits >> 32) + (0x3ff0_0000 - 0x3fe6_a09e);
+    k += (hx >> 20) as i64 - 0x3ff;
+    let hx = (hx & 0x000f_ffff) + 0x3fe6_a09e;
+    let f = f64::from_bits((hx << 32) | (bits & 0xffff_ffff)) - 1.0;
+    let hfsq = 0.5 * f * f;
+    let s = f / (2.0 + f);
+    let z = s * s;
+    let w = z * z;
+    let t1 = w * (LG[1] + w * (LG[3] + w * LG[5]));
+    let t2 = z * (LG[0] + w * (LG[2] + w * (LG[4] + w * LG[6])));
+    let dk = k as f64;
+    s * (hfsq + t1 + t2) + dk * LN2_LO - hfsq + f + dk * LN2_HI
+}
+
+/// `ln(x!)` without a lookup table
+pub fn ln_fact_exact(x: usize) -> f64 {
+    if x <= 20 {
+        // exact in u64
+        return ln((1..=x as u64).product::<u64>() as f64);
+    }
+    ln_gamma((x + 1) as f64)
+}
+
+/// `ln Γ(x)` for `x > 0`, from Stirling's series
+pub fn ln_gamma(mut x: f64) -> f64 {
+    // B_2k / (2k (2k - 1))
+    const STIRLING: [f64; 7] = [
+        1.0 / 12.0,
+        -1.0 / 360.0,
+        1.0 / 1260.0,
+        -1.0 / 1680.0,
+        1.0 / 1188.0,
+        -691.0 / 360360.0,
+        1.0 / 156.0,
+    ];
+    // 0.5 ln(2π) - 0.5
+    const HALF_LN_2PI_M_HALF: f64 = 0.4189385332046727;
+    if x.is_nan() || x <= 0.0 {
+        return f64::NAN;
+    }
+    if x <= 21.0 && x % 1.0 == 0.0 {
+        return ln_fact_exact(x as usize - 1);
+    }
+    // Γ(x) = Γ(x + k) / (x (x + 1) ... (x + k - 1)), the series is accurate from 10
+    let mut shift = 1.0;
+    while x < 10.0 {
+        shift *= x;
+        x += 1.0;
+    }
+    let z = 1.0 / x;
+    let z2 = z * z;
+    let series = STIRLING.iter().rev().fold(0.0, |acc, &c| acc * z2 + c) * z;
+    (x - 0.5) * (ln(x) - 1.0) + HALF_LN_2PI_M_HALF + series - ln(shift)
+}
+
+/// `ln B(a, b)`, the logarithm of the beta function
+pub fn ln_beta(a: f64, b: f64) -> f64 {
+    ln_gamma(a) + ln_gamma(b) - ln_gamma(a + b)
+}
+
+/// precomputed ln-factorials `ln(0!), ..., ln(m!)` in each precision. Clones share the values,
+/// so copies of a model do not duplicate them.
+#[derive(Debug, Clone, Default)]
+pub struct LnFactTable {
+    f64: Arc<[f64]>,
+    f32: Arc<[f32]>,
+}
+
+impl LnFactTable {
+    /// extend the table in precision `F` to at least `ln(m!)`
+    pub fn precompute<F: Float>(&mut self, m: usize) {
+        let values = F::ln_fact_values_mut(self);
+        if values.len() <= m {
+            *values = (0..=m).map(|x| F::from_f64(ln_fact_exact(x))).collect();
+        }
+    }
+
+    /// `ln(x!)`. `precompute::<F>(m)` *must* have been called with m ≥ x before calling this.
+    #[inline]
+    pub fn ln_fact<F: Float>(&self, x: usize) -> F {
+        F::ln_fact_values(self)[x]
+    }
+
+    /// number of precomputed ln-factorials in precision `F`
+    pub fn num_values<F: Float>(&self) -> usize {
+        F::ln_fact_values(self).len()
+    }
+}
+
+/// floating point type log-likelihoods are computed in
+pub trait Float:
+    Copy
+    + Default
+    + PartialOrd
+    + Debug
+    + Display
+    + Send
+    + Sync
+    + 'static
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Neg<Output = Self>
+{
+    fn from_f64(x: f64) -> Self;
+    fn to_f64(self) -> f64;
+    fn abs(self) -> Self;
+    /// the ln-factorials of `table` in this precision
+    fn ln_fact_values(table: &LnFactTable) -> &Arc<[Self]>;
+    fn ln_fact_values_mut(table: &mut LnFactTable) -> &mut Arc<[Self]>;
+}
+
+impl Float for f64 {
+    fn from_f64(x: f64) -> Self {
+        x
+    }
+    fn to_f64(self) -> f64 {
+        self
+    }
+    fn abs(self) -> Self {
+        self.abs()
+    }
+    #[inline]
+    fn ln_fact_values(table: &LnFactTable) -> &Arc<[Self]> {
+        &table.f64
+    }
+    fn ln_fact_values_mut(table: &mut LnFactTable) -> &mut Arc<[Self]> {
+        &mut table.f64
+    }
+}
+
+impl Float for f32 {
+    fn from_f64(x: f64) -> Self {
+        x as f32
+    }
+    fn to_f64(self) -> f64 {
+        self as f64
+    }
+    fn abs(self) -> Self {
+        self.abs()
+    }
+    #[inline]
+    fn ln_fact_values(table: &LnFactTable) -> &Arc<[Self]> {
+        &table.f32
+    }
+    fn ln_fact_values_mut(table: &mut LnFactTable) -> &mut Arc<[Self]> {
+        &mut table.f32
+    }
+}
+
+/// Neumaier compensated summation, for sums of many terms of differing magnitude
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CompensatedSum<F = f64> {
+    sum: F,
+    compensation: F,
+}
+
+impl<F: Float> CompensatedSum<F> {
+    pub fn add(&mut self, x: F) {
+        let t = self.sum + x;
+        if self.sum.abs() >= x.abs() {
+            self.compensation = self.compensation + ((self.sum - t) + x);
+        } else {
+            self.compensation = self.compensation + ((x - t) + self.sum);
+        }
+        self.sum = t;
+    }
+
+    pub fn value(&self) -> F {
+        self.sum + self.compensation
+    }
+}
+
+impl<F: Float> core::iter::Sum<F> for CompensatedSum<F> {
+    fn sum<I: Iterator<Item = F>>(iter: I) -> Self {
+        let mut acc = Self::default();
+        iter.for_each(|x| acc.add(x));
+        acc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    macro_rules! assert_close {
+        ($left:expr, $right:expr $(,)?) => {
+            let err = 0.001;
+            assert!(
+                ($left - $right).abs() < err,
+                "{}, {} differ by more than {}",
+                $left,
+                $right,
+                err
+            );
+        };
+    }
+    #[test]
+    fn test_ln_fact() {
+        let mut table = LnFactTable::default();
+        table.precompute::<f64>(100);
+        let ln_fact = |x| table.ln_fact::<f64>(x);
+        assert_eq!(ln_fact(0), 0.0);
+        assert_close!(ln_fact(2), 0.6931);
+        assert_close!(ln_fact(10), 15.1044);
+        assert_close!(ln_fact(100), 363.7394);
+        assert_eq!(ln_fact_exact(100), ln_fact(100));
+        // extending replaces the shared values, clones keep theirs
+        let copy = table.clone();
+        table.precompute::<f64>(50);
+        assert!(Arc::ptr_eq(&table.f64, &copy.f64));
+        table.precompute::<f64>(200);
+        assert_eq!(
+            (table.num_values::<f64>(), copy.num_values::<f64>()),
+            (201, 101)
+        );
+        assert_eq!(table.num_values::<f32>(), 0);
+    }
+
+    #[test]
+    fn pure_rust_math() {
+        for x in [1e-310, 1e-5, 0.5, 0.7, 1.0, 1.5, 2.0, 10.0, 1e10, 1e300] {
+            assert!(
+                (ln(x) - x.ln()).abs() <= 1e-15 * x.ln().abs().max(1.0),
+                "ln({})",
+                x
+            );
+        }
+        assert!(ln(-1.0).is_nan());
+        assert_eq!(ln(0.0), f64::NEG_INFINITY);
+        // libm's lgamma
+        for (x, lgamma) in [
+            (0.5, 0.5723649429247004),
+            (1.5, -0.12078223763524543),
+            (3.7, 1.4280723266653883),
+            (9.99, 12.779315214350197),
+            (10.5, 13.940625219403763),
+            (123.25, 468.6144829505166),
+            (1e6 + 0.5, 12815511.476902764),
+            (4.5e11, 11624631038857.352),
+        ] {
+            let err = (ln_gamma(x) - lgamma) / lgamma.abs().max(1.0);
+            assert!(err.abs() < 1e-14, "ln_gamma({}) = {}", x, ln_gamma(x));
+        }
+        assert_eq!(ln_gamma(1.0), 0.0);
+        assert!(ln_gamma(0.0).is_nan());
+        for n in 15..30 {
+            let err = ln_fact_exact(n + 1) - ln_fact_exact(n) - ((n + 1) as f64).ln();
+            assert!(err.abs() < 1e-13, "{}", n);
+        }
+    }
+
+    #[test]
+    fn single_precision() {
+        let mut table = LnFactTable::default();
+        table.precompute::<f32>(100);
+        assert_close!(table.ln_fact::<f32>(10), 15.1044);
+        assert_close!(table.ln_fact::<f32>(100), 363.7394);
+        let terms = [1.0f32, 1e30, 1.0, -1e30];
+        assert_eq!(terms.into_iter().sum::<CompensatedSum<f32>>().value(), 2.0);
+    }
+
+    #[test]
+    fn compensated_sum() {
+        let terms = [1.0, 1e100, 1.0, -1e100];
+        assert_eq!(terms.iter().sum::<f64>(), 0.0);
+        assert_eq!(terms.into_iter().sum::<CompensatedSum>().value(), 2.0);
+    }
+}
@@ -0,0 +1,13 @@
+//! the sampling core: the group model and its moves, the network, highest common groups and
+//! the likelihood. It only uses `core` and `alloc`, so it builds for `no_std` targets such as
+//! GPU dispatch frameworks; file formats, checkpoints and logging are in the std-only modules
+//! around it (`multi_group_model`, `network`, `checkpoint`, ...). `no_std_check/` builds it
+//! as a `#![no_std]` crate.
+#![deny(clippy::std_instead_of_core, clippy::std_instead_of_alloc)]
+
+pub mod hcg;
+pub mod indexed_list;
+pub mod likelihood;
+pub mod math;
+pub mod multi_group_model;
+pub mod network;
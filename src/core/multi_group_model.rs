@@ -0,0 +1,872 @@
+use super::indexed_list::FixedIndexedList;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::fmt::Debug;
+
+pub type Groups = u64; // group assignment bits
+pub type Node = u32; // node id
+
+/// the groups of a node, one bit per group: bit `g` is set if the node is in group `g`.
+/// Bit 0 is the root group, which every node is in, so the bit is set even if `groups` does
+/// not list group 0. `encode_groups(&[2, 3])` is `0b1101`, the value expected for the node in
+/// `initial_group_config`. Panics for groups above 63.
+pub fn encode_groups(groups: &[usize]) -> Groups {
+    groups.iter().fold(1, |bits, &g| {
+        assert!(g < 64, "group {} out of range", g);
+        bits | 1 << g
+    })
+}
+
+/// membership of each of the first `num_groups` groups in `bits`, see `encode_groups`
+pub fn decode_groups(bits: Groups, num_groups: usize) -> Vec<bool> {
+    (0..num_groups)
+        .map(|g| g < 64 && (bits >> g) & 1 != 0)
+        .collect()
+}
+
+/// check that `groups` is a valid configuration of `num_groups` groups: every node is in the
+/// root group and in no group from `num_groups` on. With `nested`, also require the groups to
+/// form a hierarchy, i.e. any two groups are disjoint or one contains the other. Errors name
+/// the first node violating a condition.
+pub fn validate_groups(groups: &[Groups], num_groups: u32, nested: bool) -> Result<(), String> {
+    let all = Groups::MAX >> (64 - num_groups.clamp(1, 64));
+    for (u, &g) in groups.iter().enumerate() {
+        if g & 1 == 0 {
+            return Err(format!("node {}: not in the root group ({})", u, g));
+        }
+        if g & !all != 0 {
+            return Err(format!(
+                "node {}: in group {}, beyond the {} groups ({})",
+                u,
+                63 - (g & !all).leading_zeros(),
+                num_groups,
+                g
+            ));
+        }
+    }
+    if !nested {
+        return Ok(());
+    }
+    for g in 1..num_groups {
+        for h in g + 1..num_groups {
+            let (g_bit, h_bit) = (1 << g, 1 << h);
+            let in_both = groups
+                .iter()
+                .position(|&x| x & g_bit != 0 && x & h_bit != 0);
+            let only_g = groups
+                .iter()
+                .position(|&x| x & g_bit != 0 && x & h_bit == 0);
+            let only_h = groups
+                .iter()
+                .position(|&x| x & g_bit == 0 && x & h_bit != 0);
+            if let (Some(u), Some(v), Some(w)) = (in_both, only_g, only_h) {
+                return Err(format!(
+                    "node {}: in group {} but not {}, which overlap at node {} while node {} is \
+                     only in group {}",
+                    v, g, h, u, w, h
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// a change of the configuration, see `MultiGroupModel::apply`. Node moves choose the node by
+/// its position `idx` among the members (`RemoveNodeFromGroup`) or non-members
+/// (`AddNodeToGroup`) of the group; `node` and `old_state`, its groups before the move, are
+/// filled in when the move is made.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Move {
+    /// insert an empty group at `group`, shifting the following groups up
+    AddGroup { group: usize },
+    /// remove `group`, shifting the following groups down
+    RemoveGroup { group: usize },
+    RemoveNodeFromGroup {
+        group: usize,
+        node: usize,
+        idx: usize,
+        old_state: u64,
+    },
+    AddNodeToGroup {
+        group: usize,
+        node: usize,
+        idx: usize,
+        old_state: u64,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct MultiGroupModel {
+    pub(crate) max_groups: usize,
+    pub(crate) num_groups: usize,
+    pub(crate) num_nodes: usize,
+
+    /// group assignments for each node
+    pub(crate) groups: Vec<Groups>,
+
+    /// for every group (row), list ids of nodes in group.
+    /// entries beyond the group size are invalid.
+    /// Rows are allocated up to `max_groups` once and only their index moves with the groups.
+    pub(crate) nodes_in: FixedIndexedList<Node>,
+    /// for every group (row), list ids of nodes not in group.
+    /// entries beyond (number of nodes - group size) are invalid.
+    pub(crate) nodes_out: FixedIndexedList<Node>,
+
+    pub(crate) group_size: Vec<usize>,
+    /// order in which nodes are listed in the rows of new groups
+    pub(crate) order: Vec<Node>,
+}
+
+#[inline]
+fn insert_zero_at(val: u64, pos: usize, num_groups: u32) -> u64 {
+    let group_mask = (1u64 << num_groups) - 1;
+    let select_mask = (group_mask << pos) & group_mask;
+
+    let left = val & select_mask;
+    let right = val & (!select_mask);
+
+    (left << 1) | right
+}
+
+#[inline]
+fn remove_bit_at(val: u64, pos: usize, num_groups: u32) -> u64 {
+    let group_mask = (1u64 << num_groups) - 1;
+    let upper_mask = (group_mask << (pos + 1)) & group_mask;
+    let lower_mask = (group_mask >> (num_groups as usize - pos)) & group_mask;
+
+    let upper = val & upper_mask;
+    let lower = val & lower_mask;
+
+    (upper >> 1) | lower
+}
+
+fn to_group_matrix(groups: &Vec<Groups>, num_groups: u32) -> Vec<Vec<bool>> {
+    groups
+        .iter()
+        .map(|&g| decode_groups(g, num_groups as usize))
+        .collect()
+}
+
+macro_rules! getter {
+    ($name:ident, $type:ident) => {
+        pub fn $name(&self) -> $type {
+            self.$name
+        }
+    };
+}
+
+impl MultiGroupModel {
+    pub fn with_groups(groups: Vec<Groups>, num_groups: u32, max_groups: u32) -> Self {
+        let order = (0..groups.len() as Node).collect();
+        Self::with_groups_in_order(groups, num_groups, max_groups, order)
+    }
+
+    /// like `with_groups`, but list the nodes of every group in `order` instead of by id.
+    /// Moves pick nodes by their position in these lists.
+    pub fn with_groups_in_order(
+        groups: Vec<Groups>,
+        num_groups: u32,
+        max_groups: u32,
+        order: Vec<Node>,
+    ) -> Self {
+        // hierarchical_model::set_nodes_in_out()
+        let group_matrix = to_group_matrix(&groups, num_groups);
+        let max_groups = max_groups as usize;
+        let num_groups = num_groups as usize;
+        let num_nodes = groups.len();
+
+        let num_rows = max_groups.max(num_groups);
+
+        let mut nodes_in = FixedIndexedList::new(num_rows, num_nodes, num_groups, Node::MAX);
+        let mut nodes_out = FixedIndexedList::new(num_rows, num_nodes, num_groups, Node::MAX);
+        let mut group_size = Vec::with_capacity(num_rows);
+        for r in 0..num_groups {
+            let mut in_g = 0;
+            let mut out_g = 0;
+            for &u in &order {
+                if group_matrix[u as usize][r] {
+                    nodes_in[(r, in_g)] = u;
+                    in_g += 1;
+                } else {
+                    nodes_out[(r, out_g)] = u;
+                    out_g += 1;
+                }
+            }
+            group_size.push(in_g);
+        }
+        Self {
+            max_groups,
+            num_groups,
+            num_nodes,
+            groups,
+            nodes_in,
+            nodes_out,
+            group_size,
+            order,
+        }
+    }
+
+    getter!(num_groups, usize);
+    getter!(max_groups, usize);
+    getter!(num_nodes, usize);
+
+    pub fn group_size(&self, groups: impl Into<usize>) -> usize {
+        self.group_size[groups.into()]
+    }
+
+    /// group assignments for each node
+    pub fn groups(&self) -> &[Groups] {
+        &self.groups
+    }
+
+    pub fn group_sizes(&self) -> &[usize] {
+        &self.group_size
+    }
+
+    /// ids of the nodes in `group`, in no particular order
+    pub fn members(&self, group: usize) -> &[Node] {
+        &self.nodes_in[group][..self.group_size[group]]
+    }
+
+    /// ids of the nodes not in `group`, in no particular order
+    pub fn non_members(&self, group: usize) -> &[Node] {
+        &self.nodes_out[group][..self.num_nodes - self.group_size[group]]
+    }
+
+    /// for every group, the lower group sharing most of its members (ties go to the deeper
+    /// group). The root group 0 has no parent.
+    pub fn parent_groups(&self) -> Vec<Option<usize>> {
+        (0..self.num_groups).map(|g| self.parent_group(g)).collect()
+    }
+
+    /// the parent of `group` in `parent_groups`
+    pub fn parent_group(&self, group: usize) -> Option<usize> {
+        (0..group).max_by_key(|&h| {
+            self.members(group)
+                .iter()
+                .filter(|&&u| (self.groups[u as usize] >> h) & 1 != 0)
+                .count()
+        })
+    }
+
+    /// number of ancestors of every group in the hierarchy of `parent_groups`, 0 for the root
+    pub fn group_depths(&self) -> Vec<usize> {
+        let parents = self.parent_groups();
+        let mut depth = vec![0; parents.len()];
+        for g in 0..parents.len() {
+            // parents always have a lower index
+            depth[g] = parents[g].map_or(0, |p| depth[p] + 1);
+        }
+        depth
+    }
+
+    /// for every node, its group with the most ancestors in `group_depths` (ties go to the
+    /// higher group)
+    pub fn deepest_groups(&self) -> Vec<usize> {
+        let depths = self.group_depths();
+        self.groups
+            .iter()
+            .map(|&g| {
+                (0..self.num_groups)
+                    .filter(|&h| (g >> h) & 1 != 0)
+                    .max_by_key(|&h| (depths[h], h))
+                    .unwrap_or(0)
+            })
+            .collect()
+    }
+
+    /// order in which nodes are listed in groups, see `with_groups_in_order`
+    pub fn node_order(&self) -> &[Node] {
+        &self.order
+    }
+
+    pub fn groups_of(&self, node: usize) -> Groups {
+        self.groups[node]
+    }
+
+    pub fn add_group(&mut self, group: usize) -> Move {
+        self.insert_empty_group(group);
+        self.nodes_in[group].fill(Node::MAX);
+        self.nodes_out[group].copy_from_slice(&self.order);
+
+        Move::AddGroup { group }
+    }
+
+    /// insert `group` without members, taking the rows freed last as they are
+    fn insert_empty_group(&mut self, group: usize) {
+        self.nodes_in.insert_row(group);
+        self.nodes_out.insert_row(group);
+        self.group_size.insert(group, 0);
+        for u in self.groups.iter_mut() {
+            *u = insert_zero_at(*u, group, self.num_groups as u32);
+        }
+        self.num_groups += 1;
+    }
+
+    /// remove `group` and its members from it. `undo_move` only restores empty groups, the
+    /// only ones the sampler removes.
+    pub fn remove_group(&mut self, group: usize) -> Move {
+        for u in self.groups.iter_mut() {
+            *u = remove_bit_at(*u, group, self.num_groups as u32);
+        }
+        self.nodes_in.remove_row(group);
+        self.nodes_out.remove_row(group);
+        self.group_size.remove(group);
+        self.num_groups -= 1;
+
+        Move::RemoveGroup { group }
+    }
+
+    /// remove the member at `idx` from `group`, unchecked, see `apply`
+    pub fn remove_node_from_group_by_idx(&mut self, group: usize, idx: usize) -> Move {
+        let n_out = self.num_nodes - self.group_size[group];
+
+        let node = self.nodes_in[(group, idx)] as usize;
+        self.nodes_in[(group, idx)] = self.nodes_in[(group, self.group_size[group] - 1)];
+        self.nodes_out[(group, n_out)] = node as Node;
+        let old_state = self.groups[node];
+        self.groups[node] -= 1u64 << group;
+        self.group_size[group] -= 1;
+
+        Move::RemoveNodeFromGroup {
+            group,
+            node,
+            idx,
+            old_state,
+        }
+    }
+
+    /// add the non-member at `idx` to `group`, unchecked, see `apply`
+    pub fn add_node_to_group_by_idx(&mut self, group: usize, idx: usize) -> Move {
+        let n_out = self.num_nodes - self.group_size[group];
+
+        let node = self.nodes_out[(group, idx)] as usize;
+        self.nodes_out[(group, idx)] = self.nodes_out[(group, n_out - 1)];
+        self.nodes_in[(group, self.group_size[group])] = node as Node;
+        let old_state = self.groups[node];
+        self.groups[node] += 1u64 << group;
+        self.group_size[group] += 1;
+
+        Move::AddNodeToGroup {
+            group,
+            node,
+            idx,
+            old_state,
+        }
+    }
+
+    /// Perform move `m` again, e.g. when replaying a recorded trace.
+    /// Returns the move as performed, which matches `m` if the model is in the same state
+    /// as when `m` was recorded.
+    pub fn redo_move(&mut self, m: Move) -> Move {
+        match m {
+            Move::AddGroup { group } => self.add_group(group),
+            Move::RemoveGroup { group } => self.remove_group(group),
+            Move::AddNodeToGroup { group, idx, .. } => self.add_node_to_group_by_idx(group, idx),
+            Move::RemoveNodeFromGroup { group, idx, .. } => {
+                self.remove_node_from_group_by_idx(group, idx)
+            }
+        }
+    }
+
+    /// why `m` cannot be made: the root group 0 is never added, removed or changed, groups
+    /// are within `num_groups` (`max_groups` for additions), only empty groups are removed
+    /// (so that `undo_move` can restore them) and `idx` is within the members or non-members
+    pub fn check_move(&self, m: Move) -> Result<(), String> {
+        let (group, groups) = match m {
+            Move::AddGroup { group } => (group, self.num_groups + 1),
+            Move::RemoveGroup { group }
+            | Move::AddNodeToGroup { group, .. }
+            | Move::RemoveNodeFromGroup { group, .. } => (group, self.num_groups),
+        };
+        if group == 0 || group >= groups {
+            return Err(format!(
+                "group {} is not between 1 and {}",
+                group,
+                groups - 1
+            ));
+        }
+        match m {
+            Move::AddGroup { .. } if self.num_groups == self.max_groups => Err(format!(
+                "cannot add a group to the maximum of {}",
+                self.max_groups
+            )),
+            Move::RemoveGroup { group } if self.group_size[group] > 0 => Err(format!(
+                "group {} has {} members, only empty groups are removed",
+                group, self.group_size[group]
+            )),
+            Move::AddNodeToGroup { group, idx, .. }
+                if idx >= self.num_nodes - self.group_size[group] =>
+            {
+                Err(format!(
+                    "idx {} beyond the {} non-members of group {}",
+                    idx,
+                    self.num_nodes - self.group_size[group],
+                    group
+                ))
+            }
+            Move::RemoveNodeFromGroup { group, idx, .. } if idx >= self.group_size[group] => {
+                Err(format!(
+                    "idx {} beyond the {} members of group {}",
+                    idx, self.group_size[group], group
+                ))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// make move `m` if `check_move` allows it. Returns the move as made, with the `node` and
+    /// `old_state` of node moves filled in; those of `m` are ignored.
+    pub fn apply(&mut self, m: Move) -> Result<Move, String> {
+        self.check_move(m)?;
+        Ok(self.redo_move(m))
+    }
+
+    /// Undo group modifications of move `m`, the last move made.
+    /// Undoing moves in reverse order restores every group with its members in their order.
+    /// So are the non-members, unless a removed group is restored after `add_group` reused
+    /// its rows, which lists them in `node_order`.
+    /// Does *not* restore log likelihood or hcg values.
+    pub fn undo_move(&mut self, m: Move) {
+        match m {
+            Move::RemoveNodeFromGroup {
+                group, node, idx, ..
+            } => {
+                // TODO: can this be unified with MultiGroupModel::add_node_to_group_by_idx?
+                // move the member that took the place of `node` back to the end
+                self.nodes_in[(group, self.group_size[group])] = self.nodes_in[(group, idx)];
+                self.group_size[group] += 1;
+                let n_out = self.num_nodes - self.group_size[group];
+                // `node` was appended to the non-members, unless the group was removed and
+                // restored in the meantime with its non-members in another order
+                if self.nodes_out[(group, n_out)] != node as Node {
+                    let row = &mut self.nodes_out[group];
+                    let pos = row[..n_out].iter().position(|&v| v == node as Node);
+                    row.swap(pos.expect("node is not a non-member"), n_out);
+                }
+                self.nodes_out[(group, n_out)] = Node::MAX;
+                self.nodes_in[(group, idx)] = node as Node;
+                self.groups[node] += 1u64 << group;
+            }
+            Move::RemoveGroup { group } => {
+                // the rows of the group are the last freed, and still list the non-members in
+                // the order they had, which `add_group` would reset
+                self.insert_empty_group(group);
+            }
+            Move::AddGroup { group } => {
+                self.remove_group(group);
+            }
+            Move::AddNodeToGroup {
+                group, node, idx, ..
+            } => {
+                // TODO: can this be unified with MultiGroupModel::remove_node_from_group_by_idx?
+                self.group_size[group] -= 1;
+                self.nodes_in[(group, self.group_size[group])] = Node::MAX;
+                let n_out = self.num_nodes - self.group_size[group];
+                self.nodes_out[(group, n_out - 1)] = self.nodes_out[(group, idx)];
+                self.nodes_out[(group, idx)] = node as Node;
+                self.groups[node] -= 1u64 << group;
+            }
+        }
+    }
+}
+
+impl MultiGroupModel {
+    fn fmt_subtree(
+        &self,
+        f: &mut core::fmt::Formatter,
+        children: &[Vec<usize>],
+        group: usize,
+        depth: usize,
+    ) -> core::fmt::Result {
+        write!(
+            f,
+            "{:indent$}group {} ({} nodes)",
+            "",
+            group,
+            self.group_size[group],
+            indent = 2 * depth
+        )?;
+        if f.alternate() {
+            let mut members = self.members(group).to_vec();
+            members.sort_unstable();
+            write!(f, ": {:?}", members)?;
+        }
+        writeln!(f)?;
+        for &child in &children[group] {
+            self.fmt_subtree(f, children, child, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// nested group tree with group sizes. The alternate form (`{:#}`) also lists member nodes.
+impl core::fmt::Display for MultiGroupModel {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let mut children = vec![Vec::new(); self.num_groups];
+        for (g, parent) in self.parent_groups().into_iter().enumerate() {
+            if let Some(p) = parent {
+                children[p].push(g);
+            }
+        }
+        if self.num_groups > 0 {
+            self.fmt_subtree(f, &children, 0, 0)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn _test_model() -> MultiGroupModel {
+        MultiGroupModel::with_groups(
+            vec![
+                9, 41, 25, 13, 73, 137, 11, 33, 17, 5, 65, 129, 3, 33, 33, 17, 17, 5, 5, 65, 65,
+                129, 129, 3, 3,
+            ],
+            8,
+            64,
+        )
+    }
+
+    #[test]
+    fn validation() {
+        assert_eq!(validate_groups(&[1, 3, 7, 5], 3, false), Ok(()));
+        assert_eq!(
+            validate_groups(&[1, 2, 3], 2, false),
+            Err(String::from("node 1: not in the root group (2)"))
+        );
+        assert_eq!(
+            validate_groups(&[1, 3, 9], 3, false),
+            Err(String::from("node 2: in group 3, beyond the 3 groups (9)"))
+        );
+        // group 3 inside 1, 4 disjoint from both
+        assert_eq!(validate_groups(&[1, 3, 11, 17, 17], 5, true), Ok(()));
+        assert_eq!(
+            validate_groups(&[1, 3, 7, 5], 3, true),
+            Err(String::from(
+                "node 1: in group 1 but not 2, which overlap at node 2 while node 3 is only in \
+                 group 2"
+            ))
+        );
+    }
+
+    #[test]
+    fn group_bits() {
+        assert_eq!(encode_groups(&[]), 1);
+        assert_eq!(encode_groups(&[2, 3]), 0b1101);
+        assert_eq!(encode_groups(&[0, 63]), 1 | 1 << 63);
+        assert_eq!(decode_groups(0b1101, 5), [true, false, true, true, false]);
+        assert_eq!(decode_groups(encode_groups(&[1]), 2), [true, true]);
+        assert!(!decode_groups(u64::MAX, 65)[64]);
+    }
+
+    #[test]
+    fn display() {
+        let model = MultiGroupModel::with_groups(vec![1, 3, 3, 7, 7, 9], 4, 64);
+        assert_eq!(model.parent_groups(), [None, Some(0), Some(1), Some(0)]);
+        assert_eq!(model.group_depths(), [0, 1, 2, 1]);
+        assert_eq!(model.deepest_groups(), [0, 1, 1, 2, 2, 3]);
+        assert_eq!(
+            format!("{}", model),
+            "group 0 (6 nodes)\n  group 1 (4 nodes)\n    group 2 (2 nodes)\n  group 3 (1 nodes)\n"
+        );
+        assert!(format!("{:#}", model).contains("group 2 (2 nodes): [3, 4]"));
+    }
+
+    #[test]
+    fn node_order() {
+        let order: Vec<Node> = (0..4).rev().collect();
+        let mut model = MultiGroupModel::with_groups_in_order(vec![1, 3, 1, 3], 2, 4, order);
+        assert_eq!(model.members(1), [3, 1]);
+        let moved = |m: Move| crate::trace::moved_node(&m);
+        assert_eq!(moved(model.add_node_to_group_by_idx(1, 0)), Some(2));
+        model.add_group(2);
+        assert_eq!(moved(model.add_node_to_group_by_idx(2, 0)), Some(3));
+    }
+
+    #[test]
+    fn add_group() {
+        let mut model = _test_model();
+        let g = 1;
+        let old = model.clone();
+        let op = model.add_group(g);
+        assert_eq!(model.num_groups, old.num_groups + 1);
+        assert_eq!(
+            model.group_size.iter().sum::<usize>(),
+            old.group_size.iter().sum()
+        );
+        assert_eq!(model.group_size[g], 0);
+
+        let mut undone = model.clone();
+        undone.undo_move(op);
+        assert_eq!(old.num_groups, undone.num_groups);
+        assert_eq!(old.group_size, undone.group_size);
+        assert_eq!(old.groups, undone.groups);
+    }
+    #[test]
+    fn remove_group() {
+        let mut model = _test_model();
+        let g = 1;
+        let old = model.clone();
+        model.remove_group(g);
+        assert_eq!(model.num_groups, old.num_groups - 1);
+        assert_eq!(
+            model.group_size.iter().sum::<usize>(),
+            old.group_size.iter().sum::<usize>() - old.group_size[g]
+        );
+
+        // empty the group first, leaving its non-members in a different order than new groups
+        let mut model = _test_model();
+        while model.group_size(g) > 0 {
+            model.remove_node_from_group_by_idx(g, 0);
+        }
+        let old = model.clone();
+        let op = model.remove_group(g);
+        let mut undone = model.clone();
+        undone.undo_move(op);
+        assert_same(&undone, &old);
+        assert_eq!(undone.non_members(g), old.non_members(g));
+    }
+
+    /// the same groups with the same members in the same order, and the same non-members
+    fn assert_same(a: &MultiGroupModel, b: &MultiGroupModel) {
+        assert_eq!(a.num_groups, b.num_groups);
+        assert_eq!(a.groups, b.groups);
+        assert_eq!(a.group_size, b.group_size);
+        for g in 0..a.num_groups {
+            assert_eq!(a.members(g), b.members(g), "members of {}", g);
+            let sorted = |model: &MultiGroupModel| {
+                let mut non_members = model.non_members(g).to_vec();
+                non_members.sort_unstable();
+                non_members
+            };
+            assert_eq!(sorted(a), sorted(b), "non-members of {}", g);
+        }
+    }
+
+    /// apply the move made by `make` and record it with the state before it
+    fn record(
+        model: &mut MultiGroupModel,
+        history: &mut Vec<(Move, MultiGroupModel)>,
+        make: impl FnOnce(&mut MultiGroupModel) -> Move,
+    ) {
+        let before = model.clone();
+        history.push((make(model), before));
+    }
+
+    /// add group 1, add nodes to it, empty it and remove it again
+    fn fill_and_remove_group(
+        model: &mut MultiGroupModel,
+        history: &mut Vec<(Move, MultiGroupModel)>,
+    ) {
+        record(model, history, |m| m.add_group(1));
+        for idx in [2, 0, 3] {
+            record(model, history, |m| m.add_node_to_group_by_idx(1, idx));
+        }
+        while model.group_size(1usize) > 0 {
+            record(model, history, |m| {
+                m.remove_node_from_group_by_idx(1, m.group_size(1usize) / 2)
+            });
+        }
+        record(model, history, |m| m.remove_group(1));
+    }
+
+    #[test]
+    fn undo_sequences() {
+        // xorshift, to draw moves without a random number generator
+        let mut state = 0x9e3779b97f4a7c15u64;
+        let mut draw = |n: usize| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state % n as u64) as usize
+        };
+        for _ in 0..20 {
+            let mut model = MultiGroupModel::with_groups(vec![1, 3, 3, 5, 7, 1, 1, 1], 3, 6);
+            let n = model.num_nodes();
+            let mut history = Vec::new();
+            fill_and_remove_group(&mut model, &mut history);
+            for _ in 0..200 {
+                let g = 1 + draw(model.num_groups().max(2) - 1);
+                let valid = g < model.num_groups();
+                match draw(4) {
+                    0 if model.num_groups() < model.max_groups() => {
+                        let g = 1 + draw(model.num_groups());
+                        record(&mut model, &mut history, |m| m.add_group(g));
+                    }
+                    1 if valid && model.group_size(g) == 0 => {
+                        record(&mut model, &mut history, |m| m.remove_group(g));
+                    }
+                    2 if valid && model.group_size(g) < n => {
+                        let idx = draw(n - model.group_size(g));
+                        record(&mut model, &mut history, |m| {
+                            m.add_node_to_group_by_idx(g, idx)
+                        });
+                    }
+                    3 if valid && model.group_size(g) > 0 => {
+                        let idx = draw(model.group_size(g));
+                        record(&mut model, &mut history, |m| {
+                            m.remove_node_from_group_by_idx(g, idx)
+                        });
+                    }
+                    _ => {}
+                }
+            }
+            if model.num_groups() < model.max_groups() {
+                fill_and_remove_group(&mut model, &mut history);
+            }
+            for (m, before) in history.into_iter().rev() {
+                model.undo_move(m);
+                assert_same(&model, &before);
+            }
+        }
+    }
+
+    #[test]
+    fn reuse_rows() {
+        let mut model = MultiGroupModel::with_groups(vec![1, 3, 3, 1], 2, 4);
+        model.add_group(1);
+        model.add_group(3);
+        model.add_node_to_group_by_idx(3, 0);
+        model.remove_group(1);
+        assert_eq!(model.num_groups(), 3);
+        assert_eq!(model.members(1), [1, 2]);
+        assert_eq!(model.members(2), [0]);
+        assert_eq!(model.non_members(2), [3, 1, 2]);
+
+        // the row freed above is cleared when it is used again
+        model.add_group(1);
+        assert_eq!(model.members(1), []);
+        assert_eq!(model.non_members(1), [0, 1, 2, 3]);
+        assert_eq!(model.members(3), [0]);
+    }
+
+    #[test]
+    fn apply() {
+        let mut model = MultiGroupModel::with_groups(vec![1, 3, 3, 1], 3, 4);
+        let node_move = |group, idx| Move::AddNodeToGroup {
+            group,
+            node: 0,
+            idx,
+            old_state: 0,
+        };
+        for (m, error) in [
+            (
+                Move::AddGroup { group: 0 },
+                "group 0 is not between 1 and 3",
+            ),
+            (
+                Move::AddGroup { group: 4 },
+                "group 4 is not between 1 and 3",
+            ),
+            (
+                Move::RemoveGroup { group: 1 },
+                "group 1 has 2 members, only empty groups are removed",
+            ),
+            (
+                Move::RemoveGroup { group: 3 },
+                "group 3 is not between 1 and 2",
+            ),
+            (node_move(0, 0), "group 0 is not between 1 and 2"),
+            (node_move(1, 2), "idx 2 beyond the 2 non-members of group 1"),
+        ] {
+            assert_eq!(model.apply(m), Err(String::from(error)));
+        }
+        assert_eq!(model.groups(), [1, 3, 3, 1]);
+
+        // node and old_state are filled in
+        assert_eq!(model.non_members(1), [0, 3]);
+        assert_eq!(
+            model.apply(node_move(1, 1)),
+            Ok(Move::AddNodeToGroup {
+                group: 1,
+                node: 3,
+                idx: 1,
+                old_state: 1
+            })
+        );
+        assert!(model.apply(Move::RemoveGroup { group: 2 }).is_ok());
+        model.apply(Move::AddGroup { group: 1 }).unwrap();
+        model.apply(Move::AddGroup { group: 1 }).unwrap();
+        assert_eq!(
+            model.apply(Move::AddGroup { group: 1 }),
+            Err(String::from("cannot add a group to the maximum of 4"))
+        );
+        let remove = Move::RemoveNodeFromGroup {
+            group: 3,
+            node: 0,
+            idx: 3,
+            old_state: 0,
+        };
+        assert_eq!(
+            model.apply(remove),
+            Err(String::from("idx 3 beyond the 3 members of group 3"))
+        );
+    }
+
+    #[test]
+    fn redo_move() {
+        let mut model = _test_model();
+        let old = model.clone();
+        let op = model.add_node_to_group_by_idx(2, 4);
+        let mut redone = old.clone();
+        assert_eq!(redone.redo_move(op), op);
+        assert_eq!(redone.groups, model.groups);
+        assert_eq!(redone.group_size, model.group_size);
+    }
+
+    #[test]
+    fn add_node_to_group_by_idx() {
+        let mut model = _test_model();
+        let g = 1;
+        let idx = 3;
+        let old = model.clone();
+        let op = model.add_node_to_group_by_idx(g, idx);
+        assert_eq!(model.num_groups, old.num_groups);
+        match op {
+            Move::AddNodeToGroup { node, .. } => assert!(model.groups[node] & (1 << g) != 0),
+            _ => panic!("not an add_node_to_group operation"),
+        }
+        assert_eq!(
+            model.group_size.iter().sum::<usize>(),
+            old.group_size.iter().sum::<usize>() + 1
+        );
+        assert_eq!(model.group_size[g], old.group_size[g] + 1);
+
+        let mut undone = model.clone();
+        undone.undo_move(op);
+        assert_eq!(old.num_groups, undone.num_groups);
+        assert_eq!(old.group_size, undone.group_size);
+        assert_eq!(old.groups, undone.groups);
+    }
+    #[test]
+    fn remove_node_from_group_by_idx() {
+        let mut model = _test_model();
+
+        let g = 1;
+        let idx = 3;
+        let old = model.clone();
+        let op = model.remove_node_from_group_by_idx(g, idx);
+        assert_eq!(model.num_groups, old.num_groups);
+        match op {
+            Move::RemoveNodeFromGroup { node, .. } => assert!(model.groups[node] & (1 << g) == 0),
+            _ => panic!("not an remove_node_from_group operation"),
+        }
+        assert_eq!(
+            model.group_size.iter().sum::<usize>(),
+            old.group_size.iter().sum::<usize>() - 1
+        );
+        assert_eq!(model.group_size[g], old.group_size[g] - 1);
+
+        let mut undone = model.clone();
+        undone.undo_move(op);
+        assert_eq!(old.num_groups, undone.num_groups);
+        assert_eq!(old.group_size, undone.group_size);
+        assert_eq!(old.groups, undone.groups);
+    }
+}
@@ -0,0 +1,168 @@
+//! the network as the sampler sees it: nodes, neighbors and optional labels
+use super::multi_group_model::Node;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+
+/// undirected network in compressed sparse row form.
+/// Meant to be shared between chains behind an `Arc`; edges are only inserted or removed
+/// between runs, at O(number of edges) each.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NetworkData {
+    /// `neighbors[offsets[u]..offsets[u + 1]]` are the neighbors of `u`
+    offsets: Vec<usize>,
+    neighbors: Vec<Node>,
+    num_edges: usize,
+    /// name of every node, empty if the input has none
+    labels: Vec<String>,
+}
+
+impl NetworkData {
+    /// build from an undirected edge list. Self-loops are dropped, multi-edges are kept.
+    pub fn from_edges(num_nodes: usize, edges: impl IntoIterator<Item = (Node, Node)>) -> Self {
+        let edges: Vec<(Node, Node)> = edges.into_iter().filter(|(u, v)| u != v).collect();
+
+        let mut offsets = vec![0; num_nodes + 1];
+        for &(u, v) in &edges {
+            offsets[u as usize + 1] += 1;
+            offsets[v as usize + 1] += 1;
+        }
+        for u in 0..num_nodes {
+            offsets[u + 1] += offsets[u];
+        }
+
+        let mut fill = offsets.clone();
+        let mut neighbors = vec![0; offsets[num_nodes]];
+        for &(u, v) in &edges {
+            neighbors[fill[u as usize]] = v;
+            fill[u as usize] += 1;
+            neighbors[fill[v as usize]] = u;
+            fill[v as usize] += 1;
+        }
+
+        Self {
+            offsets,
+            neighbors,
+            num_edges: edges.len(),
+            labels: Vec::new(),
+        }
+    }
+
+    /// attach a name to every node
+    pub fn with_labels(self, labels: Vec<String>) -> Result<Self, String> {
+        if labels.len() != self.node_count() {
+            return Err(format!(
+                "{} labels for {} nodes",
+                labels.len(),
+                self.node_count()
+            ));
+        }
+        Ok(Self { labels, ..self })
+    }
+
+    pub fn node_count(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    pub fn edge_count(&self) -> usize {
+        self.num_edges
+    }
+
+    pub fn neighbors(&self, u: Node) -> &[Node] {
+        &self.neighbors[self.offsets[u as usize]..self.offsets[u as usize + 1]]
+    }
+
+    /// name of node `u`, if the input had node labels
+    pub fn label(&self, u: Node) -> Option<&str> {
+        self.labels.get(u as usize).map(String::as_str)
+    }
+
+    /// whether some pair of nodes is joined by more than one edge
+    pub fn has_multi_edges(&self) -> bool {
+        let mut neighbors = Vec::new();
+        (0..self.node_count() as Node).any(|u| {
+            neighbors.clear();
+            neighbors.extend_from_slice(self.neighbors(u));
+            neighbors.sort_unstable();
+            neighbors.windows(2).any(|w| w[0] == w[1])
+        })
+    }
+
+    pub fn degree(&self, u: Node) -> usize {
+        self.offsets[u as usize + 1] - self.offsets[u as usize]
+    }
+
+    /// add the edge `{u, v}`, also if the nodes are already joined
+    pub fn insert_edge(&mut self, u: Node, v: Node) {
+        for (a, b) in [(u, v), (v, u)] {
+            self.neighbors.insert(self.offsets[a as usize + 1], b);
+            for o in &mut self.offsets[a as usize + 1..] {
+                *o += 1;
+            }
+        }
+        self.num_edges += 1;
+    }
+
+    /// remove one edge `{u, v}`. Returns whether there was one.
+    pub fn remove_edge(&mut self, u: Node, v: Node) -> bool {
+        if !self.neighbors(u).contains(&v) {
+            return false;
+        }
+        for (a, b) in [(u, v), (v, u)] {
+            let i =
+                self.offsets[a as usize] + self.neighbors(a).iter().position(|&w| w == b).unwrap();
+            self.neighbors.remove(i);
+            for o in &mut self.offsets[a as usize + 1..] {
+                *o -= 1;
+            }
+        }
+        self.num_edges -= 1;
+        true
+    }
+
+    /// the subgraph induced by `nodes`, numbered in their order. The nodes keep their labels,
+    /// or are labeled with their original id.
+    pub fn induced_subgraph(&self, nodes: &[Node]) -> Result<Self, String> {
+        let mut id = vec![Node::MAX; self.node_count()];
+        for (i, &u) in nodes.iter().enumerate() {
+            match id.get_mut(u as usize) {
+                None => return Err(format!("node {} does not exist", u)),
+                Some(&mut Node::MAX) => id[u as usize] = i as Node,
+                Some(_) => return Err(format!("node {} is listed twice", u)),
+            }
+        }
+        let edges = self
+            .edges()
+            .map(|(u, v)| (id[u as usize], id[v as usize]))
+            .filter(|&(u, v)| u != Node::MAX && v != Node::MAX);
+        let labels = nodes
+            .iter()
+            .map(|&u| self.label(u).map_or(format!("{}", u), String::from))
+            .collect();
+        Ok(Self {
+            labels,
+            ..Self::from_edges(nodes.len(), edges)
+        })
+    }
+
+    /// the network without nodes of degree 0 and the original id of every remaining node,
+    /// see `induced_subgraph`
+    pub fn without_isolated_nodes(&self) -> (Self, Vec<Node>) {
+        let kept: Vec<Node> = (0..self.node_count() as Node)
+            .filter(|&u| self.degree(u) > 0)
+            .collect();
+        // the kept nodes exist and are distinct
+        (self.induced_subgraph(&kept).unwrap(), kept)
+    }
+
+    /// every edge exactly once, as `(u, v)` with `u < v`
+    pub fn edges(&self) -> impl Iterator<Item = (Node, Node)> + '_ {
+        (0..self.node_count() as Node).flat_map(move |u| {
+            self.neighbors(u)
+                .iter()
+                .filter(move |&&v| u < v)
+                .map(move |&v| (u, v))
+        })
+    }
+}
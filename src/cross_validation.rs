@@ -0,0 +1,431 @@
+//! cross-validation. Node-level: hide every edge incident to a random set of nodes, fit the
+//! remaining network, then place each hidden node greedily into the fitted hierarchy using half
+//! of its pairs and score how well the other half is predicted. Edge-level: hide a set of
+//! edges, fit the rest and score the hidden edges against as many random non-edges. Uniform
+//! edge holdout disconnects low-degree nodes of sparse graphs and biases the AUC; the
+//! stratified scheme hides about the same fraction of the edges of every node instead and
+//! never isolates one.
+use crate::multi_group_model::{Groups, Node};
+use crate::network::NetworkData;
+use crate::parameters::{HoldoutScheme, Parameters};
+use crate::rng::DefaultRng;
+use crate::seeds::{SeedStream, Stream};
+use crate::HierarchicalModel;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use std::sync::Arc;
+
+/// a network with all edges incident to `nodes` removed
+#[derive(Debug, Clone)]
+pub struct NodeHoldout {
+    pub nodes: Vec<Node>,
+    pub train: NetworkData,
+}
+
+/// hold out `fraction` of the nodes (at least one unless the network is empty), chosen with
+/// `seed`
+pub fn hold_out_nodes(network: &NetworkData, fraction: f64, seed: u64) -> NodeHoldout {
+    let mut rng = <DefaultRng as SeedableRng>::seed_from_u64(seed);
+    let n = network.node_count();
+    let mut nodes: Vec<Node> = (0..n as Node).collect();
+    nodes.shuffle(&mut rng);
+    nodes.truncate(((fraction * n as f64).round() as usize).clamp(1.min(n), n));
+    nodes.sort_unstable();
+
+    let mut hidden = vec![false; n];
+    for &u in &nodes {
+        hidden[u as usize] = true;
+    }
+    let train = NetworkData::from_edges(
+        n,
+        network
+            .edges()
+            .filter(|&(u, v)| !hidden[u as usize] && !hidden[v as usize]),
+    );
+    NodeHoldout { nodes, train }
+}
+
+/// a network with the edges `hidden` removed
+#[derive(Debug, Clone)]
+pub struct EdgeHoldout {
+    pub hidden: Vec<(Node, Node)>,
+    pub train: NetworkData,
+}
+
+/// hold out `fraction` of the edges, chosen with `seed`. `stratified`: hide up to
+/// `fraction` of the edges of every node, rounded, but never the last one.
+pub fn hold_out_edges(
+    network: &NetworkData,
+    fraction: f64,
+    stratified: bool,
+    seed: u64,
+) -> EdgeHoldout {
+    let mut rng = <DefaultRng as SeedableRng>::seed_from_u64(seed);
+    let mut edges: Vec<(Node, Node)> = network.edges().collect();
+    edges.shuffle(&mut rng);
+    let mut hidden = Vec::new();
+    let mut train = Vec::new();
+    if stratified {
+        let budget: Vec<usize> = (0..network.node_count() as Node)
+            .map(|u| {
+                let degree = network.degree(u);
+                ((fraction * degree as f64).round() as usize).min(degree.saturating_sub(1))
+            })
+            .collect();
+        let mut removed = vec![0; network.node_count()];
+        for (u, v) in edges {
+            let (iu, iv) = (u as usize, v as usize);
+            if removed[iu] < budget[iu] && removed[iv] < budget[iv] {
+                removed[iu] += 1;
+                removed[iv] += 1;
+                hidden.push((u, v));
+            } else {
+                train.push((u, v));
+            }
+        }
+    } else {
+        let num_hidden = ((fraction * edges.len() as f64).round() as usize).min(edges.len());
+        hidden.extend_from_slice(&edges[..num_hidden]);
+        train.extend_from_slice(&edges[num_hidden..]);
+    }
+    hidden.sort_unstable();
+    EdgeHoldout {
+        hidden,
+        train: NetworkData::from_edges(network.node_count(), train),
+    }
+}
+
+/// predictive quality on the evaluation pairs of the held-out nodes or edges
+#[derive(Debug, Clone, PartialEq)]
+pub struct CvScore {
+    pub scheme: HoldoutScheme,
+    /// number of held-out nodes or edges
+    pub held_out: usize,
+    pub num_pairs: usize,
+    /// mean log-probability of the evaluation pairs
+    pub log_like_per_pair: f64,
+    /// probability that a hidden edge is ranked above a hidden non-edge
+    pub auc: f64,
+}
+
+impl std::fmt::Display for CvScore {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let held_out = match self.scheme {
+            HoldoutScheme::Nodes => "nodes",
+            HoldoutScheme::Edges | HoldoutScheme::StratifiedEdges => "edges",
+        };
+        writeln!(
+            f,
+            "held out {}: {} evaluation pairs: {}",
+            held_out, self.held_out, self.num_pairs
+        )?;
+        writeln!(
+            f,
+            "log-likelihood per pair: {:.4} AUC: {:.4}",
+            self.log_like_per_pair, self.auc
+        )
+    }
+}
+
+/// highest common group of a node in `groups` and a node in `other`
+fn hcg(groups: Groups, other: Groups) -> usize {
+    63 - (groups & other).leading_zeros() as usize
+}
+
+/// edge probability of each group, smoothed so that it is never 0 or 1
+fn group_probabilities<F: crate::Float>(hcp: &HierarchicalModel<F>) -> Vec<f64> {
+    std::iter::zip(hcp.hcg_edges(), hcp.hcg_pairs())
+        .map(|(&e, &p)| (e as f64 + 1.0) / (p as f64 + 2.0))
+        .collect()
+}
+
+fn pair_log_like(p: f64, edge: bool) -> f64 {
+    if edge {
+        p.ln()
+    } else {
+        (1.0 - p).ln()
+    }
+}
+
+/// groups for a node with the given `pairs` (other node's groups, edge), starting in the root
+/// group and adding the group that improves the likelihood most until none does
+fn place_greedily(pairs: &[(Groups, bool)], probabilities: &[f64]) -> Groups {
+    let log_like = |groups: Groups| -> f64 {
+        pairs
+            .iter()
+            .map(|&(other, edge)| pair_log_like(probabilities[hcg(groups, other)], edge))
+            .sum()
+    };
+    let mut groups = 1;
+    let mut best = log_like(groups);
+    loop {
+        let candidate = (1..probabilities.len())
+            .filter(|g| groups & (1 << g) == 0)
+            .map(|g| (groups | (1 << g), log_like(groups | (1 << g))))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        match candidate {
+            Some((c, l)) if l > best => {
+                groups = c;
+                best = l;
+            }
+            _ => return groups,
+        }
+    }
+}
+
+/// area under the ROC curve of (score, is positive) pairs, counting ties as half
+fn auc(mut scored: Vec<(f64, bool)>) -> f64 {
+    scored.sort_by(|a, b| a.0.total_cmp(&b.0));
+    let num_pos = scored.iter().filter(|s| s.1).count();
+    let num_neg = scored.len() - num_pos;
+    if num_pos == 0 || num_neg == 0 {
+        return 0.5;
+    }
+    // sum of the (tie averaged) ranks of the positives
+    let mut rank_sum = 0.0;
+    let mut i = 0;
+    while i < scored.len() {
+        let j = i + scored[i..]
+            .iter()
+            .take_while(|s| s.0 == scored[i].0)
+            .count();
+        let mean_rank = (i + j + 1) as f64 / 2.0;
+        rank_sum += mean_rank * scored[i..j].iter().filter(|s| s.1).count() as f64;
+        i = j;
+    }
+    (rank_sum - (num_pos * (num_pos + 1)) as f64 / 2.0) / (num_pos * num_neg) as f64
+}
+
+/// place the held-out nodes into the hierarchy fitted by `hcp` and score their pairs with all
+/// other nodes that were not held out. Pairs are split at random (with `seed`) into a half
+/// used for placement and a half used for evaluation.
+pub fn score_node_holdout<F: crate::Float>(
+    hcp: &HierarchicalModel<F>,
+    holdout: &NodeHoldout,
+    network: &NetworkData,
+    seed: u64,
+) -> CvScore {
+    let mut rng = <DefaultRng as SeedableRng>::seed_from_u64(seed);
+    let probabilities = group_probabilities(hcp);
+    let groups = hcp.model().groups();
+    let mut hidden = vec![false; network.node_count()];
+    for &u in &holdout.nodes {
+        hidden[u as usize] = true;
+    }
+
+    let mut evaluation = Vec::new();
+    for &u in &holdout.nodes {
+        let mut is_neighbor = vec![false; network.node_count()];
+        for &v in network.neighbors(u) {
+            is_neighbor[v as usize] = true;
+        }
+        let mut pairs: Vec<(Groups, bool)> = (0..network.node_count())
+            .filter(|&v| !hidden[v])
+            .map(|v| (groups[v], is_neighbor[v]))
+            .collect();
+        pairs.shuffle(&mut rng);
+        let (placement, rest) = pairs.split_at(pairs.len() / 2);
+        let placed = place_greedily(placement, &probabilities);
+        evaluation.extend(
+            rest.iter()
+                .map(|&(other, edge)| (probabilities[hcg(placed, other)], edge)),
+        );
+    }
+
+    score(HoldoutScheme::Nodes, holdout.nodes.len(), evaluation)
+}
+
+/// score the hidden edges and as many pairs that are not edges of `network`, chosen with
+/// `seed`, by their probability under the hierarchy fitted by `hcp`
+pub fn score_edge_holdout<F: crate::Float>(
+    hcp: &HierarchicalModel<F>,
+    holdout: &EdgeHoldout,
+    network: &NetworkData,
+    scheme: HoldoutScheme,
+    seed: u64,
+) -> CvScore {
+    let mut rng = <DefaultRng as SeedableRng>::seed_from_u64(seed);
+    let probabilities = group_probabilities(hcp);
+    let groups = hcp.model().groups();
+    let probability = |u: Node, v: Node| probabilities[hcg(groups[u as usize], groups[v as usize])];
+    let mut evaluation: Vec<(f64, bool)> = holdout
+        .hidden
+        .iter()
+        .map(|&(u, v)| (probability(u, v), true))
+        .collect();
+    let n = network.node_count() as Node;
+    let num_non_edges = (n as usize * (n as usize).saturating_sub(1) / 2) - network.edge_count();
+    for _ in 0..holdout.hidden.len().min(num_non_edges) {
+        let (u, v) = loop {
+            let (u, v) = (rng.gen_range(0..n), rng.gen_range(0..n));
+            if u != v && !network.neighbors(u).contains(&v) {
+                break (u, v);
+            }
+        };
+        evaluation.push((probability(u, v), false));
+    }
+    score(scheme, holdout.hidden.len(), evaluation)
+}
+
+/// score of (probability, is edge) evaluation pairs
+fn score(scheme: HoldoutScheme, held_out: usize, evaluation: Vec<(f64, bool)>) -> CvScore {
+    CvScore {
+        scheme,
+        held_out,
+        num_pairs: evaluation.len(),
+        log_like_per_pair: evaluation
+            .iter()
+            .map(|&(p, edge)| pair_log_like(p, edge))
+            .sum::<f64>()
+            / evaluation.len().max(1) as f64,
+        auc: auc(evaluation),
+    }
+}
+
+/// hold out `params.holdout_fraction` of the nodes or edges of `network` following
+/// `params.holdout`, fit the rest for `params.max_itr` iterations and score what was held out.
+/// Random choices use the `Stream::Holdout` sub-seeds 0 (node or edge choice) and 1 (pair
+/// split or non-edge choice) of `seeds`.
+pub fn cross_validate(
+    params: &Parameters,
+    network: &NetworkData,
+    seeds: &mut SeedStream,
+) -> Result<CvScore, String> {
+    let fit = |train: &NetworkData| -> Result<HierarchicalModel, String> {
+        let mut hcp: HierarchicalModel =
+            HierarchicalModel::with_network(params, Arc::new(train.clone()))?;
+        for _ in 0..params.max_itr {
+            hcp.step();
+        }
+        Ok(hcp)
+    };
+    let seed = seeds.derive(Stream::Holdout, 0);
+    Ok(match params.holdout {
+        HoldoutScheme::Nodes => {
+            let holdout = hold_out_nodes(network, params.holdout_fraction, seed);
+            let hcp = fit(&holdout.train)?;
+            score_node_holdout(&hcp, &holdout, network, seeds.derive(Stream::Holdout, 1))
+        }
+        scheme => {
+            let stratified = scheme == HoldoutScheme::StratifiedEdges;
+            let holdout = hold_out_edges(network, params.holdout_fraction, stratified, seed);
+            let hcp = fit(&holdout.train)?;
+            let seed = seeds.derive(Stream::Holdout, 1);
+            score_edge_holdout(&hcp, &holdout, network, scheme, seed)
+        }
+    })
+}
+
+/// cross-validate every group budget in `max_num_groups` on the same held-out nodes and
+/// return `(max_num_groups, score)`, best predictive log-likelihood first
+pub fn model_select(
+    params: &Parameters,
+    network: &NetworkData,
+    max_num_groups: &[u32],
+    seeds: &mut SeedStream,
+) -> Result<Vec<(u32, CvScore)>, String> {
+    let mut ranked = max_num_groups
+        .iter()
+        .map(|&k| {
+            let budget_params = Parameters {
+                max_num_groups: k,
+                initial_num_groups: params.initial_num_groups.min(k),
+                ..params.clone()
+            };
+            Ok((k, cross_validate(&budget_params, network, seeds)?))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    ranked.sort_by(|a, b| b.1.log_like_per_pair.total_cmp(&a.1.log_like_per_pair));
+    Ok(ranked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn holdout() {
+        let net = NetworkData::from_edges(6, [(0, 1), (1, 2), (2, 0), (3, 4), (4, 5), (5, 3)]);
+        let holdout = hold_out_nodes(&net, 0.2, 3);
+        assert_eq!(holdout.nodes.len(), 1);
+        let u = holdout.nodes[0];
+        assert_eq!(holdout.train.degree(u), 0);
+        assert_eq!(holdout.train.edge_count(), 4);
+
+        let empty = hold_out_nodes(&NetworkData::from_edges(0, []), 0.2, 3);
+        assert!(empty.nodes.is_empty());
+    }
+
+    #[test]
+    fn edge_holdout() {
+        // a star with a triangle: the leaves 4..8 have a single edge
+        let net = NetworkData::from_edges(
+            8,
+            [
+                (0, 1),
+                (1, 2),
+                (2, 0),
+                (0, 3),
+                (0, 4),
+                (0, 5),
+                (0, 6),
+                (0, 7),
+            ],
+        );
+        let uniform = hold_out_edges(&net, 0.5, false, 3);
+        assert_eq!(uniform.hidden.len(), 4);
+        assert_eq!(uniform.train.edge_count(), 4);
+
+        for seed in 0..10 {
+            let stratified = hold_out_edges(&net, 0.5, true, seed);
+            assert!((0..8).all(|u| stratified.train.degree(u) > 0));
+            assert!(stratified
+                .hidden
+                .iter()
+                .all(|&(u, v)| net.neighbors(u).contains(&v)));
+            assert_eq!(
+                stratified.hidden.len() + stratified.train.edge_count(),
+                net.edge_count()
+            );
+        }
+        // node 0 may lose 4 of its 7 edges, nodes 1 and 2 one each, the leaves none
+        let stratified = hold_out_edges(&net, 0.5, true, 1);
+        assert!(!stratified.hidden.is_empty());
+        assert!(stratified.hidden.iter().all(|&(u, v)| u.max(v) < 3));
+    }
+
+    #[test]
+    fn placement() {
+        // two groups below the root: group 1 is dense, group 2 is sparse
+        let probabilities = [0.1, 0.9, 0.1];
+        let pairs = [(0b011, true), (0b011, true), (0b101, false), (0b001, false)];
+        assert_eq!(place_greedily(&pairs, &probabilities), 0b011);
+    }
+
+    #[test]
+    fn roc() {
+        assert_eq!(auc(vec![(0.9, true), (0.1, false)]), 1.0);
+        assert_eq!(auc(vec![(0.1, true), (0.9, false)]), 0.0);
+        assert_eq!(auc(vec![(0.5, true), (0.5, false)]), 0.5);
+        assert_eq!(
+            auc(vec![(0.8, true), (0.5, false), (0.5, true), (0.2, false)]),
+            0.875
+        );
+    }
+
+    #[test]
+    fn ranking() {
+        let params = Parameters {
+            max_itr: 2000,
+            seed: Some(1),
+            quiet: true,
+            holdout_fraction: 0.25,
+            ..Parameters::load(std::io::empty()).unwrap()
+        };
+        let net = NetworkData::from_edges(8, [(0, 1), (1, 2), (2, 3), (3, 0), (0, 2), (4, 5)]);
+        let ranked = model_select(&params, &net, &[2, 4], &mut SeedStream::new(1)).unwrap();
+        assert_eq!(ranked.len(), 2);
+        assert!(ranked[0].1.log_like_per_pair >= ranked[1].1.log_like_per_pair);
+        assert_eq!(ranked[0].1.held_out, 2);
+    }
+}
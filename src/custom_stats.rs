@@ -0,0 +1,257 @@
+//! user defined scalar statistics, computed from the sampler every few iterations without
+//! storing the configurations they are derived from.
+//! ```no_run
+//! # use hcp_rs::{custom_stats::StatAccumulator, parameters::Parameters, HierarchicalModel};
+//! # let parameters = Parameters::load(&b""[..]).unwrap();
+//! let mut hcp: HierarchicalModel = HierarchicalModel::with_parameters(&parameters).unwrap();
+//! let mut stats = StatAccumulator::new();
+//! stats.register("root_density", 100, |hcp: &HierarchicalModel| hcp.group_density(0));
+//! for i in 0..parameters.max_itr {
+//!     hcp.step();
+//!     stats.observe(i, &hcp);
+//! }
+//! ```
+//! Each statistic is written to `{name}_{stat}.txt` with one `<iteration> <value>` line per
+//! evaluation. The `custom_stats` parameter records statistics of `BUILTIN` in the main run,
+//! written to `{name}_custom_{stat}.txt`.
+use crate::checkpoint::{Decoder, Encoder};
+use crate::likelihood::{BetaBinomial, Likelihood};
+use crate::output;
+use crate::parameters::{CustomStats, FloatFormat, OutputCompression};
+use crate::{Float, HierarchicalModel};
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+/// statistics the `custom_stats` parameter can name
+pub const BUILTIN: [&str; 6] = [
+    "log_like",
+    "num_groups",
+    "root_density",
+    "within_group_edges",
+    "entropy",
+    "dl",
+];
+
+type StatFn<F, L> = Box<dyn FnMut(&HierarchicalModel<F, L>) -> f64 + Send + Sync>;
+
+/// a named statistic and its trace
+struct Stat<F: Float, L: Likelihood> {
+    name: String,
+    interval: u64,
+    f: StatFn<F, L>,
+    iterations: Vec<u64>,
+    values: Vec<f64>,
+}
+
+/// statistics registered with `register`, evaluated by `observe`
+pub struct StatAccumulator<F: Float = f64, L: Likelihood = BetaBinomial> {
+    stats: Vec<Stat<F, L>>,
+}
+
+impl<F: Float, L: Likelihood> Default for StatAccumulator<F, L> {
+    fn default() -> Self {
+        Self { stats: Vec::new() }
+    }
+}
+
+impl<F: Float, L: Likelihood> fmt::Debug for StatAccumulator<F, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.stats.iter().map(|stat| &stat.name))
+            .finish()
+    }
+}
+
+impl<F: Float, L: Likelihood> StatAccumulator<F, L> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// accumulator of the `BUILTIN` statistics listed in `stats`. Panics for other names.
+    pub fn with_builtin(stats: &CustomStats) -> Self {
+        let mut acc = Self::new();
+        for (name, interval) in &stats.0 {
+            match name.as_str() {
+                "log_like" => acc.register(name, *interval, HierarchicalModel::log_like),
+                "num_groups" => acc.register(name, *interval, |hcp: &HierarchicalModel<F, L>| {
+                    hcp.model().num_groups() as f64
+                }),
+                "root_density" => acc.register(name, *interval, |hcp: &HierarchicalModel<F, L>| {
+                    hcp.group_density(0)
+                }),
+                "within_group_edges" => {
+                    acc.register(name, *interval, |hcp: &HierarchicalModel<F, L>| {
+                        hcp.trace_stats().within_group_edges
+                    })
+                }
+                "entropy" => acc.register(name, *interval, HierarchicalModel::partition_entropy),
+                "dl" => acc.register(name, *interval, HierarchicalModel::description_length),
+                _ => panic!("unknown statistic {}", name),
+            }
+        }
+        acc
+    }
+
+    /// evaluate `f` at every iteration divisible by `interval`, recorded as `name`
+    pub fn register(
+        &mut self,
+        name: impl Into<String>,
+        interval: u64,
+        f: impl FnMut(&HierarchicalModel<F, L>) -> f64 + Send + Sync + 'static,
+    ) {
+        self.stats.push(Stat {
+            name: name.into(),
+            interval: interval.max(1),
+            f: Box::new(f),
+            iterations: Vec::new(),
+            values: Vec::new(),
+        });
+    }
+
+    /// evaluate the statistics due at `iteration`; call once per iteration
+    pub fn observe(&mut self, iteration: u64, hcp: &HierarchicalModel<F, L>) {
+        for stat in &mut self.stats {
+            if iteration.is_multiple_of(stat.interval) {
+                stat.iterations.push(iteration);
+                stat.values.push((stat.f)(hcp));
+            }
+        }
+    }
+
+    /// iterations and values recorded for `name`
+    pub fn trace(&self, name: &str) -> Option<(&[u64], &[f64])> {
+        self.stats
+            .iter()
+            .find(|stat| stat.name == name)
+            .map(|stat| (&stat.iterations[..], &stat.values[..]))
+    }
+
+    /// write every statistic to `{name}_{stat}.txt`, values formatted as `format`, see
+    /// `output::write_atomic`. `append`: keep existing values.
+    pub fn dump(
+        &self,
+        save_dir: &Path,
+        name: &str,
+        format: FloatFormat,
+        compression: OutputCompression,
+        append: bool,
+    ) -> io::Result<()> {
+        fs::create_dir_all(save_dir)?;
+        for stat in &self.stats {
+            let path = save_dir.join(format!("{}_{}.txt", name, stat.name));
+            output::write_atomic(&path, compression, append, |w| {
+                for (i, &x) in stat.iterations.iter().zip(&stat.values) {
+                    writeln!(w, "{} {}", i, format.display(x))?;
+                }
+                Ok(())
+            })?;
+        }
+        Ok(())
+    }
+
+    /// number of recorded values
+    pub fn num_values(&self) -> usize {
+        self.stats.iter().map(|stat| 2 * stat.values.len()).sum()
+    }
+
+    /// write the traces, for a checkpoint
+    pub fn encode<W: Write>(&self, e: &mut Encoder<W>) -> io::Result<()> {
+        e.usize(self.stats.len())?;
+        for stat in &self.stats {
+            e.u64s(&stat.iterations)?;
+            e.f64s(&stat.values)?;
+        }
+        Ok(())
+    }
+
+    /// replace the traces with those written by `encode` for the same statistics
+    pub fn decode<R: Read>(&mut self, d: &mut Decoder<R>) -> io::Result<()> {
+        let len = d.usize()?;
+        d.expect("number of custom statistics", len, self.stats.len())?;
+        for stat in &mut self.stats {
+            stat.iterations = d.u64s()?;
+            stat.values = d.f64s()?;
+            d.expect(
+                "number of custom statistic values",
+                stat.values.len(),
+                stat.iterations.len(),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::_example_parameters;
+
+    #[test]
+    fn accumulate() {
+        let p = _example_parameters(b"seed: 2\n").unwrap();
+        let mut hcp: HierarchicalModel = HierarchicalModel::with_parameters(&p).unwrap();
+        let mut stats = StatAccumulator::new();
+        stats.register("num_groups", 3, |hcp: &HierarchicalModel| {
+            hcp.model().num_groups() as f64
+        });
+        stats.register("log_like", 1, HierarchicalModel::log_like);
+        for i in 0..10 {
+            hcp.step();
+            stats.observe(i, &hcp);
+        }
+        let (iterations, values) = stats.trace("num_groups").unwrap();
+        assert_eq!(iterations, [0, 3, 6, 9]);
+        assert_eq!(values.len(), 4);
+        assert_eq!(
+            stats.trace("log_like").unwrap().1.last(),
+            Some(&hcp.log_like())
+        );
+        assert!(stats.trace("entropy").is_none());
+
+        let dir = std::env::temp_dir().join("hcp_rs_test_custom_stats");
+        stats
+            .dump(&dir, "run", p.float_format, p.output_compression, false)
+            .unwrap();
+        let written = fs::read_to_string(dir.join("run_num_groups.txt")).unwrap();
+        assert_eq!(written.lines().count(), 4);
+        assert!(written.starts_with("0 "));
+        fs::remove_dir_all(dir).unwrap();
+
+        let mut e = Encoder::new(Vec::new()).unwrap();
+        stats.encode(&mut e).unwrap();
+        let buf = e.into_inner();
+        let mut restored = StatAccumulator::<f64, BetaBinomial>::with_builtin(
+            &"num_groups 3 log_like 1".parse().unwrap(),
+        );
+        restored
+            .decode(&mut Decoder::new(&buf[..]).unwrap())
+            .unwrap();
+        assert_eq!(restored.trace("num_groups"), stats.trace("num_groups"));
+        assert_eq!(restored.num_values(), stats.num_values());
+        assert!(StatAccumulator::<f64, BetaBinomial>::new()
+            .decode(&mut Decoder::new(&buf[..]).unwrap())
+            .is_err());
+    }
+
+    #[test]
+    fn builtin() {
+        let p = _example_parameters(b"seed: 2\ncustom_stats: dl 5 root_density 2\n").unwrap();
+        let mut hcp: HierarchicalModel = HierarchicalModel::with_parameters(&p).unwrap();
+        let mut stats = StatAccumulator::with_builtin(&p.custom_stats);
+        assert_eq!(format!("{:?}", stats), "[\"dl\", \"root_density\"]");
+        for i in 0..10 {
+            hcp.step();
+            stats.observe(i, &hcp);
+        }
+        assert_eq!(stats.trace("dl").unwrap().0, [0, 5]);
+        assert_eq!(
+            stats.trace("root_density").unwrap().1.last(),
+            Some(&hcp.group_density(0))
+        );
+        for bad in ["dl", "dl x", "size 3", "dl 1 dl 2"] {
+            assert!(bad.parse::<CustomStats>().is_err(), "{}", bad);
+        }
+    }
+}
@@ -0,0 +1,202 @@
+//! a run in slices of iterations, for embedding the sampler in other programs: `RunDriver`
+//! advances the chain a few iterations at a time and keeps the best configuration, and with
+//! the `tokio` feature `run_async` drives it inside an async task, yielding between slices,
+//! streaming `Progress` and stopping early when its cancellation token fires.
+use crate::likelihood::Likelihood;
+use crate::multi_group_model::Groups;
+#[cfg(feature = "tokio")]
+use crate::network::NetworkData;
+use crate::parameters::MoveSchedule;
+#[cfg(feature = "tokio")]
+use crate::parameters::Parameters;
+use crate::rng::SamplerRng;
+use crate::{Float, HierarchicalModel};
+#[cfg(feature = "tokio")]
+use std::sync::Arc;
+#[cfg(feature = "tokio")]
+use tokio::sync::mpsc::Sender;
+#[cfg(feature = "tokio")]
+use tokio_util::sync::CancellationToken;
+
+/// iterations between yields of `run_async`
+pub const YIELD_INTERVAL: u64 = 1000;
+
+/// state of a run after a slice of iterations
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    pub iteration: u64,
+    pub max_itr: u64,
+    pub log_like: f64,
+    pub best_log_like: f64,
+    pub num_groups: usize,
+}
+
+/// a chain of `max_itr` iterations, run in slices
+pub struct RunDriver<F: Float, L: Likelihood, R: SamplerRng> {
+    hcp: HierarchicalModel<F, L, R>,
+    max_itr: u64,
+    iteration: u64,
+    move_schedule: MoveSchedule,
+    /// log-likelihood, groups and number of groups of the best configuration so far
+    best: (f64, Vec<Groups>, usize),
+}
+
+impl<F: Float, L: Likelihood, R: SamplerRng> RunDriver<F, L, R> {
+    pub fn new(hcp: HierarchicalModel<F, L, R>, max_itr: u64) -> Self {
+        let best = Self::current(&hcp);
+        Self {
+            hcp,
+            max_itr,
+            iteration: 0,
+            move_schedule: MoveSchedule::default(),
+            best,
+        }
+    }
+
+    /// weight group additions by `schedule` over the iterations of the run
+    pub fn set_move_schedule(&mut self, schedule: MoveSchedule) {
+        self.move_schedule = schedule;
+    }
+
+    fn current(hcp: &HierarchicalModel<F, L, R>) -> (f64, Vec<Groups>, usize) {
+        (
+            hcp.log_like(),
+            hcp.model().groups().to_vec(),
+            hcp.model().num_groups(),
+        )
+    }
+
+    /// run up to `n` more iterations, stopping at `max_itr`
+    pub fn advance(&mut self, n: u64) -> Progress {
+        for _ in 0..n.min(self.max_itr - self.iteration) {
+            self.hcp
+                .set_group_move_weight(self.move_schedule.weight(self.iteration));
+            if self.hcp.get_groups().is_some() && self.hcp.log_like() > self.best.0 {
+                self.best = Self::current(&self.hcp);
+            }
+            self.iteration += 1;
+        }
+        self.progress()
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.iteration >= self.max_itr
+    }
+
+    pub fn progress(&self) -> Progress {
+        Progress {
+            iteration: self.iteration,
+            max_itr: self.max_itr,
+            log_like: self.hcp.log_like(),
+            best_log_like: self.best.0,
+            num_groups: self.hcp.model().num_groups(),
+        }
+    }
+
+    /// the current state of the chain
+    pub fn model(&self) -> &HierarchicalModel<F, L, R> {
+        &self.hcp
+    }
+
+    /// the model in the best configuration seen
+    pub fn into_best(mut self) -> HierarchicalModel<F, L, R> {
+        let (_, groups, num_groups) = self.best;
+        self.hcp.set_configuration(groups, num_groups);
+        self.hcp
+    }
+}
+
+/// result of `run_async`
+#[cfg(feature = "tokio")]
+pub struct RunOutcome {
+    /// the model in the best configuration found
+    pub model: HierarchicalModel,
+    pub iterations: u64,
+    /// whether the run stopped on `cancel` before `max_itr` iterations
+    pub cancelled: bool,
+}
+
+/// sample `network` with `params` in the current task, yielding to the executor every
+/// `YIELD_INTERVAL` iterations. Progress is sent after every slice; it is dropped while the
+/// channel is full, so a slow receiver never stalls the chain.
+#[cfg(feature = "tokio")]
+pub async fn run_async(
+    params: &Parameters,
+    network: Arc<NetworkData>,
+    cancel: CancellationToken,
+    progress: Sender<Progress>,
+) -> Result<RunOutcome, String> {
+    let mut driver = RunDriver::new(
+        HierarchicalModel::with_network(params, network)?,
+        params.max_itr,
+    );
+    driver.set_move_schedule(params.move_schedule.clone());
+    while !driver.is_done() && !cancel.is_cancelled() {
+        let _ = progress.try_send(driver.advance(YIELD_INTERVAL));
+        tokio::task::yield_now().await;
+    }
+    Ok(RunOutcome {
+        iterations: driver.progress().iteration,
+        cancelled: !driver.is_done(),
+        model: driver.into_best(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::_example_model;
+
+    #[test]
+    fn slices() {
+        let mut driver = RunDriver::new(_example_model(b"seed: 3\n"), 2500);
+        let first = driver.advance(1000);
+        assert_eq!((first.iteration, first.max_itr), (1000, 2500));
+        assert!(first.best_log_like >= first.log_like);
+        driver.advance(1000);
+        let last = driver.advance(1000);
+        assert_eq!(last.iteration, 2500);
+        assert!(driver.is_done());
+        assert_eq!(driver.advance(1000).iteration, 2500);
+
+        let best = driver.into_best();
+        assert!((best.log_like() - last.best_log_like).abs() < 1e-9);
+        assert!(best.clone().recompute().is_consistent());
+    }
+
+    #[test]
+    fn move_schedule() {
+        // no group additions from iteration 0 to 1000
+        let hcp = _example_model(b"seed: 3\n");
+        let num_groups = hcp.model().num_groups();
+        let mut driver = RunDriver::new(hcp, 2000);
+        driver.set_move_schedule("0 0 1000 5".parse().unwrap());
+        driver.advance(1000);
+        assert_eq!(driver.hcp.group_move_weight(), 0.0);
+        assert!(driver.hcp.model().num_groups() <= num_groups);
+        driver.advance(1000);
+        assert_eq!(driver.hcp.group_move_weight(), 5.0);
+    }
+
+    #[test]
+    fn concurrent_drivers() {
+        // as run_async on a multi-threaded executor: models built and sampled on several
+        // threads at once
+        let runs: Vec<_> = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..4)
+                .map(|seed| {
+                    s.spawn(move || {
+                        let hcp = _example_model(format!("seed: {}\n", seed).as_bytes());
+                        let mut driver = RunDriver::new(hcp, 1000);
+                        driver.advance(1000);
+                        driver.into_best()
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        for mut hcp in runs {
+            assert!(hcp.recompute().log_like_drift.abs() < 1e-9);
+        }
+    }
+}
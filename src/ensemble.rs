@@ -0,0 +1,227 @@
+//! ensembles of runs with different seeds. Group indices are arbitrary in every run, so the
+//! groups of each run are matched to the groups seen so far before averaging: level by level
+//! in the group hierarchy, with the assignment maximising the number of shared members.
+use crate::multi_group_model::{Groups, MultiGroupModel};
+
+/// number of groups used by `config`
+fn num_groups(config: &[Groups]) -> u32 {
+    64 - config.iter().fold(0, |a, &g| a | g).leading_zeros()
+}
+
+/// for a square matrix of `weights`, the column assigned to each row such that the total
+/// weight is maximal (Hungarian algorithm, O(n³))
+fn max_weight_assignment(weights: &[Vec<i64>]) -> Vec<usize> {
+    let n = weights.len();
+    let max = weights.iter().flatten().copied().max().unwrap_or(0);
+    let cost = |i: usize, j: usize| max - weights[i][j];
+    // potentials and matching are 1-based, column 0 is a virtual start
+    let mut u = vec![0i64; n + 1];
+    let mut v = vec![0i64; n + 1];
+    let mut row_of = vec![0usize; n + 1];
+    let mut way = vec![0usize; n + 1];
+    for i in 1..=n {
+        row_of[0] = i;
+        let mut j0 = 0;
+        let mut min_v = vec![i64::MAX; n + 1];
+        let mut used = vec![false; n + 1];
+        loop {
+            used[j0] = true;
+            let i0 = row_of[j0];
+            let mut delta = i64::MAX;
+            let mut j1 = 0;
+            for j in 1..=n {
+                if used[j] {
+                    continue;
+                }
+                let reduced = cost(i0 - 1, j - 1) - u[i0] - v[j];
+                if reduced < min_v[j] {
+                    min_v[j] = reduced;
+                    way[j] = j0;
+                }
+                if min_v[j] < delta {
+                    delta = min_v[j];
+                    j1 = j;
+                }
+            }
+            for j in 0..=n {
+                if used[j] {
+                    u[row_of[j]] += delta;
+                    v[j] -= delta;
+                } else {
+                    min_v[j] -= delta;
+                }
+            }
+            j0 = j1;
+            if row_of[j0] == 0 {
+                break;
+            }
+        }
+        while j0 != 0 {
+            let j1 = way[j0];
+            row_of[j0] = row_of[j1];
+            j0 = j1;
+        }
+    }
+    let mut col_of = vec![0; n];
+    for j in 1..=n {
+        col_of[row_of[j] - 1] = j - 1;
+    }
+    col_of
+}
+
+/// groups of several runs on the same network, relabelled consistently
+#[derive(Debug, Clone)]
+pub struct Ensemble {
+    num_runs: usize,
+    /// depth in the hierarchy of every aligned label
+    label_depth: Vec<usize>,
+    /// for every run, the aligned label of each of its groups
+    labels: Vec<Vec<usize>>,
+    /// `counts[label][node]`: number of runs in which `node` is in `label`
+    counts: Vec<Vec<u32>>,
+}
+
+impl Ensemble {
+    /// align the final configurations of several runs. The first run is the reference;
+    /// groups that cannot be matched to any group seen before get new labels.
+    pub fn align(configs: &[Vec<Groups>]) -> Result<Self, String> {
+        let num_nodes = configs.first().ok_or("no runs to align")?.len();
+        if configs.iter().any(|c| c.len() != num_nodes) {
+            return Err(String::from("runs have different numbers of nodes"));
+        }
+        let mut ensemble = Self {
+            num_runs: 0,
+            label_depth: Vec::new(),
+            labels: Vec::new(),
+            counts: Vec::new(),
+        };
+        for config in configs {
+            ensemble.push(config);
+        }
+        Ok(ensemble)
+    }
+
+    fn push(&mut self, config: &[Groups]) {
+        let n = num_groups(config);
+        let model = MultiGroupModel::with_groups(config.to_vec(), n, n);
+        let depth = model.group_depths();
+        let mut labels = vec![usize::MAX; n as usize];
+
+        for level in 0..=depth.iter().copied().max().unwrap_or(0) {
+            let groups: Vec<usize> = (0..n as usize).filter(|&g| depth[g] == level).collect();
+            let candidates: Vec<usize> = (0..self.label_depth.len())
+                .filter(|&l| self.label_depth[l] == level)
+                .collect();
+            let size = groups.len().max(candidates.len());
+            let mut weights = vec![vec![0i64; size]; size];
+            for (i, &g) in groups.iter().enumerate() {
+                for (j, &l) in candidates.iter().enumerate() {
+                    weights[i][j] = model
+                        .members(g)
+                        .iter()
+                        .map(|&u| self.counts[l][u as usize] as i64)
+                        .sum();
+                }
+            }
+            let assignment = max_weight_assignment(&weights);
+            for (i, &g) in groups.iter().enumerate() {
+                let j = assignment[i];
+                labels[g] = if j < candidates.len() && weights[i][j] > 0 {
+                    candidates[j]
+                } else {
+                    self.label_depth.push(level);
+                    self.counts.push(vec![0; config.len()]);
+                    self.label_depth.len() - 1
+                };
+            }
+        }
+
+        for (u, &groups) in config.iter().enumerate() {
+            for (g, &l) in labels.iter().enumerate() {
+                if (groups >> g) & 1 != 0 {
+                    self.counts[l][u] += 1;
+                }
+            }
+        }
+        self.labels.push(labels);
+        self.num_runs += 1;
+    }
+
+    pub fn num_runs(&self) -> usize {
+        self.num_runs
+    }
+
+    pub fn num_labels(&self) -> usize {
+        self.label_depth.len()
+    }
+
+    /// aligned label of group `group` in run `run`
+    pub fn label(&self, run: usize, group: usize) -> usize {
+        self.labels[run][group]
+    }
+
+    /// fraction of runs in which `node` is in aligned group `label`
+    pub fn membership(&self, label: usize, node: usize) -> f64 {
+        self.counts[label][node] as f64 / self.num_runs as f64
+    }
+
+    /// labels that hold at least one node in the majority of runs, ordered by depth so that
+    /// parents precede their children. At most 64 labels are kept.
+    pub fn consensus_labels(&self) -> Vec<usize> {
+        let mut labels: Vec<usize> = (0..self.num_labels())
+            .filter(|&l| {
+                self.counts[l]
+                    .iter()
+                    .any(|&c| 2 * c as usize > self.num_runs)
+            })
+            .collect();
+        labels.sort_by_key(|&l| self.label_depth[l]);
+        labels.truncate(64);
+        labels
+    }
+
+    /// configuration putting every node in the consensus labels it belongs to in the
+    /// majority of runs. Group `i` of the result is `consensus_labels()[i]`.
+    pub fn consensus(&self) -> MultiGroupModel {
+        let labels = self.consensus_labels();
+        let num_nodes = self.counts.first().map_or(0, Vec::len);
+        let groups = (0..num_nodes)
+            .map(|u| {
+                labels
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, &l)| 2 * self.counts[l][u] as usize > self.num_runs)
+                    .fold(1, |a, (i, _)| a | (1 << i))
+            })
+            .collect();
+        let n = labels.len().max(1) as u32;
+        MultiGroupModel::with_groups(groups, n, n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assignment() {
+        let weights = vec![vec![1, 7, 3], vec![8, 2, 9], vec![4, 6, 0]];
+        assert_eq!(max_weight_assignment(&weights), [1, 2, 0]);
+        assert_eq!(max_weight_assignment(&[]), []);
+    }
+
+    #[test]
+    fn label_switching() {
+        // the same two groups below the root, with labels 1 and 2 swapped in the second run
+        let a = vec![0b011, 0b011, 0b011, 0b101, 0b101, 0b101];
+        let b = vec![0b101, 0b101, 0b101, 0b011, 0b011, 0b011];
+        let c = vec![0b011, 0b011, 0b101, 0b101, 0b101, 0b101];
+        let ensemble = Ensemble::align(&[a.clone(), b, c]).unwrap();
+        assert_eq!(ensemble.num_labels(), 3);
+        assert_eq!(ensemble.label(1, 1), 2);
+        assert_eq!(ensemble.label(1, 2), 1);
+        assert_eq!(ensemble.membership(0, 4), 1.0);
+        assert_eq!(ensemble.membership(1, 2), 2.0 / 3.0);
+        assert_eq!(ensemble.consensus().groups(), a);
+    }
+}
@@ -0,0 +1,61 @@
+//! OpenCL offload of the O(n²) pair count in `HierarchicalModel::init_hcg_props`
+use crate::multi_group_model::Groups;
+use ocl::{Buffer, ProQue};
+
+/// below this many nodes the CPU loop is faster than setting up the device
+pub const MIN_NODES: usize = 100_000;
+
+const KERNEL: &str = r#"
+#pragma OPENCL EXTENSION cl_khr_int64_base_atomics : enable
+
+__kernel void hcg_pairs(__global const ulong* groups, __global ulong* counts, uint n, ulong mask) {
+    uint u = get_global_id(0);
+    ulong local_counts[64];
+    for (int g = 0; g < 64; g++) {
+        local_counts[g] = 0;
+    }
+    ulong gu = groups[u] & mask;
+    for (uint v = u + 1; v < n; v++) {
+        ulong common = gu & groups[v];
+        local_counts[63 - clz(common)] += 1;
+    }
+    for (int g = 0; g < 64; g++) {
+        if (local_counts[g] != 0) {
+            atom_add(&counts[g], local_counts[g]);
+        }
+    }
+}
+"#;
+
+/// number of node pairs for which each group is the highest common group
+pub fn hcg_pairs(groups: &[Groups], num_groups: usize) -> ocl::Result<Vec<usize>> {
+    let mask = if num_groups >= 64 {
+        u64::MAX
+    } else {
+        (1u64 << num_groups) - 1
+    };
+    let pro_que = ProQue::builder().src(KERNEL).dims(groups.len()).build()?;
+    let groups_buf = Buffer::<u64>::builder()
+        .queue(pro_que.queue().clone())
+        .len(groups.len())
+        .copy_host_slice(groups)
+        .build()?;
+    let counts_buf = Buffer::<u64>::builder()
+        .queue(pro_que.queue().clone())
+        .len(64)
+        .fill_val(0)
+        .build()?;
+    let kernel = pro_que
+        .kernel_builder("hcg_pairs")
+        .arg(&groups_buf)
+        .arg(&counts_buf)
+        .arg(groups.len() as u32)
+        .arg(mask)
+        .build()?;
+    unsafe {
+        kernel.enq()?;
+    }
+    let mut counts = vec![0u64; 64];
+    counts_buf.read(&mut counts).enq()?;
+    Ok(counts[..num_groups].iter().map(|&c| c as usize).collect())
+}
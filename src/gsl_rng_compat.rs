@@ -1,11 +1,18 @@
-use rgsl::rng::algorithms::mt19937;
-use rgsl::rng::Rng as GslRng;
+use rand::{RngCore, SeedableRng};
 use std::ops::{Bound, RangeBounds};
 
-/// simple wrapper around GSL mt19937
-/// implementing just the parts of rand::Rng I use
+const N: usize = 624;
+const M: usize = 397;
+const UPPER_MASK: u32 = 0x80000000;
+const LOWER_MASK: u32 = 0x7fffffff;
+
+/// mt19937 producing the same output as GSL's `gsl_rng_mt19937`.
+/// `gen_range` and `gen_bool` follow GSL semantics and shadow the `rand::Rng` versions.
 #[derive(Clone)]
-pub struct MT19937(GslRng);
+pub struct MT19937 {
+    mt: [u32; N],
+    mti: usize,
+}
 
 trait UsizeConvertible {
     fn as_usize(self) -> usize;
@@ -35,11 +42,70 @@ gen_usize_convertible!(u16);
 gen_usize_convertible!(u8);
 
 impl MT19937 {
-    /// semantics differ from rand::SeedableRng::seed_from_u64
+    /// semantics differ from rand::SeedableRng::seed_from_u64. Like `gsl_rng_set`, only the
+    /// low 32 bits of `seed` are used: seeds differing above them give the same stream.
     pub fn seed_from_u64(seed: u64) -> Self {
-        let mut rng = GslRng::new(mt19937()).unwrap();
-        rng.set(seed as usize);
-        Self(rng)
+        // mt19937_set(), which takes `seed & 0xffffffff`
+        let seed = match seed as u32 {
+            0 => 4357,
+            s => s,
+        };
+        let mut mt = [0; N];
+        mt[0] = seed;
+        for i in 1..N {
+            mt[i] = 1812433253u32
+                .wrapping_mul(mt[i - 1] ^ (mt[i - 1] >> 30))
+                .wrapping_add(i as u32);
+        }
+        Self { mt, mti: N }
+    }
+
+    /// mt19937_get()
+    fn get(&mut self) -> u32 {
+        let magic = |y: u32| if y & 1 != 0 { 0x9908b0df } else { 0 };
+        if self.mti >= N {
+            let mt = &mut self.mt;
+            for kk in 0..N - M {
+                let y = (mt[kk] & UPPER_MASK) | (mt[kk + 1] & LOWER_MASK);
+                mt[kk] = mt[kk + M] ^ (y >> 1) ^ magic(y);
+            }
+            for kk in N - M..N - 1 {
+                let y = (mt[kk] & UPPER_MASK) | (mt[kk + 1] & LOWER_MASK);
+                mt[kk] = mt[kk + M - N] ^ (y >> 1) ^ magic(y);
+            }
+            let y = (mt[N - 1] & UPPER_MASK) | (mt[0] & LOWER_MASK);
+            mt[N - 1] = mt[M - 1] ^ (y >> 1) ^ magic(y);
+            self.mti = 0;
+        }
+
+        let mut k = self.mt[self.mti];
+        k ^= k >> 11;
+        k ^= (k << 7) & 0x9d2c5680;
+        k ^= (k << 15) & 0xefc60000;
+        k ^= k >> 18;
+        self.mti += 1;
+        k
+    }
+
+    /// gsl_rng_uniform(): uniform in [0, 1)
+    pub(crate) fn uniform(&mut self) -> f64 {
+        self.get() as f64 / 4294967296.0
+    }
+
+    /// gsl_rng_uniform_int(): uniform in [0, n), by rejection
+    fn uniform_int(&mut self, n: usize) -> usize {
+        let range = u32::MAX as usize;
+        assert!(
+            n > 0 && n <= range,
+            "invalid n, either 0 or exceeds maximum value of generator"
+        );
+        let scale = range / n;
+        loop {
+            let k = self.get() as usize / scale;
+            if k < n {
+                return k;
+            }
+        }
     }
 
     #[allow(private_bounds)]
@@ -55,10 +121,47 @@ impl MT19937 {
             Bound::Unbounded => panic!(),
         };
 
-        N::from_usize(self.0.uniform_int(end - start) + start)
+        N::from_usize(self.uniform_int(end - start) + start)
     }
     pub fn gen_bool(&mut self, p: f64) -> bool {
-        self.0.uniform() < p
+        self.uniform() < p
+    }
+}
+
+/// raw mt19937 output, as `gsl_rng_get`
+impl RngCore for MT19937 {
+    fn next_u32(&mut self) -> u32 {
+        self.get()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let lo = self.next_u32() as u64;
+        let hi = self.next_u32() as u64;
+        (hi << 32) | lo
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        for chunk in dest.chunks_mut(4) {
+            chunk.copy_from_slice(&self.next_u32().to_le_bytes()[..chunk.len()]);
+        }
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand::Error> {
+        self.fill_bytes(dest);
+        Ok(())
+    }
+}
+
+impl SeedableRng for MT19937 {
+    type Seed = [u8; 8];
+
+    fn from_seed(seed: Self::Seed) -> Self {
+        MT19937::seed_from_u64(u64::from_le_bytes(seed))
+    }
+
+    /// seeds like `gsl_rng_set`, unlike the default implementation
+    fn seed_from_u64(seed: u64) -> Self {
+        MT19937::seed_from_u64(seed)
     }
 }
 
@@ -76,4 +179,38 @@ mod tests {
             assert!(r >= x, "{} ≥ {}", r, x);
         }
     }
+
+    #[test]
+    fn gsl_reference() {
+        // reference outputs of gsl_rng_get for gsl_rng_mt19937 with seeds 5489 and 0 (4357)
+        let mut rng = MT19937::seed_from_u64(5489);
+        let first: Vec<u32> = (0..3).map(|_| rng.get()).collect();
+        assert_eq!(first, [3499211612, 581869302, 3890346734]);
+        let mut rng = MT19937::seed_from_u64(0);
+        assert_eq!((0..1000).map(|_| rng.get()).last(), Some(1186927261));
+        let mut rng = MT19937::seed_from_u64(5489 + (1 << 32));
+        assert_eq!(rng.get(), first[0]);
+
+        // gsl_rng_uniform_int divides by floor(range / n) and rejects k >= n
+        let mut rng = MT19937::seed_from_u64(5489);
+        assert_eq!(rng.uniform_int(10), 3499211612 / (u32::MAX as usize / 10));
+        assert_eq!(rng.uniform_int(3), 581869302 / (u32::MAX as usize / 3));
+        let mut rng = MT19937::seed_from_u64(5489);
+        assert_eq!(rng.uniform(), 3499211612.0 / 4294967296.0);
+    }
+
+    #[test]
+    fn rng_core() {
+        // same stream through the rand traits
+        let mut rng = <MT19937 as SeedableRng>::seed_from_u64(4357);
+        let k = (0..1000).map(|_| rng.next_u32()).last();
+        assert_eq!(k, Some(1186927261));
+
+        fn draw<R: rand::Rng>(rng: &mut R) -> u32 {
+            rng.gen()
+        }
+        let mut a = MT19937::seed_from_u64(4);
+        let mut b = MT19937::from_seed(4u64.to_le_bytes());
+        assert_eq!(draw(&mut a), b.next_u32());
+    }
 }
@@ -1,193 +1,903 @@
-use graph_io_gml;
-use parameters::Parameters;
-use petgraph::{graph::NodeIndex, visit::EdgeRef, Graph};
-use std::fs;
+extern crate alloc;
+
+use crate::core::hcg::{self, HCG};
+use crate::core::math;
+use checkpoint::{Decoder, Encoder};
+use likelihood::{BetaBinomial, Likelihood};
+pub use math::Float;
+use network::NetworkData;
+use parameters::{EmptyGroupPolicy, InitMethod, Parameters};
+/// the petgraph version `NetworkData` converts from and to
+pub use petgraph;
+use seeds::Stream;
+use std::fs::File;
+use std::io::BufReader;
 use std::iter;
-use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
 
-#[cfg(feature = "gsl_compat")]
 mod gsl_rng_compat;
-#[cfg(feature = "gsl_compat")]
-use gsl_rng_compat::MT19937;
 use multi_group_model::{Groups, Move, MultiGroupModel, Node};
+use rng::{DefaultRng, SamplerRng};
 
-#[cfg(not(feature = "gsl_compat"))]
-use mt19937::MT19937;
-#[cfg(not(feature = "gsl_compat"))]
-use rand::{Rng, SeedableRng};
+/// time `$e` as `profiling::Phase::$phase` when the `profiling` feature is enabled
+#[cfg(feature = "profiling")]
+macro_rules! timed {
+    ($phase:ident, $e:expr) => {{
+        let start = std::time::Instant::now();
+        let r = $e;
+        profiling::record(profiling::Phase::$phase, start);
+        r
+    }};
+}
+#[cfg(not(feature = "profiling"))]
+macro_rules! timed {
+    ($phase:ident, $e:expr) => {
+        $e
+    };
+}
 
-mod indexed_list;
-mod math;
-mod multi_group_model;
+#[cfg(feature = "arrow")]
+pub mod arrow_ipc;
+mod batch;
+pub mod block_image;
+pub mod checkpoint;
+pub mod coarsening;
+pub mod convert;
+pub mod core;
+pub mod cross_validation;
+pub mod custom_stats;
+pub mod driver;
+pub mod ensemble;
+#[cfg(feature = "gpu")]
+mod gpu;
+pub use crate::core::likelihood;
+pub mod link_prediction;
+#[cfg(feature = "mpi")]
+pub mod mpi_backend;
+pub mod multi_group_model;
+pub mod network;
+pub mod npy;
+pub mod output;
+#[cfg(feature = "parallel")]
+pub mod parallel;
 pub mod parameters;
+pub mod persistence;
+pub mod pipeline;
+pub mod placement;
+pub mod preclustering;
+pub mod probe;
+#[cfg(feature = "profiling")]
+pub mod profiling;
+#[cfg(feature = "sqlite")]
+pub mod results_db;
+pub mod rng;
+pub mod rng_compare;
+pub mod seeds;
+#[cfg(feature = "serve")]
+pub mod service;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod thinning;
+pub mod trace;
+pub mod watchdog;
 
-type Network = Graph<(), ()>;
-
-trait HCG {
-    /// Highest Common Group
-    fn hcg(&self, u: Node, v: Node) -> usize;
+/// outcome of a single Monte Carlo step
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Proposal {
+    pub m: Move,
+    pub delta_log_like: f64,
+    pub accepted: bool,
+}
 
-    fn hcg_node(&self, old_state: Groups, u: Node) -> usize;
+/// number of proposed and accepted moves of each kind
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MoveCounts {
+    /// indexed by kind: add group, remove group, add node, remove node
+    pub proposed: [u64; 4],
+    pub accepted: [u64; 4],
 }
 
-#[derive(Clone)]
-pub struct HierarchicalModel {
-    rng: MT19937,
-
-    pub network: Network,
-    pub model: MultiGroupModel,
-    pub hcg_edges: Vec<usize>, // number of edges in each group
-    pub hcg_pairs: Vec<usize>, // number of possible edges in each group
-    pub log_like: f64,         // current log-likelihood
+impl MoveCounts {
+    const NAMES: [&'static str; 4] = ["add_group", "remove_group", "add_node", "remove_node"];
+
+    pub fn record(&mut self, p: &Proposal) {
+        let kind = match p.m {
+            Move::AddGroup { .. } => 0,
+            Move::RemoveGroup { .. } => 1,
+            Move::AddNodeToGroup { .. } => 2,
+            Move::RemoveNodeFromGroup { .. } => 3,
+        };
+        self.proposed[kind] += 1;
+        self.accepted[kind] += p.accepted as u64;
+    }
 }
 
-fn _read_network(gml_path: &Path) -> Result<Network, String> {
-    Ok(graph_io_gml::parse_gml(
-        &fs::read_to_string(gml_path).map_err(|e| e.to_string())?,
-        &|_| Some(()),
-        &|_| Some(()),
-    )?)
+impl std::fmt::Display for MoveCounts {
+    /// `<kind> <accepted>/<proposed>` for every kind
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for (k, name) in Self::NAMES.iter().enumerate() {
+            if k > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{} {}/{}", name, self.accepted[k], self.proposed[k])?;
+        }
+        Ok(())
+    }
 }
 
-fn calc_loglike(a: &Vec<usize>, b: &Vec<usize>) -> f64 {
-    iter::zip(a, b)
-        .map(|(&e, &p)| math::ln_fact(e) + math::ln_fact(p - e) - math::ln_fact(p + 1))
-        .sum()
+/// discrepancies between cached and freshly recomputed values, see `HierarchicalModel::recompute`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Recomputed {
+    pub hcg_edges_changed: bool,
+    pub hcg_pairs_changed: bool,
+    pub log_like_drift: f64, // cached - recomputed
 }
 
-impl HCG for MultiGroupModel {
-    fn hcg(&self, u: Node, v: Node) -> usize {
-        let group_mask = (1u64 << self.num_groups()) - 1;
-        let masked_u = self.groups_of(u as usize) & group_mask;
-        let masked_v = self.groups_of(v as usize) & group_mask;
+impl Recomputed {
+    pub fn is_consistent(&self) -> bool {
+        !self.hcg_edges_changed && !self.hcg_pairs_changed && self.log_like_drift == 0.0
+    }
+}
 
-        let common_bits = masked_u & masked_v;
-        let common_bits = common_bits | (common_bits >> 1u64);
-        let common_bits = common_bits | (common_bits >> 2u64);
-        let common_bits = common_bits | (common_bits >> 4u64);
-        let common_bits = common_bits | (common_bits >> 8u64);
-        let common_bits = common_bits | (common_bits >> 16u64);
-        let common_bits = common_bits | (common_bits >> 32u64);
+/// state of a freshly constructed model, see `HierarchicalModel::init_report`
+#[derive(Debug, Clone, PartialEq)]
+pub struct InitReport {
+    pub num_nodes: usize,
+    pub num_edges: usize,
+    pub num_groups: usize,
+    pub log_like: f64,
+    pub user_specified_config: bool, // false: random initial configuration
+    pub ln_fact_table_len: usize,    // number of precomputed ln-factorials
+    pub gpu_fallback: Option<String>, // why the pair count fell back from the GPU to the CPU
+}
 
-        (63u64 - ((common_bits - (common_bits >> 1u64)).leading_zeros() as u64)) as usize
+impl std::fmt::Display for InitReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(
+            f,
+            "assigning {} groups to nodes",
+            if self.user_specified_config {
+                "user specified"
+            } else {
+                "random"
+            }
+        )?;
+        writeln!(f, "nodes: {} edges: {}", self.num_nodes, self.num_edges)?;
+        if let Some(e) = &self.gpu_fallback {
+            writeln!(
+                f,
+                "warning: GPU pair count failed, falling back to CPU: {}",
+                e
+            )?;
+        }
+        writeln!(
+            f,
+            "groups: {} energy: {:.4}",
+            self.num_groups, self.log_like
+        )
     }
+}
 
-    fn hcg_node(&self, old_state: Groups, u: Node) -> usize {
-        let group_mask = (1u64 << self.num_groups()) - 1;
-        let masked_u = old_state & group_mask;
-        let masked_v = self.groups_of(u as usize) & group_mask;
+/// overview of the current state, see `HierarchicalModel::summary`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Summary {
+    pub num_nodes: usize,
+    pub num_edges: usize,
+    pub num_groups: usize,
+    pub log_like: f64,
+    pub group_sizes: Vec<usize>,
+    pub group_densities: Vec<f64>,
+}
 
-        let common_bits = masked_u & masked_v;
-        let common_bits = common_bits | (common_bits >> 1u64);
-        let common_bits = common_bits | (common_bits >> 2u64);
-        let common_bits = common_bits | (common_bits >> 4u64);
-        let common_bits = common_bits | (common_bits >> 8u64);
-        let common_bits = common_bits | (common_bits >> 16u64);
-        let common_bits = common_bits | (common_bits >> 32u64);
+impl Summary {
+    pub fn to_json(&self) -> String {
+        let list = |v: Vec<String>| format!("[{}]", v.join(","));
+        format!(
+            "{{\"num_nodes\":{},\"num_edges\":{},\"num_groups\":{},\"log_like\":{},\"group_sizes\":{},\"group_densities\":{}}}",
+            self.num_nodes,
+            self.num_edges,
+            self.num_groups,
+            self.log_like,
+            list(self.group_sizes.iter().map(|x| x.to_string()).collect()),
+            list(self.group_densities.iter().map(|x| x.to_string()).collect()),
+        )
+    }
+}
 
-        (63u64 - (common_bits - (common_bits >> 1u64)).leading_zeros() as u64) as usize
+impl std::fmt::Display for Summary {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "nodes: {} edges: {}", self.num_nodes, self.num_edges)?;
+        writeln!(
+            f,
+            "groups: {} energy: {:.4}",
+            self.num_groups, self.log_like
+        )?;
+        writeln!(f, "group  size  density")?;
+        for (g, (size, density)) in iter::zip(&self.group_sizes, &self.group_densities).enumerate()
+        {
+            writeln!(f, "{:>5} {:>5}  {:.4}", g, size, density)?;
+        }
+        Ok(())
     }
 }
 
-impl HierarchicalModel {
+/// statistics of a configuration that do not depend on how groups are numbered,
+/// see `HierarchicalModel::trace_stats`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TraceStats {
+    pub num_groups: usize,
+    /// fraction of edges whose highest common group is not the root
+    pub within_group_edges: f64,
+    /// Shannon entropy (in nats) of the partition at each level below the root: at level `d`
+    /// every node belongs to its highest group at most `d` levels deep
+    pub level_entropy: Vec<f64>,
+}
+
+/// the sampler. Log-likelihoods are computed in `F`; `f32` halves the memory of the
+/// ln-factorial table on large graphs, `recompute` corrects the accumulated error.
+/// `L` maps the per-group counts to the log-likelihood, see `likelihood`.
+/// `R` draws the random numbers, see `rng`.
+#[derive(Clone)]
+pub struct HierarchicalModel<
+    F: Float = f64,
+    L: Likelihood = BetaBinomial,
+    R: SamplerRng = DefaultRng,
+> {
+    rng: R,
+    likelihood: L,
+
+    network: Arc<NetworkData>, // shared between clones
+    model: MultiGroupModel,
+    hcg_edges: Vec<usize>,  // number of edges in each group
+    hcg_pairs: Vec<usize>,  // number of possible edges in each group
+    log_like: F,            // current log-likelihood
+    beta: f64,              // inverse temperature of the acceptance rule
+    group_move_weight: f64, // factor on the probability of proposing a group addition
+
+    // per-group changes made by the last node move, to revert it on rejection
+    hcg_edges_delta: [isize; 64],
+    hcg_pairs_delta: [isize; 64],
+
+    min_group_size: usize,
+    assortative: bool,
+    violations: usize, // density_violations() of the current state, if assortative
+    parents: Vec<Option<usize>>, // parent_groups() of the current model, if assortative
+    frozen: Vec<bool>, // nodes excluded from node moves, empty if all nodes are sampled
+    empty_group_policy: EmptyGroupPolicy,
+    empty_steps: Vec<u64>, // number of steps each group has been empty, for EmptyGroupPolicy::Lazy
+    pruned: Vec<usize>,    // groups removed by the empty group policy in the last step
+    batch_pool: Option<Arc<batch::BatchPool>>, // started by the first threaded batch_step
+
+    init_report: InitReport,
+}
+
+impl<F: Float, L: Likelihood, R: SamplerRng> HierarchicalModel<F, L, R> {
     pub fn with_parameters(params: &Parameters) -> Result<Self, String> {
+        let network = Arc::new(NetworkData::load(params)?);
+        Self::with_network(params, network)
+    }
+
+    /// like `with_parameters`, but reuse an already loaded network
+    /// (e.g. to run several chains on the same graph). `params.gml_path` is ignored.
+    pub fn with_network(params: &Parameters, network: Arc<NetworkData>) -> Result<Self, String> {
         if params.max_num_groups > 64 {
             return Err(String::from("number of groups cannot exceed 64"));
         }
-        let network = _read_network(&params.gml_path).map_err(|e| e.to_string())?;
-        math::precompute_ln_fact(&network.node_count().pow(2) + 1);
-        let mut rng = MT19937::seed_from_u64(params.seed.unwrap_or(0));
-        let groups = match &params.initial_group_config {
-            Some(groups) => {
-                println!("assigning user specified groups to nodes");
-                groups.clone()
+        if network.has_multi_edges() {
+            return Err(String::from(
+                "the network has multi-edges, which the likelihood does not allow",
+            ));
+        }
+        let mut likelihood = L::from_config(&params.likelihood_config())?;
+        if let Some(warning) = likelihood.prepare::<F>(network.node_count())? {
+            if !params.quiet {
+                println!("warning: {}", warning);
             }
-            _ => {
-                println!("assigning random groups to nodes");
-                let max = 1u64 << (params.initial_num_groups - 1);
-                (0..network.node_count())
-                    .map(|_| (rng.gen_range(0..max) << 1) + 1)
-                    .collect()
+        }
+        let mut rng = R::seed_from_u64(params.seed.unwrap_or(0));
+        let sources = [
+            params.initial_group_config.is_some(),
+            params.initial_group_config_path.is_some(),
+            params.init_from_partition_path.is_some(),
+            params.init_method != InitMethod::Random,
+        ];
+        if sources.iter().filter(|&&s| s).count() > 1 {
+            return Err(String::from(
+                "initial_group_config, initial_group_config_path, init_from_partition_path and \
+                 init_method are exclusive",
+            ));
+        }
+        type ReadConfig = fn(BufReader<File>) -> Result<(Vec<Groups>, u32), String>;
+        let read_config = |path: &PathBuf, read: ReadConfig| {
+            let file = File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+            let (groups, num_groups) = read(BufReader::new(file))?;
+            if groups.len() != network.node_count() {
+                return Err(format!(
+                    "{}: {} nodes, the network has {}",
+                    path.display(),
+                    groups.len(),
+                    network.node_count()
+                ));
+            }
+            if num_groups > params.max_num_groups {
+                return Err(format!(
+                    "{}: {} groups exceed max_num_groups",
+                    path.display(),
+                    num_groups
+                ));
             }
+            multi_group_model::validate_groups(&groups, num_groups, params.require_nested)
+                .map_err(|e| format!("{}: {}", path.display(), e))?;
+            Ok((groups, num_groups))
         };
+        let (groups, num_groups) = if let Some(groups) = &params.initial_group_config {
+            if groups.len() != network.node_count() {
+                return Err(format!(
+                    "initial_group_config: {} nodes, the network has {}",
+                    groups.len(),
+                    network.node_count()
+                ));
+            }
+            multi_group_model::validate_groups(
+                groups,
+                params.initial_num_groups,
+                params.require_nested,
+            )
+            .map_err(|e| format!("initial_group_config: {}", e))?;
+            (groups.clone(), params.initial_num_groups)
+        } else if let Some(path) = &params.initial_group_config_path {
+            read_config(path, multi_group_model::read_membership_matrix)?
+        } else if let Some(path) = &params.init_from_partition_path {
+            read_config(path, multi_group_model::read_partition)?
+        } else if params.init_method != InitMethod::Random {
+            let community = match params.init_method {
+                InitMethod::Louvain => preclustering::louvain(&network, &mut rng),
+                _ => preclustering::label_propagation(&network, &mut rng),
+            };
+            preclustering::to_groups(&community, params.max_num_groups)
+        } else {
+            let max = 1u64 << (params.initial_num_groups - 1);
+            let groups = (0..network.node_count())
+                .map(|_| ((rng.uniform_int(0..max as usize) as Groups) << 1) + 1)
+                .collect();
+            (groups, params.initial_num_groups)
+        };
+        let mut order: Vec<Node> = (0..network.node_count() as Node).collect();
+        if params.permute_nodes {
+            let seed = seeds::sub_seed(params.seed.unwrap_or(0), Stream::Permutation, 0);
+            R::seed_from_u64(seed).shuffle(&mut order);
+        }
         let model =
-            MultiGroupModel::with_groups(groups, params.initial_num_groups, params.max_num_groups);
+            MultiGroupModel::with_groups_in_order(groups, num_groups, params.max_num_groups, order);
 
-        let (hcg_edges, hcg_pairs) = HierarchicalModel::init_hcg_props(&network, &model);
-        let log_like = calc_loglike(&hcg_edges, &hcg_pairs);
+        let (hcg_edges, hcg_pairs, gpu_fallback) = Self::init_hcg_props(&network, &model);
+        let log_like: F = likelihood.log_like(&hcg_edges, &hcg_pairs);
+        let init_report = InitReport {
+            num_nodes: network.node_count(),
+            num_edges: network.edge_count(),
+            num_groups: model.num_groups(),
+            log_like: log_like.to_f64(),
+            user_specified_config: sources.contains(&true),
+            ln_fact_table_len: likelihood.ln_fact_table_len::<F>(),
+            gpu_fallback,
+        };
 
-        Ok(Self {
+        let frozen = match &params.sample_nodes_path {
+            Some(path) => {
+                let mut frozen = vec![true; network.node_count()];
+                for u in network::read_node_list(path)? {
+                    *frozen
+                        .get_mut(u as usize)
+                        .ok_or(format!("sampled node {} does not exist", u))? = false;
+                }
+                frozen
+            }
+            None => Vec::new(),
+        };
+
+        let mut hcp = Self {
+            likelihood,
+            frozen,
+            empty_steps: vec![0; model.num_groups()],
+            pruned: Vec::new(),
+            batch_pool: None,
             network,
             model,
             hcg_edges,
             hcg_pairs,
             log_like,
+            beta: 1.0,
+            group_move_weight: 1.0,
+            hcg_edges_delta: [0; 64],
+            hcg_pairs_delta: [0; 64],
+            min_group_size: params.min_group_size,
+            assortative: params.assortative,
+            violations: 0,
+            parents: Vec::new(),
+            empty_group_policy: params.empty_group_policy,
             rng,
-        })
-    }
-
-    /// initialize group edge count caches hcp_edges, hcp_pairs
-    fn init_hcg_props(network: &Network, model: &MultiGroupModel) -> (Vec<usize>, Vec<usize>) {
-        // void hierarchical_model::set_hcg_edges()
-        let mut hcg_edges = vec![0; model.num_groups()];
-        for edge in network.edge_references() {
-            let u = edge.source().index() as Node;
-            let v = edge.target().index() as Node;
-            let hcg = model.hcg(u, v);
-            hcg_edges[hcg] += 1;
-        }
-
-        // void hierarchical_model::set_hcg_pairs()
-        let mut hcg_pairs = vec![0; model.num_groups()];
-        for u in 0..network.node_count() as Node {
-            for v in u + 1..network.node_count() as Node {
-                let hcg = model.hcg(u, v);
-                hcg_pairs[hcg] += 1;
+            init_report,
+        };
+        hcp.refresh_violations();
+        Ok(hcp)
+    }
+
+    /// initialize group edge count caches hcp_edges, hcp_pairs, and tell why the pair count
+    /// fell back from the GPU to the CPU, if it did
+    fn init_hcg_props(
+        network: &NetworkData,
+        model: &MultiGroupModel,
+    ) -> (Vec<usize>, Vec<usize>, Option<String>) {
+        let hcg_edges = hcg::hcg_edges(network, model);
+
+        #[cfg(feature = "gpu")]
+        if network.node_count() >= gpu::MIN_NODES {
+            match gpu::hcg_pairs(model.groups(), model.num_groups()) {
+                Ok(hcg_pairs) => return (hcg_edges, hcg_pairs, None),
+                Err(e) => return (hcg_edges, hcg::hcg_pairs(model), Some(e.to_string())),
             }
         }
-        (hcg_edges, hcg_pairs)
+
+        (hcg_edges, hcg::hcg_pairs(model), None)
+    }
+
+    /// diagnostics of the construction of this model
+    pub fn init_report(&self) -> &InitReport {
+        &self.init_report
+    }
+
+    pub fn network(&self) -> &Arc<NetworkData> {
+        &self.network
+    }
+
+    pub fn model(&self) -> &MultiGroupModel {
+        &self.model
+    }
+
+    /// number of edges for which each group is the highest common group
+    pub fn hcg_edges(&self) -> &[usize] {
+        &self.hcg_edges
+    }
+
+    /// number of node pairs for which each group is the highest common group
+    pub fn hcg_pairs(&self) -> &[usize] {
+        &self.hcg_pairs
+    }
+
+    pub fn log_like(&self) -> f64 {
+        self.log_like.to_f64()
+    }
+
+    /// inverse temperature: the log-likelihood difference is multiplied by `beta` before
+    /// acceptance. 1 samples the posterior, larger values favour better configurations.
+    pub fn beta(&self) -> f64 {
+        self.beta
+    }
+
+    /// groups removed by the empty group policy in the last step, after its moves, in the
+    /// order of removal. Each is numbered as just before its removal, which renumbers the
+    /// groups after it.
+    pub fn pruned_groups(&self) -> &[usize] {
+        &self.pruned
+    }
+
+    pub fn set_beta(&mut self, beta: f64) {
+        self.beta = beta;
+    }
+
+    /// factor on the probability of proposing to add a group instead of moving a node, see
+    /// `parameters::MoveSchedule`. 1 is the mix of the C++ version.
+    pub fn group_move_weight(&self) -> f64 {
+        self.group_move_weight
+    }
+
+    pub fn set_group_move_weight(&mut self, weight: f64) {
+        self.group_move_weight = weight;
+    }
+
+    pub fn num_edges(&self) -> usize {
+        self.network.edge_count()
+    }
+
+    /// number of node pairs, i.e. possible edges
+    pub fn num_pairs(&self) -> usize {
+        let n = self.network.node_count();
+        n * n.saturating_sub(1) / 2
+    }
+
+    /// fraction of pairs with `g` as highest common group that are connected.
+    /// 0 if there are no such pairs.
+    pub fn group_density(&self, g: usize) -> f64 {
+        match self.hcg_pairs[g] {
+            0 => 0.0,
+            pairs => self.hcg_edges[g] as f64 / pairs as f64,
+        }
+    }
+
+    /// probability of an edge between nodes `u` and `v` in the current configuration: the
+    /// density of their highest common group
+    pub fn edge_probability(&self, u: Node, v: Node) -> f64 {
+        self.group_density(self.model.hcg(u, v))
+    }
+
+    /// `edge_probability` of each of `pairs`
+    pub fn edge_probabilities(&self, pairs: &[(Node, Node)]) -> Vec<f64> {
+        let densities: Vec<f64> = (0..self.model.num_groups())
+            .map(|g| self.group_density(g))
+            .collect();
+        pairs
+            .iter()
+            .map(|&(u, v)| densities[self.model.hcg(u, v)])
+            .collect()
+    }
+
+    pub fn summary(&self) -> Summary {
+        Summary {
+            num_nodes: self.network.node_count(),
+            num_edges: self.num_edges(),
+            num_groups: self.model.num_groups(),
+            log_like: self.log_like(),
+            group_sizes: self.model.group_sizes().to_vec(),
+            group_densities: (0..self.model.num_groups())
+                .map(|g| self.group_density(g))
+                .collect(),
+        }
+    }
+
+    /// label-switching invariant statistics of the current configuration.
+    /// O(n · groups), cheap enough to be computed for every snapshot.
+    pub fn trace_stats(&self) -> TraceStats {
+        let num_groups = self.model.num_groups();
+        let depths = self.model.group_depths();
+        let num_nodes = self.network.node_count();
+        let level_entropy = (1..=depths.iter().copied().max().unwrap_or(0))
+            .map(|level| {
+                let mask = depths
+                    .iter()
+                    .enumerate()
+                    .filter(|&(_, &d)| d <= level)
+                    .fold(0 as Groups, |m, (g, _)| m | (1 << g));
+                let mut block_size = vec![0usize; num_groups];
+                for &groups in self.model.groups() {
+                    block_size[63 - (groups & mask).leading_zeros() as usize] += 1;
+                }
+                block_size
+                    .into_iter()
+                    .filter(|&size| size > 0)
+                    .map(|size| {
+                        let p = size as f64 / num_nodes as f64;
+                        -p * p.ln()
+                    })
+                    .sum()
+            })
+            .collect();
+        TraceStats {
+            num_groups,
+            within_group_edges: match self.num_edges() {
+                0 => 0.0,
+                edges => 1.0 - self.hcg_edges[0] as f64 / edges as f64,
+            },
+            level_entropy,
+        }
+    }
+
+    /// restart the random number generator from `seed`
+    pub fn reseed(&mut self, seed: u64) {
+        self.rng = R::seed_from_u64(seed);
+    }
+
+    /// write the sampler state except for the random number generator, see `checkpoint`
+    pub fn encode<W: std::io::Write>(&self, e: &mut Encoder<W>) -> std::io::Result<()> {
+        self.model.encode(e)?;
+        e.usizes(&self.hcg_edges)?;
+        e.usizes(&self.hcg_pairs)?;
+        e.f64(self.log_like.to_f64())?;
+        e.f64(self.beta)?;
+        e.u64s(&self.empty_steps)
+    }
+
+    /// replace the sampler state with one written by `encode` for the same network, leaving
+    /// it unchanged if that fails
+    pub fn decode<T: std::io::Read>(&mut self, d: &mut Decoder<T>) -> std::io::Result<()> {
+        let model = MultiGroupModel::decode(d)?;
+        d.expect(
+            "number of nodes",
+            model.num_nodes(),
+            self.network.node_count(),
+        )?;
+        let hcg_edges = d.usizes()?;
+        let hcg_pairs = d.usizes()?;
+        d.expect("number of groups", hcg_edges.len(), model.num_groups())?;
+        d.expect("number of groups", hcg_pairs.len(), model.num_groups())?;
+        let log_like = d.f64()?;
+        let beta = d.f64()?;
+        let empty_steps = d.u64s()?;
+        d.expect("number of groups", empty_steps.len(), model.num_groups())?;
+        self.model = model;
+        self.hcg_edges = hcg_edges;
+        self.hcg_pairs = hcg_pairs;
+        self.log_like = F::from_f64(log_like);
+        self.beta = beta;
+        self.empty_steps = empty_steps;
+        self.refresh_violations();
+        Ok(())
+    }
+
+    /// sum over the non-root groups of the binary entropy (in nats) of membership,
+    /// i.e. the average information per node needed to state its groups given the group sizes
+    pub fn partition_entropy(&self) -> f64 {
+        let n = self.network.node_count() as f64;
+        self.model.group_sizes()[1..self.model.num_groups()]
+            .iter()
+            .map(|&size| {
+                let p = size as f64 / n;
+                [p, 1.0 - p]
+                    .into_iter()
+                    .filter(|&q| q > 0.0)
+                    .map(|q| -q * q.ln())
+                    .sum::<f64>()
+            })
+            .sum()
+    }
+
+    /// minimum description length (in nats): the cost of the edges given the configuration,
+    /// `-log_like`, plus the cost of the configuration, the size of each non-root group
+    /// (`ln(n + 1)`) and which of the `C(n, size)` possible member sets it has
+    pub fn description_length(&self) -> f64 {
+        let n = self.network.node_count();
+        let configuration: f64 = self.model.group_sizes()[1..self.model.num_groups()]
+            .iter()
+            .map(|&size| {
+                ((n + 1) as f64).ln() + math::ln_fact_exact(n)
+                    - math::ln_fact_exact(size)
+                    - math::ln_fact_exact(n - size)
+            })
+            .sum();
+        configuration - self.log_like()
+    }
+
+    /// difference between the cached log-likelihood and one recomputed from scratch
+    /// in double precision. This is O(n²) in the number of nodes.
+    pub fn log_like_drift(&self) -> f64 {
+        let (hcg_edges, hcg_pairs, _) = Self::init_hcg_props(&self.network, &self.model);
+        self.log_like() - self.likelihood.log_like_exact(&hcg_edges, &hcg_pairs)
+    }
+
+    /// deterministic hash of the sampler state, to find the first iteration at which two
+    /// implementations diverge: 64-bit FNV-1a over the little-endian `u64`s of the number of
+    /// nodes, the groups of every node, the number of groups, `hcg_edges`, `hcg_pairs` and the
+    /// log-likelihood in millionths, rounded (as `i64`)
+    pub fn state_hash(&self) -> u64 {
+        let groups = self.model.groups();
+        let words = iter::once(groups.len() as u64)
+            .chain(groups.iter().copied())
+            .chain(iter::once(self.model.num_groups() as u64))
+            .chain(self.hcg_edges.iter().map(|&x| x as u64))
+            .chain(self.hcg_pairs.iter().map(|&x| x as u64))
+            .chain(iter::once((self.log_like() * 1e6).round() as i64 as u64));
+        words
+            .flat_map(u64::to_le_bytes)
+            .fold(0xcbf29ce484222325, |h, byte| {
+                (h ^ byte as u64).wrapping_mul(0x100000001b3)
+            })
+    }
+
+    /// replace the group configuration, keeping the random number generator state.
+    /// This is O(n²) in the number of nodes.
+    pub fn set_configuration(&mut self, groups: Vec<Groups>, num_groups: usize) {
+        self.model =
+            MultiGroupModel::with_groups(groups, num_groups as u32, self.model.max_groups() as u32);
+        self.empty_steps = vec![0; num_groups];
+        self.recompute();
+    }
+
+    /// add the edge `{u, v}` to the network and update the edge counts and log-likelihood
+    /// in O(number of edges), instead of rebuilding the model. Clones the network if it is
+    /// shared with other models.
+    pub fn add_edge(&mut self, u: Node, v: Node) -> Result<(), String> {
+        self.check_edge(u, v)?;
+        if self.network.neighbors(u).contains(&v) {
+            return Err(format!("edge {} {} exists already", u, v));
+        }
+        Arc::make_mut(&mut self.network).insert_edge(u, v);
+        self.hcg_edges[self.model.hcg(u, v)] += 1;
+        self.edges_changed();
+        Ok(())
+    }
+
+    /// remove the edge `{u, v}` from the network, see `add_edge`
+    pub fn remove_edge(&mut self, u: Node, v: Node) -> Result<(), String> {
+        self.check_edge(u, v)?;
+        if !Arc::make_mut(&mut self.network).remove_edge(u, v) {
+            return Err(format!("no edge {} {}", u, v));
+        }
+        self.hcg_edges[self.model.hcg(u, v)] -= 1;
+        self.edges_changed();
+        Ok(())
+    }
+
+    fn check_edge(&self, u: Node, v: Node) -> Result<(), String> {
+        let n = self.network.node_count() as Node;
+        if u >= n || v >= n {
+            return Err(format!("edge {} {}: the network has {} nodes", u, v, n));
+        }
+        if u == v {
+            return Err(format!("self-loop {} {}", u, v));
+        }
+        Ok(())
+    }
+
+    /// refresh the values derived from `hcg_edges` after an edge update
+    fn edges_changed(&mut self) {
+        self.log_like = self.likelihood.log_like(&self.hcg_edges, &self.hcg_pairs);
+        self.refresh_violations();
+    }
+
+    /// recompute hcg_edges, hcg_pairs and log_like from scratch, replacing the cached values.
+    /// The log-likelihood is recomputed in double precision.
+    /// This is O(n²) in the number of nodes.
+    pub fn recompute(&mut self) -> Recomputed {
+        let (hcg_edges, hcg_pairs, _) = Self::init_hcg_props(&self.network, &self.model);
+        let log_like = self.likelihood.log_like_exact(&hcg_edges, &hcg_pairs);
+        let recomputed = Recomputed {
+            hcg_edges_changed: hcg_edges != self.hcg_edges,
+            hcg_pairs_changed: hcg_pairs != self.hcg_pairs,
+            log_like_drift: self.log_like() - log_like,
+        };
+        self.hcg_edges = hcg_edges;
+        self.hcg_pairs = hcg_pairs;
+        self.log_like = F::from_f64(log_like);
+        self.refresh_violations();
+        recomputed
+    }
+
+    /// number of groups with a lower edge density than their parent group
+    /// (see `MultiGroupModel::parent_groups`). Groups without pairs are not compared.
+    pub fn density_violations(&self) -> usize {
+        self.count_violations(&self.model.parent_groups())
+    }
+
+    /// `density_violations` given the `parents` of all groups
+    fn count_violations(&self, parents: &[Option<usize>]) -> usize {
+        parents
+            .iter()
+            .enumerate()
+            .filter(|&(g, &parent)| match parent {
+                Some(p) if self.hcg_pairs[g] > 0 && self.hcg_pairs[p] > 0 => {
+                    self.group_density(g) < self.group_density(p)
+                }
+                _ => false,
+            })
+            .count()
+    }
+
+    /// recompute the remembered parents and density violations from scratch, if assortative
+    fn refresh_violations(&mut self) {
+        if self.assortative {
+            self.parents = self.model.parent_groups();
+            self.violations = self.count_violations(&self.parents);
+        }
+    }
+
+    /// parents and density violations after the already performed `moves`. A node move only
+    /// changes the parents of its group and of the later groups of its node, whose overlap
+    /// with that group changed; the others are taken from the remembered parents.
+    fn violations_after(&self, moves: &[Move]) -> (Vec<Option<usize>>, usize) {
+        let mut parents = self.parents.clone();
+        for &m in moves {
+            match m {
+                Move::AddNodeToGroup { group, node, .. }
+                | Move::RemoveNodeFromGroup { group, node, .. } => {
+                    let groups = self.model.groups_of(node);
+                    for (g, parent) in parents.iter_mut().enumerate().skip(group) {
+                        if g == group || (groups >> g) & 1 != 0 {
+                            *parent = self.model.parent_group(g);
+                        }
+                    }
+                }
+                // groups are renumbered
+                Move::AddGroup { .. } | Move::RemoveGroup { .. } => {
+                    parents = self.model.parent_groups();
+                }
+            }
+        }
+        let violations = self.count_violations(&parents);
+        (parents, violations)
+    }
+
+    /// whether the already performed `moves` keep the hierarchy assortative: in assortative
+    /// mode, node moves may not increase the number of density violations. Call only for
+    /// moves that will be accepted otherwise, as the new number of violations is remembered.
+    fn keeps_assortative(&mut self, moves: &[Move]) -> bool {
+        if !self.assortative {
+            return true;
+        }
+        let (parents, violations) = self.violations_after(moves);
+        // only empty groups are added or removed, which are never compared
+        let node_moves = moves.iter().all(|m| {
+            matches!(
+                m,
+                Move::AddNodeToGroup { .. } | Move::RemoveNodeFromGroup { .. }
+            )
+        });
+        if node_moves && violations > self.violations {
+            return false;
+        }
+        (self.parents, self.violations) = (parents, violations);
+        true
+    }
+
+    fn is_frozen(&self, u: Node) -> bool {
+        !self.frozen.is_empty() && self.frozen[u as usize]
     }
 
     fn uniform_groupsize(&mut self) -> Option<Move> {
         let num_nodes = self.model.num_nodes();
         let num_groups = self.model.num_groups();
         let max_groups = self.model.max_groups();
-        let p_type2 = 1f64 / (2 * num_groups * (num_nodes + 1)) as f64;
-        if self.rng.gen_bool(p_type2) {
+        let p_type2 =
+            (self.group_move_weight / (2 * num_groups * (num_nodes + 1)) as f64).clamp(0.0, 1.0);
+        if timed!(Rng, self.rng.bernoulli(p_type2)) {
             // adds empty group or does nothing if number of groups is equal to maximum number of groups
             if num_groups == max_groups {
                 return None;
             }
             // add empty group
-            let rand_group = self.rng.gen_range(1..=num_groups);
+            let rand_group = timed!(Rng, self.rng.uniform_int(1..num_groups + 1));
             return Some(self.model.add_group(rand_group));
         } else {
-            if num_groups == 1 {
-                // if only the group of all nodes is left, do nothing
-                return None;
-            }
-            let rand_group = self.rng.gen_range(1..num_groups);
-            if self.rng.gen_bool(0.5) {
-                // remove a node
-                if self.model.group_size(rand_group) == 0 {
-                    // if empty, remove group entirely
-                    return Some(self.model.remove_group(rand_group));
-                }
-                let rand_idx = self.rng.gen_range(0..self.model.group_size(rand_group));
-                return Some(
-                    self.model
-                        .remove_node_from_group_by_idx(rand_group, rand_idx),
-                );
-            } else {
-                // add a node
-                if self.model.group_size(rand_group) == num_nodes {
-                    // if group is already full, do nothing
+            self.propose_node_move(true)
+        }
+    }
+
+    /// a node move in a random group, or the removal of the group if it is empty, the policy
+    /// is `OnSelection` and `remove_empty` is set. The move is already applied to the model.
+    fn propose_node_move(&mut self, remove_empty: bool) -> Option<Move> {
+        let num_nodes = self.model.num_nodes();
+        let num_groups = self.model.num_groups();
+        if num_groups == 1 {
+            // if only the group of all nodes is left, do nothing
+            return None;
+        }
+        let rand_group = timed!(Rng, self.rng.uniform_int(1..num_groups));
+        if timed!(Rng, self.rng.bernoulli(0.5)) {
+            // remove a node
+            if self.model.group_size(rand_group) == 0 {
+                if !remove_empty || self.empty_group_policy != EmptyGroupPolicy::OnSelection {
+                    // empty groups are handled by prune_empty_groups
                     return None;
                 }
-                let n_out: usize = self.model.num_nodes() - self.model.group_size(rand_group);
-                let rand_idx = self.rng.gen_range(0..n_out);
-                return Some(self.model.add_node_to_group_by_idx(rand_group, rand_idx));
+                // if empty, remove group entirely
+                return Some(self.model.remove_group(rand_group));
+            }
+            if self.model.group_size(rand_group) <= self.min_group_size {
+                // group would shrink below the minimum size
+                return None;
+            }
+            let rand_idx = timed!(
+                Rng,
+                self.rng.uniform_int(0..self.model.group_size(rand_group))
+            );
+            if self.is_frozen(self.model.members(rand_group)[rand_idx]) {
+                return None;
             }
+            Some(
+                self.model
+                    .remove_node_from_group_by_idx(rand_group, rand_idx),
+            )
+        } else {
+            // add a node
+            if self.model.group_size(rand_group) == num_nodes {
+                // if group is already full, do nothing
+                return None;
+            }
+            let n_out: usize = self.model.num_nodes() - self.model.group_size(rand_group);
+            let rand_idx = timed!(Rng, self.rng.uniform_int(0..n_out));
+            if self.is_frozen(self.model.non_members(rand_group)[rand_idx]) {
+                return None;
+            }
+            Some(self.model.add_node_to_group_by_idx(rand_group, rand_idx))
         }
     }
 
@@ -207,55 +917,155 @@ impl HierarchicalModel {
             | Move::RemoveNodeFromGroup {
                 node, old_state, ..
             } => {
-                let u = node as Node;
-                for v in 0..self.network.node_count() as Node {
-                    if v == u {
-                        continue;
-                    }
-                    let new = HCG::hcg(&self.model, u, v);
-                    let old = HCG::hcg_node(&self.model, old_state, v);
-                    self.hcg_pairs[old] -= 1;
-                    self.hcg_pairs[new] += 1;
+                // collect changes per group first, so a rejected move can be reverted cheaply
+                (self.hcg_pairs_delta, self.hcg_edges_delta) =
+                    hcg::node_move_deltas(&self.network, &self.model, node as Node, old_state);
+                for g in 0..self.model.num_groups() {
+                    self.hcg_pairs[g] =
+                        self.hcg_pairs[g].wrapping_add_signed(self.hcg_pairs_delta[g]);
+                    self.hcg_edges[g] =
+                        self.hcg_edges[g].wrapping_add_signed(self.hcg_edges_delta[g]);
                 }
-                for neighbor in self
-                    .network
-                    .neighbors_undirected(NodeIndex::new(u as usize))
-                {
-                    let v = neighbor.index() as u32;
-                    let new = HCG::hcg(&self.model, u, v);
-                    let old = HCG::hcg_node(&self.model, old_state, v);
-                    self.hcg_edges[old] -= 1;
-                    self.hcg_edges[new] += 1;
+            }
+        }
+    }
+
+    /// undo `update_hcg_props(m)` after the model has been reverted
+    fn revert_hcg_props(&mut self, m: Move) {
+        match m {
+            Move::AddGroup { group } => {
+                self.hcg_edges.remove(group);
+                self.hcg_pairs.remove(group);
+            }
+            Move::RemoveGroup { group } => {
+                // only empty groups are removed, which are nobody's highest common group
+                self.hcg_edges.insert(group, 0);
+                self.hcg_pairs.insert(group, 0);
+            }
+            Move::AddNodeToGroup { .. } | Move::RemoveNodeFromGroup { .. } => {
+                for g in 0..self.model.num_groups() {
+                    self.hcg_pairs[g] =
+                        self.hcg_pairs[g].wrapping_add_signed(-self.hcg_pairs_delta[g]);
+                    self.hcg_edges[g] =
+                        self.hcg_edges[g].wrapping_add_signed(-self.hcg_edges_delta[g]);
                 }
             }
         }
     }
 
-    pub fn get_groups(&mut self) {
-        let old_hcg_edges = self.hcg_edges.clone();
-        let old_hcg_pairs = self.hcg_pairs.clone();
+    /// perform one Monte Carlo step. Returns the move if one was accepted.
+    pub fn get_groups(&mut self) -> Option<Move> {
+        self.step().filter(|p| p.accepted).map(|p| p.m)
+    }
 
-        let Some(m) = self.uniform_groupsize() else {
-            return;
-        };
+    /// perform one Monte Carlo step. Returns the proposal, if a move was proposed.
+    pub fn step(&mut self) -> Option<Proposal> {
+        self.pruned.clear();
+        let m = timed!(Proposal, self.uniform_groupsize())?;
+        Some(self.metropolis(m))
+    }
 
-        self.update_hcg_props(m);
+    /// accept or reject the applied move `m` by the Metropolis rule
+    fn metropolis(&mut self, m: Move) -> Proposal {
+        let new_loglike = self.evaluate(m);
 
-        let new_loglike = if let Move::RemoveNodeFromGroup { .. } | Move::AddNodeToGroup { .. } = m
-        {
-            calc_loglike(&self.hcg_edges, &self.hcg_pairs)
+        // acceptance probability
+        let alpha = f64::exp(self.beta * (new_loglike - self.log_like).to_f64()).min(1.0);
+        let accepted = timed!(Rng, self.rng.bernoulli(alpha)) && self.keeps_assortative(&[m]);
+        self.decide(m, new_loglike, accepted)
+    }
+
+    /// propose `m` instead of a random move and decide it by the Metropolis rule,
+    /// see `testing::ScriptedProposal`
+    #[cfg(any(test, feature = "testing"))]
+    pub fn propose(&mut self, m: Move) -> Result<Proposal, String> {
+        let m = self.model.apply(m)?;
+        Ok(self.metropolis(m))
+    }
+
+    /// re-apply a recorded proposal `m` with a predetermined acceptance decision.
+    /// The returned proposal carries the freshly computed log-likelihood difference.
+    /// Fails without changing the model for moves `MultiGroupModel::check_move` rejects.
+    pub fn replay_step(&mut self, m: Move, accepted: bool) -> Result<Proposal, String> {
+        let m = self.model.apply(m)?;
+        let new_loglike = self.evaluate(m);
+        if accepted && self.assortative {
+            (self.parents, self.violations) = self.violations_after(&[m]);
+        }
+        Ok(self.decide(m, new_loglike, accepted))
+    }
+
+    /// update hcg caches for the already performed move `m` and return the new log-likelihood
+    fn evaluate(&mut self, m: Move) -> F {
+        timed!(UpdateHcgProps, self.update_hcg_props(m));
+
+        if let Move::RemoveNodeFromGroup { .. } | Move::AddNodeToGroup { .. } = m {
+            timed!(
+                CalcLoglike,
+                self.likelihood.log_like(&self.hcg_edges, &self.hcg_pairs)
+            )
         } else {
             self.log_like
-        };
+        }
+    }
 
-        let alpha = f64::exp(new_loglike - self.log_like); // acceptance probability
-        if self.rng.gen_bool(alpha) {
-            // accept move
-            self.log_like = new_loglike
+    /// remove empty groups according to the empty group policy, after the decided move `m`.
+    /// This happens outside of the Metropolis-Hastings step and does not change log_like,
+    /// since empty groups contribute neither pairs nor edges.
+    fn prune_empty_groups(&mut self, m: Move, accepted: bool) {
+        self.pruned.clear();
+        match self.empty_group_policy {
+            EmptyGroupPolicy::OnSelection | EmptyGroupPolicy::Keep => {}
+            EmptyGroupPolicy::Prune => {
+                if let Move::RemoveNodeFromGroup { group, .. } = m {
+                    if accepted && self.model.group_size(group) == 0 {
+                        self.remove_empty_group(group);
+                    }
+                }
+            }
+            EmptyGroupPolicy::Lazy(k) => {
+                for g in (1..self.model.num_groups()).rev() {
+                    if self.model.group_size(g) > 0 {
+                        self.empty_steps[g] = 0;
+                    } else if self.empty_steps[g] >= k {
+                        self.remove_empty_group(g);
+                    } else {
+                        self.empty_steps[g] += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    fn remove_empty_group(&mut self, group: usize) {
+        self.model.remove_group(group);
+        self.hcg_edges.remove(group);
+        self.hcg_pairs.remove(group);
+        self.empty_steps.remove(group);
+        self.pruned.push(group);
+        self.refresh_violations();
+    }
+
+    fn decide(&mut self, m: Move, new_loglike: F, accepted: bool) -> Proposal {
+        let delta_log_like = (new_loglike - self.log_like).to_f64();
+        if accepted {
+            self.log_like = new_loglike;
+            match m {
+                Move::AddGroup { group } => self.empty_steps.insert(group, 0),
+                Move::RemoveGroup { group } => {
+                    self.empty_steps.remove(group);
+                }
+                _ => {}
+            }
         } else {
             self.model.undo_move(m);
-            self.hcg_edges = old_hcg_edges[..self.model.num_groups()].to_owned();
-            self.hcg_pairs = old_hcg_pairs[..self.model.num_groups()].to_owned();
+            self.revert_hcg_props(m);
+        }
+        self.prune_empty_groups(m, accepted);
+        Proposal {
+            m,
+            delta_log_like,
+            accepted,
         }
     }
 }
@@ -269,7 +1079,7 @@ mod tests {
 
     #[test]
     fn example() {
-        let hcp = HierarchicalModel::with_parameters(
+        let hcp: HierarchicalModel = HierarchicalModel::with_parameters(
             &Parameters::load(File::open("examples/parameters.txt").unwrap().chain(
                 &b"initial_group_config: 9 41 25 13 73 137 11 33 17 5 65 129 3 33 33 17 17 5 5 65 65 129 129 3 3\n"[..]
             ).chain(&b"initial_num_groups: 8\n"[..])
@@ -286,5 +1096,527 @@ mod tests {
             hcp.log_like,
             -20.2637
         );
+        assert_eq!(hcp.log_like_drift(), 0.0);
+        assert_eq!(hcp.num_pairs(), 300);
+        assert_eq!(hcp.num_edges(), 57);
+        assert_eq!(hcp.group_density(0), 0.0);
+        assert_eq!(hcp.group_density(3), 1.0);
+        assert_eq!(hcp.summary().num_groups, 8);
+        let report = hcp.init_report();
+        assert_eq!(
+            (report.num_nodes, report.num_edges, report.num_groups),
+            (25, 57, 8)
+        );
+        assert_eq!(report.log_like, hcp.log_like());
+        assert!(report.user_specified_config);
+        assert!(report.ln_fact_table_len > 25 * 25);
+        assert_eq!(report.gpu_fallback, None);
+        let fallback = InitReport {
+            gpu_fallback: Some(String::from("no platform")),
+            ..report.clone()
+        };
+        assert!(fallback
+            .to_string()
+            .contains("warning: GPU pair count failed, falling back to CPU: no platform\n"));
+    }
+
+    /// examples/parameters.txt with additional parameter lines, for the tests of every module
+    pub(crate) fn _example_parameters(extra: &[u8]) -> Result<Parameters, String> {
+        Ok(
+            Parameters::load(File::open("examples/parameters.txt").unwrap().chain(extra))?
+                .resolve_paths(Path::new("examples/")),
+        )
+    }
+
+    /// model for examples/parameters.txt, with additional parameter lines
+    pub(crate) fn _example_model(extra: &[u8]) -> HierarchicalModel {
+        HierarchicalModel::with_parameters(&_example_parameters(extra).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn ln_fact_table_len() {
+        // ln(0!) to ln((25² + 2)!), of this model's own table
+        assert_eq!(_example_model(b"").init_report().ln_fact_table_len, 628);
+        let single: HierarchicalModel<f32> =
+            HierarchicalModel::with_parameters(&_example_parameters(b"").unwrap()).unwrap();
+        assert_eq!(single.init_report().ln_fact_table_len, 628);
+        let lgamma = _example_model(b"ln_fact_table_max_mb: 0\n");
+        assert_eq!(lgamma.init_report().ln_fact_table_len, 0);
+    }
+
+    #[test]
+    fn state_hash() {
+        let mut hcp = _example_model(b"seed: 4\n");
+        let hash = hcp.state_hash();
+        assert_eq!(_example_model(b"seed: 4\n").state_hash(), hash);
+        while hcp.get_groups().is_none() {}
+        assert_ne!(hcp.state_hash(), hash);
+
+        // independent of the random number generator, for comparison with other implementations
+        let hcp = _example_model(
+            b"initial_group_config: 3 3 3 7 7 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1\n\
+              initial_num_groups: 3\n",
+        );
+        assert_eq!(hcp.state_hash(), 0xea9ee75164167417);
+    }
+
+    #[test]
+    fn rejected_moves() {
+        let mut hcp = _example_model(
+            b"initial_group_config: 9 41 25 13 73 137 11 33 17 5 65 129 3 33 33 17 17 5 5 65 65 129 129 3 3\n\
+              initial_num_groups: 8\n",
+        );
+        // a group that was filled and emptied again lists its non-members in another order
+        hcp.replay_step(hcp.model.clone().add_group(8), true)
+            .unwrap();
+        for _ in 0..3 {
+            hcp.replay_step(hcp.model.clone().add_node_to_group_by_idx(8, 4), true)
+                .unwrap();
+        }
+        while hcp.model.group_size(8usize) > 0 {
+            let idx = hcp.model.group_size(8usize) / 2;
+            hcp.replay_step(
+                hcp.model.clone().remove_node_from_group_by_idx(8, idx),
+                true,
+            )
+            .unwrap();
+        }
+        let hash = hcp.state_hash();
+        let non_members = hcp.model.non_members(8).to_vec();
+        let moves = [
+            hcp.model.clone().add_group(2),
+            hcp.model.clone().remove_group(8),
+            hcp.model.clone().add_node_to_group_by_idx(3, 5),
+            hcp.model.clone().remove_node_from_group_by_idx(4, 2),
+        ];
+        for m in moves {
+            assert!(!hcp.replay_step(m, false).unwrap().accepted);
+            assert_eq!(hcp.state_hash(), hash, "{:?}", m);
+        }
+        assert_eq!(hcp.model.non_members(8), non_members);
+        assert!(hcp.recompute().is_consistent());
+    }
+
+    #[test]
+    fn trace_stats() {
+        let hcp = _example_model(
+            b"initial_group_config: 9 41 25 13 73 137 11 33 17 5 65 129 3 33 33 17 17 5 5 65 65 129 129 3 3\ninitial_num_groups: 8\n",
+        );
+        let stats = hcp.trace_stats();
+        assert_eq!(stats.num_groups, 8);
+        assert_eq!(stats.within_group_edges, 1.0);
+        // one level: blocks of 3 (three times) and 4 (four times) out of 25 nodes
+        assert_eq!(stats.level_entropy.len(), 1);
+        assert!((stats.level_entropy[0] - 1.93615).abs() < 1e-5);
+    }
+
+    #[test]
+    fn description_length() {
+        // 5 of the 25 nodes in group 1
+        let hcp = _example_model(
+            b"initial_group_config: 3 3 3 3 3 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1\ninitial_num_groups: 2\n",
+        );
+        let h = -0.2 * 0.2f64.ln() - 0.8 * 0.8f64.ln();
+        assert!((hcp.partition_entropy() - h).abs() < 1e-12);
+        let configuration = 26f64.ln() + 53130f64.ln();
+        assert!((hcp.description_length() - configuration + hcp.log_like()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn recompute_after_moves() {
+        let mut hcp = _example_model(
+            b"initial_group_config: 9 41 25 13 73 137 11 33 17 5 65 129 3 33 33 17 17 5 5 65 65 129 129 3 3\n\
+              initial_num_groups: 8\n",
+        );
+        let moves = [
+            (hcp.model.clone().add_node_to_group_by_idx(3, 5), true),
+            (hcp.model.clone().add_group(2), true),
+            (hcp.model.clone().add_node_to_group_by_idx(1, 0), false),
+        ];
+        for (m, accepted) in moves {
+            hcp.replay_step(m, accepted).unwrap();
+        }
+        hcp.replay_step(hcp.model.clone().remove_node_from_group_by_idx(4, 2), true)
+            .unwrap();
+        hcp.replay_step(hcp.model.clone().remove_group(2), false)
+            .unwrap();
+        assert!(hcp.recompute().is_consistent());
+    }
+
+    #[test]
+    fn single_precision() {
+        let mut hcp: HierarchicalModel<f32> = HierarchicalModel::with_parameters(
+            &_example_parameters(
+                b"initial_group_config: 9 41 25 13 73 137 11 33 17 5 65 129 3 33 33 17 17 5 5 65 65 129 129 3 3\n\
+                  initial_num_groups: 8\n",
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert!((hcp.log_like() - -20.2637).abs() < 0.001);
+        hcp.replay_step(hcp.model.clone().add_node_to_group_by_idx(3, 5), true)
+            .unwrap();
+        hcp.replay_step(hcp.model.clone().remove_node_from_group_by_idx(4, 2), false)
+            .unwrap();
+        let recomputed = hcp.recompute();
+        assert!(recomputed.log_like_drift.abs() < 1e-4, "{:?}", recomputed);
+        assert!(hcp.log_like_drift().abs() < 1e-4);
+    }
+
+    #[test]
+    fn min_group_size() {
+        let mut hcp = _example_model(
+            b"initial_group_config: 1 3 3 3 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1\n\
+              initial_num_groups: 2\n\
+              min_group_size: 3\n",
+        );
+        for _ in 0..1000 {
+            if let Some(m) = hcp.uniform_groupsize() {
+                assert!(!matches!(m, Move::RemoveNodeFromGroup { .. }), "{:?}", m);
+                hcp.model.undo_move(m);
+            }
+        }
+    }
+
+    #[test]
+    fn initial_config_file() {
+        let path = std::env::temp_dir().join("hcp_rs_test_initial_config.txt");
+        let rows = ["1 1 0\n"; 5].concat() + &["1 0 1\n"; 20].concat();
+        std::fs::write(&path, rows).unwrap();
+        let hcp =
+            _example_model(format!("initial_group_config_path: {}\n", path.display()).as_bytes());
+        assert_eq!(hcp.model().num_groups(), 3);
+        assert_eq!(hcp.model().group_sizes(), [25, 5, 20]);
+
+        std::fs::write(&path, "1 1\n").unwrap();
+        let params = _example_parameters(
+            format!("initial_group_config_path: {}\n", path.display()).as_bytes(),
+        )
+        .unwrap();
+        assert!(HierarchicalModel::<f64>::with_parameters(&params).is_err());
+    }
+
+    #[test]
+    fn initial_config_validation() {
+        let load = |extra: &[u8]| {
+            HierarchicalModel::<f64>::with_parameters(&_example_parameters(extra)?)
+                .map(|hcp| hcp.model().num_groups())
+        };
+        let config = b"initial_group_config: 9 41 25 13 73 137 11 33 17 5 65 129 3 33 33 17 17 5 5 65 65 129 129 3 3\n";
+        assert_eq!(
+            load(&[&config[..], b"initial_num_groups: 7\n"].concat()),
+            Err(String::from(
+                "initial_num_groups is 7, but initial_group_config has 8 groups"
+            ))
+        );
+        assert_eq!(
+            load(&[&config[..], b"initial_num_groups: 8\n"].concat()),
+            Ok(8)
+        );
+        // trailing groups start empty
+        assert_eq!(
+            load(&[&config[..], b"initial_num_groups: 10\n"].concat()),
+            Ok(10)
+        );
+        assert_eq!(
+            load(
+                &[
+                    &config[..],
+                    b"initial_num_groups: 8\nrequire_nested: true\n"
+                ]
+                .concat()
+            ),
+            Err(String::from(
+                "initial_group_config: node 12: in group 1 but not 3, which overlap at node 6 \
+                 while node 0 is only in group 3"
+            ))
+        );
+    }
+
+    #[test]
+    fn edge_updates() {
+        let mut hcp = _example_model(
+            b"initial_group_config: 9 41 25 13 73 137 11 33 17 5 65 129 3 33 33 17 17 5 5 65 65 129 129 3 3\n\
+              initial_num_groups: 8\n",
+        );
+        let shared = hcp.clone();
+        let num_edges = hcp.num_edges();
+        let (u, v) = (0..25)
+            .flat_map(|u| (u + 1..25).map(move |v| (u, v)))
+            .find(|&(u, v)| !hcp.network().neighbors(u).contains(&v))
+            .unwrap();
+        let (w, x) = hcp.network().edges().next().unwrap();
+        hcp.add_edge(u, v).unwrap();
+        hcp.remove_edge(w, x).unwrap();
+        assert_eq!(hcp.num_edges(), num_edges);
+        assert!(hcp.network().neighbors(v).contains(&u));
+        assert!(!hcp.clone().recompute().hcg_edges_changed);
+        assert!(hcp.clone().recompute().log_like_drift.abs() < 1e-9);
+        // the clone keeps the network it shared
+        assert_eq!(shared.network().edge_count(), num_edges);
+        assert!(!shared.network().neighbors(u).contains(&v));
+
+        assert!(hcp.add_edge(u, v).is_err());
+        assert!(hcp.remove_edge(w, x).is_err());
+        assert!(hcp.add_edge(3, 3).is_err());
+        assert!(hcp.add_edge(3, 25).is_err());
+        for _ in 0..2000 {
+            hcp.step();
+        }
+        assert!(hcp.recompute().log_like_drift.abs() < 1e-9);
+    }
+
+    #[test]
+    fn init_from_partition() {
+        let path = std::env::temp_dir().join("hcp_rs_test_partition.txt");
+        let labels: String = (0..25).map(|u| format!("{} c{}\n", u, u % 3)).collect();
+        std::fs::write(&path, labels).unwrap();
+        let hcp =
+            _example_model(format!("init_from_partition_path: {}\n", path.display()).as_bytes());
+        assert_eq!(hcp.model().group_sizes(), [25, 9, 8, 8]);
+        assert_eq!(hcp.model().groups()[4], 0b0101);
+    }
+
+    #[test]
+    fn init_louvain() {
+        let hcp = _example_model(b"init_method: louvain\n");
+        assert!(hcp.model().num_groups() > 1);
+        let groups = hcp.model().groups();
+        assert!(groups.iter().all(|&g| g.count_ones() <= 2));
+        assert!(hcp.log_like() > _example_model(b"").log_like());
+    }
+
+    #[test]
+    fn edge_probability() {
+        let hcp = _example_model(
+            b"initial_group_config: 3 3 3 3 3 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1\n\
+              initial_num_groups: 2\n",
+        );
+        assert_eq!(hcp.edge_probability(0, 1), hcp.group_density(1));
+        assert_eq!(hcp.edge_probability(1, 0), hcp.group_density(1));
+        assert_eq!(hcp.edge_probability(0, 5), hcp.group_density(0));
+        assert_eq!(
+            hcp.edge_probabilities(&[(0, 1), (0, 5), (7, 8)]),
+            [
+                hcp.group_density(1),
+                hcp.group_density(0),
+                hcp.group_density(0)
+            ]
+        );
+    }
+
+    #[test]
+    fn permute_nodes() {
+        let config = b"initial_group_config: 1 3 3 3 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1\n\
+                       initial_num_groups: 2\n\
+                       seed: 3\n";
+        let plain = _example_model(config);
+        let permuted = _example_model(&[&config[..], b"permute_nodes: true\n"].concat());
+        assert_eq!(plain.model().members(1), [1, 2, 3]);
+        let mut members = permuted.model().members(1).to_vec();
+        assert_ne!(permuted.model().node_order(), plain.model().node_order());
+        members.sort_unstable();
+        assert_eq!(members, [1, 2, 3]);
+        assert_eq!(permuted.model().groups(), plain.model().groups());
+        assert_eq!(permuted.log_like(), plain.log_like());
+    }
+
+    #[test]
+    fn sample_nodes() {
+        let path = std::env::temp_dir().join("hcp_rs_test_sample_nodes.txt");
+        std::fs::write(&path, "2 3\n5").unwrap();
+        let mut hcp = _example_model(format!("sample_nodes_path: {}\n", path.display()).as_bytes());
+        for _ in 0..1000 {
+            if let Some(m) = hcp.uniform_groupsize() {
+                if let Move::AddNodeToGroup { node, .. } | Move::RemoveNodeFromGroup { node, .. } =
+                    m
+                {
+                    assert!([2, 3, 5].contains(&node), "{:?}", m);
+                }
+                hcp.model.undo_move(m);
+            }
+        }
+    }
+
+    #[test]
+    fn beta() {
+        let mut hcp = _example_model(b"");
+        hcp.set_beta(0.0);
+        for _ in 0..1000 {
+            if let Some(p) = hcp.step() {
+                assert!(p.accepted, "{:?}", p);
+            }
+        }
+        hcp.set_beta(1e6);
+        for _ in 0..1000 {
+            if let Some(p) = hcp.step() {
+                assert!(!p.accepted || p.delta_log_like > -1e-3, "{:?}", p);
+            }
+        }
+        assert!(hcp.recompute().is_consistent());
+    }
+
+    #[test]
+    fn move_counts() {
+        let mut hcp = _example_model(b"seed: 6\n");
+        let mut counts = MoveCounts::default();
+        let mut steps = 0;
+        for _ in 0..5000 {
+            if let Some(p) = hcp.step() {
+                counts.record(&p);
+                steps += 1;
+            }
+        }
+        assert_eq!(counts.proposed.iter().sum::<u64>(), steps);
+        assert!((0..4).all(|k| counts.accepted[k] <= counts.proposed[k]));
+        assert!(counts.proposed[2] > 0 && counts.proposed[3] > 0);
+        let shown = counts.to_string();
+        assert!(shown.starts_with("add_group "));
+        assert!(shown.contains(&format!(
+            "add_node {}/{}",
+            counts.accepted[2], counts.proposed[2]
+        )));
+    }
+
+    #[test]
+    fn move_schedule() {
+        let schedule: parameters::MoveSchedule = "100 50 300 0".parse().unwrap();
+        assert_eq!(schedule.weight(0), 1.0);
+        assert_eq!(schedule.weight(100), 50.0);
+        assert_eq!(schedule.weight(299), 50.0);
+        assert_eq!(schedule.weight(u64::MAX), 0.0);
+        assert!("100".parse::<parameters::MoveSchedule>().is_err());
+        assert!("5 1 5 2".parse::<parameters::MoveSchedule>().is_err());
+        assert!("5 -1".parse::<parameters::MoveSchedule>().is_err());
+
+        let mut hcp = _example_model(b"seed: 4\n");
+        let count_group_moves = |hcp: &mut HierarchicalModel, weight: f64| {
+            hcp.set_group_move_weight(weight);
+            (0..20000)
+                .filter_map(|_| hcp.step())
+                .filter(|p| matches!(p.m, Move::AddGroup { .. }))
+                .count()
+        };
+        assert_eq!(count_group_moves(&mut hcp, 0.0), 0);
+        let weighted = count_group_moves(&mut hcp, 100.0);
+        let plain = count_group_moves(&mut hcp, 1.0);
+        assert!(weighted > 10 * plain.max(1), "{} {}", weighted, plain);
+    }
+
+    #[test]
+    fn assortative() {
+        let mut hcp = _example_model(b"assortative: true\nmax_num_groups: 6\n");
+        hcp.set_beta(0.0);
+        let initial = hcp.clone();
+        let mut violations = hcp.density_violations();
+        let mut proposals = Vec::new();
+        for i in 0..3000 {
+            // batches are checked on all their moves together
+            let batch = if i % 2 == 0 {
+                hcp.step().into_iter().collect()
+            } else {
+                hcp.batch_step(3, 1)
+            };
+            let v = hcp.density_violations();
+            assert!(v <= violations, "{} > {}", v, violations);
+            // the remembered parents and violations follow the touched groups
+            assert_eq!(hcp.parents, hcp.model.parent_groups());
+            assert_eq!(hcp.violations, v);
+            violations = v;
+            proposals.push(batch);
+        }
+
+        let mut replayed = initial;
+        for batch in proposals.iter().filter(|b| !b.is_empty()) {
+            if batch.len() == 1 {
+                replayed.replay_step(batch[0].m, batch[0].accepted).unwrap();
+            } else {
+                replayed.replay_batch(batch).unwrap();
+            }
+            assert_eq!(replayed.parents, replayed.model.parent_groups());
+            assert_eq!(replayed.violations, replayed.density_violations());
+        }
+        assert_eq!(replayed.model.groups(), hcp.model.groups());
+    }
+
+    #[test]
+    fn prune_empty_groups() {
+        let mut hcp = _example_model(
+            b"initial_group_config: 1 3 1 5 5 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1\n\
+              initial_num_groups: 3\n\
+              empty_group_policy: prune\n",
+        );
+        hcp.replay_step(hcp.model.clone().remove_node_from_group_by_idx(1, 0), true)
+            .unwrap();
+        assert_eq!(hcp.model.num_groups(), 2);
+        assert_eq!(hcp.model.group_sizes(), [25, 2]);
+        assert_eq!(hcp.pruned_groups(), [1]);
+        assert!(hcp.recompute().is_consistent());
+        // an added group stays until a node move leaves it empty
+        hcp.replay_step(hcp.model.clone().add_group(1), false)
+            .unwrap();
+        hcp.replay_step(hcp.model.clone().add_group(2), true)
+            .unwrap();
+        assert_eq!(hcp.model.group_sizes(), [25, 2, 0]);
+        assert!(hcp.pruned_groups().is_empty());
+        hcp.replay_step(hcp.model.clone().add_node_to_group_by_idx(2, 0), true)
+            .unwrap();
+        assert_eq!(hcp.model.group_sizes(), [25, 2, 1]);
+        let removal = hcp.model.clone().remove_node_from_group_by_idx(2, 0);
+        hcp.replay_step(removal, false).unwrap();
+        assert_eq!(hcp.model.group_sizes(), [25, 2, 1]);
+        hcp.replay_step(removal, true).unwrap();
+        assert_eq!(hcp.model.group_sizes(), [25, 2]);
+        assert_eq!(hcp.pruned_groups(), [2]);
+        assert!(hcp.recompute().is_consistent());
+
+        hcp.empty_group_policy = EmptyGroupPolicy::Lazy(2);
+        hcp.replay_step(hcp.model.clone().add_group(2), true)
+            .unwrap();
+        hcp.replay_step(hcp.model.clone().add_group(1), false)
+            .unwrap();
+        assert_eq!(hcp.model.num_groups(), 3);
+        hcp.replay_step(hcp.model.clone().add_group(1), false)
+            .unwrap();
+        assert_eq!(hcp.model.num_groups(), 2);
+        assert_eq!(hcp.pruned_groups(), [2]);
+        assert!(hcp.recompute().is_consistent());
+    }
+
+    #[test]
+    fn decode() {
+        let mut hcp = _example_model(b"seed: 3\ninitial_num_groups: 3\n");
+        hcp.beta = 0.5;
+        let mut e = Encoder::new(Vec::new()).unwrap();
+        hcp.encode(&mut e).unwrap();
+        let buf = e.into_inner();
+        let mut restored = _example_model(b"seed: 4\ninitial_num_groups: 5\n");
+        restored
+            .decode(&mut Decoder::new(&buf[..]).unwrap())
+            .unwrap();
+        assert_eq!(restored.model.groups(), hcp.model.groups());
+        assert_eq!(restored.beta, 0.5);
+        assert!(restored.recompute().is_consistent());
+
+        // the empty group counters must match the groups, and a failed decode changes nothing
+        let mut e = Encoder::new(Vec::new()).unwrap();
+        hcp.model.encode(&mut e).unwrap();
+        e.usizes(&hcp.hcg_edges).unwrap();
+        e.usizes(&hcp.hcg_pairs).unwrap();
+        e.f64(hcp.log_like()).unwrap();
+        e.f64(1.0).unwrap();
+        e.u64s(&[0; 2]).unwrap();
+        let buf = e.into_inner();
+        let mut restored = _example_model(b"seed: 4\ninitial_num_groups: 5\n");
+        let initial = restored.clone();
+        let err = restored
+            .decode(&mut Decoder::new(&buf[..]).unwrap())
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("number of groups is 2, expected 3"));
+        assert_eq!(restored.model.groups(), initial.model.groups());
+        assert_eq!(restored.empty_steps, initial.empty_steps);
+        assert_eq!(restored.log_like(), initial.log_like());
     }
 }
@@ -0,0 +1,157 @@
+//! posterior of the edge probabilities of chosen node pairs: the probability of every pair is
+//! recorded at each snapshot, so link predictions come with credible intervals instead of a
+//! single point estimate. Pairs are read from a text file with one `<u> <v>` pair per line.
+use crate::likelihood::Likelihood;
+use crate::multi_group_model::Node;
+use crate::rng::SamplerRng;
+use crate::{Float, HierarchicalModel};
+use std::io::{self, BufRead, Write};
+
+/// node pairs, one `u v` pair per line; empty lines and lines starting with `#` are skipped
+pub fn read_pairs(src: impl BufRead) -> Result<Vec<(Node, Node)>, String> {
+    let mut pairs = Vec::new();
+    for line in src.lines() {
+        let line = line.map_err(|e| e.to_string())?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let nodes = line
+            .split_whitespace()
+            .map(|w| w.parse().or(Err(format!("not a node: {}", w))))
+            .collect::<Result<Vec<Node>, String>>()?;
+        match nodes[..] {
+            [u, v] => pairs.push((u, v)),
+            _ => return Err(format!("not a node pair: {}", line)),
+        }
+    }
+    Ok(pairs)
+}
+
+/// edge probabilities of `pairs` in every observed sample
+#[derive(Debug, Clone)]
+pub struct PairPosterior {
+    pairs: Vec<(Node, Node)>,
+    /// `samples[s * pairs.len() + i]`: probability of pair `i` in sample `s`
+    samples: Vec<f64>,
+}
+
+/// summary of the posterior of one pair
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PairSummary {
+    pub mean: f64,
+    pub sd: f64,
+    /// bounds of the central credible interval
+    pub lower: f64,
+    pub upper: f64,
+}
+
+impl PairPosterior {
+    /// fails for pairs with a node outside of `0..num_nodes`
+    pub fn new(pairs: Vec<(Node, Node)>, num_nodes: usize) -> Result<Self, String> {
+        if let Some(&(u, v)) = pairs.iter().find(|&&(u, v)| u.max(v) as usize >= num_nodes) {
+            return Err(format!(
+                "pair ({}, {}) is not in a network of {} nodes",
+                u, v, num_nodes
+            ));
+        }
+        Ok(Self {
+            pairs,
+            samples: Vec::new(),
+        })
+    }
+
+    pub fn pairs(&self) -> &[(Node, Node)] {
+        &self.pairs
+    }
+
+    pub fn num_samples(&self) -> usize {
+        self.samples.len() / self.pairs.len().max(1)
+    }
+
+    /// record the edge probabilities of the current configuration of `hcp`
+    pub fn observe<F: Float, L: Likelihood, R: SamplerRng>(
+        &mut self,
+        hcp: &HierarchicalModel<F, L, R>,
+    ) {
+        self.samples.extend(hcp.edge_probabilities(&self.pairs));
+    }
+
+    /// mean, standard deviation and central `level` credible interval of pair `i`
+    pub fn summary(&self, i: usize, level: f64) -> PairSummary {
+        let mut values: Vec<f64> = self
+            .samples
+            .iter()
+            .skip(i)
+            .step_by(self.pairs.len())
+            .copied()
+            .collect();
+        if values.is_empty() {
+            return PairSummary {
+                mean: f64::NAN,
+                sd: f64::NAN,
+                lower: f64::NAN,
+                upper: f64::NAN,
+            };
+        }
+        let n = values.len() as f64;
+        let mean = values.iter().sum::<f64>() / n;
+        let var = values.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / (n - 1.0).max(1.0);
+        values.sort_by(f64::total_cmp);
+        // nearest rank quantiles, with some slack for the rounding of `q * n`
+        let quantile = |q: f64| values[((q * n - 1e-9).ceil() as usize).clamp(1, values.len()) - 1];
+        let tail = (1.0 - level.clamp(0.0, 1.0)) / 2.0;
+        PairSummary {
+            mean,
+            sd: var.sqrt(),
+            lower: quantile(tail),
+            upper: quantile(1.0 - tail),
+        }
+    }
+
+    /// write the summary of every pair as CSV: `u,v,mean,sd,lower,upper`
+    pub fn write_csv<W: Write>(&self, mut w: W, level: f64) -> io::Result<()> {
+        writeln!(w, "u,v,mean,sd,lower,upper")?;
+        for (i, &(u, v)) in self.pairs.iter().enumerate() {
+            let s = self.summary(i, level);
+            writeln!(w, "{},{},{},{},{},{}", u, v, s.mean, s.sd, s.lower, s.upper)?;
+        }
+        w.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn credible_intervals() {
+        let pairs = read_pairs(&b"# u v\n0 1\n\n2 3\n"[..]).unwrap();
+        assert_eq!(pairs, [(0, 1), (2, 3)]);
+        assert!(read_pairs(&b"0 1 2\n"[..]).is_err());
+        assert!(PairPosterior::new(vec![(0, 4)], 4).is_err());
+
+        let mut posterior = PairPosterior::new(pairs, 4).unwrap();
+        assert!(posterior.summary(0, 0.9).mean.is_nan());
+        // pair 0 takes the values 0.1, 0.2, ..., 1.0 and pair 1 is always 0.5
+        posterior.samples = (1..=10).flat_map(|k| [k as f64 / 10.0, 0.5]).collect();
+        assert_eq!(posterior.num_samples(), 10);
+        let s = posterior.summary(0, 0.8);
+        assert!((s.mean - 0.55).abs() < 1e-12);
+        assert_eq!((s.lower, s.upper), (0.1, 0.9));
+        assert_eq!(
+            posterior.summary(1, 0.95),
+            PairSummary {
+                mean: 0.5,
+                sd: 0.0,
+                lower: 0.5,
+                upper: 0.5
+            }
+        );
+
+        let mut buf = Vec::new();
+        posterior.write_csv(&mut buf, 0.8).unwrap();
+        let out = String::from_utf8(buf).unwrap();
+        assert_eq!(out.lines().nth(2), Some("2,3,0.5,0,0.5,0.5"));
+    }
+}
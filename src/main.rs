@@ -1,39 +1,233 @@
-use hcp_rs::parameters::Parameters;
-use hcp_rs::HierarchicalModel;
+#[cfg(feature = "arrow")]
+use hcp_rs::arrow_ipc;
+use hcp_rs::block_image;
+use hcp_rs::checkpoint::{Decoder, Encoder};
+use hcp_rs::coarsening;
+use hcp_rs::convert;
+use hcp_rs::cross_validation;
+use hcp_rs::custom_stats::StatAccumulator;
+use hcp_rs::ensemble::Ensemble;
+use hcp_rs::link_prediction::{self, PairPosterior};
+use hcp_rs::multi_group_model::Move;
+use hcp_rs::network::NetworkData;
+use hcp_rs::npy::NpzWriter;
+use hcp_rs::output;
+use hcp_rs::parameters::{
+    CustomStats, FloatFormat, LogFields, OnExistingOutput, OutputCompression, Parameters,
+    StallAction,
+};
+use hcp_rs::persistence;
+use hcp_rs::pipeline;
+use hcp_rs::probe::{self, ProbeSummary};
+#[cfg(feature = "profiling")]
+use hcp_rs::profiling::{self, Phase};
+#[cfg(feature = "sqlite")]
+use hcp_rs::results_db::ResultsDb;
+use hcp_rs::rng_compare;
+use hcp_rs::seeds::{self, SeedStream, Stream};
+use hcp_rs::thinning::AdaptiveThinning;
+use hcp_rs::trace::{
+    self, MoveTraceWriter, ProposalTraceReader, ProposalTraceWriter, Snapshot, SnapshotJsonReader,
+    SnapshotJsonWriter,
+};
+use hcp_rs::watchdog::Watchdog;
+use hcp_rs::{HierarchicalModel, MoveCounts, Proposal};
 use std::env;
 use std::fmt::Display;
 use std::fs;
 use std::fs::File;
 use std::io;
-use std::io::{BufWriter, Write};
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::iter;
+use std::mem;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
 use std::time;
 
+/// rows of varying length stored back to back in a single buffer,
+/// so taking a snapshot does not allocate a fresh Vec per row.
+#[derive(Debug)]
+struct Ragged<T> {
+    data: Vec<T>,
+    ends: Vec<usize>,
+}
+
+impl<T> Default for Ragged<T> {
+    fn default() -> Self {
+        Self {
+            data: Vec::new(),
+            ends: Vec::new(),
+        }
+    }
+}
+
+impl<T: Clone> Ragged<T> {
+    fn push(&mut self, row: &[T]) {
+        self.data.extend_from_slice(row);
+        self.ends.push(self.data.len());
+    }
+
+    fn extend(&mut self, row: impl IntoIterator<Item = T>) {
+        self.data.extend(row);
+        self.ends.push(self.data.len());
+    }
+
+    fn rows(&self) -> impl Iterator<Item = &[T]> {
+        iter::once(0)
+            .chain(self.ends.iter().copied())
+            .zip(self.ends.iter().copied())
+            .map(|(start, end)| &self.data[start..end])
+    }
+}
+
 #[derive(Debug, Default)]
 struct HcpLog {
-    groups: Vec<Vec<u64>>, // called `intermediate_states` and `configs` in cpp version
+    groups: Ragged<u64>, // called `intermediate_states` and `configs` in cpp version
     num_groups: Vec<usize>,
-    hcg_edges: Vec<Vec<usize>>,
-    hcg_pairs: Vec<Vec<usize>>,
-    group_size: Vec<Vec<usize>>,
+    hcg_edges: Ragged<usize>,
+    hcg_pairs: Ragged<usize>,
+    density: Ragged<f32>, // hcg_edges / hcg_pairs, 0 without pairs
+    group_size: Ragged<usize>,
     log_like: Vec<f64>, // called energies in cpp version
+    stats: Ragged<f64>, // within group edge fraction, then entropy of each level
+    entropy: Vec<f64>,
+    description_length: Vec<f64>,
+    changes: Ragged<usize>, // node, old and new deepest group of every node that changed
+    deepest: Vec<usize>,    // deepest groups at the last snapshot
+    renumbered: Vec<Option<usize>>, // current number of each group of the last snapshot
+    move_counts: Ragged<u64>, // accepted and proposed moves of each kind between snapshots
+    pending: MoveCounts,    // moves since the last snapshot
+    custom: StatAccumulator, // evaluated every few iterations, not at snapshots
+
+    fields: LogFields,
+    timestamps: bool,
+    iters: Vec<u64>, // always recorded: thinning and resuming make the grid irregular
+    times: Vec<f64>, // seconds since the unix epoch
 }
 
 impl HcpLog {
-    pub fn new() -> Self {
-        Self::default()
+    /// record the iteration and `fields` of each snapshot.
+    /// `timestamps`: also record the wall-clock time of each snapshot
+    pub fn new(fields: LogFields, timestamps: bool, custom: &CustomStats) -> Self {
+        Self {
+            fields,
+            timestamps,
+            custom: StatAccumulator::with_builtin(custom),
+            ..Self::default()
+        }
+    }
+
+    /// evaluate the custom statistics due at `iteration`; call once per iteration
+    pub fn observe(&mut self, iteration: u64, hcp: &HierarchicalModel) {
+        self.custom.observe(iteration, hcp);
+    }
+
+    /// count `p` towards the next snapshot
+    pub fn record(&mut self, p: &Proposal) {
+        self.pending.record(p);
+        if p.accepted {
+            match p.m {
+                Move::AddGroup { group } => {
+                    for g in self.renumbered.iter_mut().flatten() {
+                        if *g >= group {
+                            *g += 1;
+                        }
+                    }
+                }
+                Move::RemoveGroup { group } => self.group_removed(group),
+                Move::AddNodeToGroup { .. } | Move::RemoveNodeFromGroup { .. } => {}
+            }
+        }
+    }
+
+    /// follow the removal of `groups` by the empty group policy, see
+    /// `HierarchicalModel::pruned_groups`
+    pub fn record_pruned(&mut self, groups: &[usize]) {
+        for &group in groups {
+            self.group_removed(group);
+        }
     }
 
-    pub fn shapshot(&mut self, hcp: &HierarchicalModel) {
-        self.groups.push(hcp.model.groups.clone());
-        self.hcg_edges.push(hcp.hcg_edges.clone());
-        self.hcg_pairs.push(hcp.hcg_pairs.clone());
-        self.group_size.push(hcp.model.group_size.clone());
-        self.log_like.push(hcp.log_like.clone());
-        self.num_groups.push(hcp.model.num_groups().clone());
+    /// renumber the groups after the removed `group`
+    fn group_removed(&mut self, group: usize) {
+        for g in &mut self.renumbered {
+            *g = match *g {
+                Some(h) if h == group => None,
+                Some(h) if h > group => Some(h - 1),
+                h => h,
+            };
+        }
     }
 
-    fn dump_vec_space_separated<T: Display, W: Write>(w: &mut W, v: &Vec<T>) -> io::Result<()> {
+    pub fn shapshot(&mut self, iteration: u64, hcp: &HierarchicalModel) {
+        self.iters.push(iteration);
+        if self.timestamps {
+            self.times.push(
+                time::SystemTime::now()
+                    .duration_since(time::UNIX_EPOCH)
+                    .map_or(f64::NAN, |d| d.as_secs_f64()),
+            );
+        }
+        if self.fields.configs {
+            self.groups.push(hcp.model().groups());
+        }
+        if self.fields.edges {
+            self.hcg_edges.push(hcp.hcg_edges());
+        }
+        if self.fields.pairs {
+            self.hcg_pairs.push(hcp.hcg_pairs());
+        }
+        if self.fields.density {
+            self.density
+                .extend((0..hcp.model().num_groups()).map(|g| hcp.group_density(g) as f32));
+        }
+        if self.fields.group_size {
+            self.group_size.push(hcp.model().group_sizes());
+        }
+        if self.fields.ll {
+            self.log_like.push(hcp.log_like());
+        }
+        if self.fields.num_groups {
+            self.num_groups.push(hcp.model().num_groups());
+        }
+        if self.fields.entropy {
+            self.entropy.push(hcp.partition_entropy());
+        }
+        if self.fields.dl {
+            self.description_length.push(hcp.description_length());
+        }
+        if self.fields.changes {
+            let deepest = hcp.model().deepest_groups();
+            if !self.deepest.is_empty() {
+                // the old deepest group under its current number, if it still exists
+                let renumbered = |old: usize| self.renumbered.get(old).copied().flatten();
+                self.changes.extend(
+                    iter::zip(&self.deepest, &deepest)
+                        .enumerate()
+                        .filter(|(_, (&old, &new))| renumbered(old) != Some(new))
+                        .flat_map(|(u, (&old, &new))| [u, old, new]),
+                );
+            }
+            self.deepest = deepest;
+            self.renumbered = (0..hcp.model().num_groups()).map(Some).collect();
+        }
+        if self.fields.stats {
+            let stats = hcp.trace_stats();
+            self.stats
+                .extend(iter::once(stats.within_group_edges).chain(stats.level_entropy));
+        }
+        let pending = mem::take(&mut self.pending);
+        if self.fields.move_counts {
+            self.move_counts
+                .extend(iter::zip(pending.accepted, pending.proposed).flat_map(|(a, p)| [a, p]));
+        }
+    }
+
+    fn dump_vec_space_separated<T: Display, W: Write + ?Sized>(
+        w: &mut W,
+        v: &[T],
+    ) -> io::Result<()> {
         if let Some((last, rest)) = v.split_last() {
             for x in rest {
                 write!(w, "{} ", x)?;
@@ -43,62 +237,1177 @@ impl HcpLog {
         Ok(())
     }
 
-    pub fn dump(&self, save_dir: &Path, name: &str) -> io::Result<()> {
+    fn encode<W: Write>(&self, e: &mut Encoder<W>) -> io::Result<()> {
+        e.u64s(&self.groups.data)?;
+        e.usizes(&self.groups.ends)?;
+        e.usizes(&self.num_groups)?;
+        for r in [&self.hcg_edges, &self.hcg_pairs, &self.group_size] {
+            e.usizes(&r.data)?;
+            e.usizes(&r.ends)?;
+        }
+        let density: Vec<f64> = self.density.data.iter().map(|&x| x as f64).collect();
+        e.f64s(&density)?;
+        e.usizes(&self.density.ends)?;
+        e.f64s(&self.log_like)?;
+        e.f64s(&self.stats.data)?;
+        e.usizes(&self.stats.ends)?;
+        e.f64s(&self.entropy)?;
+        e.f64s(&self.description_length)?;
+        e.usizes(&self.changes.data)?;
+        e.usizes(&self.changes.ends)?;
+        e.usizes(&self.deepest)?;
+        // 0 for removed groups
+        let renumbered: Vec<usize> = self
+            .renumbered
+            .iter()
+            .map(|g| g.map_or(0, |g| g + 1))
+            .collect();
+        e.usizes(&renumbered)?;
+        e.u64s(&self.move_counts.data)?;
+        e.usizes(&self.move_counts.ends)?;
+        e.u64s(&self.pending.accepted)?;
+        e.u64s(&self.pending.proposed)?;
+        e.u64s(&self.iters)?;
+        e.f64s(&self.times)?;
+        self.custom.encode(e)
+    }
+
+    /// replace the recorded snapshots with those written by `encode`
+    fn decode<R: io::Read>(&mut self, d: &mut Decoder<R>) -> io::Result<()> {
+        self.groups.data = d.u64s()?;
+        self.groups.ends = d.usizes()?;
+        self.num_groups = d.usizes()?;
+        for r in [
+            &mut self.hcg_edges,
+            &mut self.hcg_pairs,
+            &mut self.group_size,
+        ] {
+            r.data = d.usizes()?;
+            r.ends = d.usizes()?;
+        }
+        self.density.data = d.f64s()?.into_iter().map(|x| x as f32).collect();
+        self.density.ends = d.usizes()?;
+        self.log_like = d.f64s()?;
+        self.stats.data = d.f64s()?;
+        self.stats.ends = d.usizes()?;
+        self.entropy = d.f64s()?;
+        self.description_length = d.f64s()?;
+        self.changes.data = d.usizes()?;
+        self.changes.ends = d.usizes()?;
+        self.deepest = d.usizes()?;
+        self.renumbered = d.usizes()?.into_iter().map(|g| g.checked_sub(1)).collect();
+        self.move_counts.data = d.u64s()?;
+        self.move_counts.ends = d.usizes()?;
+        let kinds = |v: Vec<u64>| {
+            v.try_into()
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "bad move counts"))
+        };
+        self.pending.accepted = kinds(d.u64s()?)?;
+        self.pending.proposed = kinds(d.u64s()?)?;
+        self.iters = d.u64s()?;
+        self.times = d.f64s()?;
+        self.custom.decode(d)
+    }
+
+    /// number of recorded values, each taking about 8 bytes in text or binary
+    fn num_values(&self) -> usize {
+        self.groups.data.len()
+            + self.num_groups.len()
+            + self.hcg_edges.data.len()
+            + self.hcg_pairs.data.len()
+            + self.density.data.len()
+            + self.group_size.data.len()
+            + self.log_like.len()
+            + self.stats.data.len()
+            + self.entropy.len()
+            + self.description_length.len()
+            + self.changes.data.len()
+            + self.move_counts.data.len()
+            + self.iters.len()
+            + self.times.len()
+            + self.custom.num_values()
+    }
+
+    /// write every recorded quantity to `{name}_{suffix}.txt`, floats formatted as `format`.
+    /// Every file is written, and compressed, by a thread of its own, and replaces an existing
+    /// file only once complete (`output::write_atomic`). `append`: keep existing snapshots.
+    pub fn dump(
+        &self,
+        save_dir: &Path,
+        name: &str,
+        format: FloatFormat,
+        compression: OutputCompression,
+        append: bool,
+    ) -> io::Result<()> {
         if !save_dir.exists() {
             fs::create_dir_all(save_dir)?;
         }
+        // the size of compressed output is not known in advance
+        if compression == OutputCompression::None {
+            output::check_free_space(save_dir, 8 * self.num_values() as u64)?;
+        }
 
-        macro_rules! dv {
-            ($data:expr, $suff:expr) => {{
-                let path = save_dir.join(format!("{}_{}.txt", name, $suff));
-                let mut w = BufWriter::new(File::create(path)?);
-                for row in $data {
-                    HcpLog::dump_vec_space_separated(&mut w, row)?;
-                    writeln!(w)?;
-                }
-                w.flush()?;
-            }};
+        thread::scope(|scope| {
+            let mut files = Vec::new();
+
+            macro_rules! dv {
+                ($data:expr, $suff:expr) => {{
+                    files.push(scope.spawn(move || {
+                        let path = save_dir.join(format!("{}_{}.txt", name, $suff));
+                        output::write_atomic(&path, compression, append, |w| {
+                            for row in $data {
+                                HcpLog::dump_vec_space_separated(w, &row)?;
+                                writeln!(w)?;
+                            }
+                            Ok(())
+                        })
+                    }));
+                }};
+            }
+
+            macro_rules! d {
+                ($data:expr, $suff:expr) => {{
+                    files.push(scope.spawn(move || {
+                        let path = save_dir.join(format!("{}_{}.txt", name, $suff));
+                        output::write_atomic(&path, compression, append, |w| {
+                            for x in $data {
+                                writeln!(w, "{}", x)?;
+                            }
+                            Ok(())
+                        })
+                    }));
+                }};
+            }
+
+            if self.fields.configs {
+                dv!(self.groups.rows(), "configs");
+            }
+            if self.fields.num_groups {
+                d!(&self.num_groups, "num_groups");
+            }
+            if self.fields.group_size {
+                dv!(self.group_size.rows(), "group_size");
+            }
+            if self.fields.edges {
+                dv!(self.hcg_edges.rows(), "edges");
+            }
+            if self.fields.pairs {
+                dv!(self.hcg_pairs.rows(), "pairs");
+            }
+            if self.fields.density {
+                // shortest in single precision, not the digits of its f64 conversion
+                let display = move |x: f32| match format {
+                    FloatFormat::Shortest => x.to_string(),
+                    _ => format.display(x as f64).to_string(),
+                };
+                dv!(
+                    self.density
+                        .rows()
+                        .map(|row| row.iter().map(|&x| display(x)).collect::<Vec<_>>()),
+                    "density"
+                );
+            }
+            if self.fields.ll {
+                d!(self.log_like.iter().map(|&x| format.display(x)), "ll");
+            }
+            if self.fields.entropy {
+                d!(self.entropy.iter().map(|&x| format.display(x)), "entropy");
+            }
+            if self.fields.dl {
+                d!(
+                    self.description_length.iter().map(|&x| format.display(x)),
+                    "dl"
+                );
+            }
+            if self.fields.stats {
+                dv!(
+                    self.stats
+                        .rows()
+                        .map(|row| row.iter().map(|&x| format.display(x)).collect::<Vec<_>>()),
+                    "stats"
+                );
+            }
+            if self.fields.changes {
+                // line i: `<node>:<old>:<new>` for the changes from snapshot i to i + 1, groups
+                // numbered as at each snapshot but compared through the renumbering between
+                dv!(
+                    self.changes.rows().map(|row| row
+                        .chunks(3)
+                        .map(|c| format!("{}:{}:{}", c[0], c[1], c[2]))
+                        .collect::<Vec<_>>()),
+                    "changes"
+                );
+            }
+            if self.fields.move_counts {
+                // `<accepted> <proposed>` of adding and removing groups, adding and removing nodes
+                dv!(self.move_counts.rows(), "move_counts");
+            }
+            d!(&self.iters, "iters");
+            if self.timestamps {
+                d!(self.times.iter().map(|t| format!("{:.3}", t)), "times");
+            }
+
+            // the first error, after all files are done
+            files
+                .into_iter()
+                .map(|f| f.join().expect("output thread panicked"))
+                .fold(Ok(()), Result::and)
+        })?;
+        // apart from the snapshot files of the same quantities
+        let custom_name = format!("{}_custom", name);
+        self.custom
+            .dump(save_dir, &custom_name, format, compression, append)
+    }
+}
+
+#[cfg(feature = "arrow")]
+impl HcpLog {
+    /// write every recorded quantity as a column of an Arrow table, see `arrow_ipc`
+    pub fn dump_arrow(&self, path: &Path) -> Result<(), String> {
+        let mut table = arrow_ipc::Table::new();
+        table.u64s("iteration", self.iters.iter().copied());
+        if self.timestamps {
+            table.f64s("time", self.times.iter().copied());
+        }
+        if self.fields.ll {
+            table.f64s("log_like", self.log_like.iter().copied());
+        }
+        if self.fields.num_groups {
+            table.u64s("num_groups", self.num_groups.iter().map(|&n| n as u64));
+        }
+        if self.fields.entropy {
+            table.f64s("entropy", self.entropy.iter().copied());
+        }
+        if self.fields.dl {
+            table.f64s("dl", self.description_length.iter().copied());
+        }
+        if self.fields.configs {
+            table.u64_lists("configs", self.groups.rows());
+        }
+        if self.fields.move_counts {
+            table.u64_lists("move_counts", self.move_counts.rows());
+        }
+        for (enabled, name, rows) in [
+            (self.fields.group_size, "group_size", &self.group_size),
+            (self.fields.edges, "edges", &self.hcg_edges),
+            (self.fields.pairs, "pairs", &self.hcg_pairs),
+        ] {
+            if enabled {
+                let rows: Vec<Vec<u64>> = rows
+                    .rows()
+                    .map(|row| row.iter().map(|&x| x as u64).collect())
+                    .collect();
+                table.u64_lists(name, rows.iter().map(Vec::as_slice));
+            }
+        }
+        if self.fields.stats {
+            table.f64_lists("stats", self.stats.rows());
+        }
+        if self.fields.density {
+            let rows: Vec<Vec<f64>> = self
+                .density
+                .rows()
+                .map(|row| row.iter().map(|&x| x as f64).collect())
+                .collect();
+            table.f64_lists("density", rows.iter().map(Vec::as_slice));
+        }
+        table.write(BufWriter::new(
+            File::create(path).map_err(|e| e.to_string())?,
+        ))
+    }
+}
+
+/// state of the main loop besides the sampler and the log, saved in checkpoints
+#[derive(Debug)]
+struct Progress {
+    iteration: u64, // next iteration to run
+    next_snapshot: u64,
+    thinning: Option<AdaptiveThinning>,
+    best_log_like: f64,
+    best_groups: Vec<u64>,
+    watchdog: Option<Watchdog>,
+    reheat_until: Option<u64>, // iteration at which a reheated chain returns to `beta`
+    beta: f64,                 // inverse temperature outside of reheats
+}
+
+impl Progress {
+    fn new(parameters: &Parameters, hcp: &HierarchicalModel) -> Self {
+        Self {
+            iteration: 0,
+            next_snapshot: 0,
+            thinning: parameters
+                .adaptive_thinning
+                .then(|| AdaptiveThinning::new(100)),
+            best_log_like: hcp.log_like(),
+            best_groups: hcp.model().groups().to_vec(),
+            watchdog: (parameters.stall_window > 0)
+                .then(|| Watchdog::new(parameters.stall_window, 0)),
+            reheat_until: None,
+            beta: hcp.beta(),
+        }
+    }
+
+    fn encode<W: Write>(&self, e: &mut Encoder<W>) -> io::Result<()> {
+        e.u64(self.iteration)?;
+        e.u64(self.next_snapshot)?;
+        e.f64(self.best_log_like)?;
+        e.u64s(&self.best_groups)?;
+        match &self.thinning {
+            Some(thinning) => {
+                e.u64(1)?;
+                thinning.encode(e)?;
+            }
+            None => e.u64(0)?,
+        }
+        match &self.watchdog {
+            Some(watchdog) => {
+                e.u64(1)?;
+                watchdog.encode(e)?;
+            }
+            None => e.u64(0)?,
+        }
+        // reheats end after at least one iteration, 0 stands for none
+        e.u64(self.reheat_until.unwrap_or(0))?;
+        e.f64(self.beta)
+    }
+
+    fn decode<R: io::Read>(d: &mut Decoder<R>) -> io::Result<Self> {
+        Ok(Self {
+            iteration: d.u64()?,
+            next_snapshot: d.u64()?,
+            best_log_like: d.f64()?,
+            best_groups: d.u64s()?,
+            thinning: match d.u64()? {
+                0 => None,
+                _ => Some(AdaptiveThinning::decode(d)?),
+            },
+            watchdog: match d.u64()? {
+                0 => None,
+                _ => Some(Watchdog::decode(d)?),
+            },
+            reheat_until: Some(d.u64()?).filter(|&i| i > 0),
+            beta: d.f64()?,
+        })
+    }
+}
+
+fn checkpoint_path(parameters: &Parameters) -> PathBuf {
+    parameters
+        .save_directory
+        .join(format!("{}_checkpoint.bin", parameters.saved_data_name))
+}
+
+fn write_checkpoint(
+    parameters: &Parameters,
+    progress: &Progress,
+    log: &HcpLog,
+    hcp: &HierarchicalModel,
+) -> io::Result<()> {
+    fs::create_dir_all(&parameters.save_directory)?;
+    // the configuration of the model aside, the checkpoint is about as large as the log
+    output::check_free_space(
+        &parameters.save_directory,
+        8 * (log.num_values() + hcp.model().groups().len()) as u64,
+    )?;
+    // a failed write leaves the previous checkpoint intact
+    output::write_atomic(
+        &checkpoint_path(parameters),
+        OutputCompression::None,
+        false,
+        |w| {
+            let mut e = Encoder::new(w)?;
+            progress.encode(&mut e)?;
+            log.encode(&mut e)?;
+            hcp.encode(&mut e)
+        },
+    )
+}
+
+/// restore `log` and `hcp` from the checkpoint of `parameters`
+fn read_checkpoint(
+    parameters: &Parameters,
+    log: &mut HcpLog,
+    hcp: &mut HierarchicalModel,
+) -> io::Result<Progress> {
+    let mut d = Decoder::new(BufReader::new(File::open(checkpoint_path(parameters))?))?;
+    let progress = Progress::decode(&mut d)?;
+    log.decode(&mut d)?;
+    hcp.decode(&mut d)?;
+    Ok(progress)
+}
+
+fn load_parameters(parameters_file: &Path) -> Result<Parameters, String> {
+    Ok(
+        Parameters::load(File::open(parameters_file).map_err(|e| e.to_string())?)?
+            .resolve_paths(parameters_file.parent().unwrap_or(Path::new("."))),
+    )
+}
+
+/// the suffixes of the files `{name}_{suffix}` a run with `parameters` writes, before
+/// compression
+fn output_suffixes(parameters: &Parameters) -> Vec<String> {
+    let mut suffixes: Vec<String> = parameters
+        .log_fields
+        .names()
+        .into_iter()
+        .chain(["iters"])
+        .chain(parameters.save_timestamps.then_some("times"))
+        .map(|field| format!("{}.txt", field))
+        .collect();
+    suffixes.extend(
+        parameters
+            .custom_stats
+            .0
+            .iter()
+            .map(|(stat, _)| format!("custom_{}.txt", stat)),
+    );
+    for (written, suffix) in [
+        (true, "manifest.txt"),
+        (parameters.checkpoint_interval > 0, "checkpoint.bin"),
+        (parameters.save_moves, "moves.txt"),
+        (parameters.trace_moves, "proposals.bin"),
+        (parameters.state_hash_interval > 0, "state_hash.txt"),
+        (parameters.arrow_output, "trace.arrow"),
+        (parameters.save_best, "best.txt"),
+        (parameters.save_hierarchy, "hierarchy.csv"),
+        (
+            parameters.predict_pairs_path.is_some(),
+            "pair_probabilities.csv",
+        ),
+        (parameters.save_core_periphery, "core_periphery.csv"),
+        (parameters.save_nodes, "nodes.csv"),
+        (parameters.save_labels, "labels.txt"),
+    ] {
+        if written {
+            suffixes.push(suffix.to_string());
         }
+    }
+    suffixes
+}
 
-        macro_rules! d {
-            ($data:expr, $suff:expr) => {{
-                let path = save_dir.join(format!("{}_{}.txt", name, $suff));
-                let mut w = BufWriter::new(File::create(path)?);
-                for x in $data {
-                    writeln!(w, "{}", x)?;
+/// write the seed and every seed derived from it to `{name}_manifest.txt`
+fn write_manifest(parameters: &Parameters, seeds: &SeedStream) -> io::Result<()> {
+    fs::create_dir_all(&parameters.save_directory)?;
+    let path = parameters
+        .save_directory
+        .join(format!("{}_manifest.txt", parameters.saved_data_name));
+    let mut w = BufWriter::new(File::create(path)?);
+    writeln!(w, "seed: {}", seeds.seed())?;
+    for (stream, index, seed) in seeds.derived() {
+        writeln!(w, "{} {} seed: {}", stream, index, seed)?;
+    }
+    if parameters.permute_nodes {
+        // the node order is drawn when the model is built, outside of `seeds`
+        let seed = seeds::sub_seed(seeds.seed(), Stream::Permutation, 0);
+        writeln!(w, "{} 0 seed: {}", Stream::Permutation, seed)?;
+    }
+    w.flush()
+}
+
+/// `hcp-rs replay <proposals.bin> <parameters file>`: re-apply every recorded proposal
+/// with its recorded decision and report where the log-likelihood differences diverge.
+fn replay_proposals(
+    trace: ProposalTraceReader<impl io::Read>,
+    mut args: impl Iterator<Item = String>,
+) -> Result<(), String> {
+    let parameters_file =
+        PathBuf::from(args.next().ok_or(String::from("missing parameters file"))?);
+    let parameters = Parameters {
+        seed: Some(trace.seed),
+        ..load_parameters(&parameters_file)?
+    };
+    let mut hcp: HierarchicalModel = HierarchicalModel::with_parameters(&parameters)?;
+    let mut num_proposals = 0u64;
+    let mut num_diverged = 0u64;
+    let mut replay = |i: u64, batch: &[Proposal]| -> Result<(), String> {
+        let replayed = match batch {
+            [recorded] => hcp
+                .replay_step(recorded.m, recorded.accepted)
+                .map(|p| vec![p]),
+            _ => hcp.replay_batch(batch),
+        }
+        .map_err(|e| format!("cannot replay iteration {}: {}", i, e))?;
+        for (recorded, replayed) in batch.iter().zip(replayed) {
+            num_proposals += 1;
+            let same_move = trace::moved_node(&replayed.m) == trace::moved_node(&recorded.m);
+            if !same_move || replayed.delta_log_like != recorded.delta_log_like {
+                num_diverged += 1;
+                if num_diverged <= 10 {
+                    println!(
+                        "iteration {}: recorded {:?} (Δ {}), replayed {:?} (Δ {})",
+                        i, recorded.m, recorded.delta_log_like, replayed.m, replayed.delta_log_like
+                    );
                 }
-                w.flush()?;
-            }};
+            }
+        }
+        Ok(())
+    };
+    // the proposals of a batch share their iteration
+    let mut batch: Vec<Proposal> = Vec::new();
+    let mut iteration = 0;
+    for record in trace {
+        let (i, recorded) = record?;
+        if i != iteration && !batch.is_empty() {
+            replay(iteration, &batch)?;
+            batch.clear();
         }
+        iteration = i;
+        batch.push(recorded);
+    }
+    if !batch.is_empty() {
+        replay(iteration, &batch)?;
+    }
+    println!(
+        "replayed {} proposals, {} diverged",
+        num_proposals, num_diverged
+    );
+    println!("final energy: {:.4}", hcp.log_like());
+    Ok(())
+}
+
+/// `hcp-rs replay <trace file> [iteration]`: print the configuration at `iteration`
+fn replay(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let trace_file = args.next().ok_or(String::from("missing trace file"))?;
+    let trace_file = Path::new(&trace_file);
+    if let Ok(trace) =
+        ProposalTraceReader::new(output::open(trace_file).map_err(|e| e.to_string())?)
+    {
+        return replay_proposals(trace, args);
+    }
+    let iteration = args.next().map_or(Ok(u64::MAX), |s| {
+        s.parse().or(Err(format!("not an integer: {}", s)))
+    })?;
+    let model = trace::replay(
+        BufReader::new(output::open(trace_file).map_err(|e| e.to_string())?),
+        iteration,
+    )?;
+    let mut out = io::stdout().lock();
+    HcpLog::dump_vec_space_separated(&mut out, model.groups()).map_err(|e| e.to_string())?;
+    writeln!(out).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// `hcp-rs convert --from-cpp <directory> <name> <snapshot file>` or
+/// `hcp-rs convert --to-cpp <snapshot file> <directory> <name>`: convert between the
+/// `{name}_configs.txt` and `{name}_energies.txt` outputs of the C++ implementation in
+/// `directory` and a JSON Lines snapshot stream.
+fn convert(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let direction = args
+        .next()
+        .ok_or(String::from("missing --from-cpp or --to-cpp"))?;
+    let mut next = |what: &str| args.next().ok_or(format!("missing {}", what));
+    let cpp_paths = |dir: &str, name: &str| {
+        let dir = Path::new(dir);
+        (
+            dir.join(format!("{}_configs.txt", name)),
+            dir.join(format!("{}_energies.txt", name)),
+        )
+    };
+    match direction.as_str() {
+        "--from-cpp" => {
+            let (configs, energies) = cpp_paths(&next("directory")?, &next("name")?);
+            let snapshots = convert::read_cpp(
+                BufReader::new(output::open(&configs).map_err(|e| e.to_string())?),
+                BufReader::new(output::open(&energies).map_err(|e| e.to_string())?),
+            )?;
+            let path = PathBuf::from(next("snapshot file")?);
+            let mut w = SnapshotJsonWriter::new(
+                output::create(&path, OutputCompression::None).map_err(|e| e.to_string())?,
+            );
+            for snapshot in &snapshots {
+                w.write(snapshot).map_err(|e| e.to_string())?;
+            }
+            w.into_inner().finish().map_err(|e| e.to_string())
+        }
+        "--to-cpp" => {
+            let path = PathBuf::from(next("snapshot file")?);
+            let snapshots = SnapshotJsonReader::new(BufReader::new(
+                output::open(&path).map_err(|e| e.to_string())?,
+            ))
+            .collect::<Result<Vec<Snapshot>, String>>()?;
+            let dir = next("directory")?;
+            fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+            let (configs, energies) = cpp_paths(&dir, &next("name")?);
+            let mut configs =
+                output::create(&configs, OutputCompression::None).map_err(|e| e.to_string())?;
+            let mut energies =
+                output::create(&energies, OutputCompression::None).map_err(|e| e.to_string())?;
+            convert::write_cpp(&snapshots, &mut configs, &mut energies)
+                .map_err(|e| e.to_string())?;
+            configs.finish().map_err(|e| e.to_string())?;
+            energies.finish().map_err(|e| e.to_string())
+        }
+        _ => Err(format!("unknown conversion: {}", direction)),
+    }
+}
+
+/// `hcp-rs pipe [parameters file] < edges.txt`: read an edge list from stdin and write the
+/// final configuration, the group hierarchy and a JSON summary to stdout.
+/// No files are written; `gml_path` and the output parameters are ignored.
+fn pipe(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let parameters = Parameters {
+        quiet: true,
+        ..match args.next() {
+            Some(parameters_file) => load_parameters(Path::new(&parameters_file))?,
+            None => Parameters::load(io::empty())?,
+        }
+    }
+    .fix_seed();
+    let network = Arc::new(NetworkData::read_edge_list(io::stdin().lock())?);
+    let mut hcp: HierarchicalModel = HierarchicalModel::with_network(&parameters, network)?;
+    for _ in 0..parameters.max_itr {
+        hcp.step();
+    }
+
+    let mut out = io::stdout().lock();
+    HcpLog::dump_vec_space_separated(&mut out, hcp.model().groups()).map_err(|e| e.to_string())?;
+    writeln!(out).map_err(|e| e.to_string())?;
+    write!(out, "{}", hcp.model()).map_err(|e| e.to_string())?;
+    writeln!(out, "{}", hcp.summary().to_json()).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// `hcp-rs hierarchy <parameters file> [--json]`: print the group hierarchy of
+/// `initial_group_config` with the size and density of every group as CSV, or with `--json`
+/// as JSON for `d3.hierarchy`, see `trace::write_hierarchy_json`.
+fn hierarchy(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let parameters_file =
+        PathBuf::from(args.next().ok_or(String::from("missing parameters file"))?);
+    let json = match args.next().as_deref() {
+        None => false,
+        Some("--json") => true,
+        Some(arg) => return Err(format!("unknown argument: {}", arg)),
+    };
+    let parameters = load_parameters(&parameters_file)?;
+    let hcp: HierarchicalModel = HierarchicalModel::with_parameters(&parameters)?;
+    if json {
+        trace::write_hierarchy_json(io::stdout().lock(), &hcp)
+    } else {
+        trace::write_hierarchy_csv(io::stdout().lock(), &hcp)
+    }
+    .map_err(|e| e.to_string())
+}
 
-        dv!(&self.groups, "configs");
-        d!(&self.num_groups, "num_groups");
-        dv!(&self.group_size, "group_size");
-        dv!(&self.hcg_edges, "edges");
-        dv!(&self.hcg_pairs, "pairs");
-        d!(&self.log_like, "ll");
+/// `hcp-rs probe <parameters file> <config file> [--summary]`: write the log-likelihood
+/// difference of every single node move from the configuration as CSV, or with `--summary`
+/// their distribution. The config file is a `{name}_best.txt` or `{name}_configs.txt`, of which
+/// the last configuration is probed.
+fn probe(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let parameters_file =
+        PathBuf::from(args.next().ok_or(String::from("missing parameters file"))?);
+    let config_file = PathBuf::from(args.next().ok_or(String::from("missing config file"))?);
+    let summary = match args.next().as_deref() {
+        None => false,
+        Some("--summary") => true,
+        Some(arg) => return Err(format!("unknown argument: {}", arg)),
+    };
+    let parameters = config_parameters(&parameters_file, &config_file)?;
+    let hcp: HierarchicalModel = HierarchicalModel::with_parameters(&parameters)?;
+    let moves = hcp.probe();
+    if summary {
+        print!("{}", ProbeSummary::new(&moves));
         Ok(())
+    } else {
+        probe::write_csv(BufWriter::new(io::stdout().lock()), &moves).map_err(|e| e.to_string())
     }
 }
 
-fn main() -> Result<(), String> {
-    let parameters_file = PathBuf::from(
-        env::args()
-            .nth(1)
-            .ok_or(String::from("missing parameters file"))?,
-    );
-    let parameters = Parameters::load(File::open(&parameters_file).map_err(|e| e.to_string())?)?
-        .resolve_paths(&parameters_file.parent().unwrap_or(Path::new(".")))
-        .fix_seed();
+/// `hcp-rs image <parameters file> <config file> <output file> [--size <pixels>]`: write the
+/// adjacency matrix in the order of the configuration's hierarchy as a PNG, or a PGM if the
+/// output file ends in `.pgm`, see `block_image`. The config file is read as by `probe`;
+/// images are at most 1024 pixels square by default. The output file is replaced only once
+/// complete, and an empty network is an error for PNG.
+fn image(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let parameters_file =
+        PathBuf::from(args.next().ok_or(String::from("missing parameters file"))?);
+    let config_file = PathBuf::from(args.next().ok_or(String::from("missing config file"))?);
+    let path = PathBuf::from(args.next().ok_or(String::from("missing output file"))?);
+    let mut size = 1024;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--size" => {
+                let value = args.next().ok_or(String::from("missing --size value"))?;
+                size = value
+                    .parse()
+                    .map_err(|_| format!("not an integer: {}", value))?;
+            }
+            _ => return Err(format!("unknown argument: {}", arg)),
+        }
+    }
+    let parameters = config_parameters(&parameters_file, &config_file)?;
+    let hcp: HierarchicalModel = HierarchicalModel::with_parameters(&parameters)?;
+    let image = block_image::render(hcp.network(), hcp.model(), size);
+    let pgm = path.extension().is_some_and(|e| e == "pgm");
+    output::write_atomic(&path, OutputCompression::None, false, |w| {
+        if pgm {
+            image.write_pgm(w)
+        } else {
+            image.write_png(w)
+        }
+    })
+    .map_err(|e| e.to_string())
+}
+
+/// `hcp-rs place <parameters file> <config file> <nodes file>`: place new nodes in the frozen
+/// hierarchy of the configuration, read as by `probe`, one after another without refitting,
+/// see `placement::FrozenHierarchy`. Every line of the nodes file lists the neighbors of one
+/// new node; new node `i` has id `n + i` for `n` fitted nodes. Prints `<node> <groups>
+/// <log-likelihood difference>` for every new node.
+fn place(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let parameters_file =
+        PathBuf::from(args.next().ok_or(String::from("missing parameters file"))?);
+    let config_file = PathBuf::from(args.next().ok_or(String::from("missing config file"))?);
+    let nodes_file = PathBuf::from(args.next().ok_or(String::from("missing nodes file"))?);
+    if let Some(arg) = args.next() {
+        return Err(format!("unknown argument: {}", arg));
+    }
+    let parameters = config_parameters(&parameters_file, &config_file)?;
+    let hcp: HierarchicalModel = HierarchicalModel::with_parameters(&parameters)?;
+    let mut frozen = hcp.freeze_hierarchy();
+    let mut out = BufWriter::new(io::stdout().lock());
+    let src = BufReader::new(File::open(&nodes_file).map_err(|e| e.to_string())?);
+    for line in src.lines() {
+        let line = line.map_err(|e| e.to_string())?;
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let neighbors = line
+            .split_whitespace()
+            .map(|w| w.parse().map_err(|_| format!("not a node id: {}", w)))
+            .collect::<Result<Vec<u32>, String>>()?;
+        let placement = frozen.place(&neighbors)?;
+        writeln!(
+            out,
+            "{} {} {}",
+            frozen.node_count() - 1,
+            placement.groups,
+            parameters.float_format.display(placement.delta_log_like)
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    out.flush().map_err(|e| e.to_string())
+}
+
+/// the parameters with the last configuration of `config_file` as `initial_group_config`
+fn config_parameters(parameters_file: &Path, config_file: &Path) -> Result<Parameters, String> {
+    let src = fs::read_to_string(config_file).map_err(|e| e.to_string())?;
+    let groups = src
+        .lines()
+        .rfind(|l| !l.trim().is_empty())
+        .ok_or(format!("{}: no configuration", config_file.display()))?
+        .split_whitespace()
+        .map(|w| w.parse().map_err(|_| format!("not an integer: {}", w)))
+        .collect::<Result<Vec<u64>, String>>()?;
+    let num_groups = 64 - groups.iter().fold(1, |a, &g| a | g).leading_zeros();
+    Ok(load_parameters(parameters_file)?.with_initial_config(groups, num_groups))
+}
+
+/// `hcp-rs cv <parameters file>`: hide the edges of `holdout_fraction` of the nodes, fit the
+/// rest and report how well the hidden connections are predicted.
+fn cv(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let parameters_file =
+        PathBuf::from(args.next().ok_or(String::from("missing parameters file"))?);
+    let parameters = load_parameters(&parameters_file)?.fix_seed();
+    let mut seeds = SeedStream::new(parameters.seed.unwrap_or(0));
+    let network = NetworkData::load(&parameters)?;
+    let score = cross_validation::cross_validate(&parameters, &network, &mut seeds)?;
+    write_manifest(&parameters, &seeds).map_err(|e| e.to_string())?;
+    print!("{}", score);
+    Ok(())
+}
+
+/// `hcp-rs model_select <parameters file>`: cross-validate each of `model_select_groups` as
+/// `max_num_groups` and print them ranked by predictive log-likelihood per held-out pair.
+fn model_select(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let parameters_file =
+        PathBuf::from(args.next().ok_or(String::from("missing parameters file"))?);
+    let parameters = Parameters {
+        quiet: true,
+        ..load_parameters(&parameters_file)?.fix_seed()
+    };
+    let budgets = parameters
+        .model_select_groups
+        .clone()
+        .ok_or(String::from("missing model_select_groups"))?;
+    let mut seeds = SeedStream::new(parameters.seed.unwrap_or(0));
+    let network = NetworkData::load(&parameters)?;
+    let ranked = cross_validation::model_select(&parameters, &network, &budgets, &mut seeds)?;
+    write_manifest(&parameters, &seeds).map_err(|e| e.to_string())?;
+    println!("rank max_num_groups log_like_per_pair auc");
+    for (rank, (k, score)) in ranked.iter().enumerate() {
+        println!(
+            "{:4} {:14} {:17.4} {:.4}",
+            rank + 1,
+            k,
+            score.log_like_per_pair,
+            score.auc
+        );
+    }
+    Ok(())
+}
+
+/// `hcp-rs ensemble [--npz <file>] [--arrow <file>] <configs file>...`: align the last
+/// configuration of each `_configs.txt` file and print the consensus configuration, its
+/// hierarchy, and for every node the fraction of runs in which it is in each consensus group.
+/// With `--npz` the consensus configuration and the membership matrix are also saved as the
+/// arrays `consensus` and `membership` of a NumPy archive, with `--arrow` the membership
+/// matrix is saved as an Arrow table with a column per consensus group.
+fn ensemble(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut args = args.peekable();
+    let mut npz_path = None;
+    let mut arrow_path = None;
+    while let Some(option) = args.next_if(|a| a == "--npz" || a == "--arrow") {
+        let path = PathBuf::from(args.next().ok_or(format!("missing {} file", option))?);
+        if option == "--npz" {
+            npz_path = Some(path);
+        } else {
+            arrow_path = Some(path);
+        }
+    }
+    #[cfg(not(feature = "arrow"))]
+    if arrow_path.is_some() {
+        return Err(String::from("--arrow requires the `arrow` feature"));
+    }
+    let configs = args
+        .map(|path| {
+            let text = fs::read_to_string(&path).map_err(|e| format!("{}: {}", path, e))?;
+            text.lines()
+                .last()
+                .ok_or(format!("{}: no configurations", path))?
+                .split_whitespace()
+                .map(|w| w.parse().or(Err(format!("not an integer: {}", w))))
+                .collect::<Result<Vec<u64>, String>>()
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let ensemble = Ensemble::align(&configs)?;
+    let consensus = ensemble.consensus();
+    let labels = ensemble.consensus_labels();
+
+    let mut out = io::stdout().lock();
+    HcpLog::dump_vec_space_separated(&mut out, consensus.groups()).map_err(|e| e.to_string())?;
+    writeln!(out).map_err(|e| e.to_string())?;
+    write!(out, "{}", consensus).map_err(|e| e.to_string())?;
+    for u in 0..consensus.num_nodes() {
+        let membership: Vec<String> = labels
+            .iter()
+            .map(|&l| format!("{:.3}", ensemble.membership(l, u)))
+            .collect();
+        HcpLog::dump_vec_space_separated(&mut out, &membership).map_err(|e| e.to_string())?;
+        writeln!(out).map_err(|e| e.to_string())?;
+    }
+    let num_nodes = consensus.num_nodes();
+    let membership: Vec<f64> = (0..num_nodes)
+        .flat_map(|u| labels.iter().map(move |&l| (l, u)))
+        .map(|(l, u)| ensemble.membership(l, u))
+        .collect();
+    #[cfg(feature = "arrow")]
+    if let Some(path) = arrow_path {
+        let names: Vec<String> = (0..labels.len()).map(|g| format!("group_{}", g)).collect();
+        arrow_ipc::Table::from_matrix(&names, &membership).write(BufWriter::new(
+            File::create(path).map_err(|e| e.to_string())?,
+        ))?;
+    }
+    if let Some(path) = npz_path {
+        let mut npz = NpzWriter::new(BufWriter::new(
+            File::create(path).map_err(|e| e.to_string())?,
+        ));
+        npz.add("consensus", &[num_nodes], consensus.groups())
+            .and_then(|_| npz.add("membership", &[num_nodes, labels.len()], &membership))
+            .and_then(|_| npz.finish())
+            .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// `hcp-rs persistence [--min-overlap <x>] <configs file>`: follow the groups of a run's
+/// snapshots and print the lifetime of each, longest first
+fn persistence(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut args = args.peekable();
+    let mut min_overlap = 0.5;
+    if args.next_if(|a| a == "--min-overlap").is_some() {
+        let x = args
+            .next()
+            .ok_or(String::from("missing --min-overlap value"))?;
+        min_overlap = x.parse().or(Err(format!("not a number: {}", x)))?;
+    }
+    let path = PathBuf::from(args.next().ok_or(String::from("missing configs file"))?);
+    let configs = BufReader::new(output::open(&path).map_err(|e| e.to_string())?)
+        .lines()
+        .map(|line| {
+            line.map_err(|e| e.to_string())?
+                .split_whitespace()
+                .map(|w| w.parse().or(Err(format!("not an integer: {}", w))))
+                .collect::<Result<Vec<u64>, String>>()
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    let mut lineages = persistence::track(&configs, min_overlap)?;
+    lineages.sort_by_key(|l| std::cmp::Reverse(l.lifetime()));
+
+    let mut out = io::stdout().lock();
+    writeln!(out, "birth death lifetime group mean_size mean_overlap")
+        .map_err(|e| e.to_string())?;
+    for l in &lineages {
+        writeln!(
+            out,
+            "{} {} {} {} {:.2} {:.3}",
+            l.birth,
+            l.death,
+            l.lifetime(),
+            l.group,
+            l.mean_size,
+            l.mean_overlap
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+/// `hcp-rs <parameters file> [--print-final] [--resume] [--parallel]`
+///
+/// With `--resume` the run continues from the last checkpoint and writes the same outputs an
+/// uninterrupted run would, except for wall-clock times. Streaming outputs cannot be resumed.
+/// With a list of `seeds` there is one run per seed, see `Parameters::per_seed`, one after the
+/// other or with `--parallel` all at once.
+fn run(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let parameters_file =
+        PathBuf::from(args.next().ok_or(String::from("missing parameters file"))?);
+    let mut print_final = false;
+    let mut resume = false;
+    let mut parallel = false;
+    for arg in args {
+        match arg.as_str() {
+            "--print-final" => print_final = true,
+            "--resume" => resume = true,
+            "--parallel" => parallel = true,
+            _ => return Err(format!("unknown argument: {}", arg)),
+        }
+    }
+    let runs = load_parameters(&parameters_file)?.fix_seed().per_seed();
+    if !parallel {
+        return runs
+            .into_iter()
+            .try_for_each(|parameters| run_seed(parameters, print_final, resume));
+    }
+    thread::scope(|s| {
+        let handles: Vec<_> = runs
+            .into_iter()
+            .map(|parameters| {
+                let seed = parameters.seed.unwrap_or(0);
+                (
+                    seed,
+                    s.spawn(move || run_seed(parameters, print_final, resume)),
+                )
+            })
+            .collect();
+        // join all runs before reporting the first failure
+        let results: Vec<_> = handles
+            .into_iter()
+            .map(|(seed, h)| {
+                h.join()
+                    .unwrap()
+                    .map_err(|e| format!("seed {}: {}", seed, e))
+            })
+            .collect();
+        results.into_iter().collect()
+    })
+}
+
+/// one run of `run` with the seed of `parameters`
+fn run_seed(parameters: Parameters, print_final: bool, resume: bool) -> Result<(), String> {
+    if resume
+        && (parameters.save_moves
+            || parameters.trace_moves
+            || parameters.state_hash_interval > 0
+            || parameters.snapshot_json_path.is_some()
+            || parameters.predict_pairs_path.is_some()
+            || parameters.results_db
+            || parameters.on_existing_output == OnExistingOutput::Append)
+    {
+        return Err(String::from(
+            "--resume does not support save_moves, trace_moves, state_hash_interval, \
+             snapshot_json_path, predict_pairs_path, results_db or on_existing_output: append",
+        ));
+    }
     println!("{:?}", parameters);
-    let mut hcp = HierarchicalModel::with_parameters(&parameters).map_err(|e| e.to_string())?;
-    let mut log = HcpLog::new();
+    let mut seeds = SeedStream::new(parameters.seed.unwrap_or(0));
+    let mut log = HcpLog::new(
+        parameters.log_fields,
+        parameters.save_timestamps,
+        &parameters.custom_stats,
+    );
+    let mut hcp = if resume {
+        HierarchicalModel::with_parameters(&parameters)?
+    } else if parameters.coarse_num_groups > 0 {
+        let network = Arc::new(NetworkData::load(&parameters)?);
+        pipeline::coarse_to_fine(&parameters, network, &mut seeds)?
+    } else if parameters.coarsen_overlap > 0.0 {
+        let network = Arc::new(NetworkData::load(&parameters)?);
+        coarsening::coarsened_fit(&parameters, network, &mut seeds)?
+    } else if parameters.num_chains > 1 {
+        #[cfg(feature = "parallel")]
+        {
+            let network = Arc::new(NetworkData::load(&parameters)?);
+            hcp_rs::parallel::run_chains(&parameters, network, &mut seeds)?
+        }
+        #[cfg(not(feature = "parallel"))]
+        return Err(String::from(
+            "num_chains > 1 requires the `parallel` feature",
+        ));
+    } else {
+        HierarchicalModel::with_parameters(&parameters)?
+    };
+    let mut progress = if resume {
+        let progress = read_checkpoint(&parameters, &mut log, &mut hcp)
+            .map_err(|e| format!("cannot resume: {}", e))?;
+        println!("resuming at iteration {}", progress.iteration);
+        progress
+    } else {
+        output::prepare_outputs(
+            &parameters.save_directory,
+            &parameters.saved_data_name,
+            &output_suffixes(&parameters),
+            parameters.on_existing_output,
+        )
+        .map_err(|e| e.to_string())?;
+        // the manifest of the interrupted run stays valid
+        write_manifest(&parameters, &seeds).map_err(|e| e.to_string())?;
+        Progress::new(&parameters, &hcp)
+    };
+    print!("{}", hcp.network().stats());
+    print!("{}", hcp.init_report());
+    let mut moves = if parameters.save_moves {
+        fs::create_dir_all(&parameters.save_directory).map_err(|e| e.to_string())?;
+        let path = parameters
+            .save_directory
+            .join(format!("{}_moves.txt", parameters.saved_data_name));
+        let w = output::create(&path, parameters.output_compression).map_err(|e| e.to_string())?;
+        Some(MoveTraceWriter::new(w, hcp.model()).map_err(|e| e.to_string())?)
+    } else {
+        None
+    };
+    let mut proposals = if parameters.trace_moves {
+        fs::create_dir_all(&parameters.save_directory).map_err(|e| e.to_string())?;
+        let path = parameters
+            .save_directory
+            .join(format!("{}_proposals.bin", parameters.saved_data_name));
+        let w = output::create(&path, parameters.output_compression).map_err(|e| e.to_string())?;
+        Some(ProposalTraceWriter::new(w, parameters.seed.unwrap_or(0)).map_err(|e| e.to_string())?)
+    } else {
+        None
+    };
+    // `<iteration> <HierarchicalModel::state_hash in hex>`, written as the run goes
+    let mut state_hashes = if parameters.state_hash_interval > 0 {
+        fs::create_dir_all(&parameters.save_directory).map_err(|e| e.to_string())?;
+        let path = parameters
+            .save_directory
+            .join(format!("{}_state_hash.txt", parameters.saved_data_name));
+        Some(output::create(&path, parameters.output_compression).map_err(|e| e.to_string())?)
+    } else {
+        None
+    };
+    let mut snapshots = match &parameters.snapshot_json_path {
+        Some(path) if path == Path::new("-") => {
+            Some(SnapshotJsonWriter::new(output::OutputWriter::stdout()))
+        }
+        Some(path) => Some(SnapshotJsonWriter::new(
+            output::create(path, parameters.output_compression).map_err(|e| e.to_string())?,
+        )),
+        None => None,
+    };
+    let mut pair_posterior = match &parameters.predict_pairs_path {
+        Some(path) => {
+            let src = BufReader::new(File::open(path).map_err(|e| e.to_string())?);
+            let pairs = link_prediction::read_pairs(src)?;
+            Some(PairPosterior::new(pairs, hcp.network().node_count())?)
+        }
+        None => None,
+    };
+    #[cfg(feature = "sqlite")]
+    let mut results_db = if parameters.results_db {
+        fs::create_dir_all(&parameters.save_directory).map_err(|e| e.to_string())?;
+        Some(ResultsDb::open(
+            &parameters.save_directory.join("results.db"),
+            &parameters,
+        )?)
+    } else {
+        None
+    };
+    #[cfg(not(feature = "sqlite"))]
+    if parameters.results_db {
+        return Err(String::from("results_db requires the `sqlite` feature"));
+    }
+    #[cfg(not(feature = "arrow"))]
+    if parameters.arrow_output {
+        return Err(String::from("arrow_output requires the `arrow` feature"));
+    }
 
     println!("seed: {}", parameters.seed.unwrap_or(0));
-    println!("number of pairs: {:?}", hcp.hcg_pairs);
-    println!("number of edges: {:?}", hcp.hcg_edges);
-    for i in 0..parameters.max_itr {
-        hcp.get_groups();
+    println!("number of pairs: {:?}", hcp.hcg_pairs());
+    println!("number of edges: {:?}", hcp.hcg_edges());
+    let start = progress.iteration;
+    // since the last status report
+    let mut move_counts = MoveCounts::default();
+    for i in start..parameters.max_itr {
+        if parameters.checkpoint_interval > 0 && i > 0 && i % parameters.checkpoint_interval == 0 {
+            if i > start {
+                progress.iteration = i;
+                write_checkpoint(&parameters, &progress, &log, &hcp).map_err(|e| e.to_string())?;
+            }
+            hcp.reseed(seeds::sub_seed(
+                parameters.seed.unwrap_or(0),
+                Stream::Checkpoint,
+                i,
+            ));
+        }
+        hcp.set_group_move_weight(parameters.move_schedule.weight(i));
+        let batch: Vec<Proposal> = if parameters.batch_moves > 1 {
+            hcp.batch_step(parameters.batch_moves, parameters.batch_threads)
+        } else {
+            hcp.step().into_iter().collect()
+        };
+        if let Some(watchdog) = &mut progress.watchdog {
+            if progress.reheat_until == Some(i) {
+                hcp.set_beta(progress.beta);
+                progress.reheat_until = None;
+            }
+            if watchdog.observe(i, batch.iter().any(|p| p.accepted)) {
+                println!(
+                    "warning: no move accepted in {} iterations up to iteration {}",
+                    watchdog.window(),
+                    i
+                );
+                match parameters.stall_action {
+                    StallAction::Warn => {}
+                    StallAction::Stop => {
+                        println!("stopping the stalled chain");
+                        break;
+                    }
+                    StallAction::Reheat(reheat_beta) => {
+                        if progress.reheat_until.is_none() {
+                            hcp.set_beta(reheat_beta);
+                        }
+                        progress.reheat_until = Some(i + watchdog.window());
+                    }
+                }
+            }
+        }
+        if hcp.log_like() > progress.best_log_like {
+            progress.best_log_like = hcp.log_like();
+            progress.best_groups.copy_from_slice(hcp.model().groups());
+        }
+        #[cfg(feature = "profiling")]
+        let logging_start = time::Instant::now();
+        for p in &batch {
+            move_counts.record(p);
+            log.record(p);
+            if let Some(trace) = &mut proposals {
+                trace.record(i, p).map_err(|e| e.to_string())?;
+            }
+            if let (Some(trace), true) = (&mut moves, p.accepted) {
+                trace.record(i, &p.m).map_err(|e| e.to_string())?;
+            }
+        }
+        log.record_pruned(hcp.pruned_groups());
         if i % 10000000 == 0 {
             println!("-----------------------------------------------------");
             println!(
@@ -107,18 +1416,470 @@ fn main() -> Result<(), String> {
                     .duration_since(time::UNIX_EPOCH)
                     .map_or("???".to_string(), |d| d.as_secs().to_string())
             );
-            println!("iteration: {} energy: {:.4}", i, hcp.log_like);
-            println!("number of pairs: {:?}", hcp.hcg_pairs);
-            println!("number of edges: {:?}", hcp.hcg_edges);
-            println!("group sizes: {:?}", hcp.model.group_size);
+            println!("iteration: {} energy: {:.4}", i, hcp.log_like());
+            println!("number of pairs: {:?}", hcp.hcg_pairs());
+            println!("number of edges: {:?}", hcp.hcg_edges());
+            println!("group sizes: {:?}", hcp.model().group_sizes());
+            println!("accepted/proposed moves: {}", move_counts);
+            move_counts = MoveCounts::default();
+            if parameters.check_drift {
+                println!("log-likelihood drift: {:e}", hcp.log_like_drift());
+            }
         }
 
-        if (i > 10000000) && (i % 1500 == 0) {
-            log.shapshot(&hcp);
+        log.observe(i, &hcp);
+
+        if let Some(w) = &mut state_hashes {
+            if i % parameters.state_hash_interval == 0 {
+                writeln!(w, "{} {:016x}", i, hcp.state_hash()).map_err(|e| e.to_string())?;
+            }
         }
+
+        if parameters.recompute_interval > 0 && i % parameters.recompute_interval == 0 {
+            let recomputed = hcp.recompute();
+            if !recomputed.is_consistent() {
+                println!("iteration {}: corrected cached values: {:?}", i, recomputed);
+            }
+        }
+
+        let take_snapshot = match &mut progress.thinning {
+            Some(thinning) => {
+                thinning.observe(i, hcp.log_like());
+                let due = i > 10000000 && i >= progress.next_snapshot;
+                if due {
+                    progress.next_snapshot = i + thinning.interval().unwrap_or(1500);
+                }
+                due
+            }
+            None => (i > 10000000) && (i % 1500 == 0),
+        };
+        if take_snapshot {
+            log.shapshot(i, &hcp);
+            if let Some(w) = &mut snapshots {
+                w.record(i, &hcp).map_err(|e| e.to_string())?;
+            }
+            if let Some(posterior) = &mut pair_posterior {
+                posterior.observe(&hcp);
+            }
+            #[cfg(feature = "sqlite")]
+            if let Some(db) = &mut results_db {
+                db.record(i, &hcp)?;
+            }
+        }
+        #[cfg(feature = "profiling")]
+        profiling::record(Phase::Logging, logging_start);
+    }
+    println!("-----------------------------------------------------");
+    print!("{}", hcp.summary());
+    println!("best energy: {:.4}", progress.best_log_like);
+    if print_final {
+        println!("-----------------------------------------------------");
+        print!("{:#}", hcp.model());
     }
     println!("Writing data to file.");
-    log.dump(&parameters.save_directory, &parameters.saved_data_name)
-        .map_err(|e| e.to_string())?;
+    #[cfg(feature = "sqlite")]
+    if let Some(db) = &mut results_db {
+        db.finish(&hcp)?;
+    }
+    log.dump(
+        &parameters.save_directory,
+        &parameters.saved_data_name,
+        parameters.float_format,
+        parameters.output_compression,
+        parameters.on_existing_output == OnExistingOutput::Append,
+    )
+    .map_err(|e| e.to_string())?;
+    #[cfg(feature = "arrow")]
+    if parameters.arrow_output {
+        log.dump_arrow(
+            &parameters
+                .save_directory
+                .join(format!("{}_trace.arrow", parameters.saved_data_name)),
+        )?;
+    }
+    if parameters.save_best {
+        let path = parameters
+            .save_directory
+            .join(format!("{}_best.txt", parameters.saved_data_name));
+        let mut w =
+            output::create(&path, parameters.output_compression).map_err(|e| e.to_string())?;
+        HcpLog::dump_vec_space_separated(&mut w, &progress.best_groups)
+            .map_err(|e| e.to_string())?;
+        writeln!(w).map_err(|e| e.to_string())?;
+        w.finish().map_err(|e| e.to_string())?;
+    }
+    if parameters.save_hierarchy {
+        let path = parameters
+            .save_directory
+            .join(format!("{}_hierarchy.csv", parameters.saved_data_name));
+        let mut w =
+            output::create(&path, parameters.output_compression).map_err(|e| e.to_string())?;
+        trace::write_hierarchy_csv(&mut w, &hcp).map_err(|e| e.to_string())?;
+        w.finish().map_err(|e| e.to_string())?;
+    }
+    if let Some(posterior) = &pair_posterior {
+        let path = parameters.save_directory.join(format!(
+            "{}_pair_probabilities.csv",
+            parameters.saved_data_name
+        ));
+        let mut w =
+            output::create(&path, parameters.output_compression).map_err(|e| e.to_string())?;
+        posterior
+            .write_csv(&mut w, parameters.credible_level)
+            .map_err(|e| e.to_string())?;
+        w.finish().map_err(|e| e.to_string())?;
+    }
+    if parameters.save_core_periphery || parameters.save_nodes {
+        let mut best = hcp.clone();
+        let num_groups = 64
+            - progress
+                .best_groups
+                .iter()
+                .fold(1, |a, &g| a | g)
+                .leading_zeros();
+        best.set_configuration(progress.best_groups.clone(), num_groups as usize);
+        if parameters.save_core_periphery {
+            let path = parameters
+                .save_directory
+                .join(format!("{}_core_periphery.csv", parameters.saved_data_name));
+            let mut w =
+                output::create(&path, parameters.output_compression).map_err(|e| e.to_string())?;
+            trace::write_core_periphery_csv(&mut w, &best).map_err(|e| e.to_string())?;
+            w.finish().map_err(|e| e.to_string())?;
+        }
+        if parameters.save_nodes {
+            let path = parameters
+                .save_directory
+                .join(format!("{}_nodes.csv", parameters.saved_data_name));
+            let mut w =
+                output::create(&path, parameters.output_compression).map_err(|e| e.to_string())?;
+            trace::write_node_attributes_csv(&mut w, &best).map_err(|e| e.to_string())?;
+            w.finish().map_err(|e| e.to_string())?;
+        }
+    }
+    let network = hcp.network();
+    if parameters.save_labels && network.label(0).is_some() {
+        let path = parameters
+            .save_directory
+            .join(format!("{}_labels.txt", parameters.saved_data_name));
+        let mut w =
+            output::create(&path, parameters.output_compression).map_err(|e| e.to_string())?;
+        for u in 0..network.node_count() as u32 {
+            writeln!(w, "{}", network.label(u).unwrap_or_default()).map_err(|e| e.to_string())?;
+        }
+        w.finish().map_err(|e| e.to_string())?;
+    }
+    if let Some(trace) = moves {
+        trace.into_inner().finish().map_err(|e| e.to_string())?;
+    }
+    if let Some(trace) = proposals {
+        trace.into_inner().finish().map_err(|e| e.to_string())?;
+    }
+    if let Some(w) = state_hashes {
+        w.finish().map_err(|e| e.to_string())?;
+    }
+    if let Some(w) = snapshots {
+        w.into_inner().finish().map_err(|e| e.to_string())?;
+    }
+    #[cfg(feature = "profiling")]
+    profiling::print_report();
     Ok(())
 }
+
+/// `hcp-rs mpi <parameters file>`: one chain per MPI rank, results written by rank 0
+#[cfg(feature = "mpi")]
+fn run_mpi(mut args: impl Iterator<Item = String>) -> Result<(), String> {
+    let parameters_file =
+        PathBuf::from(args.next().ok_or(String::from("missing parameters file"))?);
+    let parameters = load_parameters(&parameters_file)?.fix_seed();
+    let mut seeds = SeedStream::new(parameters.seed.unwrap_or(0));
+    let Some(result) = hcp_rs::mpi_backend::run_distributed(&parameters, 1500, &mut seeds)? else {
+        return Ok(());
+    };
+    write_manifest(&parameters, &seeds).map_err(|e| e.to_string())?;
+    print!("{}", result.best.summary());
+    fs::create_dir_all(&parameters.save_directory).map_err(|e| e.to_string())?;
+    let path = parameters
+        .save_directory
+        .join(format!("{}_rank_ll.txt", parameters.saved_data_name));
+    let mut w = output::create(&path, parameters.output_compression).map_err(|e| e.to_string())?;
+    for trace in &result.log_like_traces {
+        HcpLog::dump_vec_space_separated(&mut w, trace).map_err(|e| e.to_string())?;
+        writeln!(w).map_err(|e| e.to_string())?;
+    }
+    w.finish().map_err(|e| e.to_string())
+}
+
+/// `rng-compare [--seed <seed>] [--draws <n>]`: where the random numbers of the default and
+/// the `gsl_compat` backends diverge, see `hcp_rs::rng_compare`
+fn rng_compare(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let (mut seed, mut draws) = (0, 10000);
+    let mut args = args.peekable();
+    while let Some(flag) = args.next() {
+        let value = args.next().ok_or(format!("missing {} value", flag))?;
+        match flag.as_str() {
+            "--seed" => {
+                seed = value
+                    .parse()
+                    .or(Err(format!("not an integer: {}", value)))?
+            }
+            "--draws" => {
+                draws = value
+                    .parse()
+                    .or(Err(format!("not an integer: {}", value)))?
+            }
+            _ => return Err(format!("unknown option: {}", flag)),
+        }
+    }
+    rng_compare::report(io::stdout().lock(), seed, draws).map_err(|e| e.to_string())
+}
+
+/// `serve [--addr <host:port>]`: answer HTTP requests until killed, see `hcp_rs::service`
+#[cfg(feature = "serve")]
+fn serve(args: impl Iterator<Item = String>) -> Result<(), String> {
+    let mut args = args.peekable();
+    let mut addr = String::from("127.0.0.1:8080");
+    if args.next_if(|a| a == "--addr").is_some() {
+        addr = args.next().ok_or(String::from("missing --addr value"))?;
+    }
+    hcp_rs::service::serve(addr.as_str(), Default::default()).map_err(|e| e.to_string())
+}
+
+fn main() -> Result<(), String> {
+    let mut args = env::args().skip(1).peekable();
+    match args.peek().map(String::as_str) {
+        Some("replay") => replay(args.skip(1)),
+        Some("pipe") => pipe(args.skip(1)),
+        Some("convert") => convert(args.skip(1)),
+        Some("cv") => cv(args.skip(1)),
+        Some("hierarchy") => hierarchy(args.skip(1)),
+        Some("probe") => probe(args.skip(1)),
+        Some("image") => image(args.skip(1)),
+        Some("place") => place(args.skip(1)),
+        Some("ensemble") => ensemble(args.skip(1)),
+        Some("model_select") => model_select(args.skip(1)),
+        Some("persistence") => persistence(args.skip(1)),
+        Some("rng-compare") => rng_compare(args.skip(1)),
+        #[cfg(feature = "mpi")]
+        Some("mpi") => run_mpi(args.skip(1)),
+        #[cfg(feature = "serve")]
+        Some("serve") => serve(args.skip(1)),
+        _ => run(args),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    fn example_model() -> HierarchicalModel {
+        example_model_with(b"")
+    }
+
+    /// the example model with the `extra` parameters
+    fn example_model_with(extra: &[u8]) -> HierarchicalModel {
+        let parameters =
+            Parameters::load(File::open("examples/parameters.txt").unwrap().chain(extra))
+                .unwrap()
+                .resolve_paths(Path::new("examples/"));
+        HierarchicalModel::with_parameters(&parameters).unwrap()
+    }
+
+    /// `log` after encoding and decoding it into a new log of the same fields
+    fn round_trip(log: &HcpLog) -> HcpLog {
+        let mut e = Encoder::new(Vec::new()).unwrap();
+        log.encode(&mut e).unwrap();
+        let buf = e.into_inner();
+        let mut decoded = HcpLog::new(log.fields, log.timestamps, &CustomStats::default());
+        decoded
+            .decode(&mut Decoder::new(&buf[..]).unwrap())
+            .unwrap();
+        decoded
+    }
+
+    #[test]
+    fn move_counts() {
+        let mut hcp = example_model();
+        let mut log = HcpLog::new(
+            "move_counts".parse().unwrap(),
+            false,
+            &CustomStats::default(),
+        );
+        let mut counts = MoveCounts::default();
+        for i in 0..3500 {
+            if let Some(p) = hcp.step() {
+                counts.record(&p);
+                log.record(&p);
+            }
+            if i % 1000 == 999 {
+                log.shapshot(i, &hcp);
+            }
+        }
+        let rows: Vec<&[u64]> = log.move_counts.rows().collect();
+        assert_eq!(rows.len(), 3);
+        for k in 0..4 {
+            let logged: u64 = rows.iter().map(|row| row[2 * k + 1]).sum();
+            assert_eq!(logged + log.pending.proposed[k], counts.proposed[k]);
+        }
+
+        let decoded = round_trip(&log);
+        assert_eq!(decoded.move_counts.data, log.move_counts.data);
+        assert_eq!(decoded.move_counts.ends, log.move_counts.ends);
+        assert_eq!(decoded.pending, log.pending);
+
+        let dir = std::env::temp_dir().join(format!("hcp_rs_move_counts_{}", std::process::id()));
+        log.dump(
+            &dir,
+            "run",
+            FloatFormat::default(),
+            OutputCompression::None,
+            false,
+        )
+        .unwrap();
+        let text = fs::read_to_string(dir.join("run_move_counts.txt")).unwrap();
+        let first: Vec<String> = rows[0].iter().map(u64::to_string).collect();
+        assert_eq!(text.lines().count(), 3);
+        assert_eq!(text.lines().next().unwrap(), first.join(" "));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// `hcp.replay_step(m, true)`, recorded in `log` as in `run`
+    fn accept(hcp: &mut HierarchicalModel, log: &mut HcpLog, m: Move) {
+        let p = hcp.replay_step(m, true).unwrap();
+        log.record(&p);
+        log.record_pruned(hcp.pruned_groups());
+    }
+
+    /// move of `node` into or out of `group`
+    fn node_move(hcp: &HierarchicalModel, node: usize, group: usize, add: bool) -> Move {
+        let mut model = hcp.model().clone();
+        let nodes = if add {
+            model.non_members(group)
+        } else {
+            model.members(group)
+        };
+        let idx = nodes.iter().position(|&u| u as usize == node).unwrap();
+        if add {
+            model.add_node_to_group_by_idx(group, idx)
+        } else {
+            model.remove_node_from_group_by_idx(group, idx)
+        }
+    }
+
+    #[test]
+    fn changes() {
+        // group 1 = {1, ..., 5} with child group 2 = {4, 5}, group 3 = {7, 8}
+        let config = b"initial_group_config: 1 3 3 3 7 7 1 9 9 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1\n\
+                       initial_num_groups: 4\n";
+        let mut hcp = example_model_with(&[&config[..], b"empty_group_policy: keep\n"].concat());
+        let mut log = HcpLog::new("changes".parse().unwrap(), false, &CustomStats::default());
+        log.shapshot(0, &hcp);
+        // adding a group renumbers the groups after it, but moves no node
+        let m = hcp.model().clone().add_group(1);
+        accept(&mut hcp, &mut log, m);
+        log.shapshot(1, &hcp);
+        // node 6 joins group 2, formerly 1, then removing the empty group 1 renumbers it back
+        let m = node_move(&hcp, 6, 2, true);
+        accept(&mut hcp, &mut log, m);
+        let m = hcp.model().clone().remove_group(1);
+        accept(&mut hcp, &mut log, m);
+        log.shapshot(2, &hcp);
+
+        let rows: Vec<&[usize]> = log.changes.rows().collect();
+        assert_eq!(rows, [&[][..], &[6, 0, 1][..]]);
+
+        let decoded = round_trip(&log);
+        assert_eq!(decoded.changes.data, log.changes.data);
+        assert_eq!(decoded.deepest, log.deepest);
+        assert_eq!(decoded.renumbered, log.renumbered);
+
+        let dir = std::env::temp_dir().join(format!("hcp_rs_changes_{}", std::process::id()));
+        log.dump(
+            &dir,
+            "run",
+            FloatFormat::default(),
+            OutputCompression::None,
+            false,
+        )
+        .unwrap();
+        let text = fs::read_to_string(dir.join("run_changes.txt")).unwrap();
+        assert_eq!(text.lines().collect::<Vec<_>>(), ["", "6:0:1"]);
+        fs::remove_dir_all(dir).unwrap();
+
+        // pruning the emptied group 2 renumbers group 3, whose members stay
+        let mut hcp = example_model_with(&[&config[..], b"empty_group_policy: prune\n"].concat());
+        let mut log = HcpLog::new("changes".parse().unwrap(), false, &CustomStats::default());
+        log.shapshot(0, &hcp);
+        for node in [4, 5] {
+            let m = node_move(&hcp, node, 2, false);
+            accept(&mut hcp, &mut log, m);
+        }
+        assert_eq!(hcp.model().num_groups(), 3);
+        log.shapshot(1, &hcp);
+        assert_eq!(log.changes.data, [4, 2, 1, 5, 2, 1]);
+    }
+
+    #[test]
+    fn density() {
+        let mut hcp = example_model();
+        let mut log = HcpLog::new("density".parse().unwrap(), false, &CustomStats::default());
+        let mut expected = Vec::new();
+        for i in 0..2000 {
+            hcp.step();
+            if i % 1000 == 999 {
+                log.shapshot(i, &hcp);
+                expected.push(
+                    (0..hcp.model().num_groups())
+                        .map(|g| hcp.group_density(g) as f32)
+                        .collect::<Vec<_>>(),
+                );
+            }
+        }
+        let rows: Vec<&[f32]> = log.density.rows().collect();
+        assert_eq!(rows, expected.iter().map(Vec::as_slice).collect::<Vec<_>>());
+
+        let decoded = round_trip(&log);
+        assert_eq!(decoded.density.data, log.density.data);
+        assert_eq!(decoded.density.ends, log.density.ends);
+
+        let dir = std::env::temp_dir().join(format!("hcp_rs_density_{}", std::process::id()));
+        log.dump(
+            &dir,
+            "run",
+            FloatFormat::default(),
+            OutputCompression::None,
+            false,
+        )
+        .unwrap();
+        let text = fs::read_to_string(dir.join("run_density.txt")).unwrap();
+        let lines: Vec<Vec<f32>> = text
+            .lines()
+            .map(|line| line.split(' ').map(|x| x.parse().unwrap()).collect())
+            .collect();
+        assert_eq!(lines, expected);
+
+        #[cfg(feature = "arrow")]
+        {
+            use arrow_array::cast::AsArray;
+            use arrow_array::types::Float64Type;
+            use arrow_ipc::reader::FileReader;
+
+            let path = dir.join("run_trace.arrow");
+            log.dump_arrow(&path).unwrap();
+            let batch = FileReader::try_new(File::open(&path).unwrap(), None)
+                .unwrap()
+                .next()
+                .unwrap()
+                .unwrap();
+            let column = batch.column_by_name("density").unwrap().as_list::<i32>();
+            for (k, row) in expected.iter().enumerate() {
+                let values = column.value(k);
+                let values: Vec<f64> = values.as_primitive::<Float64Type>().values().to_vec();
+                let row: Vec<f64> = row.iter().map(|&x| x as f64).collect();
+                assert_eq!(values, row);
+            }
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
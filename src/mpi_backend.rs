@@ -0,0 +1,94 @@
+//! distributed runs: every MPI rank runs an independent chain, and the best configurations and
+//! log-likelihood traces are gathered on rank 0. Launch with e.g.
+//! `mpirun -n 16 hcp-rs mpi parameters.txt`.
+use crate::multi_group_model::Groups;
+use crate::network::NetworkData;
+use crate::parameters::Parameters;
+use crate::seeds::{SeedStream, Stream};
+use crate::HierarchicalModel;
+use mpi::traits::*;
+use std::sync::Arc;
+
+/// gathered on rank 0
+pub struct DistributedResult {
+    /// model in the best configuration found by any rank
+    pub best: HierarchicalModel,
+    /// for every rank, the log-likelihood every `trace_interval` iterations
+    pub log_like_traces: Vec<Vec<f64>>,
+}
+
+/// run one chain per rank, seeded with the `Stream::Chain` sub-seed `rank` of `seeds`.
+/// Returns `Some` on rank 0 and `None` on all other ranks. On rank 0, `seeds` records the
+/// seeds of all ranks.
+pub fn run_distributed(
+    params: &Parameters,
+    trace_interval: u64,
+    seeds: &mut SeedStream,
+) -> Result<Option<DistributedResult>, String> {
+    let universe = mpi::initialize().ok_or(String::from("MPI already initialized"))?;
+    let world = universe.world();
+    let rank = world.rank();
+    let size = world.size() as usize;
+    let root = world.process_at_rank(0);
+
+    let network = Arc::new(NetworkData::load(params)?);
+    let chain_params = Parameters {
+        seed: Some(seeds.derive(Stream::Chain, rank as u64)),
+        ..params.clone()
+    };
+    let mut hcp: HierarchicalModel =
+        HierarchicalModel::with_network(&chain_params, network.clone())?;
+    let mut best_log_like = hcp.log_like();
+    let mut best_groups = hcp.model().groups().to_vec();
+    let mut best_num_groups = hcp.model().num_groups() as u64;
+    let mut trace = Vec::new();
+    for i in 0..params.max_itr {
+        if hcp.get_groups().is_some() && hcp.log_like() > best_log_like {
+            best_log_like = hcp.log_like();
+            best_groups.copy_from_slice(hcp.model().groups());
+            best_num_groups = hcp.model().num_groups() as u64;
+        }
+        if i % trace_interval.max(1) == 0 {
+            trace.push(hcp.log_like());
+        }
+    }
+
+    // every rank has the same number of nodes and trace length, so fixed size gathers suffice
+    if rank != 0 {
+        root.gather_into(&best_log_like);
+        root.gather_into(&best_num_groups);
+        root.gather_into(&best_groups[..]);
+        root.gather_into(&trace[..]);
+        return Ok(None);
+    }
+    let mut log_likes = vec![0f64; size];
+    let mut num_groups = vec![0u64; size];
+    let mut groups: Vec<Groups> = vec![0; size * best_groups.len()];
+    let mut traces = vec![0f64; size * trace.len()];
+    root.gather_into_root(&best_log_like, &mut log_likes[..]);
+    root.gather_into_root(&best_num_groups, &mut num_groups[..]);
+    root.gather_into_root(&best_groups[..], &mut groups[..]);
+    root.gather_into_root(&trace[..], &mut traces[..]);
+
+    for r in 1..size {
+        seeds.derive(Stream::Chain, r as u64);
+    }
+    let best_rank = (0..size)
+        .max_by(|&a, &b| log_likes[a].total_cmp(&log_likes[b]))
+        .unwrap();
+    let n = best_groups.len();
+    let best = HierarchicalModel::with_network(
+        &params.with_initial_config(
+            groups[best_rank * n..(best_rank + 1) * n].to_vec(),
+            num_groups[best_rank] as u32,
+        ),
+        network,
+    )?;
+    Ok(Some(DistributedResult {
+        best,
+        log_like_traces: traces
+            .chunks(trace.len().max(1))
+            .map(<[f64]>::to_vec)
+            .collect(),
+    }))
+}
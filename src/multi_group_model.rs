@@ -1,116 +1,54 @@
-use crate::indexed_list::IndexedList;
-use std::fmt::Debug;
-
-pub type Groups = u64; // group assignment bits
-pub type Node = u32; // node id
-
-#[derive(Debug, PartialEq, Eq, Clone, Copy)]
-pub enum Move {
-    AddGroup {
-        group: usize,
-    },
-    RemoveGroup {
-        group: usize,
-    },
-    RemoveNodeFromGroup {
-        group: usize,
-        node: usize,
-        idx: usize,
-        old_state: u64,
-    },
-    AddNodeToGroup {
-        group: usize,
-        node: usize,
-        idx: usize,
-        old_state: u64,
-    },
-}
-
-#[derive(Debug, Clone)]
-pub struct MultiGroupModel {
-    max_groups: usize,
-    num_groups: usize,
-    num_nodes: usize,
-
-    /// group assignments for each node
-    pub groups: Vec<Groups>, // FIXME: pub for HcpLog
-
-    /// for every group (row), list ids of nodes in group.
-    /// entries beyond the group size are invalid.
-    nodes_in: IndexedList<Node>,
-    /// for every group (row), list ids of nodes not in group.
-    /// entries beyond (number of nodes - group size) are invalid.
-    nodes_out: IndexedList<Node>,
-
-    pub group_size: Vec<usize>, // FIXME: pub for HcpLog
-}
-
-#[inline]
-fn insert_zero_at(val: u64, pos: usize, num_groups: u32) -> u64 {
-    let group_mask = (1u64 << num_groups) - 1;
-    let select_mask = (group_mask << pos) & group_mask;
-
-    let left = val & select_mask;
-    let right = val & (!select_mask);
-
-    (left << 1) | right
-}
-
-#[inline]
-fn remove_bit_at(val: u64, pos: usize, num_groups: u32) -> u64 {
-    let group_mask = (1u64 << num_groups) - 1;
-    let upper_mask = (group_mask << (pos + 1)) & group_mask;
-    let lower_mask = (group_mask >> (num_groups as usize - pos)) & group_mask;
-
-    let upper = val & upper_mask;
-    let lower = val & lower_mask;
-
-    (upper >> 1) | lower
-}
-
-fn to_group_matrix(groups: &Vec<Groups>, num_groups: u32) -> Vec<Vec<bool>> {
-    groups
-        .iter()
-        .map(|g| (0..num_groups).map(|r| (g >> r) & 1 != 0).collect())
-        .collect()
-}
-
-macro_rules! getter {
-    ($name:ident, $type:ident) => {
-        pub fn $name(&self) -> $type {
-            self.$name
-        }
-    };
-}
+//! file formats of group configurations and checkpoint encoding of the model, on top of the
+//! std-free model in `core::multi_group_model`
+use crate::checkpoint::{Decoder, Encoder};
+use crate::core::indexed_list::{FixedIndexedList, IndexedList};
+pub use crate::core::multi_group_model::*;
+use std::collections::HashMap;
+use std::io::{self, BufRead, Read, Write};
 
 impl MultiGroupModel {
-    pub fn with_groups(groups: Vec<Groups>, num_groups: u32, max_groups: u32) -> Self {
-        // hierarchical_model::set_nodes_in_out()
-        let group_matrix = to_group_matrix(&groups, num_groups);
-        let max_groups = max_groups as usize;
-        let num_groups = num_groups as usize;
-        let num_nodes = groups.len();
+    /// write the complete state, including the order of the member lists,
+    /// which determines the moves proposed from here on
+    pub fn encode<W: Write>(&self, e: &mut Encoder<W>) -> io::Result<()> {
+        e.usize(self.max_groups)?;
+        e.usize(self.num_groups)?;
+        e.u64s(&self.groups)?;
+        e.u32s(self.nodes_in.storage().flat())?;
+        e.u32s(self.nodes_out.storage().flat())?;
+        e.usizes(self.nodes_in.row_index())?;
+        e.usizes(&self.group_size)?;
+        e.u32s(&self.order)
+    }
 
-        let mut nodes_in = IndexedList::new(num_nodes);
-        let mut nodes_out = IndexedList::new(num_nodes);
-        let mut group_size = Vec::new();
-        for r in 0..(num_groups as usize) {
-            nodes_in.push_row(&vec![Node::MAX; num_nodes]);
-            nodes_out.push_row(&vec![Node::MAX; num_nodes]);
-            let mut in_g = 0;
-            let mut out_g = 0;
-            for u in 0..num_nodes {
-                if group_matrix[u][r] {
-                    nodes_in[(r, in_g)] = u as Node;
-                    in_g += 1;
-                } else {
-                    nodes_out[(r, out_g)] = u as Node;
-                    out_g += 1;
-                }
-            }
-            group_size.push(in_g);
+    /// read a state written by `encode`. Errors if the state is not consistent, so a corrupt
+    /// checkpoint cannot produce a model that panics later.
+    pub fn decode<R: Read>(d: &mut Decoder<R>) -> io::Result<Self> {
+        let invalid =
+            |e: String| io::Error::new(io::ErrorKind::InvalidData, format!("invalid model: {}", e));
+        let max_groups = d.usize()?;
+        let num_groups = d.usize()?;
+        if !(1..=64).contains(&max_groups) || !(1..=max_groups).contains(&num_groups) {
+            return Err(invalid(format!(
+                "{} of at most {} groups",
+                num_groups, max_groups
+            )));
         }
-        Self {
+        let groups = d.u64s()?;
+        validate_groups(&groups, num_groups as u32, false).map_err(invalid)?;
+        let num_nodes = groups.len();
+        let nodes_in = IndexedList::from_flat(d.u32s()?, num_nodes).map_err(invalid)?;
+        let nodes_out = IndexedList::from_flat(d.u32s()?, num_nodes).map_err(invalid)?;
+        let rows = d.usizes()?;
+        d.expect("number of group rows", rows.len(), max_groups)?;
+        let nodes_in =
+            FixedIndexedList::from_parts(nodes_in, rows.clone(), num_groups).map_err(invalid)?;
+        let nodes_out =
+            FixedIndexedList::from_parts(nodes_out, rows, num_groups).map_err(invalid)?;
+        let group_size = d.usizes()?;
+        d.expect("number of group sizes", group_size.len(), num_groups)?;
+        let order = d.u32s()?;
+        d.expect("number of ordered nodes", order.len(), num_nodes)?;
+        let model = Self {
             max_groups,
             num_groups,
             num_nodes,
@@ -118,224 +56,196 @@ impl MultiGroupModel {
             nodes_in,
             nodes_out,
             group_size,
-        }
+            order,
+        };
+        model.check_lists().map_err(invalid)?;
+        Ok(model)
     }
 
-    getter!(num_groups, usize);
-    getter!(max_groups, usize);
-    getter!(num_nodes, usize);
-
-    pub fn group_size(&self, groups: impl Into<usize>) -> usize {
-        self.group_size[groups.into()]
-    }
-
-    pub fn groups_of(&self, node: usize) -> Groups {
-        self.groups[node]
-    }
-
-    pub fn add_group(&mut self, group: usize) -> Move {
-        self.nodes_in
-            .insert_row(group, &vec![Node::MAX; self.num_nodes]);
-        // TODO: avoid .collect
-        self.nodes_out
-            .insert_row(group, &(0..self.num_nodes as Node).collect::<Vec<_>>());
-        self.group_size.insert(group, 0);
-        self.groups = self
-            .groups
-            .iter()
-            .map(|&u| insert_zero_at(u, group, self.num_groups as u32))
-            .collect();
-        self.num_groups += 1;
-
-        Move::AddGroup { group }
-    }
-
-    pub fn remove_group(&mut self, group: usize) -> Move {
-        self.groups = self
-            .groups
-            .iter()
-            .map(|&u| remove_bit_at(u, group, self.num_groups as u32))
-            .collect();
-        self.nodes_in.remove_row(group);
-        self.nodes_out.remove_row(group);
-        self.group_size.remove(group);
-        self.num_groups -= 1;
-
-        Move::RemoveGroup { group }
-    }
-
-    pub fn remove_node_from_group_by_idx(&mut self, group: usize, idx: usize) -> Move {
-        let n_out = self.num_nodes - self.group_size[group];
-
-        let node = self.nodes_in[(group, idx)] as usize;
-        self.nodes_in[(group, idx)] = self.nodes_in[(group, self.group_size[group] - 1)];
-        self.nodes_out[(group, n_out)] = node as Node;
-        let old_state = self.groups[node];
-        self.groups[node] -= 1u64 << group;
-        self.group_size[group] -= 1;
-
-        Move::RemoveNodeFromGroup {
-            group,
-            node,
-            idx,
-            old_state,
+    /// check that the member lists hold exactly the members and non-members of every group
+    /// and that `order` lists every node once
+    fn check_lists(&self) -> Result<(), String> {
+        let is_permutation = |nodes: &[&[Node]]| {
+            let mut seen = vec![false; self.num_nodes];
+            nodes.iter().flat_map(|list| list.iter()).all(|&u| {
+                (u as usize) < self.num_nodes && !std::mem::replace(&mut seen[u as usize], true)
+            })
+        };
+        if !is_permutation(&[&self.order]) {
+            return Err(String::from("node order is not a permutation"));
         }
+        for g in 0..self.num_groups {
+            let size = self.group_size[g];
+            if size > self.num_nodes {
+                return Err(format!("group {} has {} nodes", g, size));
+            }
+            let in_g = |&u: &Node| self.groups[u as usize] >> g & 1 != 0;
+            if !is_permutation(&[self.members(g), self.non_members(g)])
+                || !self.members(g).iter().all(in_g)
+                || self.non_members(g).iter().any(in_g)
+            {
+                return Err(format!(
+                    "member lists of group {} do not match the groups",
+                    g
+                ));
+            }
+        }
+        Ok(())
     }
+}
 
-    pub fn add_node_to_group_by_idx(&mut self, group: usize, idx: usize) -> Move {
-        let n_out = self.num_nodes - self.group_size[group];
-
-        let node = self.nodes_out[(group, idx)] as usize;
-        self.nodes_out[(group, idx)] = self.nodes_out[(group, n_out - 1)];
-        self.nodes_in[(group, self.group_size[group])] = node as Node;
-        let old_state = self.groups[node];
-        self.groups[node] += 1u64 << group;
-        self.group_size[group] += 1;
-
-        Move::AddNodeToGroup {
-            group,
-            node,
-            idx,
-            old_state,
+/// read a configuration written as one row of 0/1 columns per node, column `g` being
+/// membership of group `g`. Column 0 is the root group and must be 1. Empty lines and lines
+/// starting with `#` are skipped. Returns the groups of every node and the number of groups.
+pub fn read_membership_matrix(src: impl BufRead) -> Result<(Vec<Groups>, u32), String> {
+    let mut groups = Vec::new();
+    let mut num_groups = None;
+    for line in src.lines() {
+        let line = line.map_err(|e| e.to_string())?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let mut row = 0;
+        let mut columns = 0;
+        for (g, w) in line.split_whitespace().enumerate() {
+            match w {
+                "0" => {}
+                "1" if g < 64 => row |= 1 << g,
+                "1" => return Err(String::from("number of groups cannot exceed 64")),
+                _ => return Err(format!("Malformed membership row: {}", line)),
+            }
+            columns += 1;
+        }
+        if *num_groups.get_or_insert(columns) != columns {
+            return Err(format!("membership rows have different lengths: {}", line));
+        }
+        if row & 1 == 0 {
+            return Err(format!(
+                "node {} is not in the root group (column 0)",
+                groups.len()
+            ));
         }
+        groups.push(row);
     }
+    Ok((groups, num_groups.unwrap_or(1)))
+}
 
-    /// Undo group modifications of move `m`.
-    /// Does *not* restore log likelihood or hcg values.
-    pub fn undo_move(&mut self, m: Move) {
-        match m {
-            Move::RemoveNodeFromGroup {
-                group, node, idx, ..
-            } => {
-                // TODO: can this be unified with MultiGroupModel::add_node_to_group_by_idx?
-                self.group_size[group] += 1;
-                let n_out = self.num_nodes - self.group_size[group];
-                self.nodes_out[(group, n_out)] = Node::MAX;
-                self.nodes_in[(group, idx)] = node as Node;
-                self.groups[node] += 1u64 << group;
-            }
-            Move::RemoveGroup { group } => {
-                self.add_group(group);
-            }
-            Move::AddGroup { group } => {
-                self.remove_group(group);
-            }
-            Move::AddNodeToGroup {
-                group, node, idx, ..
-            } => {
-                // TODO: can this be unified with MultiGroupModel::remove_node_from_group_by_idx?
-                self.group_size[group] -= 1;
-                self.nodes_in[(group, self.group_size[group])] = Node::MAX;
-                self.nodes_out[(group, idx)] = node as Node;
-                self.groups[node] -= 1u64 << group;
-            }
+/// read a flat clustering as a two level hierarchy: the root and one group per community.
+/// Every line is either `<node> <label>` or just `<label>` for the nodes in order; labels are
+/// arbitrary words, numbered by first appearance. Empty lines and lines starting with `#` are
+/// skipped. Returns the groups of every node and the number of groups.
+pub fn read_partition(src: impl BufRead) -> Result<(Vec<Groups>, u32), String> {
+    let mut labels = HashMap::new();
+    let mut groups: Vec<Option<Groups>> = Vec::new();
+    for line in src.lines() {
+        let line = line.map_err(|e| e.to_string())?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let (node, label) = match words[..] {
+            [label] => (groups.len(), label),
+            [node, label] => (
+                node.parse()
+                    .or(Err(format!("Malformed partition line: {}", line)))?,
+                label,
+            ),
+            _ => return Err(format!("Malformed partition line: {}", line)),
+        };
+        let num_labels = labels.len();
+        let group = *labels.entry(label.to_owned()).or_insert(num_labels + 1);
+        if group >= 64 {
+            return Err(String::from("number of groups cannot exceed 64"));
+        }
+        if groups.len() <= node {
+            groups.resize(node + 1, None);
+        }
+        if groups[node].replace(1 | 1 << group).is_some() {
+            return Err(format!("node {} is assigned twice", node));
         }
     }
+    let groups = groups
+        .into_iter()
+        .enumerate()
+        .map(|(u, g)| g.ok_or(format!("node {} has no community", u)))
+        .collect::<Result<_, _>>()?;
+    Ok((groups, labels.len() as u32 + 1))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    fn _test_model() -> MultiGroupModel {
-        MultiGroupModel::with_groups(
-            vec![
-                9, 41, 25, 13, 73, 137, 11, 33, 17, 5, 65, 129, 3, 33, 33, 17, 17, 5, 5, 65, 65,
-                129, 129, 3, 3,
-            ],
-            8,
-            64,
-        )
-    }
-
     #[test]
-    fn add_group() {
-        let mut model = _test_model();
-        let g = 1;
-        let old = model.clone();
-        let op = model.add_group(g);
-        assert_eq!(model.num_groups, old.num_groups + 1);
+    fn membership_matrix() {
+        let src = b"# node per row\n1 1 0\n1 0 1\n\n1 1 0\n";
         assert_eq!(
-            model.group_size.iter().sum::<usize>(),
-            old.group_size.iter().sum()
+            read_membership_matrix(&src[..]).unwrap(),
+            (vec![0b011, 0b101, 0b011], 3)
         );
-        assert_eq!(model.group_size[g], 0);
-
-        let mut undone = model.clone();
-        undone.undo_move(op);
-        assert_eq!(old.num_groups, undone.num_groups);
-        assert_eq!(old.group_size, undone.group_size);
-        assert_eq!(old.groups, undone.groups);
+        assert!(read_membership_matrix(&b"1 1\n1\n"[..]).is_err());
+        assert!(read_membership_matrix(&b"0 1\n"[..]).is_err());
+        assert!(read_membership_matrix(&b"1 2\n"[..]).is_err());
     }
+
     #[test]
-    fn remove_group() {
-        let mut model = _test_model();
-        let g = 1;
-        let old = model.clone();
-        let op = model.remove_group(g);
-        assert_eq!(model.num_groups, old.num_groups - 1);
+    fn partition() {
+        let src = b"# louvain\n0 b\n2 a\n1 b\n";
         assert_eq!(
-            model.group_size.iter().sum::<usize>(),
-            old.group_size.iter().sum::<usize>() - old.group_size[g]
+            read_partition(&src[..]).unwrap(),
+            (vec![0b011, 0b011, 0b101], 3)
         );
-
-        let mut undone = model.clone();
-        undone.undo_move(op);
-        assert_eq!(old.num_groups, undone.num_groups);
-        // HACK: remove_group assumes groups are empty, but I couldn't be bothered to properly
-        //       set that up.
-
-        // assert_eq!(old.group_size, undone.group_size);
-        // assert_eq!(old.groups, undone.groups);
-    }
-
-    #[test]
-    fn add_node_to_group_by_idx() {
-        let mut model = _test_model();
-        let g = 1;
-        let idx = 3;
-        let old = model.clone();
-        let op = model.add_node_to_group_by_idx(g, idx);
-        assert_eq!(model.num_groups, old.num_groups);
-        match op {
-            Move::AddNodeToGroup { node, .. } => assert!(model.groups[node] & (1 << g) != 0),
-            _ => panic!("not an add_node_to_group operation"),
-        }
         assert_eq!(
-            model.group_size.iter().sum::<usize>(),
-            old.group_size.iter().sum::<usize>() + 1
+            read_partition(&b"7\n7\n3\n"[..]).unwrap(),
+            (vec![3, 3, 5], 3)
         );
-        assert_eq!(model.group_size[g], old.group_size[g] + 1);
-
-        let mut undone = model.clone();
-        undone.undo_move(op);
-        assert_eq!(old.num_groups, undone.num_groups);
-        assert_eq!(old.group_size, undone.group_size);
-        assert_eq!(old.groups, undone.groups);
+        assert!(read_partition(&b"0 a\n0 b\n"[..]).is_err());
+        assert!(read_partition(&b"1 a\n"[..]).is_err());
     }
-    #[test]
-    fn remove_node_from_group_by_idx() {
-        let mut model = _test_model();
 
-        let g = 1;
-        let idx = 3;
-        let old = model.clone();
-        let op = model.remove_node_from_group_by_idx(g, idx);
-        assert_eq!(model.num_groups, old.num_groups);
-        match op {
-            Move::RemoveNodeFromGroup { node, .. } => assert!(model.groups[node] & (1 << g) == 0),
-            _ => panic!("not an remove_node_from_group operation"),
-        }
-        assert_eq!(
-            model.group_size.iter().sum::<usize>(),
-            old.group_size.iter().sum::<usize>() - 1
+    #[test]
+    fn checkpoint() {
+        let mut model = MultiGroupModel::with_groups(
+            vec![
+                9, 41, 25, 13, 73, 137, 11, 33, 17, 5, 65, 129, 3, 33, 33, 17, 17, 5, 5, 65, 65,
+                129, 129, 3, 3,
+            ],
+            8,
+            64,
         );
-        assert_eq!(model.group_size[g], old.group_size[g] - 1);
-
-        let mut undone = model.clone();
-        undone.undo_move(op);
-        assert_eq!(old.num_groups, undone.num_groups);
-        assert_eq!(old.group_size, undone.group_size);
-        assert_eq!(old.groups, undone.groups);
+        model.add_node_to_group_by_idx(2, 4);
+        model.remove_group(3);
+        let mut e = Encoder::new(Vec::new()).unwrap();
+        model.encode(&mut e).unwrap();
+        let buf = e.into_inner();
+        let restored = MultiGroupModel::decode(&mut Decoder::new(&buf[..]).unwrap()).unwrap();
+        assert_eq!(restored.groups, model.groups);
+        assert_eq!(restored.nodes_in, model.nodes_in);
+        assert_eq!(restored.nodes_out, model.nodes_out);
+        assert_eq!(restored.group_size, model.group_size);
+        assert_eq!(restored.num_groups, model.num_groups);
+
+        // inconsistent states are errors
+        let corruptions: [fn(&mut MultiGroupModel); 5] = [
+            |m| m.max_groups = 65,
+            |m| m.groups[3] |= 1 << 20,
+            |m| m.nodes_in[(1, 0)] = 99,
+            |m| m.group_size[2] += 1,
+            |m| m.order[0] = m.order[1],
+        ];
+        for (i, corrupt) in corruptions.iter().enumerate() {
+            let mut bad = model.clone();
+            corrupt(&mut bad);
+            let mut e = Encoder::new(Vec::new()).unwrap();
+            bad.encode(&mut e).unwrap();
+            let buf = e.into_inner();
+            assert!(
+                MultiGroupModel::decode(&mut Decoder::new(&buf[..]).unwrap()).is_err(),
+                "{}",
+                i
+            );
+        }
     }
 }
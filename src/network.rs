@@ -0,0 +1,709 @@
+pub use crate::core::network::NetworkData;
+use crate::multi_group_model::Node;
+use crate::parameters::{IsolatedNodes, NetworkFormat, Parameters};
+use asexp::token::{Token, Tokenizer};
+use asexp::Sexp;
+use petgraph::graph::{IndexType, UnGraph};
+use petgraph::{visit::EdgeRef, EdgeType, Graph};
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+impl NetworkData {
+    /// read the network of `params`, restricted to `params.node_subset_path` if given and with
+    /// isolated nodes handled as `params.isolated_nodes`
+    pub fn load(params: &Parameters) -> Result<Self, String> {
+        let mut network = Self::read(params.require_gml_path()?, params.network_format)?;
+        if let Some(path) = &params.node_subset_path {
+            let nodes = read_node_list(path)?;
+            let subgraph = network.induced_subgraph(&nodes)?;
+            if !params.quiet {
+                println!(
+                    "node subset: {} of {} nodes, {} of {} edges ({} dropped), renumbered and \
+                     labeled with their original ids",
+                    subgraph.node_count(),
+                    network.node_count(),
+                    subgraph.edge_count(),
+                    network.edge_count(),
+                    network.edge_count() - subgraph.edge_count()
+                );
+            }
+            network = subgraph;
+        }
+        let isolated = (0..network.node_count() as Node)
+            .filter(|&u| network.degree(u) == 0)
+            .count();
+        if isolated == 0 {
+            return Ok(network);
+        }
+        match params.isolated_nodes {
+            IsolatedNodes::Keep => {
+                if !params.quiet {
+                    println!(
+                        "warning: {} isolated nodes add pairs but no edges to the root group \
+                         (isolated_nodes: drop removes them)",
+                        isolated
+                    );
+                }
+                Ok(network)
+            }
+            IsolatedNodes::Drop => {
+                let (network, _) = network.without_isolated_nodes();
+                if !params.quiet {
+                    println!(
+                        "dropped {} isolated nodes, the other nodes are renumbered and labeled \
+                         with their original ids",
+                        isolated
+                    );
+                }
+                Ok(network)
+            }
+        }
+    }
+
+    /// read the network at `path` in `format`
+    pub fn read(path: &Path, format: NetworkFormat) -> Result<Self, String> {
+        match format {
+            NetworkFormat::Auto if path.extension().is_some_and(|e| e == "mtx") => {
+                Self::read(path, NetworkFormat::Mtx)
+            }
+            NetworkFormat::Auto if path.extension().is_some_and(|e| e == "net") => {
+                Self::read(path, NetworkFormat::Pajek)
+            }
+            NetworkFormat::Auto | NetworkFormat::Gml => Self::read_gml(path),
+            NetworkFormat::Mtx | NetworkFormat::Pajek => {
+                let file = BufReader::new(
+                    fs::File::open(path).map_err(|e| format!("{}: {}", path.display(), e))?,
+                );
+                if format == NetworkFormat::Mtx {
+                    Self::read_mtx(file)
+                } else {
+                    Self::read_pajek(file)
+                }
+            }
+        }
+    }
+
+    pub fn read_gml(gml_path: &Path) -> Result<Self, String> {
+        Self::parse_gml(&fs::read_to_string(gml_path).map_err(|e| e.to_string())?)
+    }
+
+    /// parse the contents of a GML file. Nodes are numbered in order of declaration, edges are
+    /// taken as undirected with repeated edges merged, and other attributes are ignored.
+    pub fn parse_gml(gml: &str) -> Result<Self, String> {
+        let tokens = Tokenizer::new(gml, true)
+            .with_curly_around()
+            .map(|t| match t {
+                Token::OpenBracket => Token::OpenCurly,
+                Token::CloseBracket => Token::CloseCurly,
+                t => t,
+            });
+        let sexp = Sexp::parse_iter(tokens).or(Err("Invalid GML"))?;
+        let Some(Sexp::Map(items)) = sexp.into_map()?.remove("graph") else {
+            return Err(String::from("no graph given or invalid"));
+        };
+        let mut ids = BTreeMap::new();
+        let mut edges = Vec::new();
+        for (key, value) in items {
+            match key.get_str() {
+                Some("directed") if matches!(value.get_uint(), Some(0 | 1)) => {}
+                Some("node") => {
+                    let info = value.into_map()?;
+                    let id = info
+                        .get("id")
+                        .and_then(Sexp::get_uint)
+                        .ok_or("Invalid id")?;
+                    let next = ids.len();
+                    if ids.insert(id, next).is_some() {
+                        return Err(format!("duplicate node id {}", id));
+                    }
+                }
+                Some("edge") => {
+                    let info = value.into_map()?;
+                    let end = |k: &str| {
+                        info.get(k)
+                            .and_then(Sexp::get_uint)
+                            .ok_or(format!("Invalid {} id", k))
+                    };
+                    edges.push((end("source")?, end("target")?));
+                }
+                _ => return Err(format!("invalid GML item: {}", key)),
+            }
+        }
+        let num_nodes = check_node_count(ids.len())?;
+        let node = |id| match ids.get(&id) {
+            Some(&u) => Ok(u as Node),
+            None => Err(format!("edge to undeclared node {}", id)),
+        };
+        let mut edges = edges
+            .into_iter()
+            .map(|(s, t)| Ok((node(s)?, node(t)?)))
+            .collect::<Result<Vec<_>, String>>()?;
+        merge_repeated(&mut edges);
+        Ok(Self::from_edges(num_nodes, edges))
+    }
+
+    /// read an edge list with one whitespace separated `u v` pair per line. Empty lines and
+    /// lines starting with `#` are skipped, repeated edges in either direction are merged. The
+    /// number of nodes is one more than the largest id.
+    pub fn read_edge_list(src: impl BufRead) -> Result<Self, String> {
+        let mut edges = Vec::new();
+        for line in src.lines() {
+            let line = line.map_err(|e| e.to_string())?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut words = line.split_whitespace().map(|w| w.parse::<Node>());
+            match (words.next(), words.next(), words.next()) {
+                (Some(Ok(u)), Some(Ok(v)), None) => edges.push((u, v)),
+                _ => return Err(format!("Malformed edge list line: {}", line)),
+            }
+        }
+        let num_nodes = edges.iter().map(|&(u, v)| u.max(v) as usize + 1).max();
+        let num_nodes = check_node_count(num_nodes.unwrap_or(0))?;
+        merge_repeated(&mut edges);
+        Ok(Self::from_edges(num_nodes, edges))
+    }
+
+    /// read a square Matrix Market matrix in coordinate format. Every nonzero entry is an edge
+    /// between its row and column; for general (unsymmetric) matrices, entries `(i, j)` and
+    /// `(j, i)` are the same edge.
+    pub fn read_mtx(src: impl BufRead) -> Result<Self, String> {
+        let mut lines = src.lines();
+        let header = lines
+            .next()
+            .ok_or("empty Matrix Market file")?
+            .map_err(|e| e.to_string())?
+            .to_lowercase();
+        let words: Vec<&str> = header.split_whitespace().collect();
+        let (field, symmetry) = match words[..] {
+            ["%%matrixmarket", "matrix", "coordinate", field, symmetry] => (field, symmetry),
+            _ => return Err(format!("not a Matrix Market coordinate matrix: {}", header)),
+        };
+        if !["pattern", "real", "integer", "complex"].contains(&field)
+            || !["general", "symmetric", "skew-symmetric", "hermitian"].contains(&symmetry)
+        {
+            return Err(format!("unsupported Matrix Market matrix: {}", header));
+        }
+
+        let mut size = None;
+        let mut edges = Vec::new();
+        for line in lines {
+            let line = line.map_err(|e| e.to_string())?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('%') {
+                continue;
+            }
+            let malformed = || format!("Malformed Matrix Market line: {}", line);
+            let mut words = line.split_whitespace();
+            let mut next = || -> Result<usize, String> {
+                words
+                    .next()
+                    .and_then(|w| w.parse().ok())
+                    .ok_or_else(malformed)
+            };
+            let Some(rows) = size else {
+                let (rows, cols, num_entries) = (next()?, next()?, next()?);
+                if rows != cols {
+                    return Err(format!("adjacency matrix is not square: {}x{}", rows, cols));
+                }
+                size = Some(check_node_count(rows)?);
+                // the entry count is only a hint, don't trust it with a huge allocation
+                edges.reserve(num_entries.min(1 << 20));
+                continue;
+            };
+            let (i, j) = (next()?, next()?);
+            if !(1..=rows).contains(&i) || !(1..=rows).contains(&j) {
+                return Err(malformed());
+            }
+            let value = words
+                .next()
+                .map_or(Ok(1.0), |w| w.parse::<f64>().map_err(|_| malformed()))?;
+            if value != 0.0 {
+                edges.push(((i - 1) as Node, (j - 1) as Node));
+            }
+        }
+        let rows = size.ok_or("missing Matrix Market size line")?;
+        if symmetry == "general" {
+            for e in &mut edges {
+                *e = (e.0.min(e.1), e.0.max(e.1));
+            }
+            edges.sort_unstable();
+            edges.dedup();
+        }
+        Ok(Self::from_edges(rows, edges))
+    }
+
+    /// read a Pajek network. Arcs are taken as undirected edges, with repeated edges merged;
+    /// weights are ignored except that zero entries of a `*Matrix` are not edges. Vertex labels
+    /// become node labels, unlabelled vertices are named by their number.
+    pub fn read_pajek(src: impl BufRead) -> Result<Self, String> {
+        #[derive(PartialEq)]
+        enum Section {
+            None,
+            Vertices,
+            Edges,
+            Arcs,
+            EdgesList,
+            ArcsList,
+            Matrix(usize),
+        }
+        let mut section = Section::None;
+        let mut num_nodes = None;
+        let mut labels: Vec<Option<String>> = Vec::new();
+        let mut edges = Vec::new();
+        let mut arcs = Vec::new();
+
+        for line in src.lines() {
+            let line = line.map_err(|e| e.to_string())?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('%') {
+                continue;
+            }
+            let malformed = || format!("Malformed Pajek line: {}", line);
+            if let Some(header) = line.strip_prefix('*') {
+                let mut words = header.split_whitespace();
+                section = match words.next().unwrap_or("").to_lowercase().as_str() {
+                    "vertices" => {
+                        let n: usize = words
+                            .next()
+                            .and_then(|w| w.parse().ok())
+                            .ok_or_else(malformed)?;
+                        num_nodes = Some(check_node_count(n)?);
+                        labels = vec![None; n];
+                        Section::Vertices
+                    }
+                    "network" => Section::None,
+                    "edges" => Section::Edges,
+                    "arcs" => Section::Arcs,
+                    "edgeslist" => Section::EdgesList,
+                    "arcslist" => Section::ArcsList,
+                    "matrix" => Section::Matrix(0),
+                    _ => return Err(format!("unsupported Pajek section: {}", line)),
+                };
+                continue;
+            }
+            let n = num_nodes.ok_or("Pajek file without *Vertices")?;
+            let node = |w: &str| -> Result<Node, String> {
+                match w.parse::<usize>() {
+                    Ok(u) if (1..=n).contains(&u) => Ok((u - 1) as Node),
+                    _ => Err(malformed()),
+                }
+            };
+            let mut words = line.split_whitespace();
+            match &mut section {
+                Section::None => return Err(malformed()),
+                Section::Vertices => {
+                    let id = words.next().ok_or_else(malformed)?;
+                    let rest = line[id.len()..].trim_start();
+                    let label = match rest.strip_prefix('"') {
+                        Some(quoted) => quoted.split_once('"').ok_or_else(malformed)?.0,
+                        None => rest.split_whitespace().next().unwrap_or(id),
+                    };
+                    labels[node(id)? as usize] = Some(label.to_owned());
+                }
+                Section::Edges | Section::Arcs => {
+                    let (u, v) = (
+                        node(words.next().ok_or_else(malformed)?)?,
+                        node(words.next().ok_or_else(malformed)?)?,
+                    );
+                    if section == Section::Edges {
+                        edges.push((u, v));
+                    } else {
+                        arcs.push((u.min(v), u.max(v)));
+                    }
+                }
+                Section::EdgesList | Section::ArcsList => {
+                    let u = node(words.next().ok_or_else(malformed)?)?;
+                    for w in words {
+                        let v = node(w)?;
+                        if section == Section::EdgesList {
+                            edges.push((u, v));
+                        } else {
+                            arcs.push((u.min(v), u.max(v)));
+                        }
+                    }
+                }
+                Section::Matrix(row) => {
+                    if *row >= n {
+                        return Err(malformed());
+                    }
+                    for (col, w) in words.enumerate() {
+                        let value: f64 = w.parse().map_err(|_| malformed())?;
+                        // the upper triangle, mirrored for asymmetric matrices
+                        if value != 0.0 && col < n {
+                            arcs.push(((*row).min(col) as Node, (*row).max(col) as Node));
+                        }
+                    }
+                    *row += 1;
+                }
+            }
+        }
+
+        let n = num_nodes.ok_or("Pajek file without *Vertices")?;
+        arcs.sort_unstable();
+        arcs.dedup();
+        edges.extend(arcs);
+        merge_repeated(&mut edges);
+        let network = Self::from_edges(n, edges);
+        if labels.iter().all(Option::is_none) {
+            return Ok(network);
+        }
+        network.with_labels(
+            labels
+                .into_iter()
+                .enumerate()
+                .map(|(u, l)| l.unwrap_or_else(|| (u + 1).to_string()))
+                .collect(),
+        )
+    }
+
+    /// summary statistics, see `GraphStats`
+    pub fn stats(&self) -> GraphStats {
+        let n = self.node_count();
+        let mut degrees: Vec<usize> = (0..n as Node).map(|u| self.degree(u)).collect();
+        degrees.sort_unstable();
+
+        // connected components by breadth-first search
+        let mut component = vec![usize::MAX; n];
+        let mut sizes = Vec::new();
+        let mut queue = Vec::new();
+        for start in 0..n {
+            if component[start] != usize::MAX {
+                continue;
+            }
+            component[start] = sizes.len();
+            queue.push(start as Node);
+            let mut size = 0;
+            while let Some(u) = queue.pop() {
+                size += 1;
+                for &v in self.neighbors(u) {
+                    if component[v as usize] == usize::MAX {
+                        component[v as usize] = sizes.len();
+                        queue.push(v);
+                    }
+                }
+            }
+            sizes.push(size);
+        }
+
+        // local clustering coefficients, counting multi-edges once
+        let mut neighbor_of = vec![Node::MAX; n];
+        let mut counted = vec![Node::MAX; n];
+        // `visited[w] == visit`: link to `w` already counted in this visit of a neighbor
+        let mut visited = vec![0; n];
+        let mut visit = 0;
+        let mut clustering = 0.0;
+        for u in 0..n as Node {
+            let mut k = 0;
+            for &v in self.neighbors(u) {
+                if neighbor_of[v as usize] != u {
+                    neighbor_of[v as usize] = u;
+                    k += 1;
+                }
+            }
+            if k < 2 {
+                continue;
+            }
+            let mut links = 0;
+            for &v in self.neighbors(u) {
+                if counted[v as usize] == u {
+                    continue;
+                }
+                counted[v as usize] = u;
+                visit += 1;
+                for &w in self.neighbors(v) {
+                    if neighbor_of[w as usize] == u && visited[w as usize] != visit {
+                        visited[w as usize] = visit;
+                        links += 1;
+                    }
+                }
+            }
+            clustering += links as f64 / (k * (k - 1)) as f64;
+        }
+
+        GraphStats {
+            num_nodes: n,
+            num_edges: self.edge_count(),
+            density: match n {
+                0 | 1 => 0.0,
+                _ => self.edge_count() as f64 / (n * (n - 1) / 2) as f64,
+            },
+            min_degree: degrees.first().copied().unwrap_or(0),
+            median_degree: degrees.get(n / 2).copied().unwrap_or(0),
+            mean_degree: 2.0 * self.edge_count() as f64 / n.max(1) as f64,
+            max_degree: degrees.last().copied().unwrap_or(0),
+            isolated_nodes: degrees.iter().take_while(|&&d| d == 0).count(),
+            num_components: sizes.len(),
+            largest_component: sizes.iter().copied().max().unwrap_or(0),
+            clustering: clustering / n.max(1) as f64,
+        }
+    }
+}
+
+/// basic statistics of a network. Isolated nodes, several components or a density near 0 or
+/// 1 leave little for the hierarchy to explain.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GraphStats {
+    pub num_nodes: usize,
+    pub num_edges: usize,
+    /// fraction of node pairs that are connected
+    pub density: f64,
+    pub min_degree: usize,
+    pub median_degree: usize,
+    pub mean_degree: f64,
+    pub max_degree: usize,
+    pub isolated_nodes: usize,
+    pub num_components: usize,
+    /// number of nodes in the largest connected component
+    pub largest_component: usize,
+    /// mean local clustering coefficient, 0 for nodes with fewer than two neighbors
+    pub clustering: f64,
+}
+
+impl std::fmt::Display for GraphStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(
+            f,
+            "nodes: {} edges: {} density: {:.4}",
+            self.num_nodes, self.num_edges, self.density
+        )?;
+        writeln!(
+            f,
+            "degree min: {} median: {} mean: {:.2} max: {} isolated nodes: {}",
+            self.min_degree,
+            self.median_degree,
+            self.mean_degree,
+            self.max_degree,
+            self.isolated_nodes
+        )?;
+        writeln!(
+            f,
+            "components: {} largest: {} clustering coefficient: {:.4}",
+            self.num_components, self.largest_component, self.clustering
+        )
+    }
+}
+
+/// node `i` of the graph becomes node `i` of the network; weights are ignored and directed
+/// edges are taken as undirected
+impl<N, E, Ty: EdgeType, Ix: IndexType> From<&Graph<N, E, Ty, Ix>> for NetworkData {
+    fn from(graph: &Graph<N, E, Ty, Ix>) -> Self {
+        Self::from_edges(
+            graph.node_count(),
+            graph
+                .edge_references()
+                .map(|e| (e.source().index() as Node, e.target().index() as Node)),
+        )
+    }
+}
+
+impl From<&NetworkData> for UnGraph<(), ()> {
+    fn from(network: &NetworkData) -> Self {
+        let mut graph = UnGraph::with_capacity(network.node_count(), network.edge_count());
+        for _ in 0..network.node_count() {
+            graph.add_node(());
+        }
+        graph.extend_with_edges(network.edges());
+        graph
+    }
+}
+
+/// read whitespace separated node ids from a file
+pub fn read_node_list(path: &Path) -> Result<Vec<Node>, String> {
+    fs::read_to_string(path)
+        .map_err(|e| e.to_string())?
+        .split_whitespace()
+        .map(|w| w.parse().or(Err(format!("not an integer: {}", w))))
+        .collect()
+}
+
+/// drop repeated edges, in either direction, keeping the first
+fn merge_repeated(edges: &mut Vec<(Node, Node)>) {
+    let mut seen = HashSet::new();
+    edges.retain(|&(u, v)| seen.insert((u.min(v), u.max(v))));
+}
+
+/// fails for more nodes than `Node` can number
+fn check_node_count(n: usize) -> Result<usize, String> {
+    if n > Node::MAX as usize {
+        return Err(format!("{} nodes, at most {} are supported", n, Node::MAX));
+    }
+    Ok(n)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stats() {
+        // a triangle with a pendant node and a doubled edge, a separate edge, an isolated node
+        let net = NetworkData::from_edges(7, [(0, 1), (1, 2), (2, 0), (2, 3), (2, 3), (4, 5)]);
+        let stats = net.stats();
+        assert_eq!(stats.num_nodes, 7);
+        assert_eq!(stats.num_edges, 6);
+        assert_eq!(stats.density, 6.0 / 21.0);
+        assert_eq!(
+            (stats.min_degree, stats.median_degree, stats.max_degree),
+            (0, 2, 4)
+        );
+        assert_eq!(stats.isolated_nodes, 1);
+        assert_eq!((stats.num_components, stats.largest_component), (3, 4));
+        // nodes 0 and 1: 1, node 2: 1 of 3 pairs of its 3 distinct neighbors
+        assert!((stats.clustering - (2.0 + 1.0 / 3.0) / 7.0).abs() < 1e-12);
+        assert!(stats.to_string().contains("components: 3"));
+    }
+
+    #[test]
+    fn csr() {
+        let net = NetworkData::from_edges(4, [(0, 1), (1, 2), (2, 0), (3, 3)]);
+        assert_eq!(net.node_count(), 4);
+        assert_eq!(net.edge_count(), 3);
+        assert_eq!(net.neighbors(0), [1, 2]);
+        assert_eq!(net.neighbors(2), [1, 0]);
+        assert_eq!(net.degree(3), 0);
+        assert_eq!(net.edges().collect::<Vec<_>>(), [(0, 1), (0, 2), (1, 2)]);
+        assert!(!net.has_multi_edges());
+        assert!(NetworkData::from_edges(2, [(0, 1), (1, 0)]).has_multi_edges());
+
+        let mut net = net;
+        net.insert_edge(3, 1);
+        assert_eq!(net.edge_count(), 4);
+        assert_eq!(
+            (net.neighbors(1), net.neighbors(3)),
+            (&[0, 2, 3][..], &[1][..])
+        );
+        assert!(net.remove_edge(0, 2));
+        assert!(!net.remove_edge(0, 2));
+        assert_eq!(net, NetworkData::from_edges(4, [(0, 1), (1, 2), (1, 3)]));
+    }
+
+    #[test]
+    fn isolated_nodes() {
+        let net = NetworkData::from_edges(5, [(1, 3), (3, 4)]);
+        let (dropped, kept) = net.without_isolated_nodes();
+        assert_eq!(kept, [1, 3, 4]);
+        assert_eq!(dropped.edges().collect::<Vec<_>>(), [(0, 1), (1, 2)]);
+        assert_eq!(
+            (0..3)
+                .map(|u| dropped.label(u).unwrap())
+                .collect::<Vec<_>>(),
+            ["1", "3", "4"]
+        );
+        let labeled = net
+            .clone()
+            .with_labels((0..5).map(|u| format!("n{}", u)).collect())
+            .unwrap();
+        assert_eq!(labeled.without_isolated_nodes().0.label(2), Some("n4"));
+
+        let subgraph = labeled.induced_subgraph(&[4, 3, 0]).unwrap();
+        assert_eq!(subgraph.edges().collect::<Vec<_>>(), [(0, 1)]);
+        assert_eq!(subgraph.label(1), Some("n3"));
+        assert!(net.induced_subgraph(&[5]).is_err());
+        assert!(net.induced_subgraph(&[1, 1]).is_err());
+    }
+
+    #[test]
+    fn edge_list() {
+        let net = NetworkData::read_edge_list(&b"# triangle\n0 1\n1 2\n\n2 0\n3 3\n"[..]).unwrap();
+        assert_eq!(net, NetworkData::from_edges(4, [(0, 1), (1, 2), (2, 0)]));
+        assert!(NetworkData::read_edge_list(&b"0 1 2\n"[..]).is_err());
+        assert!(NetworkData::read_edge_list(&b"0 x\n"[..]).is_err());
+        let net = NetworkData::read_edge_list(&b"0 1\n1 0\n0 1\n"[..]).unwrap();
+        assert_eq!(net.edge_count(), 1);
+        // node ids must leave room for the node count
+        assert!(NetworkData::read_edge_list(&b"0 4294967295\n"[..]).is_err());
+    }
+
+    #[test]
+    fn gml() {
+        let src = "graph [\n  directed 1\n  node [ id 7 label \"a b\" ]\n  node [ id 3 ]\n  \
+                   node [ id 5 ]\n  edge [ source 7 target 3 ]\n  edge [ source 5 target 3 ]\n]\n";
+        let net = NetworkData::parse_gml(src).unwrap();
+        assert_eq!(net, NetworkData::from_edges(3, [(0, 1), (2, 1)]));
+        assert!(
+            NetworkData::parse_gml("graph [ node [ id 1 ] edge [ source 1 target 2 ] ]").is_err()
+        );
+        assert!(NetworkData::parse_gml("graph [ node [ id 1 ] node [ id 1 ] ]").is_err());
+        assert!(NetworkData::parse_gml("graph [ node [ id 1 ]").is_err());
+        assert!(NetworkData::parse_gml("graph [ directed 2 ]").is_err());
+    }
+
+    #[test]
+    fn matrix_market() {
+        let symmetric = b"%%MatrixMarket matrix coordinate pattern symmetric\n\
+                          % triangle and an isolated node\n\
+                          4 4 3\n2 1\n3 2\n3 1\n";
+        let triangle = NetworkData::from_edges(4, [(1, 0), (2, 1), (2, 0)]);
+        assert_eq!(NetworkData::read_mtx(&symmetric[..]).unwrap(), triangle);
+
+        let general = b"%%MatrixMarket matrix coordinate real general\n\
+                        4 4 6\n1 2 1.0\n2 1 1.0\n2 3 0.5\n1 3 2\n4 4 1\n1 4 0\n";
+        let net = NetworkData::read_mtx(&general[..]).unwrap();
+        assert_eq!(net.edges().collect::<Vec<_>>(), [(0, 1), (0, 2), (1, 2)]);
+        assert_eq!(net.node_count(), 4);
+
+        assert!(NetworkData::read_mtx(&b"%%MatrixMarket matrix array real general\n"[..]).is_err());
+        assert!(NetworkData::read_mtx(
+            &b"%%MatrixMarket matrix coordinate pattern general\n2 3 1\n1 2\n"[..]
+        )
+        .is_err());
+        assert!(NetworkData::read_mtx(
+            &b"%%MatrixMarket matrix coordinate pattern general\n2 2 1\n1 5\n"[..]
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn pajek() {
+        let src = b"% karate club excerpt\n\
+                    *Vertices 4\n\
+                    1 \"Mr Hi\" 0.1 0.2 0.5\n\
+                    2 \"Officer\"\n\
+                    4 d\n\
+                    *Arcs\n\
+                    1 2 1\n2 1 1\n\
+                    *Edges\n\
+                    2 3\n\
+                    *Edgeslist\n\
+                    4 1 3\n";
+        let net = NetworkData::read_pajek(&src[..]).unwrap();
+        assert_eq!(
+            net.edges().collect::<Vec<_>>(),
+            [(0, 3), (0, 1), (1, 2), (2, 3)]
+        );
+        assert_eq!(net.label(0), Some("Mr Hi"));
+        assert_eq!(net.label(2), Some("3"));
+        assert_eq!(net.label(3), Some("d"));
+
+        let matrix = b"*Vertices 3\n*Matrix\n0 1 0\n1 0 2\n0 0 0\n";
+        let net = NetworkData::read_pajek(&matrix[..]).unwrap();
+        assert_eq!(net, NetworkData::from_edges(3, [(0, 1), (1, 2)]));
+        assert_eq!(net.label(0), None);
+
+        assert!(NetworkData::read_pajek(&b"*Vertices 2\n*Edges\n1 3\n"[..]).is_err());
+        assert!(NetworkData::read_pajek(&b"*Edges\n1 2\n"[..]).is_err());
+        assert!(NetworkData::read_pajek(&b"*Vertices 99999999999\n"[..]).is_err());
+    }
+
+    #[test]
+    fn petgraph_conversion() {
+        let mut graph = Graph::<&str, f64>::new();
+        let a = graph.add_node("a");
+        let b = graph.add_node("b");
+        let c = graph.add_node("c");
+        graph.extend_with_edges([(a, b, 1.0), (c, b, 2.0)]);
+        let net = NetworkData::from(&graph);
+        assert_eq!(net, NetworkData::from_edges(3, [(0, 1), (2, 1)]));
+
+        let back = UnGraph::from(&net);
+        assert_eq!(back.node_count(), 3);
+        assert_eq!(
+            NetworkData::from(&back).edges().collect::<Vec<_>>(),
+            [(0, 1), (1, 2)]
+        );
+    }
+}
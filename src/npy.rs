@@ -0,0 +1,184 @@
+//! NumPy `.npy` arrays and uncompressed `.npz` archives of them, so matrix outputs can be read
+//! with `np.load`. Arrays are little endian and in C (row major) order.
+use std::io::{self, Write};
+
+/// element types that can be stored in an array
+pub trait Element: Copy {
+    /// NumPy type descriptor
+    const DESCR: &'static str;
+    fn write_le<W: Write>(self, w: &mut W) -> io::Result<()>;
+}
+
+impl Element for f64 {
+    const DESCR: &'static str = "<f8";
+    fn write_le<W: Write>(self, w: &mut W) -> io::Result<()> {
+        w.write_all(&self.to_le_bytes())
+    }
+}
+
+impl Element for u64 {
+    const DESCR: &'static str = "<u8";
+    fn write_le<W: Write>(self, w: &mut W) -> io::Result<()> {
+        w.write_all(&self.to_le_bytes())
+    }
+}
+
+/// write `data` as a `.npy` array of the given `shape`
+pub fn write_npy<T: Element, W: Write>(mut w: W, shape: &[usize], data: &[T]) -> io::Result<()> {
+    if shape.iter().product::<usize>() != data.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} elements do not fit shape {:?}", data.len(), shape),
+        ));
+    }
+    let dims: Vec<String> = shape.iter().map(|d| d.to_string()).collect();
+    let shape = match dims.len() {
+        1 => format!("({},)", dims[0]),
+        _ => format!("({})", dims.join(", ")),
+    };
+    let mut header = format!(
+        "{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}",
+        T::DESCR,
+        shape
+    );
+    // magic, version and header length take 10 bytes; the data starts 64 byte aligned
+    header.push_str(&" ".repeat(63 - (10 + header.len()) % 64));
+    header.push('\n');
+
+    w.write_all(b"\x93NUMPY\x01\x00")?;
+    w.write_all(&(header.len() as u16).to_le_bytes())?;
+    w.write_all(header.as_bytes())?;
+    for &x in data {
+        x.write_le(&mut w)?;
+    }
+    w.flush()
+}
+
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+/// writes arrays into a `.npz` (zip) archive without compression
+pub struct NpzWriter<W: Write> {
+    w: W,
+    offset: usize,
+    /// name, crc and size of every array written, and the offset of its local header
+    entries: Vec<(String, u32, usize, usize)>,
+}
+
+impl<W: Write> NpzWriter<W> {
+    pub fn new(w: W) -> Self {
+        Self {
+            w,
+            offset: 0,
+            entries: Vec::new(),
+        }
+    }
+
+    /// add `data` of `shape` as the array `name`
+    pub fn add<T: Element>(&mut self, name: &str, shape: &[usize], data: &[T]) -> io::Result<()> {
+        let mut npy = Vec::new();
+        write_npy(&mut npy, shape, data)?;
+        if self.offset + npy.len() > u32::MAX as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "npz archives over 4 GiB are not supported",
+            ));
+        }
+        let name = format!("{}.npy", name);
+        let crc = crc32(&npy);
+
+        let mut header = Vec::new();
+        header.extend(0x0403_4b50u32.to_le_bytes());
+        header.extend(20u16.to_le_bytes()); // version needed
+        header.extend([0; 8]); // flags, method (stored), time, date
+        header.extend(crc.to_le_bytes());
+        header.extend((npy.len() as u32).to_le_bytes());
+        header.extend((npy.len() as u32).to_le_bytes());
+        header.extend((name.len() as u16).to_le_bytes());
+        header.extend(0u16.to_le_bytes()); // extra field length
+        header.extend(name.as_bytes());
+        self.w.write_all(&header)?;
+        self.w.write_all(&npy)?;
+
+        self.entries.push((name, crc, npy.len(), self.offset));
+        self.offset += header.len() + npy.len();
+        Ok(())
+    }
+
+    /// write the central directory
+    pub fn finish(mut self) -> io::Result<()> {
+        let mut directory = Vec::new();
+        for (name, crc, size, offset) in &self.entries {
+            directory.extend(0x0201_4b50u32.to_le_bytes());
+            directory.extend(20u16.to_le_bytes()); // version made by
+            directory.extend(20u16.to_le_bytes()); // version needed
+            directory.extend([0; 8]); // flags, method, time, date
+            directory.extend(crc.to_le_bytes());
+            directory.extend((*size as u32).to_le_bytes());
+            directory.extend((*size as u32).to_le_bytes());
+            directory.extend((name.len() as u16).to_le_bytes());
+            directory.extend([0; 12]); // extra, comment, disk, attributes
+            directory.extend((*offset as u32).to_le_bytes());
+            directory.extend(name.as_bytes());
+        }
+        let num_entries = (self.entries.len() as u16).to_le_bytes();
+        self.w.write_all(&directory)?;
+        self.w.write_all(&0x0605_4b50u32.to_le_bytes())?;
+        self.w.write_all(&[0; 4])?; // disk numbers
+        self.w.write_all(&num_entries)?;
+        self.w.write_all(&num_entries)?;
+        self.w.write_all(&(directory.len() as u32).to_le_bytes())?;
+        self.w.write_all(&(self.offset as u32).to_le_bytes())?;
+        self.w.write_all(&[0; 2])?; // comment length
+        self.w.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn npy_header() {
+        let mut buf = Vec::new();
+        write_npy(&mut buf, &[2, 3], &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]).unwrap();
+        assert_eq!(&buf[..8], b"\x93NUMPY\x01\x00");
+        let data = 10 + u16::from_le_bytes([buf[8], buf[9]]) as usize;
+        assert_eq!(data % 64, 0);
+        assert_eq!(buf.len(), data + 6 * 8);
+        let header = std::str::from_utf8(&buf[10..data]).unwrap();
+        assert!(header.starts_with("{'descr': '<f8', 'fortran_order': False, 'shape': (2, 3), }"));
+        assert!(header.ends_with(" \n"));
+        assert_eq!(buf[data..data + 8], 1.0f64.to_le_bytes());
+
+        let mut buf = Vec::new();
+        write_npy(&mut buf, &[2], &[7u64, 8]).unwrap();
+        assert!(std::str::from_utf8(&buf[10..128])
+            .unwrap()
+            .contains("'<u8', 'fortran_order': False, 'shape': (2,)"));
+        assert!(write_npy(&mut buf, &[3], &[1u64]).is_err());
+    }
+
+    #[test]
+    fn npz_archive() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        let mut buf = Vec::new();
+        let mut npz = NpzWriter::new(&mut buf);
+        npz.add("a", &[1], &[1.0]).unwrap();
+        npz.add("b", &[2], &[1u64, 2]).unwrap();
+        npz.finish().unwrap();
+        // two local headers with their arrays, two directory entries and the end record
+        let local = |name_len: usize, data_len: usize| 30 + name_len + 128 + data_len;
+        let directory = 2 * 46 + 2 * 5;
+        assert_eq!(buf.len(), local(5, 8) + local(5, 16) + directory + 22);
+        assert_eq!(&buf[buf.len() - 22..buf.len() - 18], b"PK\x05\x06");
+    }
+}
@@ -0,0 +1,427 @@
+//! output files, optionally compressed according to the `output_compression` parameter
+use crate::parameters::{OnExistingOutput, OutputCompression};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// `path` with the file extension of `compression` appended, e.g. `data_ll.txt.zst`
+pub fn compressed_path(path: &Path, compression: OutputCompression) -> PathBuf {
+    let mut p = path.as_os_str().to_owned();
+    match compression {
+        OutputCompression::None => {}
+        OutputCompression::Gzip => p.push(".gz"),
+        OutputCompression::Zstd => p.push(".zst"),
+    }
+    PathBuf::from(p)
+}
+
+/// a buffered output file written through its compressor. `finish` completes the compressed
+/// stream and reports any error; dropping the writer without calling it may lose data silently.
+pub struct OutputWriter(Encoder);
+
+enum Encoder {
+    Stdout(io::Stdout),
+    Plain(BufWriter<File>),
+    #[cfg(feature = "gzip")]
+    Gzip(BufWriter<flate2::write::GzEncoder<File>>),
+    #[cfg(feature = "zstd")]
+    Zstd(BufWriter<zstd::stream::write::Encoder<'static, File>>),
+}
+
+impl OutputWriter {
+    /// standard output, e.g. for a `-` path
+    pub fn stdout() -> Self {
+        Self(Encoder::Stdout(io::stdout()))
+    }
+
+    /// flush the buffer, finish the compressed stream and sync the file to disk
+    pub fn finish(self) -> io::Result<()> {
+        let file = match self.0 {
+            Encoder::Stdout(mut w) => return w.flush(),
+            Encoder::Plain(w) => w.into_inner()?,
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(w) => w.into_inner()?.finish()?,
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(w) => w.into_inner()?.finish()?,
+        };
+        file.sync_all()
+    }
+}
+
+impl Write for OutputWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.0 {
+            Encoder::Stdout(w) => w.write(buf),
+            Encoder::Plain(w) => w.write(buf),
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(w) => w.write(buf),
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.0 {
+            Encoder::Stdout(w) => w.flush(),
+            Encoder::Plain(w) => w.flush(),
+            #[cfg(feature = "gzip")]
+            Encoder::Gzip(w) => w.flush(),
+            #[cfg(feature = "zstd")]
+            Encoder::Zstd(w) => w.flush(),
+        }
+    }
+}
+
+/// create `compressed_path(path, compression)` for writing through the compressor. Callers
+/// must `finish` the writer.
+pub fn create(path: &Path, compression: OutputCompression) -> io::Result<OutputWriter> {
+    let encoder = match compression {
+        OutputCompression::None => Encoder::Plain(BufWriter::new(File::create(compressed_path(
+            path,
+            compression,
+        ))?)),
+        #[cfg(feature = "gzip")]
+        OutputCompression::Gzip => Encoder::Gzip(BufWriter::new(flate2::write::GzEncoder::new(
+            File::create(compressed_path(path, compression))?,
+            flate2::Compression::default(),
+        ))),
+        #[cfg(feature = "zstd")]
+        OutputCompression::Zstd => {
+            Encoder::Zstd(BufWriter::new(zstd::stream::write::Encoder::new(
+                File::create(compressed_path(path, compression))?,
+                0,
+            )?))
+        }
+        #[allow(unreachable_patterns)]
+        _ => return Err(unsupported(compression)),
+    };
+    Ok(OutputWriter(encoder))
+}
+
+fn unsupported(compression: OutputCompression) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!(
+            "{:?} output requires the corresponding feature",
+            compression
+        ),
+    )
+}
+
+/// write `compressed_path(path, compression)` by writing `{path}.tmp` through the compressor,
+/// finishing the compressed stream, syncing it to disk and renaming it. Readers see the old
+/// file or the complete new one, never a truncated one; on failure the temporary file is
+/// removed. `append`: the new file starts with the old one, if any. Concatenated gzip or zstd
+/// streams decompress to the concatenated contents.
+pub fn write_atomic(
+    path: &Path,
+    compression: OutputCompression,
+    append: bool,
+    write: impl FnOnce(&mut dyn Write) -> io::Result<()>,
+) -> io::Result<()> {
+    let path = compressed_path(path, compression);
+    let tmp = tmp_path(&path);
+    let result = (|| {
+        let mut file = File::create(&tmp)?;
+        if append && path.exists() {
+            io::copy(&mut File::open(&path)?, &mut file)?;
+        }
+        let file = match compression {
+            OutputCompression::None => {
+                let mut w = BufWriter::new(file);
+                write(&mut w)?;
+                w.into_inner()?
+            }
+            #[cfg(feature = "gzip")]
+            OutputCompression::Gzip => {
+                let mut w = BufWriter::new(flate2::write::GzEncoder::new(
+                    file,
+                    flate2::Compression::default(),
+                ));
+                write(&mut w)?;
+                w.into_inner()?.finish()?
+            }
+            #[cfg(feature = "zstd")]
+            OutputCompression::Zstd => {
+                let mut w = BufWriter::new(zstd::stream::write::Encoder::new(file, 0)?);
+                write(&mut w)?;
+                w.into_inner()?.finish()?
+            }
+            #[allow(unreachable_patterns)]
+            _ => return Err(unsupported(compression)),
+        };
+        file.sync_all()?;
+        fs::rename(&tmp, &path)
+    })();
+    result.map_err(|e| {
+        let _ = fs::remove_file(&tmp);
+        match e.kind() {
+            io::ErrorKind::StorageFull => io::Error::new(
+                e.kind(),
+                format!("no space left on device writing {}", path.display()),
+            ),
+            _ => e,
+        }
+    })
+}
+
+/// the files `{name}_{suffix}` in `dir` for each of `suffixes`, compressed or not, sorted
+pub fn existing_outputs(dir: &Path, name: &str, suffixes: &[String]) -> io::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for suffix in suffixes {
+        let path = dir.join(format!("{}_{}", name, suffix));
+        for compression in [
+            OutputCompression::None,
+            OutputCompression::Gzip,
+            OutputCompression::Zstd,
+        ] {
+            let path = compressed_path(&path, compression);
+            if path.is_file() {
+                paths.push(path);
+            }
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
+
+/// deal with the `existing_outputs` of `name` and `suffixes` in `dir` before a run as
+/// `policy`. Rotated files move to the new directory `{name}_{seconds since the epoch}`, or
+/// `{name}_{seconds}_{k}` for the `k`-th rotation within the same second.
+pub fn prepare_outputs(
+    dir: &Path,
+    name: &str,
+    suffixes: &[String],
+    policy: OnExistingOutput,
+) -> io::Result<()> {
+    let existing = existing_outputs(dir, name, suffixes)?;
+    if existing.is_empty() {
+        return Ok(());
+    }
+    match policy {
+        OnExistingOutput::Overwrite | OnExistingOutput::Append => Ok(()),
+        OnExistingOutput::Error => Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!(
+                "{} output files of {} exist, e.g. {}; set on_existing_output to overwrite, \
+                 append or rotate",
+                existing.len(),
+                name,
+                existing[0].display()
+            ),
+        )),
+        OnExistingOutput::Rotate => {
+            let secs = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs());
+            let mut rotated = dir.join(format!("{}_{}", name, secs));
+            let mut k = 0;
+            // creating the directory claims it, even against concurrent runs
+            while let Err(e) = fs::create_dir(&rotated) {
+                if e.kind() != io::ErrorKind::AlreadyExists {
+                    return Err(e);
+                }
+                k += 1;
+                rotated = dir.join(format!("{}_{}_{}", name, secs, k));
+            }
+            for path in existing {
+                // existing paths have a file name
+                fs::rename(&path, rotated.join(path.file_name().unwrap()))?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// `path` with `.tmp` appended
+fn tmp_path(path: &Path) -> PathBuf {
+    let mut p = path.as_os_str().to_owned();
+    p.push(".tmp");
+    PathBuf::from(p)
+}
+
+/// bytes available to unprivileged users on the file system of `dir`, `None` where unknown
+pub fn free_space(dir: &Path) -> Option<u64> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        let dir = std::ffi::CString::new(dir.as_os_str().as_bytes()).ok()?;
+        // SAFETY: `dir` is a valid C string and `stat` is written by `statvfs` only
+        let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+        if unsafe { libc::statvfs(dir.as_ptr(), &mut stat) } != 0 {
+            return None;
+        }
+        Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = dir;
+        None
+    }
+}
+
+/// fails if the file system of `dir` is known to have less than `needed` bytes available
+pub fn check_free_space(dir: &Path, needed: u64) -> io::Result<()> {
+    match free_space(dir) {
+        Some(free) if free < needed => Err(io::Error::new(
+            io::ErrorKind::StorageFull,
+            format!(
+                "{} needs about {} MB, but only {} MB are free",
+                dir.display(),
+                needed.div_ceil(1 << 20),
+                free / (1 << 20)
+            ),
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// open `path` for reading, decompressing files ending in `.gz` or `.zst`. Compressed files
+/// are an error when the corresponding feature is off.
+pub fn open(path: &Path) -> io::Result<Box<dyn Read>> {
+    let compression = match path.extension().and_then(|e| e.to_str()) {
+        Some("gz") => OutputCompression::Gzip,
+        Some("zst") => OutputCompression::Zstd,
+        _ => OutputCompression::None,
+    };
+    let file = File::open(path)?;
+    Ok(match compression {
+        OutputCompression::None => Box::new(file),
+        #[cfg(feature = "gzip")]
+        OutputCompression::Gzip => Box::new(flate2::read::GzDecoder::new(file)),
+        #[cfg(feature = "zstd")]
+        OutputCompression::Zstd => Box::new(zstd::stream::read::Decoder::new(file)?),
+        #[allow(unreachable_patterns)]
+        _ => {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!(
+                    "reading {} requires the {:?} feature",
+                    path.display(),
+                    compression
+                ),
+            ))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extension() {
+        let path = Path::new("out/data_ll.txt");
+        assert_eq!(compressed_path(path, OutputCompression::None), path);
+        assert_eq!(
+            compressed_path(path, OutputCompression::Zstd),
+            Path::new("out/data_ll.txt.zst")
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let path = std::env::temp_dir().join("hcp_rs_output_round_trip.txt");
+        let mut w = create(&path, OutputCompression::None).unwrap();
+        w.write_all(b"1 2 3\n").unwrap();
+        w.finish().unwrap();
+        let mut s = String::new();
+        open(&path).unwrap().read_to_string(&mut s).unwrap();
+        assert_eq!(s, "1 2 3\n");
+        std::fs::remove_file(&path).unwrap();
+
+        for (compression, feature) in [
+            (OutputCompression::Gzip, cfg!(feature = "gzip")),
+            (OutputCompression::Zstd, cfg!(feature = "zstd")),
+        ] {
+            let compressed = compressed_path(&path, compression);
+            if feature {
+                let mut w = create(&path, compression).unwrap();
+                w.write_all(b"4 5 6\n").unwrap();
+                w.finish().unwrap();
+                let mut s = String::new();
+                open(&compressed).unwrap().read_to_string(&mut s).unwrap();
+                assert_eq!(s, "4 5 6\n");
+            } else {
+                assert!(create(&path, compression).is_err());
+                std::fs::write(&compressed, b"4 5 6\n").unwrap();
+                let e = open(&compressed).err().unwrap();
+                assert_eq!(e.kind(), io::ErrorKind::Unsupported);
+            }
+            std::fs::remove_file(compressed).unwrap();
+        }
+    }
+
+    #[test]
+    fn atomic() {
+        let path = std::env::temp_dir().join("hcp_rs_output_atomic.txt");
+        write_atomic(&path, OutputCompression::None, false, |w| {
+            w.write_all(b"old\n")
+        })
+        .unwrap();
+        let failed = write_atomic(&path, OutputCompression::None, false, |w| {
+            w.write_all(b"new, but")?;
+            Err(io::Error::other("interrupted"))
+        });
+        assert!(failed.is_err());
+        assert_eq!(std::fs::read(&path).unwrap(), b"old\n");
+        assert!(!tmp_path(&path).exists());
+        write_atomic(&path, OutputCompression::None, true, |w| {
+            w.write_all(b"new\n")
+        })
+        .unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), b"old\nnew\n");
+        std::fs::remove_file(path).unwrap();
+
+        assert!(check_free_space(&std::env::temp_dir(), 0).is_ok());
+        assert!(check_free_space(&std::env::temp_dir(), u64::MAX).is_err());
+    }
+
+    #[test]
+    fn existing() {
+        let dir = std::env::temp_dir().join("hcp_rs_output_existing");
+        let _ = std::fs::remove_dir_all(&dir);
+        let suffixes = ["ll.txt", "best.txt"].map(String::from);
+        assert!(prepare_outputs(&dir, "run", &suffixes, OnExistingOutput::Error).is_ok());
+        std::fs::create_dir_all(&dir).unwrap();
+        let files = [
+            "run_ll.txt",
+            "run_best.txt.gz",
+            "other_ll.txt",
+            "run_notes.txt",
+            "run_2_ll.txt",
+        ];
+        for file in files {
+            std::fs::write(dir.join(file), "").unwrap();
+        }
+        let existing = existing_outputs(&dir, "run", &suffixes).unwrap();
+        assert_eq!(
+            existing,
+            [dir.join("run_best.txt.gz"), dir.join("run_ll.txt")]
+        );
+        assert!(prepare_outputs(&dir, "run", &suffixes, OnExistingOutput::Overwrite).is_ok());
+        assert!(prepare_outputs(&dir, "run", &suffixes, OnExistingOutput::Error).is_err());
+
+        // two rotations in the same second go to different directories
+        let rotated = |dir: &Path| -> Vec<PathBuf> {
+            std::fs::read_dir(dir)
+                .unwrap()
+                .map(|e| e.unwrap().path())
+                .filter(|p| p.is_dir())
+                .collect()
+        };
+        prepare_outputs(&dir, "run", &suffixes, OnExistingOutput::Rotate).unwrap();
+        assert!(existing_outputs(&dir, "run", &suffixes).unwrap().is_empty());
+        std::fs::write(dir.join("run_ll.txt"), "second").unwrap();
+        prepare_outputs(&dir, "run", &suffixes, OnExistingOutput::Rotate).unwrap();
+        let mut rotated = rotated(&dir);
+        rotated.sort();
+        assert_eq!(rotated.len(), 2, "{:?}", rotated);
+        assert!(rotated.iter().all(|r| r.join("run_ll.txt").exists()));
+        for file in &files[2..] {
+            assert!(dir.join(file).exists());
+        }
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,206 @@
+//! population-based sampling: several chains at the same temperature run in parallel and
+//! periodically report their best configuration to a coordinator, which has the worse half
+//! of the chains continue from the overall best one.
+use crate::multi_group_model::Groups;
+use crate::network::NetworkData;
+use crate::parameters::Parameters;
+use crate::seeds::{SeedStream, Stream};
+use crate::HierarchicalModel;
+use crossbeam_channel::{bounded, unbounded};
+use std::sync::Arc;
+use std::thread;
+
+/// best configuration seen by a chain: log-likelihood, groups, number of groups
+type Best = (f64, Vec<Groups>, usize);
+
+enum Instruction {
+    Continue,
+    Adopt(Arc<Best>),
+}
+
+/// run one chain, reporting its best configuration every `interval` iterations
+fn chain(
+    params: Parameters,
+    network: Arc<NetworkData>,
+    interval: u64,
+    report: impl Fn(Result<Best, String>),
+    instructions: impl Fn() -> Option<Instruction>,
+) {
+    let mut hcp = match HierarchicalModel::with_network(&params, network) {
+        Ok(hcp) => hcp,
+        Err(e) => return report(Err(e)),
+    };
+    let current = |hcp: &HierarchicalModel| {
+        (
+            hcp.log_like(),
+            hcp.model().groups().to_vec(),
+            hcp.model().num_groups(),
+        )
+    };
+    let mut done = 0;
+    while done < params.max_itr {
+        let mut best = current(&hcp);
+        for i in done..done + interval.min(params.max_itr - done) {
+            hcp.set_group_move_weight(params.move_schedule.weight(i));
+            if hcp.get_groups().is_some() && hcp.log_like() > best.0 {
+                best = current(&hcp);
+            }
+        }
+        done += interval;
+        report(Ok(best));
+        match instructions() {
+            Some(Instruction::Continue) => {}
+            Some(Instruction::Adopt(best)) => hcp.set_configuration(best.1.clone(), best.2),
+            None => return,
+        }
+    }
+}
+
+/// run `params.num_chains` chains of `params.max_itr` iterations, sharing configurations
+/// every `params.exchange_interval` iterations (0: only at the end).
+/// Chain `i` is seeded with the `Stream::Chain` sub-seed `i` of `seeds`.
+/// Returns a model in the best configuration found by any chain.
+pub fn run_chains(
+    params: &Parameters,
+    network: Arc<NetworkData>,
+    seeds: &mut SeedStream,
+) -> Result<HierarchicalModel, String> {
+    let num_chains = params.num_chains.max(1);
+    let interval = match params.exchange_interval {
+        0 => params.max_itr.max(1),
+        n => n,
+    };
+    let num_rounds = params.max_itr.div_ceil(interval);
+
+    let (report_tx, report_rx) = unbounded();
+    let mut instruction_txs = Vec::new();
+    let mut best: Option<Arc<Best>> = None;
+    let mut error = None;
+    thread::scope(|scope| {
+        for i in 0..num_chains {
+            let (instruction_tx, instruction_rx) = bounded(1);
+            instruction_txs.push(instruction_tx);
+            let report_tx = report_tx.clone();
+            let chain_params = Parameters {
+                seed: Some(seeds.derive(Stream::Chain, i as u64)),
+                ..params.clone()
+            };
+            let network = network.clone();
+            scope.spawn(move || {
+                chain(
+                    chain_params,
+                    network,
+                    interval,
+                    |r| {
+                        let _ = report_tx.send((i, r));
+                    },
+                    || instruction_rx.recv().ok(),
+                )
+            });
+        }
+        drop(report_tx);
+
+        for _ in 0..num_rounds {
+            let mut reports = Vec::with_capacity(num_chains);
+            for _ in 0..num_chains {
+                match report_rx.recv() {
+                    Ok((i, Ok(b))) => reports.push((i, b)),
+                    Ok((_, Err(e))) => error = Some(e),
+                    Err(_) => break,
+                }
+            }
+            if error.is_some() || reports.len() < num_chains {
+                break;
+            }
+            reports.sort_by(|a, b| b.1 .0.total_cmp(&a.1 .0));
+            let round_best = Arc::new(reports[0].1.clone());
+            if best.as_ref().is_none_or(|b| round_best.0 > b.0) {
+                best = Some(round_best.clone());
+            }
+            for (rank, (i, _)) in reports.iter().enumerate() {
+                let instruction = if rank < num_chains.div_ceil(2) {
+                    Instruction::Continue
+                } else {
+                    Instruction::Adopt(round_best.clone())
+                };
+                let _ = instruction_txs[*i].send(instruction);
+            }
+        }
+        // chains waiting for instructions stop once their channel is closed
+        instruction_txs.clear();
+    });
+
+    if let Some(e) = error {
+        return Err(e);
+    }
+    let (_, groups, num_groups) = best
+        .ok_or(String::from("no chain finished"))?
+        .as_ref()
+        .clone();
+    HierarchicalModel::with_network(
+        &params.with_initial_config(groups, num_groups as u32),
+        network,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::_example_parameters;
+
+    #[test]
+    fn concurrent_runs() {
+        // the chains of both runs build their models, and ln-factorial tables of different
+        // sizes, at the same time
+        let params = Parameters {
+            num_chains: 4,
+            exchange_interval: 250,
+            max_itr: 1000,
+            .._example_parameters(b"").unwrap()
+        };
+        let small = Arc::new(NetworkData::load(&params).unwrap());
+        let large = Arc::new(NetworkData::from_edges(400, (0..399).map(|u| (u, u + 1))));
+        let results = thread::scope(|s| {
+            let runs = [(small, 1), (large, 2)].map(|(network, seed)| {
+                let params = &params;
+                s.spawn(move || run_chains(params, network, &mut SeedStream::new(seed)))
+            });
+            runs.map(|run| run.join().unwrap().unwrap())
+        });
+        for mut hcp in results {
+            assert!(hcp.recompute().is_consistent());
+        }
+    }
+
+    #[test]
+    fn move_schedule() {
+        // without group additions no chain finds more groups than it started with
+        let params = Parameters {
+            num_chains: 2,
+            exchange_interval: 250,
+            max_itr: 1000,
+            .._example_parameters(b"move_schedule: 0 0\n").unwrap()
+        };
+        let network = Arc::new(NetworkData::load(&params).unwrap());
+        let hcp = run_chains(&params, network, &mut SeedStream::new(1)).unwrap();
+        assert!(hcp.model().num_groups() <= params.initial_num_groups as usize);
+    }
+
+    #[test]
+    fn init_method() {
+        // the best configuration replaces the initialization in the final model
+        let params = Parameters {
+            num_chains: 2,
+            exchange_interval: 250,
+            max_itr: 500,
+            .._example_parameters(
+                b"init_method: label_propagation
+",
+            )
+            .unwrap()
+        };
+        let network = Arc::new(NetworkData::load(&params).unwrap());
+        let mut hcp = run_chains(&params, network, &mut SeedStream::new(1)).unwrap();
+        assert!(hcp.recompute().is_consistent());
+    }
+}
@@ -1,3 +1,5 @@
+use crate::core::likelihood::LikelihoodConfig;
+pub use crate::core::likelihood::LnFactOverflow;
 use std::collections::HashMap;
 use std::env;
 use std::io::Read;
@@ -6,16 +8,541 @@ use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::time;
 
-#[derive(Debug)]
+/// what happens to groups without members
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EmptyGroupPolicy {
+    /// removed when picked for a node removal move (the behavior of the C++ version)
+    #[default]
+    OnSelection,
+    /// never removed, the chain can grow back into them
+    Keep,
+    /// removed as soon as a node move leaves them empty. Groups added by a move stay until
+    /// they have had members and lost them again.
+    Prune,
+    /// removed once they have been empty for more than the given number of iterations
+    Lazy(u64),
+}
+
+impl FromStr for EmptyGroupPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_whitespace().collect::<Vec<_>>()[..] {
+            ["on_selection"] => Ok(Self::OnSelection),
+            ["keep"] => Ok(Self::Keep),
+            ["prune"] => Ok(Self::Prune),
+            ["lazy", k] => k
+                .parse()
+                .map(Self::Lazy)
+                .or(Err(format!("not an integer: {}", k))),
+            _ => Err(format!("unknown empty group policy: {}", s)),
+        }
+    }
+}
+
+/// how floating point values are written to the output files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FloatFormat {
+    /// shortest representation that reads back to the same value
+    #[default]
+    Shortest,
+    /// the given number of decimal places
+    Fixed(usize),
+    /// scientific notation with the given number of decimal places in the mantissa
+    Scientific(usize),
+}
+
+impl FloatFormat {
+    /// `x` formatted according to `self`
+    pub fn display(self, x: f64) -> FormattedFloat {
+        FormattedFloat(x, self)
+    }
+}
+
+/// a float with its output format, see `FloatFormat::display`
+#[derive(Debug, Clone, Copy)]
+pub struct FormattedFloat(f64, FloatFormat);
+
+impl std::fmt::Display for FormattedFloat {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.1 {
+            FloatFormat::Shortest => write!(f, "{}", self.0),
+            FloatFormat::Fixed(p) => write!(f, "{:.*}", p, self.0),
+            FloatFormat::Scientific(p) => write!(f, "{:.*e}", p, self.0),
+        }
+    }
+}
+
+impl FromStr for FloatFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let precision = |p: &str| p.parse().or(Err(format!("not an integer: {}", p)));
+        match s.split_whitespace().collect::<Vec<_>>()[..] {
+            ["shortest"] => Ok(Self::Shortest),
+            ["fixed", p] => precision(p).map(Self::Fixed),
+            ["scientific", p] => precision(p).map(Self::Scientific),
+            _ => Err(format!("unknown float format: {}", s)),
+        }
+    }
+}
+
+/// how the initial configuration is chosen when none is given
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InitMethod {
+    /// every node in a random subset of `initial_num_groups` groups
+    #[default]
+    Random,
+    /// one group per community found by the Louvain method
+    Louvain,
+    /// one group per community found by label propagation
+    LabelPropagation,
+}
+
+impl FromStr for InitMethod {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "random" => Ok(Self::Random),
+            "louvain" => Ok(Self::Louvain),
+            "label_propagation" => Ok(Self::LabelPropagation),
+            _ => Err(format!("unknown init method: {}", s)),
+        }
+    }
+}
+
+/// what cross-validation hides from the fit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum HoldoutScheme {
+    /// every edge of `holdout_fraction` of the nodes
+    #[default]
+    Nodes,
+    /// `holdout_fraction` of the edges, uniformly
+    Edges,
+    /// about `holdout_fraction` of the edges of every node, leaving each node at least one
+    StratifiedEdges,
+}
+
+impl FromStr for HoldoutScheme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "nodes" => Ok(Self::Nodes),
+            "edges" => Ok(Self::Edges),
+            "stratified_edges" => Ok(Self::StratifiedEdges),
+            _ => Err(format!("unknown holdout scheme: {}", s)),
+        }
+    }
+}
+
+/// what happens to nodes without edges, which add pairs but no edges to the root group
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IsolatedNodes {
+    /// keep them and report their number
+    #[default]
+    Keep,
+    /// remove them when the network is loaded; the other nodes are renumbered and labeled
+    /// with their original ids, unless they have labels
+    Drop,
+}
+
+impl FromStr for IsolatedNodes {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(Self::Keep),
+            "drop" => Ok(Self::Drop),
+            _ => Err(format!("unknown isolated nodes handling: {}", s)),
+        }
+    }
+}
+
+/// what happens when no move is accepted for `stall_window` iterations
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum StallAction {
+    /// print a warning and continue
+    #[default]
+    Warn,
+    /// print a warning and end the run, writing the outputs as usual
+    Stop,
+    /// print a warning and sample at the given inverse temperature for one window
+    Reheat(f64),
+}
+
+impl FromStr for StallAction {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_whitespace().collect::<Vec<_>>()[..] {
+            ["warn"] => Ok(Self::Warn),
+            ["stop"] => Ok(Self::Stop),
+            ["reheat", beta] => beta
+                .parse()
+                .map(Self::Reheat)
+                .or(Err(format!("not a number: {}", beta))),
+            _ => Err(format!("unknown stall action: {}", s)),
+        }
+    }
+}
+
+impl FromStr for LnFactOverflow {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lgamma" => Ok(Self::Lgamma),
+            "error" => Ok(Self::Error),
+            _ => Err(format!("unknown ln_fact_overflow: {}", s)),
+        }
+    }
+}
+
+/// file format of the network at `gml_path`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NetworkFormat {
+    /// Matrix Market for `.mtx` files, Pajek for `.net` files, GML otherwise
+    #[default]
+    Auto,
+    Gml,
+    /// Matrix Market coordinate format
+    Mtx,
+    Pajek,
+}
+
+impl FromStr for NetworkFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "gml" => Ok(Self::Gml),
+            "mtx" => Ok(Self::Mtx),
+            "pajek" => Ok(Self::Pajek),
+            _ => Err(format!("unknown network format: {}", s)),
+        }
+    }
+}
+
+/// compression of the output files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputCompression {
+    #[default]
+    None,
+    /// `.gz` files, requires the `gzip` feature
+    Gzip,
+    /// `.zst` files, requires the `zstd` feature
+    Zstd,
+}
+
+impl FromStr for OutputCompression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            #[cfg(feature = "gzip")]
+            "gzip" => Ok(Self::Gzip),
+            #[cfg(feature = "zstd")]
+            "zstd" => Ok(Self::Zstd),
+            "gzip" | "zstd" => Err(format!("{} output requires the `{}` feature", s, s)),
+            _ => Err(format!("unknown output compression: {}", s)),
+        }
+    }
+}
+
+/// what a run does with the output files of an earlier run with the same `saved_data_name`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnExistingOutput {
+    /// refuse to start
+    Error,
+    #[default]
+    Overwrite,
+    /// append the snapshots to the log files, overwrite the other files
+    Append,
+    /// move the files to the subdirectory `{name}_{unix time}` first
+    Rotate,
+}
+
+impl FromStr for OnExistingOutput {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(Self::Error),
+            "overwrite" => Ok(Self::Overwrite),
+            "append" => Ok(Self::Append),
+            "rotate" => Ok(Self::Rotate),
+            _ => Err(format!("unknown handling of existing output: {}", s)),
+        }
+    }
+}
+
+/// piecewise constant weight of group addition proposals over the iterations, relative to the
+/// fixed mix of the C++ version. Chains only sample the posterior while the weight is 1.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MoveSchedule {
+    /// iterations from which each weight applies, in increasing order
+    steps: Vec<(u64, f64)>,
+}
+
+impl MoveSchedule {
+    /// group move weight at `iteration`, 1 before the first step
+    pub fn weight(&self, iteration: u64) -> f64 {
+        match self.steps.partition_point(|&(i, _)| i <= iteration) {
+            0 => 1.0,
+            k => self.steps[k - 1].1,
+        }
+    }
+}
+
+impl FromStr for MoveSchedule {
+    type Err = String;
+
+    /// `<iteration> <weight>` pairs, e.g. `0 10 100000 1`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        if !words.len().is_multiple_of(2) {
+            return Err(format!("not iteration weight pairs: {}", s));
+        }
+        let steps = words
+            .chunks(2)
+            .map(|pair| {
+                let i = pair[0]
+                    .parse()
+                    .or(Err(format!("not an integer: {}", pair[0])))?;
+                let w: f64 = pair[1]
+                    .parse()
+                    .or(Err(format!("not a number: {}", pair[1])))?;
+                if !(w >= 0.0 && w.is_finite()) {
+                    return Err(format!("not a valid weight: {}", pair[1]));
+                }
+                Ok((i, w))
+            })
+            .collect::<Result<Vec<(u64, f64)>, String>>()?;
+        if steps.windows(2).any(|w| w[0].0 >= w[1].0) {
+            return Err(format!("iterations are not increasing: {}", s));
+        }
+        Ok(Self { steps })
+    }
+}
+
+/// statistics of `custom_stats::BUILTIN` and the iterations between their evaluations, recorded
+/// by a `StatAccumulator` independently of snapshots
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct CustomStats(pub Vec<(String, u64)>);
+
+impl FromStr for CustomStats {
+    type Err = String;
+
+    /// `<stat> <interval>` pairs, e.g. `root_density 100 num_groups 10`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = s.split_whitespace().collect();
+        if !words.len().is_multiple_of(2) {
+            return Err(format!("not stat interval pairs: {}", s));
+        }
+        let stats = words
+            .chunks(2)
+            .map(|pair| {
+                if !crate::custom_stats::BUILTIN.contains(&pair[0]) {
+                    return Err(format!("unknown statistic: {}", pair[0]));
+                }
+                let interval = pair[1]
+                    .parse()
+                    .or(Err(format!("not an integer: {}", pair[1])))?;
+                Ok((pair[0].to_string(), interval))
+            })
+            .collect::<Result<Vec<(String, u64)>, String>>()?;
+        if let Some((name, _)) = stats
+            .iter()
+            .find(|&(name, _)| stats.iter().filter(|(other, _)| other == name).count() > 1)
+        {
+            return Err(format!("statistic {} is listed twice", name));
+        }
+        Ok(Self(stats))
+    }
+}
+
+/// quantities saved for every snapshot, named by their file suffix
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LogFields {
+    pub configs: bool,
+    pub num_groups: bool,
+    pub group_size: bool,
+    pub edges: bool,
+    pub pairs: bool,
+    pub ll: bool,
+    /// label-switching invariant statistics, see `HierarchicalModel::trace_stats`. Off by
+    /// default.
+    pub stats: bool,
+    /// partition entropy, see `HierarchicalModel::partition_entropy`. Off by default.
+    pub entropy: bool,
+    /// description length, see `HierarchicalModel::description_length`. Off by default.
+    pub dl: bool,
+    /// edge density of every group in single precision, a compact alternative to `edges` and
+    /// `pairs`. Off by default.
+    pub density: bool,
+    /// nodes whose deepest group changed since the previous snapshot, see
+    /// `MultiGroupModel::deepest_groups`. Groups are followed through the renumbering by added
+    /// and removed groups in between, so nodes of a renumbered group do not appear as
+    /// changed. Off by default.
+    pub changes: bool,
+    /// accepted and proposed moves of each kind since the previous snapshot, see `MoveCounts`.
+    /// Off by default.
+    pub move_counts: bool,
+}
+
+impl Default for LogFields {
+    fn default() -> Self {
+        Self {
+            configs: true,
+            num_groups: true,
+            group_size: true,
+            edges: true,
+            pairs: true,
+            ll: true,
+            stats: false,
+            entropy: false,
+            dl: false,
+            density: false,
+            changes: false,
+            move_counts: false,
+        }
+    }
+}
+
+impl LogFields {
+    /// the names of the saved quantities, which are also their file suffixes
+    pub fn names(&self) -> Vec<&'static str> {
+        [
+            (self.configs, "configs"),
+            (self.num_groups, "num_groups"),
+            (self.group_size, "group_size"),
+            (self.edges, "edges"),
+            (self.pairs, "pairs"),
+            (self.ll, "ll"),
+            (self.stats, "stats"),
+            (self.entropy, "entropy"),
+            (self.dl, "dl"),
+            (self.density, "density"),
+            (self.changes, "changes"),
+            (self.move_counts, "move_counts"),
+        ]
+        .into_iter()
+        .filter_map(|(enabled, name)| enabled.then_some(name))
+        .collect()
+    }
+}
+
+impl FromStr for LogFields {
+    type Err = String;
+
+    /// whitespace separated field names, e.g. `ll num_groups configs`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut fields = Self {
+            configs: false,
+            num_groups: false,
+            group_size: false,
+            edges: false,
+            pairs: false,
+            ll: false,
+            stats: false,
+            entropy: false,
+            dl: false,
+            density: false,
+            changes: false,
+            move_counts: false,
+        };
+        for name in s.split_whitespace() {
+            *match name {
+                "configs" => &mut fields.configs,
+                "num_groups" => &mut fields.num_groups,
+                "group_size" => &mut fields.group_size,
+                "edges" => &mut fields.edges,
+                "pairs" => &mut fields.pairs,
+                "ll" => &mut fields.ll,
+                "stats" => &mut fields.stats,
+                "entropy" => &mut fields.entropy,
+                "dl" => &mut fields.dl,
+                "density" => &mut fields.density,
+                "changes" => &mut fields.changes,
+                "move_counts" => &mut fields.move_counts,
+                _ => return Err(format!("unknown log field: {}", name)),
+            } = true;
+        }
+        Ok(fields)
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Parameters {
-    pub gml_path: PathBuf,                      // path to gml file
-    pub max_itr: u64,                           // maximum number of monte carlo steps
-    pub seed: Option<u64>,                      // random number generator seed
+    pub gml_path: Option<PathBuf>,         // path to network file
+    pub network_format: NetworkFormat,     // auto, gml, mtx or pajek
+    pub isolated_nodes: IsolatedNodes,     // keep or drop nodes without edges
+    pub node_subset_path: Option<PathBuf>, // fit the subgraph induced by these nodes
+    pub max_itr: u64,                      // maximum number of monte carlo steps
+    pub seed: Option<u64>,                 // random number generator seed
+    /// run once per seed if more than one, see `per_seed`
+    pub seeds: Vec<u64>,
     pub max_num_groups: u32,                    // maximum number of groups
     pub initial_num_groups: u32,                // number of groups to initialize simulation with
     pub initial_group_config: Option<Vec<u64>>, // group configuration to initialize simulation with
-    pub saved_data_name: String,                // name to prepend saved data files with
-    pub save_directory: PathBuf,                // location where data will be saved to
+    /// same as 0/1 rows, one column per group
+    pub initial_group_config_path: Option<PathBuf>,
+    pub init_from_partition_path: Option<PathBuf>, // community label per node, one group each
+    pub init_method: InitMethod,                   // random, louvain or label_propagation
+    pub require_nested: bool, // reject initial configurations with overlapping groups
+    pub saved_data_name: String, // name to prepend saved data files with
+    pub save_directory: PathBuf, // location where data will be saved to
+    pub save_moves: bool,     // also save initial config + accepted moves
+    pub save_best: bool,      // save the best configuration found to {name}_best.txt
+    pub save_hierarchy: bool, // save the final group hierarchy to {name}_hierarchy.csv
+    pub save_labels: bool,    // save the node labels, if any, to {name}_labels.txt
+    pub save_core_periphery: bool, // save the core-periphery table of the best configuration
+    pub save_nodes: bool,     // save the node attribute table of the best configuration
+    pub trace_moves: bool,    // record every proposal in a binary trace
+    pub check_drift: bool,    // report log-likelihood drift in status output
+    pub state_hash_interval: u64, // iterations between lines of {name}_state_hash.txt, 0: never
+    pub recompute_interval: u64, // recompute caches every n iterations, 0: never
+    pub empty_group_policy: EmptyGroupPolicy, // when to remove empty groups
+    pub min_group_size: usize, // non-root groups are never shrunk below this
+    pub sample_nodes_path: Option<PathBuf>, // only move these nodes, freeze all others
+    pub coarse_num_groups: u32, // start with coarse-to-fine fitting if > 0
+    /// heuristic initializer if > 0: first fit the network of supernodes of nodes overlapping
+    /// this much, unweighted, and start from its projection
+    pub coarsen_overlap: f64,
+    pub num_chains: usize, // start with this many chains in parallel if > 1
+    pub exchange_interval: u64, // iterations between sharing best configurations
+    pub save_timestamps: bool, // also save the wall-clock time of each snapshot
+    pub snapshot_json_path: Option<PathBuf>, // stream snapshots as JSON Lines, "-": stdout
+    pub quiet: bool,       // library code prints nothing to stdout
+    pub log_fields: LogFields, // quantities saved for every snapshot
+    pub adaptive_thinning: bool, // one snapshot per autocorrelation time
+    pub holdout_fraction: f64, // fraction of nodes or edges hidden in cross-validation
+    pub holdout: HoldoutScheme, // nodes, edges or stratified_edges
+    pub model_select_groups: Option<Vec<u32>>, // group budgets compared by model_select
+    pub predict_pairs_path: Option<PathBuf>, // node pairs to record edge probabilities of
+    pub credible_level: f64, // probability mass of reported credible intervals
+    pub float_format: FloatFormat, // shortest, fixed <places> or scientific <places>
+    pub output_compression: OutputCompression, // none, gzip or zstd
+    pub on_existing_output: OnExistingOutput, // error, overwrite, append or rotate
+    pub results_db: bool,  // also record run and snapshots in results.db
+    pub arrow_output: bool, // also save the trace as {name}_trace.arrow
+    pub checkpoint_interval: u64, // iterations between checkpoints, 0: never
+    pub density_prior_a: f64, // Beta(a, b) prior on group edge densities
+    pub density_prior_b: f64, // a = b = 1: uniform
+    pub assortative: bool, // reject moves making children sparser than parents
+    pub permute_nodes: bool, // list nodes in a random order instead of by id
+    pub move_schedule: MoveSchedule, // <iteration> <group move weight> pairs
+    pub stall_window: u64, // iterations without accepted move until stall_action, 0: off
+    pub stall_action: StallAction, // warn, stop or reheat <beta>
+    pub ln_fact_table_max_mb: usize, // memory limit of the ln-factorial table
+    pub ln_fact_overflow: LnFactOverflow, // lgamma or error when over the limit
+    pub batch_moves: usize, // experimental: independent node moves per step if > 1
+    pub batch_threads: usize, // threads evaluating the moves of a batch
+    pub custom_stats: CustomStats, // <stat> <interval> pairs, saved as {name}_custom_{stat}.txt
 }
 
 fn _get_int<T: FromStr>(m: &HashMap<String, String>, key: &str, default: T) -> Result<T, String> {
@@ -33,26 +560,89 @@ fn _get_ints<T: FromStr>(m: &HashMap<String, String>, key: &str) -> Result<Optio
     })
 }
 
+fn _get_float(m: &HashMap<String, String>, key: &str, default: f64) -> Result<f64, String> {
+    m.get(key).map_or(Ok(default), |s| {
+        s.parse().or(Err(format!("not a number: {}", s)))
+    })
+}
+
+fn _get_bool(m: &HashMap<String, String>, key: &str, default: bool) -> Result<bool, String> {
+    m.get(key)
+        .map_or(Ok(default), |s| match s.to_lowercase().as_str() {
+            "true" | "yes" | "1" => Ok(true),
+            "false" | "no" | "0" => Ok(false),
+            _ => Err(format!("not a boolean: {}", s)),
+        })
+}
+
 impl Parameters {
     pub fn load(src: impl Read) -> Result<Self, String> {
+        // `key: value` or `key = value` lines; blank lines and `#` comments are skipped
         let map = BufReader::new(src)
             .lines()
+            .filter(|l| {
+                l.as_ref()
+                    .map_or(true, |l| !l.trim().is_empty() && !l.trim().starts_with('#'))
+            })
             .map(|l| {
-                l.expect("I/O error")
-                    .split_once(":")
-                    .ok_or(String::from("Malformed parameters file: missing ':'"))
+                l.map_err(|e| e.to_string())?
+                    .split_once([':', '='])
+                    .ok_or(String::from(
+                        "Malformed parameters file: missing ':' or '='",
+                    ))
                     .map(|(k, v)| (k.trim().to_lowercase(), v.trim().to_owned()))
             })
             .collect::<Result<HashMap<_, _>, String>>()?;
-        Ok(Self {
-            gml_path: PathBuf::from(
-                map.get("gml_path")
-                    .ok_or("Missing required parameter 'gml_path'")?,
-            ),
+        // `seed: 1 2 3` or `seeds: 1 2 3` for several runs
+        let seeds: Vec<u64> = match (_get_ints(&map, "seed")?, _get_ints(&map, "seeds")?) {
+            (Some(_), Some(_)) => return Err(String::from("seed and seeds are exclusive")),
+            (seed, seeds) => seeds.or(seed).unwrap_or_default(),
+        };
+        if let Some(i) = (1..seeds.len()).find(|&i| seeds[..i].contains(&seeds[i])) {
+            return Err(format!(
+                "seed {} is listed twice, its runs would write the same outputs",
+                seeds[i]
+            ));
+        }
+        let initial_group_config: Option<Vec<u64>> = _get_ints(&map, "initial_group_config")?;
+        let mut initial_num_groups = _get_int(&map, "initial_num_groups", 2)?;
+        if let Some(config) = &initial_group_config {
+            // one group past the highest group of any node
+            let num_groups = config
+                .iter()
+                .map(|&g| 64 - g.leading_zeros())
+                .max()
+                .unwrap_or(0)
+                .max(1);
+            if !map.contains_key("initial_num_groups") {
+                initial_num_groups = num_groups;
+            } else if initial_num_groups < num_groups {
+                // more groups are fine, they start empty
+                return Err(format!(
+                    "initial_num_groups is {}, but initial_group_config has {} groups",
+                    initial_num_groups, num_groups
+                ));
+            }
+        }
+        Self {
+            gml_path: map.get("gml_path").map(PathBuf::from),
+            network_format: map
+                .get("network_format")
+                .map_or(Ok(NetworkFormat::default()), |s| s.parse())?,
+            isolated_nodes: map
+                .get("isolated_nodes")
+                .map_or(Ok(IsolatedNodes::default()), |s| s.parse())?,
+            node_subset_path: map.get("node_subset_path").map(PathBuf::from),
             max_itr: _get_int(&map, "max_itr", 1000000000)?,
             max_num_groups: _get_int(&map, "max_num_groups", 64)?,
-            initial_num_groups: _get_int(&map, "initial_num_groups", 2)?,
-            initial_group_config: _get_ints(&map, "initial_group_config")?,
+            initial_num_groups,
+            initial_group_config,
+            initial_group_config_path: map.get("initial_group_config_path").map(PathBuf::from),
+            init_from_partition_path: map.get("init_from_partition_path").map(PathBuf::from),
+            init_method: map
+                .get("init_method")
+                .map_or(Ok(InitMethod::default()), |s| s.parse())?,
+            require_nested: _get_bool(&map, "require_nested", false)?,
             saved_data_name: map
                 .get("saved_data_name")
                 .map_or(String::from("data"), String::from),
@@ -62,22 +652,234 @@ impl Parameters {
                 ))?,
                 PathBuf::from,
             ),
-            seed: map
-                .get("seed")
-                .map(|s| u64::from_str(&s).or(Err(format!("not an integer: {}", s))))
-                .transpose()?,
-        })
+            save_moves: _get_bool(&map, "save_moves", false)?,
+            save_best: _get_bool(&map, "save_best", false)?,
+            save_hierarchy: _get_bool(&map, "save_hierarchy", false)?,
+            save_labels: _get_bool(&map, "save_labels", false)?,
+            save_core_periphery: _get_bool(&map, "save_core_periphery", false)?,
+            save_nodes: _get_bool(&map, "save_nodes", false)?,
+            trace_moves: _get_bool(&map, "trace_moves", false)?,
+            check_drift: _get_bool(&map, "check_drift", false)?,
+            state_hash_interval: _get_int(&map, "state_hash_interval", 0)?,
+            recompute_interval: _get_int(&map, "recompute_interval", 0)?,
+            min_group_size: _get_int(&map, "min_group_size", 0)?,
+            sample_nodes_path: map.get("sample_nodes_path").map(PathBuf::from),
+            coarse_num_groups: _get_int(&map, "coarse_num_groups", 0)?,
+            coarsen_overlap: _get_float(&map, "coarsen_overlap", 0.0)?,
+            num_chains: _get_int(&map, "num_chains", 1)?,
+            exchange_interval: _get_int(&map, "exchange_interval", 0)?,
+            save_timestamps: _get_bool(&map, "save_timestamps", false)?,
+            snapshot_json_path: map.get("snapshot_json_path").map(PathBuf::from),
+            quiet: _get_bool(&map, "quiet", false)?,
+            adaptive_thinning: _get_bool(&map, "adaptive_thinning", false)?,
+            holdout_fraction: _get_float(&map, "holdout_fraction", 0.1)?,
+            holdout: map
+                .get("holdout")
+                .map_or(Ok(HoldoutScheme::default()), |s| s.parse())?,
+            model_select_groups: _get_ints(&map, "model_select_groups")?,
+            predict_pairs_path: map.get("predict_pairs_path").map(PathBuf::from),
+            credible_level: _get_float(&map, "credible_level", 0.95)?,
+            log_fields: map
+                .get("log_fields")
+                .map_or(Ok(LogFields::default()), |s| s.parse())?,
+            results_db: _get_bool(&map, "results_db", false)?,
+            arrow_output: _get_bool(&map, "arrow_output", false)?,
+            checkpoint_interval: _get_int(&map, "checkpoint_interval", 0)?,
+            density_prior_a: _get_float(&map, "density_prior_a", 1.0)?,
+            density_prior_b: _get_float(&map, "density_prior_b", 1.0)?,
+            assortative: _get_bool(&map, "assortative", false)?,
+            permute_nodes: _get_bool(&map, "permute_nodes", false)?,
+            move_schedule: map
+                .get("move_schedule")
+                .map_or(Ok(MoveSchedule::default()), |s| s.parse())?,
+            stall_window: _get_int(&map, "stall_window", 0)?,
+            stall_action: map
+                .get("stall_action")
+                .map_or(Ok(StallAction::default()), |s| s.parse())?,
+            ln_fact_table_max_mb: _get_int(&map, "ln_fact_table_max_mb", 2048)?,
+            ln_fact_overflow: map
+                .get("ln_fact_overflow")
+                .map_or(Ok(LnFactOverflow::default()), |s| s.parse())?,
+            batch_moves: _get_int(&map, "batch_moves", 1)?,
+            batch_threads: _get_int(&map, "batch_threads", 1)?,
+            custom_stats: map
+                .get("custom_stats")
+                .map_or(Ok(CustomStats::default()), |s| s.parse())?,
+            output_compression: map
+                .get("output_compression")
+                .map_or(Ok(OutputCompression::default()), |s| s.parse())?,
+            on_existing_output: map
+                .get("on_existing_output")
+                .map_or(Ok(OnExistingOutput::default()), |s| s.parse())?,
+            float_format: map
+                .get("float_format")
+                .map_or(Ok(FloatFormat::default()), |s| s.parse())?,
+            empty_group_policy: map
+                .get("empty_group_policy")
+                .map_or(Ok(EmptyGroupPolicy::default()), |s| s.parse())?,
+            seed: seeds.first().copied(),
+            seeds: if seeds.len() > 1 { seeds } else { Vec::new() },
+        }
+        .validate()
+    }
+
+    /// reject values that are well-formed but make no sense
+    fn validate(self) -> Result<Self, String> {
+        if !(1..=64).contains(&self.max_num_groups) {
+            return Err(format!(
+                "max_num_groups must be between 1 and 64: {}",
+                self.max_num_groups
+            ));
+        }
+        if !(1..=self.max_num_groups).contains(&self.initial_num_groups) {
+            return Err(format!(
+                "initial_num_groups must be between 1 and max_num_groups: {}",
+                self.initial_num_groups
+            ));
+        }
+        for (key, x) in [
+            ("holdout_fraction", self.holdout_fraction),
+            ("credible_level", self.credible_level),
+        ] {
+            if !(0.0..=1.0).contains(&x) {
+                return Err(format!("{} must be between 0 and 1: {}", key, x));
+            }
+        }
+        if !(0.0..=1.0).contains(&self.coarsen_overlap) {
+            return Err(format!(
+                "coarsen_overlap must be between 0 and 1: {}",
+                self.coarsen_overlap
+            ));
+        }
+        if self.coarsen_overlap > 0.0
+            && (self.coarse_num_groups > 0
+                || self.initial_group_config.is_some()
+                || self.initial_group_config_path.is_some()
+                || self.init_from_partition_path.is_some()
+                || self.sample_nodes_path.is_some())
+        {
+            return Err(String::from(
+                "coarsen_overlap cannot be combined with coarse_num_groups, initial \
+                 configurations or sample_nodes_path",
+            ));
+        }
+        if (self.isolated_nodes == IsolatedNodes::Drop || self.node_subset_path.is_some())
+            && (self.initial_group_config.is_some()
+                || self.initial_group_config_path.is_some()
+                || self.init_from_partition_path.is_some()
+                || self.sample_nodes_path.is_some()
+                || self.predict_pairs_path.is_some())
+        {
+            return Err(String::from(
+                "isolated_nodes: drop and node_subset_path renumber the nodes, so initial \
+                 configurations, sample_nodes_path and predict_pairs_path cannot refer to them",
+            ));
+        }
+        if self.batch_moves == 0 || self.batch_threads == 0 {
+            return Err(String::from(
+                "batch_moves and batch_threads must be at least 1",
+            ));
+        }
+        if self.batch_moves > 1
+            && !matches!(
+                self.empty_group_policy,
+                EmptyGroupPolicy::OnSelection | EmptyGroupPolicy::Keep
+            )
+        {
+            // pruning between the moves of a batch would renumber the groups of later moves
+            return Err(String::from(
+                "batch_moves requires empty_group_policy on_selection or keep",
+            ));
+        }
+        Ok(self)
     }
+
     /// prepend base to relative paths
     pub fn resolve_paths(self, base: &Path) -> Parameters {
-        let resolve = |p: PathBuf| if p.is_absolute() { p } else { base.join(p) };
+        let resolve = |p: PathBuf| {
+            if p.is_absolute() || p == Path::new("-") {
+                p
+            } else {
+                base.join(p)
+            }
+        };
         Self {
-            gml_path: resolve(self.gml_path),
+            gml_path: self.gml_path.map(resolve),
             save_directory: resolve(self.save_directory),
+            initial_group_config_path: self.initial_group_config_path.map(resolve),
+            init_from_partition_path: self.init_from_partition_path.map(resolve),
+            node_subset_path: self.node_subset_path.map(resolve),
+            sample_nodes_path: self.sample_nodes_path.map(resolve),
+            snapshot_json_path: self.snapshot_json_path.map(resolve),
+            predict_pairs_path: self.predict_pairs_path.map(resolve),
             ..self
         }
     }
 
+    /// start from `groups` with `num_groups` groups, replacing any other source of the
+    /// initial configuration, e.g. to rebuild a model in a configuration found by sampling
+    pub fn with_initial_config(&self, groups: Vec<u64>, num_groups: u32) -> Parameters {
+        Parameters {
+            initial_num_groups: num_groups,
+            initial_group_config: Some(groups),
+            initial_group_config_path: None,
+            init_from_partition_path: None,
+            init_method: InitMethod::Random,
+            ..self.clone()
+        }
+    }
+
+    /// the settings of the likelihood
+    pub fn likelihood_config(&self) -> LikelihoodConfig {
+        LikelihoodConfig {
+            density_prior_a: self.density_prior_a,
+            density_prior_b: self.density_prior_b,
+            ln_fact_table_max_mb: self.ln_fact_table_max_mb,
+            ln_fact_overflow: self.ln_fact_overflow,
+        }
+    }
+
+    /// the network file, required unless the network is read from elsewhere
+    pub fn require_gml_path(&self) -> Result<&Path, String> {
+        self.gml_path
+            .as_deref()
+            .ok_or(String::from("Missing required parameter 'gml_path'"))
+    }
+
+    /// the parameters of one run per seed of `seeds`, with `_seed<seed>` appended to
+    /// `saved_data_name` and to the file name of `snapshot_json_path`. Just `self` if there
+    /// is no list of seeds.
+    pub fn per_seed(&self) -> Vec<Parameters> {
+        if self.seeds.is_empty() {
+            return vec![self.clone()];
+        }
+        self.seeds
+            .iter()
+            .map(|&seed| {
+                let suffix = format!("_seed{}", seed);
+                let snapshot_json_path = self.snapshot_json_path.as_ref().map(|p| {
+                    match (p.file_stem(), p.extension()) {
+                        _ if p == Path::new("-") => p.clone(),
+                        (Some(stem), Some(ext)) => p.with_file_name(format!(
+                            "{}{}.{}",
+                            stem.to_string_lossy(),
+                            suffix,
+                            ext.to_string_lossy()
+                        )),
+                        _ => PathBuf::from(format!("{}{}", p.display(), suffix)),
+                    }
+                });
+                Parameters {
+                    seed: Some(seed),
+                    seeds: Vec::new(),
+                    saved_data_name: format!("{}{}", self.saved_data_name, suffix),
+                    snapshot_json_path,
+                    ..self.clone()
+                }
+            })
+            .collect()
+    }
+
     /// if no seed has been set yet, set based on current time.
     pub fn fix_seed(self) -> Parameters {
         Self {
@@ -88,3 +890,138 @@ impl Parameters {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn malformed() {
+        assert!(Parameters::load(&b"max_itr: 10\ncheckpoint_interval: 1\n"[..]).is_ok());
+        assert!(Parameters::load(&b"max_itr 10\n"[..]).is_err());
+        // invalid UTF-8 is an I/O error of the reader
+        assert!(Parameters::load(&b"quiet: \xff\n"[..]).is_err());
+        for bad in [
+            "max_num_groups: 0",
+            "max_num_groups: 65",
+            "initial_num_groups: 0",
+            "max_num_groups: 3\ninitial_num_groups: 4",
+            "holdout_fraction: 1.5",
+            "credible_level: NaN",
+            "max_itr: -1",
+            "batch_moves: 0",
+            "batch_moves: 4\nempty_group_policy: prune",
+            "isolated_nodes: remove",
+            "on_existing_output: keep",
+            "coarsen_overlap: 1.5",
+            "coarsen_overlap: 0.8\ncoarse_num_groups: 2",
+            "isolated_nodes: drop\ninitial_group_config: 1 1",
+            "node_subset_path: a.txt\nsample_nodes_path: b.txt",
+        ] {
+            assert!(Parameters::load(bad.as_bytes()).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn comments() {
+        let p = Parameters::load(
+            &b"# shared config\n\nmax_itr = 10\n  # checkpoints below\ncheckpoint_interval: 1\nsave_directory = out\n"[..],
+        )
+        .unwrap();
+        assert_eq!((p.max_itr, p.checkpoint_interval), (10, 1));
+        assert_eq!(p.save_directory, PathBuf::from("out"));
+        assert!(Parameters::load(&b"max_itr 10 # no separator\n"[..]).is_err());
+    }
+
+    #[test]
+    fn seed_lists() {
+        let p = Parameters::load(&b"seed: 7\n"[..]).unwrap();
+        assert_eq!((p.seed, p.seeds.len()), (Some(7), 0));
+        assert_eq!(p.per_seed().len(), 1);
+        assert!(Parameters::load(&b"seed: 7 x\n"[..]).is_err());
+
+        let p = Parameters::load(
+            &b"seeds: 3 5\nsaved_data_name: run\nsnapshot_json_path: out/s.jsonl\n"[..],
+        )
+        .unwrap();
+        assert_eq!((p.seed, &p.seeds[..]), (Some(3), &[3, 5][..]));
+        let runs = p.per_seed();
+        assert_eq!(
+            runs.iter()
+                .map(|r| (r.seed, r.saved_data_name.as_str()))
+                .collect::<Vec<_>>(),
+            [(Some(3), "run_seed3"), (Some(5), "run_seed5")]
+        );
+        assert_eq!(
+            runs[1].snapshot_json_path,
+            Some(PathBuf::from("out/s_seed5.jsonl"))
+        );
+        assert!(runs.iter().all(|r| r.seeds.is_empty()));
+
+        assert_eq!(
+            Parameters::load(&b"seed: 1\nseeds: 3 5\n"[..]).unwrap_err(),
+            "seed and seeds are exclusive"
+        );
+        assert_eq!(
+            Parameters::load(&b"seed: 3 5 3\n"[..]).unwrap_err(),
+            "seed 3 is listed twice, its runs would write the same outputs"
+        );
+    }
+
+    #[test]
+    fn with_initial_config() {
+        let params = Parameters::load(
+            &b"init_method: louvain\ninitial_group_config_path: a.txt\ninit_from_partition_path: b.txt\n"[..],
+        )
+        .unwrap()
+        .with_initial_config(vec![1, 3], 2);
+        assert_eq!(params.initial_group_config, Some(vec![1, 3]));
+        assert_eq!(params.initial_num_groups, 2);
+        assert_eq!(params.initial_group_config_path, None);
+        assert_eq!(params.init_from_partition_path, None);
+        assert_eq!(params.init_method, InitMethod::Random);
+    }
+
+    #[test]
+    fn initial_num_groups_from_config() {
+        let load = |s: &str| Parameters::load(s.as_bytes()).map(|p| p.initial_num_groups);
+        assert_eq!(load("initial_group_config: 1 3 9 1"), Ok(4));
+        assert_eq!(load("initial_group_config: 1 1"), Ok(1));
+        assert_eq!(
+            load("initial_group_config: 1 5\ninitial_num_groups: 3"),
+            Ok(3)
+        );
+        assert_eq!(
+            load("initial_group_config: 1 5\ninitial_num_groups: 4"),
+            Ok(4)
+        );
+        assert_eq!(
+            load("initial_group_config: 1 5\ninitial_num_groups: 2"),
+            Err(String::from(
+                "initial_num_groups is 2, but initial_group_config has 3 groups"
+            ))
+        );
+        assert_eq!(load("max_num_groups: 3"), Ok(2));
+    }
+
+    #[test]
+    fn log_fields() {
+        let load = |s: &str| Parameters::load(s.as_bytes()).map(|p| p.log_fields.names());
+        assert_eq!(
+            load(""),
+            Ok(vec![
+                "configs",
+                "num_groups",
+                "group_size",
+                "edges",
+                "pairs",
+                "ll"
+            ])
+        );
+        assert_eq!(
+            load("log_fields: ll stats entropy dl"),
+            Ok(vec!["ll", "stats", "entropy", "dl"])
+        );
+        assert!(load("log_fields: ll energy").is_err());
+    }
+}
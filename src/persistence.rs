@@ -0,0 +1,156 @@
+//! how long groups persist across the snapshots of a run. Group indices are reused as groups
+//! are removed and added, so the groups of consecutive snapshots are matched by membership
+//! instead: greedily by Jaccard index, one to one, as long as it is at least `min_overlap`.
+//! A chain of matched groups is a lineage; its lifetime separates robust communities from
+//! transient fluctuations. The root is in every snapshot and not tracked.
+use crate::multi_group_model::Groups;
+
+/// a group followed through consecutive snapshots
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lineage {
+    /// first and last snapshot the group is in
+    pub birth: usize,
+    pub death: usize,
+    /// group index in the last snapshot
+    pub group: usize,
+    pub mean_size: f64,
+    /// mean Jaccard index of the matches along the lineage, 1 for a single snapshot
+    pub mean_overlap: f64,
+}
+
+impl Lineage {
+    /// number of snapshots the group is in
+    pub fn lifetime(&self) -> usize {
+        self.death - self.birth + 1
+    }
+}
+
+/// number of members of every group in `config`
+fn group_sizes(config: &[Groups]) -> [usize; 64] {
+    let mut sizes = [0; 64];
+    for &groups in config {
+        for (g, size) in sizes.iter_mut().enumerate() {
+            *size += (groups >> g & 1) as usize;
+        }
+    }
+    sizes
+}
+
+/// for every group of `next`, the group of `prev` it continues and their Jaccard index
+fn match_groups(prev: &[Groups], next: &[Groups], min_overlap: f64) -> [Option<(usize, f64)>; 64] {
+    let mut shared = vec![[0usize; 64]; 64];
+    for (&a, &b) in prev.iter().zip(next) {
+        for g in (1..64).filter(|g| a >> g & 1 == 1) {
+            for h in (1..64).filter(|h| b >> h & 1 == 1) {
+                shared[g][h] += 1;
+            }
+        }
+    }
+    let (prev_sizes, next_sizes) = (group_sizes(prev), group_sizes(next));
+    let mut candidates = Vec::new();
+    for g in 1..64 {
+        for h in 1..64 {
+            if shared[g][h] > 0 {
+                let union = prev_sizes[g] + next_sizes[h] - shared[g][h];
+                candidates.push((shared[g][h] as f64 / union as f64, g, h));
+            }
+        }
+    }
+    candidates.sort_by(|a, b| b.0.total_cmp(&a.0).then((a.1, a.2).cmp(&(b.1, b.2))));
+    let mut matched = [None; 64];
+    let mut taken = [false; 64];
+    for (overlap, g, h) in candidates {
+        if overlap >= min_overlap && !taken[g] && matched[h].is_none() {
+            taken[g] = true;
+            matched[h] = Some((g, overlap));
+        }
+    }
+    matched
+}
+
+/// lineages of the non-root groups in `configs`, consecutive snapshots of one run, in order of
+/// birth
+pub fn track(configs: &[Vec<Groups>], min_overlap: f64) -> Result<Vec<Lineage>, String> {
+    if let Some(c) = configs.iter().find(|c| c.len() != configs[0].len()) {
+        return Err(format!(
+            "snapshots of {} and {} nodes",
+            configs[0].len(),
+            c.len()
+        ));
+    }
+    // per lineage: birth, last snapshot, group, summed size and overlap, number of matches
+    let mut lineages: Vec<(usize, usize, usize, usize, f64, usize)> = Vec::new();
+    // lineage of every group in the previous snapshot
+    let mut current: [Option<usize>; 64] = [None; 64];
+    for (s, config) in configs.iter().enumerate() {
+        let sizes = group_sizes(config);
+        let matched = match s {
+            0 => [None; 64],
+            _ => match_groups(&configs[s - 1], config, min_overlap),
+        };
+        let mut next = [None; 64];
+        for h in (1..64).filter(|&h| sizes[h] > 0) {
+            let id = match matched[h].and_then(|(g, overlap)| Some((current[g]?, overlap))) {
+                Some((id, overlap)) => {
+                    lineages[id].4 += overlap;
+                    lineages[id].5 += 1;
+                    id
+                }
+                None => {
+                    lineages.push((s, s, h, 0, 0.0, 0));
+                    lineages.len() - 1
+                }
+            };
+            lineages[id].1 = s;
+            lineages[id].2 = h;
+            lineages[id].3 += sizes[h];
+            next[h] = Some(id);
+        }
+        current = next;
+    }
+    Ok(lineages
+        .into_iter()
+        .map(|(birth, death, group, size, overlap, matches)| Lineage {
+            birth,
+            death,
+            group,
+            mean_size: size as f64 / (death - birth + 1) as f64,
+            mean_overlap: if matches == 0 {
+                1.0
+            } else {
+                overlap / matches as f64
+            },
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lineages() {
+        let configs = [
+            vec![3, 3, 3, 5, 5, 1],
+            // group 1 keeps its members, group 2 gains one
+            vec![3, 3, 3, 5, 5, 5],
+            // the members of group 1 move to group 2 and the other group dissolves
+            vec![5, 5, 5, 1, 1, 1],
+            // a new group
+            vec![5, 5, 5, 3, 3, 1],
+        ];
+        let lineages = track(&configs, 0.5).unwrap();
+        let summary: Vec<_> = lineages
+            .iter()
+            .map(|l| (l.birth, l.death, l.group, l.lifetime()))
+            .collect();
+        assert_eq!(summary, [(0, 3, 2, 4), (0, 1, 2, 2), (3, 3, 1, 1)]);
+        assert_eq!(lineages[0].mean_size, 3.0);
+        assert_eq!(lineages[1].mean_overlap, 2.0 / 3.0);
+        assert_eq!(lineages[2].mean_overlap, 1.0);
+
+        // below the overlap threshold every snapshot starts new lineages
+        assert_eq!(track(&configs[..2], 0.9).unwrap().len(), 3);
+        assert!(track(&[vec![1, 1], vec![1]], 0.5).is_err());
+    }
+}
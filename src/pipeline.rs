@@ -0,0 +1,145 @@
+//! coarse-to-fine fitting: sample with a small group budget, split the leaf groups of the
+//! best configuration found, and continue with a larger budget until that stops improving.
+use crate::multi_group_model::{Groups, MultiGroupModel, Node};
+use crate::network::NetworkData;
+use crate::parameters::Parameters;
+use crate::seeds::{SeedStream, Stream};
+use crate::HierarchicalModel;
+use std::sync::Arc;
+
+/// insert a zero bit at `pos`, shifting all higher bits up by one
+fn insert_zero_bit(x: Groups, pos: usize) -> Groups {
+    let low = x & ((1 << pos) - 1);
+    ((x >> pos) << (pos + 1)) | low
+}
+
+/// split every leaf group of `model` (while staying within `max_groups`) by adding a new
+/// nested group containing the half of its members with the most edges inside the leaf.
+/// Returns the new configuration and number of groups.
+pub fn split_leaves(
+    model: &MultiGroupModel,
+    network: &NetworkData,
+    max_groups: usize,
+) -> (Vec<Groups>, usize) {
+    let mut is_leaf = vec![true; model.num_groups()];
+    for p in model.parent_groups().into_iter().flatten() {
+        is_leaf[p] = false;
+    }
+
+    let mut groups = model.groups().to_vec();
+    let mut num_groups = model.num_groups();
+    // go from the deepest group up, so that inserting groups does not shift unvisited ones
+    for g in (0..model.num_groups()).rev() {
+        if !is_leaf[g] || model.group_size(g) < 2 || num_groups >= max_groups {
+            continue;
+        }
+        let in_leaf = |v: &Node| (model.groups_of(*v as usize) >> g) & 1 != 0;
+        let mut members: Vec<(usize, Node)> = model
+            .members(g)
+            .iter()
+            .map(|&u| {
+                (
+                    network.neighbors(u).iter().filter(|v| in_leaf(v)).count(),
+                    u,
+                )
+            })
+            .collect();
+        members.sort_unstable_by(|a, b| b.cmp(a));
+
+        for x in groups.iter_mut() {
+            *x = insert_zero_bit(*x, g + 1);
+        }
+        for &(_, u) in &members[..members.len().div_ceil(2)] {
+            groups[u as usize] |= 1 << (g + 1);
+        }
+        num_groups += 1;
+    }
+    (groups, num_groups)
+}
+
+/// run coarse-to-fine fitting, starting with `params.coarse_num_groups` groups and doubling
+/// the budget up to `params.max_num_groups` for every stage of `params.max_itr` iterations.
+/// Stage `k` is seeded with the `Stream::Stage` sub-seed `k` of `seeds`.
+/// Returns a model in the best configuration found, with the full group budget.
+pub fn coarse_to_fine(
+    params: &Parameters,
+    network: Arc<NetworkData>,
+    seeds: &mut SeedStream,
+) -> Result<HierarchicalModel, String> {
+    let mut budget = params.coarse_num_groups.clamp(1, params.max_num_groups);
+    let mut stage_params = Parameters {
+        max_num_groups: budget,
+        initial_num_groups: params.initial_num_groups.min(budget),
+        seed: Some(seeds.derive(Stream::Stage, 0)),
+        ..params.clone()
+    };
+    let mut best: Option<(f64, Vec<Groups>, usize)> = None;
+    for stage in 0.. {
+        let mut hcp: HierarchicalModel =
+            HierarchicalModel::with_network(&stage_params, network.clone())?;
+        let mut stage_best = (
+            hcp.log_like(),
+            hcp.model().groups().to_vec(),
+            hcp.model().num_groups(),
+        );
+        for i in 0..params.max_itr {
+            hcp.set_group_move_weight(params.move_schedule.weight(i));
+            if hcp.get_groups().is_some() && hcp.log_like() > stage_best.0 {
+                stage_best = (
+                    hcp.log_like(),
+                    hcp.model().groups().to_vec(),
+                    hcp.model().num_groups(),
+                );
+            }
+        }
+        if !params.quiet {
+            println!(
+                "coarse-to-fine stage {}: {} groups max, best energy {:.4}",
+                stage, budget, stage_best.0
+            );
+        }
+
+        if best.as_ref().is_some_and(|b| stage_best.0 <= b.0) {
+            break;
+        }
+        best = Some(stage_best);
+        if budget == params.max_num_groups {
+            break;
+        }
+
+        let (_, groups, num_groups) = best.as_ref().unwrap();
+        budget = (2 * budget).min(params.max_num_groups);
+        let model = MultiGroupModel::with_groups(groups.clone(), *num_groups as u32, budget);
+        let (groups, num_groups) = split_leaves(&model, &network, budget as usize);
+        stage_params = Parameters {
+            max_num_groups: budget,
+            seed: Some(seeds.derive(Stream::Stage, stage + 1)),
+            ..params.with_initial_config(groups, num_groups as u32)
+        };
+    }
+
+    let (_, groups, num_groups) = best.unwrap();
+    HierarchicalModel::with_network(
+        &params.with_initial_config(groups, num_groups as u32),
+        network,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split() {
+        // triangle 0-1-2 with pendant 3, plus an isolated pair 4-5
+        let network = NetworkData::from_edges(6, [(0, 1), (1, 2), (2, 0), (2, 3), (4, 5)]);
+        let model = MultiGroupModel::with_groups(vec![3, 3, 3, 3, 1, 1], 2, 64);
+        let (groups, num_groups) = split_leaves(&model, &network, 64);
+        assert_eq!(num_groups, 3);
+        assert_eq!(groups, [3, 7, 7, 3, 1, 1]);
+
+        let (groups, num_groups) = split_leaves(&model, &network, 2);
+        assert_eq!(num_groups, 2);
+        assert_eq!(groups, model.groups());
+    }
+}
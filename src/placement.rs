@@ -0,0 +1,255 @@
+//! placement of new nodes in a fitted hierarchy without refitting (`freeze_hierarchy`): the
+//! groups of the fitted nodes stay as they are, and every new node joins one path of the group
+//! tree of `MultiGroupModel::parent_groups`, from the root down to any group. Each placement
+//! is scored by the change of the log-likelihood when the node and its edges are added. A
+//! `FrozenHierarchy` places a stream of nodes one after another, each counting the earlier ones.
+use crate::likelihood::Likelihood;
+use crate::multi_group_model::{Groups, Node};
+use crate::rng::SamplerRng;
+use crate::{Float, HierarchicalModel};
+
+/// groups of a new node and the log-likelihood difference of adding it with them
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Placement {
+    pub groups: Groups,
+    pub delta_log_like: f64,
+}
+
+/// a fitted model with new nodes added in the paths of its frozen group tree. New node `i` has
+/// id `n + i`, `n` being the number of fitted nodes; its edges go to fitted nodes and to the
+/// new nodes placed before it.
+#[derive(Clone)]
+pub struct FrozenHierarchy<'a, F: Float, L: Likelihood, R: SamplerRng> {
+    hcp: &'a HierarchicalModel<F, L, R>,
+    /// the group paths a new node can join
+    paths: Vec<Groups>,
+    hcg_edges: Vec<usize>,
+    hcg_pairs: Vec<usize>,
+    /// groups of the new nodes
+    placed: Vec<Groups>,
+    log_like: f64,
+}
+
+/// the highest common group of nodes in `a` and in `b`
+fn hcg(a: Groups, b: Groups) -> usize {
+    63 - (a & b).leading_zeros() as usize
+}
+
+impl<F: Float, L: Likelihood, R: SamplerRng> HierarchicalModel<F, L, R> {
+    /// the model as a hierarchy to place new nodes in, see `FrozenHierarchy`
+    pub fn freeze_hierarchy(&self) -> FrozenHierarchy<'_, F, L, R> {
+        let parents = self.model.parent_groups();
+        let mut paths: Vec<Groups> = Vec::with_capacity(parents.len());
+        for (g, parent) in parents.iter().enumerate() {
+            // parents always have a lower index
+            paths.push(parent.map_or(0, |p| paths[p]) | 1 << g);
+        }
+        paths.sort_unstable();
+        paths.dedup();
+        FrozenHierarchy {
+            hcp: self,
+            paths,
+            hcg_edges: self.hcg_edges.clone(),
+            hcg_pairs: self.hcg_pairs.clone(),
+            placed: Vec::new(),
+            log_like: self
+                .likelihood
+                .log_like_exact(&self.hcg_edges, &self.hcg_pairs),
+        }
+    }
+
+    /// every placement of a new node adjacent to `neighbors`, best first. The model is left
+    /// as it is; the log-likelihoods are computed in double precision.
+    pub fn place_node(&self, neighbors: &[Node]) -> Result<Vec<Placement>, String> {
+        self.freeze_hierarchy().placements(neighbors)
+    }
+
+    /// the best placement of `place_node`
+    pub fn best_placement(&self, neighbors: &[Node]) -> Result<Placement, String> {
+        // the root alone is always a placement
+        Ok(self.place_node(neighbors)?[0])
+    }
+}
+
+impl<F: Float, L: Likelihood, R: SamplerRng> FrozenHierarchy<'_, F, L, R> {
+    /// number of fitted and placed nodes
+    pub fn node_count(&self) -> usize {
+        self.hcp.network.node_count() + self.placed.len()
+    }
+
+    /// groups of the placed nodes, in order of placement
+    pub fn placed(&self) -> &[Groups] {
+        &self.placed
+    }
+
+    /// log-likelihood of the fitted and placed nodes, in double precision
+    pub fn log_like(&self) -> f64 {
+        self.log_like
+    }
+
+    /// the groups of node `u`, fitted or placed
+    fn groups_of(&self, u: usize) -> Groups {
+        let n = self.hcp.network.node_count();
+        if u < n {
+            self.hcp.model.groups_of(u)
+        } else {
+            self.placed[u - n]
+        }
+    }
+
+    /// the group pair counts with a new node in `groups` adjacent to `adjacent` added
+    fn counts_with(&self, groups: Groups, adjacent: &[bool]) -> (Vec<usize>, Vec<usize>) {
+        let (mut hcg_edges, mut hcg_pairs) = (self.hcg_edges.clone(), self.hcg_pairs.clone());
+        for (u, &is_adjacent) in adjacent.iter().enumerate() {
+            let g = hcg(groups, self.groups_of(u));
+            hcg_pairs[g] += 1;
+            hcg_edges[g] += is_adjacent as usize;
+        }
+        (hcg_edges, hcg_pairs)
+    }
+
+    /// whether every node is adjacent to the new node with `neighbors`
+    fn adjacency(&self, neighbors: &[Node]) -> Result<Vec<bool>, String> {
+        let mut adjacent = vec![false; self.node_count()];
+        for &v in neighbors {
+            match adjacent.get_mut(v as usize) {
+                None => return Err(format!("neighbor {} does not exist", v)),
+                Some(true) => return Err(format!("neighbor {} is listed twice", v)),
+                Some(a) => *a = true,
+            }
+        }
+        Ok(adjacent)
+    }
+
+    /// every placement of a new node adjacent to `neighbors`, best first, without adding it
+    pub fn placements(&self, neighbors: &[Node]) -> Result<Vec<Placement>, String> {
+        let adjacent = self.adjacency(neighbors)?;
+        let mut placements: Vec<Placement> = self
+            .paths
+            .iter()
+            .map(|&groups| {
+                let (hcg_edges, hcg_pairs) = self.counts_with(groups, &adjacent);
+                Placement {
+                    groups,
+                    delta_log_like: self.hcp.likelihood.log_like_exact(&hcg_edges, &hcg_pairs)
+                        - self.log_like,
+                }
+            })
+            .collect();
+        placements.sort_by(|a, b| b.delta_log_like.total_cmp(&a.delta_log_like));
+        Ok(placements)
+    }
+
+    /// add a new node adjacent to `neighbors` in its best placement, which is returned
+    pub fn place(&mut self, neighbors: &[Node]) -> Result<Placement, String> {
+        // the root alone is always a placement
+        let best = self.placements(neighbors)?[0];
+        let (hcg_edges, hcg_pairs) = self.counts_with(best.groups, &self.adjacency(neighbors)?);
+        self.log_like = self.hcp.likelihood.log_like_exact(&hcg_edges, &hcg_pairs);
+        (self.hcg_edges, self.hcg_pairs) = (hcg_edges, hcg_pairs);
+        self.placed.push(best.groups);
+        Ok(best)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::NetworkData;
+    use crate::tests::{_example_model, _example_parameters};
+    use std::sync::Arc;
+
+    /// parameter lines starting from `groups`
+    fn config(groups: &[Groups]) -> String {
+        format!(
+            "initial_num_groups: {}\ninitial_group_config: {}\n",
+            64 - groups.iter().fold(1, |a, &g| a | g).leading_zeros(),
+            groups
+                .iter()
+                .map(|g| g.to_string())
+                .collect::<Vec<_>>()
+                .join(" ")
+        )
+    }
+
+    /// the example model of `network` in `groups`
+    fn grown_model(network: Arc<NetworkData>, groups: &[Groups]) -> HierarchicalModel {
+        let params = _example_parameters(config(groups).as_bytes()).unwrap();
+        HierarchicalModel::with_network(&params, network).unwrap()
+    }
+
+    /// group 1 with nodes 0 to 9, group 2 inside it with nodes 0 to 4, group 3 on its own
+    fn fitted_groups() -> Vec<Groups> {
+        (0..25)
+            .map(|u| match u {
+                0..=4 => 0b111,
+                5..=9 => 0b11,
+                10..=14 => 0b1001,
+                _ => 1,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn placements() {
+        let groups = fitted_groups();
+        let hcp = _example_model(config(&groups).as_bytes());
+        let neighbors = [0, 1, 2, 3];
+        let placements = hcp.place_node(&neighbors).unwrap();
+        let mut paths: Vec<Groups> = placements.iter().map(|p| p.groups).collect();
+        paths.sort();
+        assert_eq!(paths, [0b1, 0b11, 0b111, 0b1001]);
+        assert!(placements
+            .windows(2)
+            .all(|w| w[0].delta_log_like >= w[1].delta_log_like));
+        assert_eq!(hcp.best_placement(&neighbors).unwrap(), placements[0]);
+
+        // each difference is that of the model with the node added
+        let network = hcp.network();
+        let edges = network.edges().chain(neighbors.iter().map(|&v| (v, 25)));
+        let grown = Arc::new(NetworkData::from_edges(26, edges));
+        for p in &placements {
+            let with_node = grown_model(grown.clone(), &[&groups[..], &[p.groups]].concat());
+            let delta = with_node.log_like() - hcp.log_like();
+            assert!((delta - p.delta_log_like).abs() < 1e-9, "{:?}", p);
+        }
+
+        assert!(hcp.place_node(&[25]).is_err());
+        assert!(hcp.place_node(&[3, 3]).is_err());
+    }
+
+    #[test]
+    fn stream() {
+        let groups = fitted_groups();
+        let hcp = _example_model(config(&groups).as_bytes());
+        let mut frozen = hcp.freeze_hierarchy();
+        // node 25 joins the clique of group 2, node 26 is adjacent to it and to group 3
+        let new_nodes: [&[Node]; 2] = [&[0, 1, 2, 3, 4], &[25, 10, 11, 12]];
+        assert!(frozen.place(&[25]).is_err());
+        let first = frozen.place(new_nodes[0]).unwrap();
+        assert_eq!(first, hcp.best_placement(new_nodes[0]).unwrap());
+        let second = frozen.place(new_nodes[1]).unwrap();
+        assert_eq!(frozen.placed(), [first.groups, second.groups]);
+        assert_eq!(frozen.node_count(), 27);
+        // the fitted model is unchanged
+        assert_eq!(hcp.model().groups(), &groups[..]);
+
+        // the log-likelihood is that of the model with both nodes added
+        let edges = hcp.network().edges().chain(
+            new_nodes
+                .iter()
+                .zip([25, 26])
+                .flat_map(|(neighbors, u)| neighbors.iter().map(move |&v| (v, u))),
+        );
+        let grown = grown_model(
+            Arc::new(NetworkData::from_edges(27, edges)),
+            &[&groups[..], frozen.placed()].concat(),
+        );
+        assert!((grown.log_like() - frozen.log_like()).abs() < 1e-9);
+        assert!(
+            (frozen.log_like() - hcp.log_like() - first.delta_log_like - second.delta_log_like)
+                .abs()
+                < 1e-9
+        );
+    }
+}
@@ -0,0 +1,216 @@
+//! fast flat clusterings used as warm starts (`init_method`): Louvain modularity optimisation
+//! and label propagation. Either becomes a two level hierarchy of the root and one group per
+//! community. Both are deterministic for a given random number generator state.
+use crate::multi_group_model::Groups;
+use crate::network::NetworkData;
+use crate::rng::SamplerRng;
+use std::collections::BTreeMap;
+
+/// weighted graph of communities in a Louvain level
+struct Level {
+    /// neighbors and edge weights, without self-loops
+    adj: Vec<Vec<(usize, f64)>>,
+    /// twice the weight of the edges inside every node
+    self_weight: Vec<f64>,
+}
+
+impl Level {
+    fn degree(&self, u: usize) -> f64 {
+        self.adj[u].iter().map(|&(_, w)| w).sum::<f64>() + self.self_weight[u]
+    }
+
+    /// move nodes between communities while modularity improves.
+    /// Returns the community of every node and whether any node moved.
+    fn local_moves(&self, rng: &mut impl SamplerRng) -> (Vec<usize>, bool) {
+        let n = self.adj.len();
+        let degree: Vec<f64> = (0..n).map(|u| self.degree(u)).collect();
+        let total: f64 = degree.iter().sum();
+        let mut community: Vec<usize> = (0..n).collect();
+        let mut community_degree = degree.clone();
+        let mut order: Vec<usize> = (0..n).collect();
+        let mut moved = false;
+        if total == 0.0 {
+            return (community, moved);
+        }
+        loop {
+            rng.shuffle(&mut order);
+            let mut improved = false;
+            for &u in &order {
+                let old = community[u];
+                community_degree[old] -= degree[u];
+                let mut links: BTreeMap<usize, f64> = BTreeMap::from([(old, 0.0)]);
+                for &(v, w) in &self.adj[u] {
+                    *links.entry(community[v]).or_default() += w;
+                }
+                let gain = |c: usize, w: f64| w - community_degree[c] * degree[u] / total;
+                let mut best = (old, gain(old, links[&old]));
+                for (&c, &w) in &links {
+                    let g = gain(c, w);
+                    if g > best.1 + 1e-12 {
+                        best = (c, g);
+                    }
+                }
+                community[u] = best.0;
+                community_degree[best.0] += degree[u];
+                if best.0 != old {
+                    improved = true;
+                    moved = true;
+                }
+            }
+            if !improved {
+                return (community, moved);
+            }
+        }
+    }
+
+    /// the graph of `communities`, numbered `0..k`
+    fn aggregate(&self, community: &[usize]) -> Self {
+        let k = community.iter().max().map_or(0, |&c| c + 1);
+        let mut weights: Vec<BTreeMap<usize, f64>> = vec![BTreeMap::new(); k];
+        let mut self_weight = vec![0.0; k];
+        for (u, neighbors) in self.adj.iter().enumerate() {
+            let cu = community[u];
+            self_weight[cu] += self.self_weight[u];
+            for &(v, w) in neighbors {
+                if community[v] == cu {
+                    self_weight[cu] += w;
+                } else {
+                    *weights[cu].entry(community[v]).or_default() += w;
+                }
+            }
+        }
+        Self {
+            adj: weights
+                .into_iter()
+                .map(|m| m.into_iter().collect())
+                .collect(),
+            self_weight,
+        }
+    }
+}
+
+/// renumber `community` to `0..k` in order of first appearance
+fn renumber(community: &mut [usize]) {
+    let mut ids = BTreeMap::new();
+    for c in community.iter_mut() {
+        let next = ids.len();
+        *c = *ids.entry(*c).or_insert(next);
+    }
+}
+
+/// community of every node, by the Louvain method
+pub fn louvain(network: &NetworkData, rng: &mut impl SamplerRng) -> Vec<usize> {
+    let mut level = Level {
+        adj: (0..network.node_count() as u32)
+            .map(|u| {
+                network
+                    .neighbors(u)
+                    .iter()
+                    .map(|&v| (v as usize, 1.0))
+                    .collect()
+            })
+            .collect(),
+        self_weight: vec![0.0; network.node_count()],
+    };
+    let mut community: Vec<usize> = (0..network.node_count()).collect();
+    loop {
+        let (mut level_community, moved) = level.local_moves(rng);
+        if !moved {
+            renumber(&mut community);
+            return community;
+        }
+        renumber(&mut level_community);
+        for c in &mut community {
+            *c = level_community[*c];
+        }
+        level = level.aggregate(&level_community);
+    }
+}
+
+/// community of every node, by asynchronous label propagation with random tie breaking
+pub fn label_propagation(network: &NetworkData, rng: &mut impl SamplerRng) -> Vec<usize> {
+    const MAX_PASSES: usize = 100;
+    let n = network.node_count();
+    let mut community: Vec<usize> = (0..n).collect();
+    let mut order: Vec<u32> = (0..n as u32).collect();
+    let mut counts = BTreeMap::new();
+    for _ in 0..MAX_PASSES {
+        rng.shuffle(&mut order);
+        let mut changed = false;
+        for &u in &order {
+            counts.clear();
+            for &v in network.neighbors(u) {
+                *counts.entry(community[v as usize]).or_insert(0) += 1;
+            }
+            let Some(&max) = counts.values().max() else {
+                continue;
+            };
+            // keep the current label when it is among the most frequent, so the pass ends
+            if counts.get(&community[u as usize]) == Some(&max) {
+                continue;
+            }
+            let best: Vec<usize> = counts
+                .iter()
+                .filter(|&(_, &count)| count == max)
+                .map(|(&c, _)| c)
+                .collect();
+            community[u as usize] = best[rng.uniform_int(0..best.len())];
+            changed = true;
+        }
+        if !changed {
+            break;
+        }
+    }
+    renumber(&mut community);
+    community
+}
+
+/// the root and one group for each of the `max_groups - 1` largest communities with at least
+/// two nodes; nodes of the other communities are only in the root.
+/// Returns the groups of every node and the number of groups.
+pub fn to_groups(community: &[usize], max_groups: u32) -> (Vec<Groups>, u32) {
+    let k = community.iter().max().map_or(0, |&c| c + 1);
+    let mut size = vec![0; k];
+    for &c in community {
+        size[c] += 1;
+    }
+    let mut by_size: Vec<usize> = (0..k).filter(|&c| size[c] >= 2).collect();
+    by_size.sort_by_key(|&c| std::cmp::Reverse(size[c]));
+    by_size.truncate(max_groups.clamp(1, 64) as usize - 1);
+    let mut group = vec![0; k];
+    for (g, &c) in by_size.iter().enumerate() {
+        group[c] = g + 1;
+    }
+    let groups = community.iter().map(|&c| 1 | 1 << group[c]).collect();
+    (groups, by_size.len() as u32 + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::DefaultRng;
+
+    /// two 5-cliques joined by a single edge, and an isolated node
+    fn two_cliques() -> NetworkData {
+        let clique = |o: u32| (0..5).flat_map(move |u| (u + 1..5).map(move |v| (o + u, o + v)));
+        NetworkData::from_edges(11, clique(0).chain(clique(5)).chain([(4, 5)]))
+    }
+
+    #[test]
+    fn communities() {
+        let net = two_cliques();
+        let mut rng = DefaultRng::seed_from_u64(1);
+        for community in [louvain(&net, &mut rng), label_propagation(&net, &mut rng)] {
+            assert!(community[..5].iter().all(|&c| c == community[0]));
+            assert!(community[5..10].iter().all(|&c| c == community[5]));
+            assert_ne!(community[0], community[5]);
+            assert_eq!(to_groups(&community, 64), {
+                let mut groups = vec![0b011; 5];
+                groups.extend([0b101; 5]);
+                groups.push(1);
+                (groups, 3)
+            });
+        }
+        assert_eq!(to_groups(&[0, 0, 1, 1, 1], 2), (vec![1, 1, 3, 3, 3], 2));
+    }
+}
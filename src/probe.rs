@@ -0,0 +1,157 @@
+//! the likelihood landscape around a configuration: the log-likelihood difference of every
+//! single node move, i.e. adding or removing any node to or from any non-root group. If no
+//! move improves the log-likelihood the configuration is a local optimum of single node moves,
+//! so a chain stuck there is not just mixing slowly.
+use crate::core::hcg;
+use crate::likelihood::Likelihood;
+use crate::multi_group_model::Node;
+use crate::rng::SamplerRng;
+use crate::{Float, HierarchicalModel};
+use std::fmt;
+use std::io::{self, Write};
+
+/// a node move away from the probed configuration
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ProbedMove {
+    pub node: Node,
+    pub group: usize,
+    pub add: bool, // false: remove the node from the group
+    pub delta_log_like: f64,
+}
+
+/// distribution of the log-likelihood differences of `HierarchicalModel::probe`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProbeSummary {
+    pub moves: usize,
+    pub improving: usize, // moves with a positive difference
+    pub best: Option<ProbedMove>,
+    /// differences at the 0, 5, 25, 50, 75, 95 and 100th percentile
+    pub quantiles: Vec<f64>,
+}
+
+const PERCENTILES: [usize; 7] = [0, 5, 25, 50, 75, 95, 100];
+
+impl ProbeSummary {
+    pub fn new(moves: &[ProbedMove]) -> Self {
+        let mut deltas: Vec<f64> = moves.iter().map(|m| m.delta_log_like).collect();
+        deltas.sort_by(f64::total_cmp);
+        let quantiles = match deltas.len() {
+            0 => Vec::new(),
+            n => PERCENTILES
+                .iter()
+                .map(|&p| deltas[p * (n - 1) / 100])
+                .collect(),
+        };
+        Self {
+            moves: moves.len(),
+            improving: deltas.iter().filter(|&&d| d > 0.0).count(),
+            best: moves
+                .iter()
+                .copied()
+                .max_by(|a, b| a.delta_log_like.total_cmp(&b.delta_log_like)),
+            quantiles,
+        }
+    }
+}
+
+impl fmt::Display for ProbeSummary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "moves: {} improving: {}", self.moves, self.improving)?;
+        if let Some(best) = self.best {
+            writeln!(
+                f,
+                "best: {} node {} {} group {}: {:.4}",
+                if best.add { "add" } else { "remove" },
+                best.node,
+                if best.add { "to" } else { "from" },
+                best.group,
+                best.delta_log_like
+            )?;
+        }
+        for (p, q) in PERCENTILES.iter().zip(&self.quantiles) {
+            writeln!(f, "{:>3}%: {:.4}", p, q)?;
+        }
+        Ok(())
+    }
+}
+
+/// `node,group,move,delta_log_like` for every move
+pub fn write_csv<W: Write>(mut w: W, moves: &[ProbedMove]) -> io::Result<()> {
+    writeln!(w, "node,group,move,delta_log_like")?;
+    for m in moves {
+        writeln!(
+            w,
+            "{},{},{},{}",
+            m.node,
+            m.group,
+            if m.add { "add" } else { "remove" },
+            m.delta_log_like
+        )?;
+    }
+    w.flush()
+}
+
+impl<F: Float, L: Likelihood, R: SamplerRng> HierarchicalModel<F, L, R> {
+    /// the log-likelihood difference of every single node move from the current configuration,
+    /// by node and then group. The configuration is left as it is.
+    pub fn probe(&self) -> Vec<ProbedMove> {
+        let mut model = self.model.clone();
+        let num_groups = model.num_groups();
+        let mut moves = Vec::with_capacity(model.groups().len() * (num_groups - 1));
+        let (mut hcg_edges, mut hcg_pairs) = (self.hcg_edges.clone(), self.hcg_pairs.clone());
+        for u in 0..model.groups().len() {
+            let old = model.groups[u];
+            for group in 1..num_groups {
+                // the deltas only depend on the groups of the nodes, not on the member lists
+                model.groups[u] = old ^ (1 << group);
+                let (pairs, edges) = hcg::node_move_deltas(&self.network, &model, u as Node, old);
+                for g in 0..num_groups {
+                    hcg_pairs[g] = self.hcg_pairs[g].wrapping_add_signed(pairs[g]);
+                    hcg_edges[g] = self.hcg_edges[g].wrapping_add_signed(edges[g]);
+                }
+                let log_like: F = self.likelihood.log_like(&hcg_edges, &hcg_pairs);
+                moves.push(ProbedMove {
+                    node: u as Node,
+                    group,
+                    add: (old >> group) & 1 == 0,
+                    delta_log_like: (log_like - self.log_like).to_f64(),
+                });
+            }
+            model.groups[u] = old;
+        }
+        moves
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::_example_model;
+
+    #[test]
+    fn single_moves() {
+        let hcp = _example_model(b"seed: 3\ninitial_num_groups: 3\n");
+        let moves = hcp.probe();
+        assert_eq!(moves.len(), 25 * 2);
+
+        // every difference is that of actually making the move
+        for m in &moves {
+            let mut groups = hcp.model().groups().to_vec();
+            groups[m.node as usize] ^= 1 << m.group;
+            assert_eq!(groups[m.node as usize] >> m.group & 1 == 1, m.add);
+            let mut moved = hcp.clone();
+            moved.set_configuration(groups, 3);
+            assert!((moved.log_like() - hcp.log_like() - m.delta_log_like).abs() < 1e-9);
+        }
+
+        let summary = ProbeSummary::new(&moves);
+        assert_eq!(summary.moves, 50);
+        assert_eq!(
+            summary.improving,
+            moves.iter().filter(|m| m.delta_log_like > 0.0).count()
+        );
+        assert_eq!(summary.quantiles.len(), PERCENTILES.len());
+        assert_eq!(summary.quantiles[6], summary.best.unwrap().delta_log_like);
+        assert!(summary.quantiles.windows(2).all(|w| w[0] <= w[1]));
+    }
+}
@@ -0,0 +1,53 @@
+//! per-phase timing instrumentation, compiled only with the `profiling` feature.
+//! Totals are global, so with several chains they are summed over all threads.
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy)]
+pub enum Phase {
+    /// generating a proposal (includes its random draws)
+    Proposal,
+    UpdateHcgProps,
+    CalcLoglike,
+    /// random draws, both during proposal generation and acceptance
+    Rng,
+    /// periodic status output, snapshots and traces
+    Logging,
+}
+
+const PHASES: [(Phase, &str); 5] = [
+    (Phase::Proposal, "proposal generation"),
+    (Phase::UpdateHcgProps, "update_hcg_props"),
+    (Phase::CalcLoglike, "calc_loglike"),
+    (Phase::Rng, "rng"),
+    (Phase::Logging, "logging"),
+];
+
+static TOTAL_NANOS: [AtomicU64; 5] = [const { AtomicU64::new(0) }; 5];
+static CALLS: [AtomicU64; 5] = [const { AtomicU64::new(0) }; 5];
+
+/// add the time elapsed since `start` to `phase`
+pub fn record(phase: Phase, start: Instant) {
+    let nanos = start.elapsed().as_nanos() as u64;
+    TOTAL_NANOS[phase as usize].fetch_add(nanos, Ordering::Relaxed);
+    CALLS[phase as usize].fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn total(phase: Phase) -> Duration {
+    Duration::from_nanos(TOTAL_NANOS[phase as usize].load(Ordering::Relaxed))
+}
+
+pub fn print_report() {
+    println!("time per phase:");
+    for (phase, name) in PHASES {
+        let calls = CALLS[phase as usize].load(Ordering::Relaxed);
+        let t = total(phase);
+        println!(
+            "  {:<20} {:>12.3} s {:>14} calls {:>10.1} ns/call",
+            name,
+            t.as_secs_f64(),
+            calls,
+            t.as_nanos() as f64 / calls.max(1) as f64
+        );
+    }
+}
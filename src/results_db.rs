@@ -0,0 +1,137 @@
+//! SQLite database of runs and their snapshots, `results.db` in the save directory.
+//! Every run is a row in `runs`, keyed by `id`; its snapshots reference it by `run_id`:
+//! ```sql
+//! SELECT runs.name, MAX(snapshots.log_like) FROM snapshots JOIN runs ON runs.id = run_id
+//! GROUP BY run_id;
+//! ```
+use crate::parameters::Parameters;
+use crate::{Float, HierarchicalModel};
+use rusqlite::{params, Connection};
+use std::path::Path;
+use std::time;
+
+const SCHEMA: &str = "
+PRAGMA journal_mode = WAL;
+CREATE TABLE IF NOT EXISTS runs (
+    id INTEGER PRIMARY KEY,
+    name TEXT NOT NULL,
+    seed INTEGER NOT NULL,
+    parameters TEXT NOT NULL,
+    started REAL NOT NULL,
+    final_log_like REAL,
+    final_num_groups INTEGER
+);
+CREATE TABLE IF NOT EXISTS snapshots (
+    run_id INTEGER NOT NULL REFERENCES runs(id),
+    iteration INTEGER NOT NULL,
+    log_like REAL NOT NULL,
+    num_groups INTEGER NOT NULL,
+    config TEXT NOT NULL,
+    PRIMARY KEY (run_id, iteration)
+);
+";
+
+fn now() -> f64 {
+    time::SystemTime::now()
+        .duration_since(time::UNIX_EPOCH)
+        .map_or(f64::NAN, |d| d.as_secs_f64())
+}
+
+/// a run recorded in the results database
+pub struct ResultsDb {
+    conn: Connection,
+    run_id: i64,
+}
+
+impl ResultsDb {
+    /// open or create the database at `path` and add a run for `params`
+    pub fn open(path: &Path, params: &Parameters) -> Result<Self, String> {
+        Self::with_connection(Connection::open(path).map_err(|e| e.to_string())?, params)
+    }
+
+    fn with_connection(conn: Connection, params: &Parameters) -> Result<Self, String> {
+        conn.execute_batch(SCHEMA).map_err(|e| e.to_string())?;
+        conn.execute(
+            "INSERT INTO runs (name, seed, parameters, started) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                params.saved_data_name,
+                // stored bit for bit, SQLite integers are signed
+                params.seed.unwrap_or(0) as i64,
+                format!("{:?}", params),
+                now()
+            ],
+        )
+        .map_err(|e| e.to_string())?;
+        let run_id = conn.last_insert_rowid();
+        Ok(Self { conn, run_id })
+    }
+
+    pub fn run_id(&self) -> i64 {
+        self.run_id
+    }
+
+    pub fn record<F: Float>(
+        &mut self,
+        iteration: u64,
+        hcp: &HierarchicalModel<F>,
+    ) -> Result<(), String> {
+        let config = hcp
+            .model()
+            .groups()
+            .iter()
+            .map(|g| g.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.conn
+            .execute(
+                "INSERT INTO snapshots (run_id, iteration, log_like, num_groups, config)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    self.run_id,
+                    iteration as i64,
+                    hcp.log_like(),
+                    hcp.model().num_groups() as i64,
+                    config
+                ],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// store the final state of the run
+    pub fn finish<F: Float>(&mut self, hcp: &HierarchicalModel<F>) -> Result<(), String> {
+        self.conn
+            .execute(
+                "UPDATE runs SET final_log_like = ?1, final_num_groups = ?2 WHERE id = ?3",
+                params![hcp.log_like(), hcp.model().num_groups() as i64, self.run_id],
+            )
+            .map_err(|e| e.to_string())?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::_example_parameters;
+
+    #[test]
+    fn runs_and_snapshots() {
+        let params = _example_parameters(b"").unwrap();
+        let hcp: HierarchicalModel = HierarchicalModel::with_parameters(&params).unwrap();
+        let mut db =
+            ResultsDb::with_connection(Connection::open_in_memory().unwrap(), &params).unwrap();
+        db.record(0, &hcp).unwrap();
+        db.record(1500, &hcp).unwrap();
+        db.finish(&hcp).unwrap();
+        let (count, log_like): (i64, f64) = db
+            .conn
+            .query_row(
+                "SELECT COUNT(*), MAX(log_like) FROM snapshots WHERE run_id = ?1",
+                params![db.run_id()],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert_eq!((count, log_like), (2, hcp.log_like()));
+    }
+}
@@ -0,0 +1,117 @@
+//! the random numbers of the sampler: `SamplerRng` has the operations it draws, implemented by
+//! the `mt19937` crate (with the `rand` semantics of `gen_range`, `gen_bool` and `shuffle`) and
+//! by the GSL compatible generator. `DefaultRng` is the one selected by the `gsl_compat`
+//! feature; other generators, e.g. scripted ones for tests, can be plugged into
+//! `HierarchicalModel` through its `R` parameter.
+use crate::gsl_rng_compat;
+use core::ops::Range;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+/// the generator of `HierarchicalModel` unless chosen otherwise
+#[cfg(feature = "gsl_compat")]
+pub type DefaultRng = gsl_rng_compat::MT19937;
+#[cfg(not(feature = "gsl_compat"))]
+pub type DefaultRng = mt19937::MT19937;
+
+/// operations of the sampler on its random number generator
+pub trait SamplerRng: Clone {
+    fn seed_from_u64(seed: u64) -> Self;
+    /// uniform integer in `range`, which must not be empty
+    fn uniform_int(&mut self, range: Range<usize>) -> usize;
+    /// `true` with probability `p`
+    fn bernoulli(&mut self, p: f64) -> bool;
+    /// uniform in [0, 1)
+    fn uniform(&mut self) -> f64;
+    /// uniform random permutation of `slice`
+    fn shuffle<T>(&mut self, slice: &mut [T]);
+}
+
+impl SamplerRng for mt19937::MT19937 {
+    fn seed_from_u64(seed: u64) -> Self {
+        <Self as SeedableRng>::seed_from_u64(seed)
+    }
+
+    fn uniform_int(&mut self, range: Range<usize>) -> usize {
+        self.gen_range(range)
+    }
+
+    fn bernoulli(&mut self, p: f64) -> bool {
+        self.gen_bool(p)
+    }
+
+    fn uniform(&mut self) -> f64 {
+        self.gen()
+    }
+
+    fn shuffle<T>(&mut self, slice: &mut [T]) {
+        slice.shuffle(self)
+    }
+}
+
+impl SamplerRng for gsl_rng_compat::MT19937 {
+    fn seed_from_u64(seed: u64) -> Self {
+        Self::seed_from_u64(seed)
+    }
+
+    fn uniform_int(&mut self, range: Range<usize>) -> usize {
+        self.gen_range(range)
+    }
+
+    fn bernoulli(&mut self, p: f64) -> bool {
+        self.gen_bool(p)
+    }
+
+    fn uniform(&mut self) -> f64 {
+        self.uniform()
+    }
+
+    /// `rand`'s shuffle on the raw GSL stream, as the sampler has always done
+    fn shuffle<T>(&mut self, slice: &mut [T]) {
+        slice.shuffle(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// draws through the trait, so both backends go through the same code
+    fn draws<R: SamplerRng>(seed: u64) -> (Vec<usize>, Vec<bool>, Vec<u32>) {
+        let mut rng = R::seed_from_u64(seed);
+        let ints = (0..100).map(|i| rng.uniform_int(i..i + 7)).collect();
+        let bools = (0..100).map(|_| rng.bernoulli(0.3)).collect();
+        let mut order: Vec<u32> = (0..20).collect();
+        rng.shuffle(&mut order);
+        assert!((0..100).all(|_| (0.0..1.0).contains(&rng.uniform())));
+        (ints, bools, order)
+    }
+
+    #[test]
+    fn backends() {
+        for (ints, bools, mut order) in [
+            draws::<mt19937::MT19937>(5),
+            draws::<gsl_rng_compat::MT19937>(5),
+        ] {
+            assert!(ints
+                .iter()
+                .enumerate()
+                .all(|(i, &k)| (i..i + 7).contains(&k)));
+            assert!(bools.iter().any(|&b| b) && bools.iter().any(|&b| !b));
+            order.sort();
+            assert_eq!(order, (0..20).collect::<Vec<_>>());
+        }
+        assert_eq!(draws::<DefaultRng>(5), draws::<DefaultRng>(5));
+
+        // the trait keeps the semantics of the generators' own methods
+        let mut a = gsl_rng_compat::MT19937::seed_from_u64(9);
+        let mut b = a.clone();
+        assert_eq!(
+            SamplerRng::uniform_int(&mut a, 1..5),
+            b.gen_range(1..5usize)
+        );
+        let mut a = <mt19937::MT19937 as SamplerRng>::seed_from_u64(9);
+        let mut b = a.clone();
+        assert_eq!(SamplerRng::bernoulli(&mut a, 0.4), b.gen_bool(0.4));
+    }
+}
@@ -0,0 +1,150 @@
+//! matched draws of the two random number backends, for the operations the sampler uses:
+//! the `mt19937` crate with the `rand::Rng` methods (default) and the GSL compatible generator
+//! of the `gsl_compat` feature. A chain is only reproducible across the two features as far as
+//! every operation it draws gives the same values on both.
+//!
+//! The backends differ in two places, compared separately:
+//! - seeding: both are seeded from the same `u64` (`Seeding::Native`), but `gsl_rng_set`
+//!   expands it differently from the `mt19937` crate, so raw words can differ from the start
+//! - operations: on one shared raw stream (`Seeding::Shared`), `gen_range` and `gen_bool`
+//!   follow GSL in `gsl_compat` (one 32 bit word per draw, `uniform_int` rejection and
+//!   `uniform() < p`) and `rand` otherwise (64 bit words for `usize` ranges and probabilities),
+//!   so they diverge even on identical words
+use crate::gsl_rng_compat::MT19937 as GslMT19937;
+use rand::{Rng, RngCore, SeedableRng};
+use std::fmt;
+use std::io::{self, Write};
+
+/// an operation of the sampler on its random number generator
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Op {
+    /// a raw 32 bit word
+    Word,
+    /// `gen_range(0..n)`
+    Range(usize),
+    /// `gen_bool(p)`
+    Bool(f64),
+}
+
+impl fmt::Display for Op {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Op::Word => write!(f, "next_u32"),
+            Op::Range(n) => write!(f, "gen_range(0..{})", n),
+            Op::Bool(p) => write!(f, "gen_bool({})", p),
+        }
+    }
+}
+
+/// the operations compared by `report`: group and node choices of the sampler and its
+/// acceptance tests
+pub const OPS: [Op; 8] = [
+    Op::Word,
+    Op::Range(2),
+    Op::Range(3),
+    Op::Range(64),
+    Op::Range(1000),
+    Op::Bool(0.5),
+    Op::Bool(0.01),
+    Op::Bool(0.999),
+];
+
+/// how the two sides of a comparison are seeded
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Seeding {
+    /// each backend from the seed, as the sampler does
+    Native,
+    /// both from the GSL seeded stream, so only the operations differ
+    Shared,
+}
+
+/// `draws` matched draws of one operation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Comparison {
+    pub op: Op,
+    pub seeding: Seeding,
+    pub draws: usize,
+    /// draws giving the same value on both backends
+    pub matching: usize,
+    pub first_divergence: Option<usize>,
+}
+
+fn draw_gsl(rng: &mut GslMT19937, op: Op) -> u64 {
+    match op {
+        Op::Word => rng.next_u32() as u64,
+        Op::Range(n) => rng.gen_range(0..n) as u64,
+        Op::Bool(p) => rng.gen_bool(p) as u64,
+    }
+}
+
+fn draw_rand<R: Rng>(rng: &mut R, op: Op) -> u64 {
+    match op {
+        Op::Word => rng.next_u32() as u64,
+        Op::Range(n) => Rng::gen_range(rng, 0..n) as u64,
+        Op::Bool(p) => Rng::gen_bool(rng, p) as u64,
+    }
+}
+
+/// draw `op` `draws` times from both backends, each freshly seeded with `seed`
+pub fn compare(op: Op, seed: u64, draws: usize, seeding: Seeding) -> Comparison {
+    let mut gsl = GslMT19937::seed_from_u64(seed);
+    let mut other: Box<dyn RngCore> = match seeding {
+        Seeding::Native => Box::new(<mt19937::MT19937 as SeedableRng>::seed_from_u64(seed)),
+        Seeding::Shared => Box::new(gsl.clone()),
+    };
+    let mut matching = 0;
+    let mut first_divergence = None;
+    for i in 0..draws {
+        if draw_gsl(&mut gsl, op) == draw_rand(&mut other, op) {
+            matching += 1;
+        } else if first_divergence.is_none() {
+            first_divergence = Some(i);
+        }
+    }
+    Comparison {
+        op,
+        seeding,
+        draws,
+        matching,
+        first_divergence,
+    }
+}
+
+/// compare every operation of `OPS` with both seedings and write a table of the results
+pub fn report<W: Write>(mut w: W, seed: u64, draws: usize) -> io::Result<()> {
+    writeln!(w, "seeding op draws matching first_divergence")?;
+    for seeding in [Seeding::Native, Seeding::Shared] {
+        for op in OPS {
+            let c = compare(op, seed, draws, seeding);
+            let first = c
+                .first_divergence
+                .map_or(String::from("-"), |i| i.to_string());
+            let seeding = format!("{:?}", c.seeding).to_lowercase();
+            writeln!(w, "{} {} {} {} {}", seeding, op, c.draws, c.matching, first)?;
+        }
+    }
+    w.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shared_stream() {
+        // the raw words are the same, only the operations differ
+        let words = compare(Op::Word, 7, 1000, Seeding::Shared);
+        assert_eq!((words.matching, words.first_divergence), (1000, None));
+        for op in [Op::Range(10), Op::Bool(0.5)] {
+            let c = compare(op, 7, 1000, Seeding::Shared);
+            assert!(c.first_divergence.is_some(), "{}", op);
+            assert!(c.matching < c.draws);
+        }
+
+        let mut buf = Vec::new();
+        report(&mut buf, 7, 100).unwrap();
+        let out = String::from_utf8(buf).unwrap();
+        assert_eq!(out.lines().count(), 1 + 2 * OPS.len());
+        assert!(out.contains("\nshared next_u32 100 100 -\n"));
+    }
+}
@@ -0,0 +1,124 @@
+//! Reproducible sub-seeds.
+//!
+//! Every stochastic subsystem that needs its own random number generator derives its seed from
+//! the single user provided seed as
+//! `splitmix64(splitmix64(splitmix64(seed) ^ stream_tag) ^ index)`,
+//! where `stream_tag` identifies the subsystem (see `Stream`) and `index` the user within it,
+//! e.g. the chain number. Tags are fixed, so derived seeds are stable between versions.
+//! A plain single chain run uses the user provided seed directly.
+
+/// a subsystem drawing sub-seeds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stream {
+    /// parallel chains and MPI ranks, by chain number. Tag 1.
+    Chain,
+    /// coarse-to-fine stages, by stage number. Tag 2.
+    Stage,
+    /// holdout splits, by split number. Tag 3.
+    Holdout,
+    /// synthetic network generators, by network number. Tag 4.
+    Synthetic,
+    /// generator restarts at checkpoints, by iteration. Tag 5.
+    Checkpoint,
+    /// node order permutations (`permute_nodes`), always index 0. Tag 6.
+    Permutation,
+}
+
+impl Stream {
+    fn tag(self) -> u64 {
+        match self {
+            Stream::Chain => 1,
+            Stream::Stage => 2,
+            Stream::Holdout => 3,
+            Stream::Synthetic => 4,
+            Stream::Checkpoint => 5,
+            Stream::Permutation => 6,
+        }
+    }
+}
+
+impl std::fmt::Display for Stream {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(match self {
+            Stream::Chain => "chain",
+            Stream::Stage => "stage",
+            Stream::Holdout => "holdout",
+            Stream::Synthetic => "synthetic",
+            Stream::Checkpoint => "checkpoint",
+            Stream::Permutation => "permutation",
+        })
+    }
+}
+
+fn splitmix64(z: u64) -> u64 {
+    let z = z.wrapping_add(0x9e3779b97f4a7c15);
+    let z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    let z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+/// the seed for user `index` of `stream`
+pub fn sub_seed(seed: u64, stream: Stream, index: u64) -> u64 {
+    splitmix64(splitmix64(splitmix64(seed) ^ stream.tag()) ^ index)
+}
+
+/// derives sub-seeds from the user provided seed and remembers every derived seed,
+/// so it can be written to the run manifest
+#[derive(Debug, Clone)]
+pub struct SeedStream {
+    seed: u64,
+    derived: Vec<(Stream, u64, u64)>,
+}
+
+impl SeedStream {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            derived: Vec::new(),
+        }
+    }
+
+    /// the user provided seed
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// the seed for user `index` of `stream`
+    pub fn derive(&mut self, stream: Stream, index: u64) -> u64 {
+        let seed = sub_seed(self.seed, stream, index);
+        if !self.derived.contains(&(stream, index, seed)) {
+            self.derived.push((stream, index, seed));
+        }
+        seed
+    }
+
+    /// `(stream, index, seed)` for every seed derived so far, in order of derivation
+    pub fn derived(&self) -> &[(Stream, u64, u64)] {
+        &self.derived
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn derive() {
+        let mut seeds = SeedStream::new(5);
+        let a = seeds.derive(Stream::Chain, 0);
+        let b = seeds.derive(Stream::Chain, 1);
+        let c = seeds.derive(Stream::Stage, 0);
+        assert!(a != b && a != c && b != c);
+        assert_eq!(seeds.derive(Stream::Chain, 0), a);
+        assert_eq!(sub_seed(5, Stream::Chain, 1), b);
+        assert_ne!(sub_seed(6, Stream::Chain, 1), b);
+        assert_eq!(
+            seeds.derived(),
+            [
+                (Stream::Chain, 0, a),
+                (Stream::Chain, 1, b),
+                (Stream::Stage, 0, c)
+            ]
+        );
+    }
+}
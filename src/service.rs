@@ -0,0 +1,530 @@
+//! HTTP service mode (`serve` feature): networks are submitted with their parameters, sampled
+//! in background threads, polled and their results downloaded. Endpoints:
+//! - `POST /runs?format=<gml|edgelist|mtx|pajek>&<parameter>=<value>...` with the network file
+//!   as body starts a run and answers `{"id":<id>}`. Parameters are those of the parameters
+//!   file, except for file paths.
+//! - `GET /runs/<id>`: state and progress as JSON
+//! - `GET /runs/<id>/groups`: the best configuration, one line of group bits per node, once
+//!   the run has finished or was cancelled
+//! - `DELETE /runs/<id>`: cancel the run
+//!
+//! Every connection is handled in its own thread and closed after one request. `Limits`
+//! bounds the connections, runs and run sizes; requests over a limit are answered 503 or 400.
+use crate::driver::{Progress, RunDriver, YIELD_INTERVAL};
+use crate::multi_group_model::Groups;
+use crate::network::NetworkData;
+use crate::parameters::Parameters;
+use crate::trace::json_string;
+use crate::HierarchicalModel;
+use std::collections::BTreeMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// resource limits of a `Service`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    /// largest accepted request body in bytes
+    pub max_body: usize,
+    /// connections handled at the same time, more are answered 503
+    pub max_connections: usize,
+    /// time allowed for every read and write of a connection
+    pub timeout: Duration,
+    /// runs sampling at the same time, more submissions are answered 503
+    pub max_running: usize,
+    /// stopped runs kept for polling, the oldest are forgotten beyond this
+    pub max_stopped: usize,
+    /// largest `max_itr` of a run
+    pub max_itr: u64,
+    /// largest network of a run
+    pub max_nodes: usize,
+    /// `ln_fact_table_max_mb` of a run is lowered to this
+    pub ln_fact_table_max_mb: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_body: 64 << 20,
+            max_connections: 64,
+            timeout: Duration::from_secs(30),
+            max_running: thread::available_parallelism().map_or(4, |n| n.get()),
+            max_stopped: 1000,
+            max_itr: 1_000_000_000,
+            max_nodes: 100_000,
+            ln_fact_table_max_mb: 256,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum State {
+    Running,
+    Done,
+    Cancelled,
+    Failed(String),
+}
+
+#[derive(Debug)]
+struct Status {
+    state: State,
+    progress: Option<Progress>,
+    /// best configuration, once the run has stopped
+    groups: Vec<Groups>,
+}
+
+struct Run {
+    status: Mutex<Status>,
+    cancel: AtomicBool,
+}
+
+/// an HTTP response: status code, content type and body
+pub type Response = (u16, &'static str, String);
+
+/// the runs submitted to a server
+#[derive(Default)]
+pub struct Service {
+    limits: Limits,
+    /// runs by id, ids are never reused
+    runs: Mutex<BTreeMap<usize, Arc<Run>>>,
+    next_id: AtomicUsize,
+    connections: Arc<AtomicUsize>,
+}
+
+/// a connection counted against `Limits::max_connections` until dropped
+struct ConnectionSlot(Arc<AtomicUsize>);
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+fn error(code: u16, msg: impl std::fmt::Display) -> Response {
+    (
+        code,
+        "application/json",
+        format!("{{\"error\":{}}}", json_string(&msg.to_string())),
+    )
+}
+
+/// decode `%xx` escapes and `+` of a query string component
+fn percent_decode(s: &str) -> Result<String, String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut it = s.bytes();
+    while let Some(b) = it.next() {
+        match b {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex = [it.next(), it.next()];
+                let value = match hex {
+                    [Some(h), Some(l)] => std::str::from_utf8(&[h, l])
+                        .ok()
+                        .and_then(|h| u8::from_str_radix(h, 16).ok()),
+                    _ => None,
+                };
+                bytes.push(value.ok_or(format!("bad escape in {}", s))?);
+            }
+            b => bytes.push(b),
+        }
+    }
+    String::from_utf8(bytes).map_err(|e| e.to_string())
+}
+
+/// the network in `body` and the parameters of a `POST /runs` query, within `limits`
+fn parse_submission(
+    query: &str,
+    body: &[u8],
+    limits: &Limits,
+) -> Result<(Parameters, NetworkData), String> {
+    let mut format = String::from("gml");
+    let mut lines = String::new();
+    for pair in query.split('&').filter(|p| !p.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        let (key, value) = (percent_decode(key)?, percent_decode(value)?);
+        if key == "format" {
+            format = value;
+        } else if key.contains("path") || key.contains("directory") || key.contains('\n') {
+            return Err(format!("parameter not allowed: {}", key));
+        } else if value.contains('\n') {
+            return Err(format!("bad value for {}", key));
+        } else {
+            lines += &format!("{}: {}\n", key, value);
+        }
+    }
+    let mut params = Parameters::load(lines.as_bytes())?.fix_seed();
+    if params.max_itr > limits.max_itr {
+        return Err(format!("max_itr is limited to {}", limits.max_itr));
+    }
+    params.ln_fact_table_max_mb = params.ln_fact_table_max_mb.min(limits.ln_fact_table_max_mb);
+    let network = match format.as_str() {
+        "gml" => NetworkData::parse_gml(std::str::from_utf8(body).map_err(|e| e.to_string())?),
+        "edgelist" => NetworkData::read_edge_list(body),
+        "mtx" => NetworkData::read_mtx(body),
+        "pajek" => NetworkData::read_pajek(body),
+        _ => Err(format!("unknown network format: {}", format)),
+    }?;
+    if network.node_count() > limits.max_nodes {
+        return Err(format!(
+            "networks are limited to {} nodes",
+            limits.max_nodes
+        ));
+    }
+    Ok((params, network))
+}
+
+/// sample in the calling thread, publishing progress to `run`
+fn sample(run: &Run, params: &Parameters, network: NetworkData) {
+    let hcp: HierarchicalModel = match HierarchicalModel::with_network(params, Arc::new(network)) {
+        Ok(hcp) => hcp,
+        Err(e) => {
+            run.status.lock().unwrap().state = State::Failed(e);
+            return;
+        }
+    };
+    let mut driver = RunDriver::new(hcp, params.max_itr);
+    driver.set_move_schedule(params.move_schedule.clone());
+    while !driver.is_done() && !run.cancel.load(Ordering::Relaxed) {
+        let progress = driver.advance(YIELD_INTERVAL);
+        run.status.lock().unwrap().progress = Some(progress);
+    }
+    let cancelled = !driver.is_done();
+    let groups = driver.into_best().model().groups().to_vec();
+    let mut status = run.status.lock().unwrap();
+    status.groups = groups;
+    status.state = if cancelled {
+        State::Cancelled
+    } else {
+        State::Done
+    };
+}
+
+impl Service {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_limits(limits: Limits) -> Self {
+        Self {
+            limits,
+            ..Self::default()
+        }
+    }
+
+    fn run(&self, id: &str) -> Option<(usize, Arc<Run>)> {
+        let id: usize = id.parse().ok()?;
+        let run = self.runs.lock().unwrap().get(&id).cloned()?;
+        Some((id, run))
+    }
+
+    /// register a new run unless `Limits::max_running` runs are sampling, forgetting the
+    /// oldest stopped runs beyond `Limits::max_stopped`
+    fn add_run(&self) -> Option<(usize, Arc<Run>)> {
+        let mut runs = self.runs.lock().unwrap();
+        let running = |run: &Run| run.status.lock().unwrap().state == State::Running;
+        let stopped: Vec<usize> = runs
+            .iter()
+            .filter(|(_, run)| !running(run))
+            .map(|(&id, _)| id)
+            .collect();
+        if runs.len() - stopped.len() >= self.limits.max_running {
+            return None;
+        }
+        let excess = (stopped.len() + 1).saturating_sub(self.limits.max_stopped);
+        for id in &stopped[..excess.min(stopped.len())] {
+            runs.remove(id);
+        }
+        let run = Arc::new(Run {
+            status: Mutex::new(Status {
+                state: State::Running,
+                progress: None,
+                groups: Vec::new(),
+            }),
+            cancel: AtomicBool::new(false),
+        });
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        runs.insert(id, run.clone());
+        Some((id, run))
+    }
+
+    /// a slot for a new connection, unless `Limits::max_connections` are open
+    fn open_connection(&self) -> Option<ConnectionSlot> {
+        let open = self.connections.fetch_add(1, Ordering::Relaxed);
+        let slot = ConnectionSlot(self.connections.clone());
+        (open < self.limits.max_connections).then_some(slot)
+    }
+
+    /// answer one request. `target` is the request path with its query string.
+    pub fn handle(&self, method: &str, target: &str, body: &[u8]) -> Response {
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        match (method, &segments[..]) {
+            ("POST", ["runs"]) => {
+                let (params, network) = match parse_submission(query, body, &self.limits) {
+                    Ok(submission) => submission,
+                    Err(e) => return error(400, e),
+                };
+                let Some((id, run)) = self.add_run() else {
+                    return error(503, "too many running runs");
+                };
+                thread::spawn(move || sample(&run, &params, network));
+                (202, "application/json", format!("{{\"id\":{}}}", id))
+            }
+            ("GET", ["runs", id]) => match self.run(id) {
+                Some((id, run)) => {
+                    let status = run.status.lock().unwrap();
+                    let (state, message) = match &status.state {
+                        State::Running => ("running", None),
+                        State::Done => ("done", None),
+                        State::Cancelled => ("cancelled", None),
+                        State::Failed(e) => ("failed", Some(e)),
+                    };
+                    let mut json = format!("{{\"id\":{},\"state\":\"{}\"", id, state);
+                    if let Some(p) = status.progress {
+                        json += &format!(
+                            ",\"iteration\":{},\"max_itr\":{},\"log_like\":{},\
+                             \"best_log_like\":{},\"num_groups\":{}",
+                            p.iteration, p.max_itr, p.log_like, p.best_log_like, p.num_groups
+                        );
+                    }
+                    if let Some(e) = message {
+                        json += &format!(",\"error\":{}", json_string(e));
+                    }
+                    (200, "application/json", json + "}")
+                }
+                None => error(404, "no such run"),
+            },
+            ("GET", ["runs", id, "groups"]) => match self.run(id) {
+                Some((_, run)) => {
+                    let status = run.status.lock().unwrap();
+                    match status.state {
+                        State::Done | State::Cancelled => {
+                            let lines: Vec<String> =
+                                status.groups.iter().map(|g| g.to_string()).collect();
+                            (200, "text/plain", lines.join("\n") + "\n")
+                        }
+                        State::Running => error(409, "run has not finished"),
+                        State::Failed(_) => error(409, "run failed"),
+                    }
+                }
+                None => error(404, "no such run"),
+            },
+            ("DELETE", ["runs", id]) => match self.run(id) {
+                Some((_, run)) => {
+                    run.cancel.store(true, Ordering::Relaxed);
+                    (202, "application/json", String::from("{}"))
+                }
+                None => error(404, "no such run"),
+            },
+            _ => error(404, "not found"),
+        }
+    }
+
+    /// read one request from `stream` and answer it
+    fn connection(&self, stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(self.limits.timeout))?;
+        stream.set_write_timeout(Some(self.limits.timeout))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        let mut words = line.split_whitespace();
+        let (method, target) = (
+            words.next().unwrap_or("").to_owned(),
+            words.next().unwrap_or("").to_owned(),
+        );
+        let mut content_length = 0;
+        loop {
+            line.clear();
+            if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                if name.trim().eq_ignore_ascii_case("content-length") {
+                    content_length = value.trim().parse().unwrap_or(usize::MAX);
+                }
+            }
+        }
+        let response = if content_length > self.limits.max_body {
+            error(413, "request body too large")
+        } else {
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body)?;
+            self.handle(&method, &target, &body)
+        };
+        respond(stream, response)
+    }
+}
+
+/// write `response` to `stream`
+fn respond(mut stream: TcpStream, (code, content_type, body): Response) -> io::Result<()> {
+    let reason = match code {
+        200 => "OK",
+        202 => "Accepted",
+        400 => "Bad Request",
+        404 => "Not Found",
+        409 => "Conflict",
+        413 => "Payload Too Large",
+        503 => "Service Unavailable",
+        _ => "",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        code,
+        reason,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+/// serve the endpoints on `addr` within `limits` until the process ends
+pub fn serve(addr: impl ToSocketAddrs, limits: Limits) -> io::Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    eprintln!("listening on {}", listener.local_addr()?);
+    let service = Arc::new(Service::with_limits(limits));
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let Some(slot) = service.open_connection() else {
+            // fits in the send buffer, the timeout only guards against a full one
+            stream.set_write_timeout(Some(Duration::from_secs(1)))?;
+            let _ = respond(stream, error(503, "too many connections"));
+            continue;
+        };
+        let service = service.clone();
+        thread::spawn(move || {
+            let _slot = slot;
+            if let Err(e) = service.connection(stream) {
+                eprintln!("connection failed: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn runs() {
+        let service = Service::new();
+        let gml = std::fs::read("examples/clique_cp.gml").unwrap();
+        assert_eq!(service.handle("POST", "/runs?gml_path=%2Fetc", &gml).0, 400);
+        assert_eq!(service.handle("POST", "/runs?format=csv", &gml).0, 400);
+        assert_eq!(service.handle("GET", "/runs/0", b"").0, 404);
+
+        let (code, _, body) =
+            service.handle("POST", "/runs?max_itr=3000&max_num_groups=4&seed=1", &gml);
+        assert_eq!((code, body.as_str()), (202, "{\"id\":0}"));
+        let status = loop {
+            let (code, _, status) = service.handle("GET", "/runs/0", b"");
+            assert_eq!(code, 200);
+            if !status.contains("running") {
+                break status;
+            }
+            thread::sleep(Duration::from_millis(10));
+        };
+        assert!(status.contains("\"state\":\"done\""), "{}", status);
+        assert!(status.contains("\"iteration\":3000"));
+        let (code, _, groups) = service.handle("GET", "/runs/0/groups", b"");
+        assert_eq!(code, 200);
+        assert_eq!(groups.lines().count(), 25);
+
+        // edge lists, cancelled before they finish
+        let (_, _, body) = service.handle("POST", "/runs?format=edgelist", b"0 1\n1 2\n");
+        assert_eq!(body, "{\"id\":1}");
+        assert_eq!(service.handle("DELETE", "/runs/1", b"").0, 202);
+        while service.handle("GET", "/runs/1", b"").2.contains("running") {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(service
+            .handle("GET", "/runs/1", b"")
+            .2
+            .contains("cancelled"));
+        assert_eq!(service.handle("GET", "/runs/1/groups", b"").0, 200);
+    }
+
+    #[test]
+    fn limits() {
+        let service = Service::with_limits(Limits {
+            max_running: 1,
+            max_stopped: 1,
+            max_itr: 10_000_000,
+            max_nodes: 3,
+            ..Limits::default()
+        });
+        let submit = |query: &str| service.handle("POST", query, b"0 1\n1 2\n");
+        let long = "/runs?format=edgelist&max_itr=10000000";
+        assert_eq!(
+            submit("/runs?format=edgelist&max_itr=10000001"),
+            error(400, "max_itr is limited to 10000000")
+        );
+        assert_eq!(
+            service.handle("POST", long, b"0 3\n").2,
+            "{\"error\":\"networks are limited to 3 nodes\"}"
+        );
+        let cancel = |id| {
+            service.handle("DELETE", &format!("/runs/{}", id), b"");
+            while service.run(id).unwrap().1.status.lock().unwrap().state == State::Running {
+                thread::sleep(Duration::from_millis(10));
+            }
+        };
+        assert_eq!(submit(long).2, "{\"id\":0}");
+        assert_eq!(submit(long), error(503, "too many running runs"));
+        cancel("0");
+        // the stopped run 0 is forgotten for run 1, ids are not reused
+        assert_eq!(submit(long).2, "{\"id\":1}");
+        assert_eq!(service.handle("GET", "/runs/0", b"").0, 404);
+        cancel("1");
+
+        // control characters are escaped
+        assert_eq!(error(400, "a\"b\n").2, "{\"error\":\"a\\\"b\\u000a\"}");
+        let (_, run) = service.run("1").unwrap();
+        run.status.lock().unwrap().state = State::Failed(String::from("line\nbreak"));
+        assert!(service
+            .handle("GET", "/runs/1", b"")
+            .2
+            .ends_with(",\"error\":\"line\\u000abreak\"}"));
+    }
+
+    #[test]
+    fn connection_limit() {
+        let service = Service::with_limits(Limits {
+            max_connections: 2,
+            ..Limits::default()
+        });
+        let first = service.open_connection();
+        assert!(first.is_some());
+        assert!(service.open_connection().is_some());
+        let second = service.open_connection();
+        assert!(second.is_some());
+        assert!(service.open_connection().is_none());
+        drop(first);
+        assert!(service.open_connection().is_some());
+    }
+
+    #[test]
+    fn http() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            Service::new().connection(stream).unwrap();
+        });
+        let mut client = TcpStream::connect(addr).unwrap();
+        write!(client, "GET /runs/7 HTTP/1.1\r\nHost: x\r\n\r\n").unwrap();
+        let mut response = String::new();
+        client.read_to_string(&mut response).unwrap();
+        server.join().unwrap();
+        assert!(response.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(response.ends_with("{\"error\":\"no such run\"}"));
+        assert_eq!(percent_decode("a%20b+c").unwrap(), "a b c");
+        assert!(percent_decode("%zz").is_err());
+    }
+}
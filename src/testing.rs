@@ -0,0 +1,130 @@
+//! deterministic move sequences for tests (`cfg(test)` or the `testing` feature): a script of
+//! moves, each accepted, rejected or decided by the Metropolis rule, replaces the random
+//! proposals so cache updates and acceptance can be checked for exact sequences.
+use crate::likelihood::Likelihood;
+use crate::multi_group_model::Move;
+use crate::rng::SamplerRng;
+use crate::{Float, HierarchicalModel, Proposal};
+
+/// how a scripted move is decided
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Accept,
+    Reject,
+    /// by the acceptance rule of `HierarchicalModel::step`, drawing from the sampler's rng
+    Metropolis,
+}
+
+/// a sequence of moves to run instead of random proposals.
+/// Node moves address nodes by their index among the members or non-members of the group,
+/// like the moves of the sampler.
+#[derive(Debug, Clone, Default)]
+pub struct ScriptedProposal {
+    script: Vec<(Move, Decision)>,
+}
+
+impl ScriptedProposal {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_group(mut self, group: usize, decision: Decision) -> Self {
+        self.script.push((Move::AddGroup { group }, decision));
+        self
+    }
+
+    pub fn remove_group(mut self, group: usize, decision: Decision) -> Self {
+        self.script.push((Move::RemoveGroup { group }, decision));
+        self
+    }
+
+    /// add the `idx`th non-member of `group` to it
+    pub fn add_node(mut self, group: usize, idx: usize, decision: Decision) -> Self {
+        let m = Move::AddNodeToGroup {
+            group,
+            node: 0,
+            idx,
+            old_state: 0,
+        };
+        self.script.push((m, decision));
+        self
+    }
+
+    /// remove the `idx`th member of `group` from it
+    pub fn remove_node(mut self, group: usize, idx: usize, decision: Decision) -> Self {
+        let m = Move::RemoveNodeFromGroup {
+            group,
+            node: 0,
+            idx,
+            old_state: 0,
+        };
+        self.script.push((m, decision));
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.script.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.script.is_empty()
+    }
+
+    /// run the script on `hcp`, returning the proposal of every move. Fails at the first move
+    /// that does not fit the model, see `MultiGroupModel::check_move`.
+    pub fn run<F: Float, L: Likelihood, R: SamplerRng>(
+        &self,
+        hcp: &mut HierarchicalModel<F, L, R>,
+    ) -> Result<Vec<Proposal>, String> {
+        self.script
+            .iter()
+            .map(|&(m, decision)| match decision {
+                Decision::Accept => hcp.replay_step(m, true),
+                Decision::Reject => hcp.replay_step(m, false),
+                Decision::Metropolis => hcp.propose(m),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::_example_model;
+
+    #[test]
+    fn scripted_moves() {
+        let mut hcp = _example_model(
+            b"initial_group_config: 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1\n\
+              initial_num_groups: 1\n\
+              max_num_groups: 4\n",
+        );
+        let initial = hcp.clone();
+        let script = ScriptedProposal::new()
+            .add_group(1, Decision::Accept)
+            .add_node(1, 0, Decision::Accept)
+            .add_node(1, 0, Decision::Accept)
+            .add_node(1, 0, Decision::Reject);
+        let proposals = script.run(&mut hcp).unwrap();
+        assert_eq!(proposals.len(), script.len());
+        // removing a non-member moves the last one into its place
+        assert_eq!(hcp.model().members(1), [0, 24]);
+        assert_eq!(hcp.hcg_pairs(), [299, 1]);
+        let connected = hcp.network().neighbors(0).contains(&24) as usize;
+        assert_eq!(hcp.hcg_edges()[1], connected);
+        assert!(!proposals[3].accepted);
+        assert!(hcp.recompute().is_consistent());
+
+        // undo everything by the Metropolis rule at infinite temperature
+        hcp.set_beta(0.0);
+        let proposals = ScriptedProposal::new()
+            .remove_node(1, 0, Decision::Metropolis)
+            .remove_node(1, 0, Decision::Metropolis)
+            .remove_group(1, Decision::Metropolis)
+            .run(&mut hcp)
+            .unwrap();
+        assert!(proposals.iter().all(|p| p.accepted));
+        assert_eq!(hcp.model().groups(), initial.model().groups());
+        assert_eq!(hcp.log_like(), initial.log_like());
+    }
+}
@@ -0,0 +1,143 @@
+//! snapshot thinning adapted to the integrated autocorrelation time of the log-likelihood,
+//! so that roughly one snapshot is taken per effective sample.
+use crate::checkpoint::{Decoder, Encoder};
+use std::io::{self, Read, Write};
+
+/// samples per estimate
+const WINDOW: usize = 4096;
+/// an estimate is trusted once the window is this many autocorrelation times long
+const MIN_WINDOWS_PER_TAU: f64 = 50.0;
+
+/// integrated autocorrelation time of `x` in units of its spacing, with Sokal's automatic
+/// window (sum up to lag `k` once `k ≥ 5 τ`). 1 for uncorrelated or constant series.
+pub fn integrated_time(x: &[f64]) -> f64 {
+    let n = x.len();
+    if n < 2 {
+        return 1.0;
+    }
+    let mean = x.iter().sum::<f64>() / n as f64;
+    let c0 = x.iter().map(|&v| (v - mean).powi(2)).sum::<f64>() / n as f64;
+    if c0 == 0.0 {
+        return 1.0;
+    }
+    let mut tau = 1.0;
+    for k in 1..n {
+        let ck = x[..n - k]
+            .iter()
+            .zip(&x[k..])
+            .map(|(&a, &b)| (a - mean) * (b - mean))
+            .sum::<f64>()
+            / n as f64;
+        tau += 2.0 * ck / c0;
+        if k as f64 >= 5.0 * tau {
+            break;
+        }
+    }
+    tau.max(1.0)
+}
+
+/// online estimate of the number of iterations per effective sample.
+/// The trace is recorded every `stride` iterations; the stride doubles while the trace is too
+/// correlated for a reliable estimate and halves while it is much finer than needed.
+#[derive(Debug, Clone)]
+pub struct AdaptiveThinning {
+    stride: u64,
+    samples: Vec<f64>,
+    interval: Option<u64>,
+}
+
+impl AdaptiveThinning {
+    pub fn new(initial_stride: u64) -> Self {
+        Self {
+            stride: initial_stride.max(1),
+            samples: Vec::with_capacity(WINDOW),
+            interval: None,
+        }
+    }
+
+    /// record the value `x` at `iteration`
+    pub fn observe(&mut self, iteration: u64, x: f64) {
+        if !iteration.is_multiple_of(self.stride) {
+            return;
+        }
+        self.samples.push(x);
+        if self.samples.len() < WINDOW {
+            return;
+        }
+
+        let tau = integrated_time(&self.samples);
+        if tau * MIN_WINDOWS_PER_TAU > WINDOW as f64 {
+            // keep every other sample and continue at twice the stride
+            self.stride *= 2;
+            let kept: Vec<f64> = self.samples.iter().step_by(2).copied().collect();
+            self.samples = kept;
+            return;
+        }
+        self.interval = Some((tau * self.stride as f64).ceil() as u64);
+        if tau < 2.0 && self.stride > 1 {
+            self.stride /= 2;
+        }
+        self.samples.clear();
+    }
+
+    /// iterations per effective sample, once the first estimate is available
+    pub fn interval(&self) -> Option<u64> {
+        self.interval
+    }
+
+    pub fn encode<W: Write>(&self, e: &mut Encoder<W>) -> io::Result<()> {
+        e.u64(self.stride)?;
+        e.f64s(&self.samples)?;
+        // intervals are at least 1, 0 stands for no estimate yet
+        e.u64(self.interval.unwrap_or(0))
+    }
+
+    pub fn decode<R: Read>(d: &mut Decoder<R>) -> io::Result<Self> {
+        let stride = d.u64()?.max(1);
+        let mut samples = d.f64s()?;
+        samples.reserve(WINDOW.saturating_sub(samples.len()));
+        let interval = Some(d.u64()?).filter(|&i| i > 0);
+        Ok(Self {
+            stride,
+            samples,
+            interval,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    /// AR(1) process with autocorrelation time (1 + phi) / (1 - phi)
+    fn ar1(phi: f64, n: usize) -> Vec<f64> {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut x = 0.0;
+        (0..n)
+            .map(|_| {
+                x = phi * x + rng.gen::<f64>() - 0.5;
+                x
+            })
+            .collect()
+    }
+
+    #[test]
+    fn ar1_time() {
+        let tau = integrated_time(&ar1(0.9, 50000));
+        assert!((tau - 19.0).abs() < 4.0, "{}", tau);
+        assert_eq!(integrated_time(&[1.0; 10]), 1.0);
+    }
+
+    #[test]
+    fn adaptive() {
+        let trace = ar1(0.99, 600000);
+        let mut thinning = AdaptiveThinning::new(1);
+        for (i, &x) in trace.iter().enumerate() {
+            thinning.observe(i as u64, x);
+        }
+        let interval = thinning.interval().unwrap();
+        assert!((100..=300).contains(&interval), "{}", interval);
+    }
+}
@@ -0,0 +1,886 @@
+//! Compact run outputs.
+//!
+//! Accepted move trace (text, one record per line): the initial configuration followed by
+//! every accepted move.
+//! ```text
+//! <num_groups> <max_groups>
+//! <groups of node 0> <groups of node 1> ...
+//! [order <node> <node> ...]
+//! <iteration> add_group <group>
+//! <iteration> remove_group <group>
+//! <iteration> add_node <group> <node> <idx>
+//! <iteration> remove_node <group> <node> <idx>
+//! ```
+//! The `order` line is only written for models with a permuted node order (`permute_nodes`).
+//!
+//! Proposal trace (binary, little endian): every proposed move with its log-likelihood
+//! difference and acceptance decision. A header of `b"HCPT"` and the `u64` seed is followed by
+//! fixed size records of
+//! `iteration: u64, kind: u8, group: u8, accepted: u8, node: u32, idx: u32, delta_log_like: f64`.
+//!
+//! Snapshot stream (JSON Lines): one object per snapshot,
+//! ```text
+//! {"iteration":..,"log_like":..,"num_groups":..,"group_sizes":[..],"config":[[groups of node 0],..]}
+//! ```
+//!
+//! Group hierarchy (CSV): the fitted parameters of a configuration, one row per group with
+//! its parent (empty for the root), number of members, and the edges, pairs and edge density
+//! of the pairs it is the highest common group of.
+//! ```text
+//! group,parent,size,edges,pairs,density
+//! ```
+//!
+//! Core and periphery (CSV): for every group but the root, its members with their degree,
+//! the number of their edges to other members and the fraction that is, most central first.
+//! ```text
+//! group,node,degree,inside,fraction
+//! ```
+use crate::likelihood::Likelihood;
+use crate::multi_group_model::{self, Groups, Move, MultiGroupModel, Node};
+use crate::rng::SamplerRng;
+use crate::{Float, HierarchicalModel, Proposal};
+use std::io::{self, BufRead, Read, Write};
+
+const PROPOSAL_MAGIC: &[u8; 4] = b"HCPT";
+const PROPOSAL_RECORD_LEN: usize = 8 + 1 + 1 + 1 + 4 + 4 + 8;
+
+fn parse<T: std::str::FromStr>(word: Option<&str>, line: &str) -> Result<T, String> {
+    word.and_then(|w| w.parse().ok())
+        .ok_or(format!("Malformed trace line: {}", line))
+}
+
+fn write_move<W: Write>(w: &mut W, iteration: u64, m: &Move) -> io::Result<()> {
+    match *m {
+        Move::AddGroup { group } => writeln!(w, "{} add_group {}", iteration, group),
+        Move::RemoveGroup { group } => writeln!(w, "{} remove_group {}", iteration, group),
+        Move::AddNodeToGroup {
+            group, node, idx, ..
+        } => writeln!(w, "{} add_node {} {} {}", iteration, group, node, idx),
+        Move::RemoveNodeFromGroup {
+            group, node, idx, ..
+        } => writeln!(w, "{} remove_node {} {} {}", iteration, group, node, idx),
+    }
+}
+
+/// parse a move line. `old_state` is not stored in the trace and is left as 0.
+fn parse_move(line: &str) -> Result<(u64, Move), String> {
+    let mut words = line.split_whitespace();
+    let iteration = parse(words.next(), line)?;
+    let kind = words.next().unwrap_or("");
+    let group = parse(words.next(), line)?;
+    let m = match kind {
+        "add_group" => Move::AddGroup { group },
+        "remove_group" => Move::RemoveGroup { group },
+        "add_node" => Move::AddNodeToGroup {
+            group,
+            node: parse(words.next(), line)?,
+            idx: parse(words.next(), line)?,
+            old_state: 0,
+        },
+        "remove_node" => Move::RemoveNodeFromGroup {
+            group,
+            node: parse(words.next(), line)?,
+            idx: parse(words.next(), line)?,
+            old_state: 0,
+        },
+        _ => return Err(format!("Malformed trace line: {}", line)),
+    };
+    Ok((iteration, m))
+}
+
+/// the node moved by `m`, if any
+pub fn moved_node(m: &Move) -> Option<usize> {
+    match *m {
+        Move::AddNodeToGroup { node, .. } | Move::RemoveNodeFromGroup { node, .. } => Some(node),
+        _ => None,
+    }
+}
+
+/// streams accepted moves to a writer
+pub struct MoveTraceWriter<W: Write> {
+    w: W,
+}
+
+impl<W: Write> MoveTraceWriter<W> {
+    /// write the header describing the initial state of `model`
+    pub fn new(mut w: W, model: &MultiGroupModel) -> io::Result<Self> {
+        writeln!(w, "{} {}", model.num_groups(), model.max_groups())?;
+        let groups: Vec<String> = model.groups().iter().map(|g| g.to_string()).collect();
+        writeln!(w, "{}", groups.join(" "))?;
+        let order = model.node_order();
+        if order.iter().enumerate().any(|(i, &u)| i != u as usize) {
+            let order: Vec<String> = order.iter().map(|u| u.to_string()).collect();
+            writeln!(w, "order {}", order.join(" "))?;
+        }
+        Ok(Self { w })
+    }
+
+    pub fn record(&mut self, iteration: u64, m: &Move) -> io::Result<()> {
+        write_move(&mut self.w, iteration, m)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.w.flush()
+    }
+
+    /// the underlying writer, e.g. to finish it
+    pub fn into_inner(self) -> W {
+        self.w
+    }
+}
+
+/// reconstruct the model state after all moves recorded up to and including `iteration`
+pub fn replay(src: impl BufRead, iteration: u64) -> Result<MultiGroupModel, String> {
+    let mut lines = src.lines();
+    // `None` at the end of the trace, read errors are errors
+    let mut next_line = || -> Result<Option<String>, String> {
+        lines.next().transpose().map_err(|e| e.to_string())
+    };
+    let truncated = || String::from("Truncated trace");
+
+    let header = next_line()?.ok_or_else(truncated)?;
+    let mut words = header.split_whitespace();
+    let num_groups: u32 = parse(words.next(), &header)?;
+    let max_groups: u32 = parse(words.next(), &header)?;
+    if !(1..=64).contains(&num_groups) || max_groups > 64 {
+        return Err(format!("Malformed trace line: {}", header));
+    }
+    let groups = next_line()?
+        .ok_or_else(truncated)?
+        .split_whitespace()
+        .map(|w| w.parse().or(Err(format!("not an integer: {}", w))))
+        .collect::<Result<Vec<Groups>, String>>()?;
+    let mut line = next_line()?;
+    let order = match line.as_deref().and_then(|l| l.strip_prefix("order ")) {
+        Some(order) => {
+            let order = order
+                .split_whitespace()
+                .map(|w| w.parse().or(Err(format!("not an integer: {}", w))))
+                .collect::<Result<Vec<Node>, String>>()?;
+            line = next_line()?;
+            order
+        }
+        _ => (0..groups.len() as Node).collect(),
+    };
+    if order.len() != groups.len() {
+        return Err(format!(
+            "node order has {} nodes, configuration {}",
+            order.len(),
+            groups.len()
+        ));
+    }
+    let mut sorted = order.clone();
+    sorted.sort_unstable();
+    if sorted.iter().enumerate().any(|(i, &u)| i != u as usize) {
+        return Err(String::from("node order is not a permutation"));
+    }
+    multi_group_model::validate_groups(&groups, num_groups, false)
+        .map_err(|e| format!("Initial configuration: {}", e))?;
+    let mut model = MultiGroupModel::with_groups_in_order(groups, num_groups, max_groups, order);
+
+    while let Some(l) = line {
+        let (i, m) = parse_move(&l)?;
+        if i > iteration {
+            break;
+        }
+        let done = model
+            .apply(m)
+            .map_err(|e| format!("Invalid move at iteration {}: {}", i, e))?;
+        if moved_node(&done) != moved_node(&m) {
+            return Err(format!("Trace diverged at iteration {}: {:?}", i, done));
+        }
+        line = next_line()?;
+    }
+    Ok(model)
+}
+
+/// streams every proposal to a writer in binary form
+pub struct ProposalTraceWriter<W: Write> {
+    w: W,
+}
+
+impl<W: Write> ProposalTraceWriter<W> {
+    pub fn new(mut w: W, seed: u64) -> io::Result<Self> {
+        w.write_all(PROPOSAL_MAGIC)?;
+        w.write_all(&seed.to_le_bytes())?;
+        Ok(Self { w })
+    }
+
+    pub fn record(&mut self, iteration: u64, p: &Proposal) -> io::Result<()> {
+        let (kind, group, node, idx) = match p.m {
+            Move::AddGroup { group } => (0u8, group, 0, 0),
+            Move::RemoveGroup { group } => (1, group, 0, 0),
+            Move::AddNodeToGroup {
+                group, node, idx, ..
+            } => (2, group, node, idx),
+            Move::RemoveNodeFromGroup {
+                group, node, idx, ..
+            } => (3, group, node, idx),
+        };
+        let mut buf = [0u8; PROPOSAL_RECORD_LEN];
+        buf[0..8].copy_from_slice(&iteration.to_le_bytes());
+        buf[8] = kind;
+        buf[9] = group as u8;
+        buf[10] = p.accepted as u8;
+        buf[11..15].copy_from_slice(&(node as u32).to_le_bytes());
+        buf[15..19].copy_from_slice(&(idx as u32).to_le_bytes());
+        buf[19..27].copy_from_slice(&p.delta_log_like.to_le_bytes());
+        self.w.write_all(&buf)
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.w.flush()
+    }
+
+    /// the underlying writer, e.g. to finish it
+    pub fn into_inner(self) -> W {
+        self.w
+    }
+}
+
+/// reads a binary proposal trace as `(iteration, proposal)` pairs
+pub struct ProposalTraceReader<R: Read> {
+    r: R,
+    pub seed: u64,
+}
+
+impl<R: Read> ProposalTraceReader<R> {
+    pub fn new(mut r: R) -> Result<Self, String> {
+        let mut header = [0u8; 12];
+        r.read_exact(&mut header).map_err(|e| e.to_string())?;
+        if &header[0..4] != PROPOSAL_MAGIC {
+            return Err(String::from("not a proposal trace"));
+        }
+        let seed = u64::from_le_bytes(header[4..12].try_into().unwrap());
+        Ok(Self { r, seed })
+    }
+}
+
+impl<R: Read> Iterator for ProposalTraceReader<R> {
+    type Item = Result<(u64, Proposal), String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut buf = [0u8; PROPOSAL_RECORD_LEN];
+        // the trace ends between records, a partial record is truncated
+        let mut len = 0;
+        while len < buf.len() {
+            match self.r.read(&mut buf[len..]) {
+                Ok(0) if len == 0 => return None,
+                Ok(0) => {
+                    return Some(Err(format!(
+                        "truncated proposal record: {} of {} bytes",
+                        len,
+                        buf.len()
+                    )))
+                }
+                Ok(n) => len += n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(e) => return Some(Err(e.to_string())),
+            }
+        }
+        let iteration = u64::from_le_bytes(buf[0..8].try_into().unwrap());
+        let group = buf[9] as usize;
+        let node = u32::from_le_bytes(buf[11..15].try_into().unwrap()) as usize;
+        let idx = u32::from_le_bytes(buf[15..19].try_into().unwrap()) as usize;
+        let m = match buf[8] {
+            0 => Move::AddGroup { group },
+            1 => Move::RemoveGroup { group },
+            2 => Move::AddNodeToGroup {
+                group,
+                node,
+                idx,
+                old_state: 0,
+            },
+            3 => Move::RemoveNodeFromGroup {
+                group,
+                node,
+                idx,
+                old_state: 0,
+            },
+            k => return Some(Err(format!("unknown move kind {}", k))),
+        };
+        Some(Ok((
+            iteration,
+            Proposal {
+                m,
+                delta_log_like: f64::from_le_bytes(buf[19..27].try_into().unwrap()),
+                accepted: buf[10] != 0,
+            },
+        )))
+    }
+}
+
+/// configuration and log-likelihood at one iteration, as stored in the snapshot stream
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub iteration: u64,
+    pub log_like: f64,
+    pub num_groups: u32,
+    pub groups: Vec<Groups>,
+}
+
+impl Snapshot {
+    pub fn new<F: Float, L: Likelihood, R: SamplerRng>(
+        iteration: u64,
+        hcp: &HierarchicalModel<F, L, R>,
+    ) -> Self {
+        Self {
+            iteration,
+            log_like: hcp.log_like(),
+            num_groups: hcp.model().num_groups() as u32,
+            groups: hcp.model().groups().to_vec(),
+        }
+    }
+}
+
+/// streams snapshots as JSON Lines, flushing after every line so the output can be followed
+/// while the run is in progress
+pub struct SnapshotJsonWriter<W: Write> {
+    w: W,
+}
+
+impl<W: Write> SnapshotJsonWriter<W> {
+    pub fn new(w: W) -> Self {
+        Self { w }
+    }
+
+    pub fn record<F: Float>(
+        &mut self,
+        iteration: u64,
+        hcp: &HierarchicalModel<F>,
+    ) -> io::Result<()> {
+        self.write(&Snapshot::new(iteration, hcp))
+    }
+
+    pub fn write(&mut self, snapshot: &Snapshot) -> io::Result<()> {
+        let list = |v: Vec<String>| format!("[{}]", v.join(","));
+        let in_group = |u: Groups, g: u32| (u >> g) & 1 != 0;
+        let config = snapshot
+            .groups
+            .iter()
+            .map(|&u| {
+                list(
+                    (0..snapshot.num_groups)
+                        .filter(|&g| in_group(u, g))
+                        .map(|g| g.to_string())
+                        .collect(),
+                )
+            })
+            .collect();
+        let group_sizes = (0..snapshot.num_groups)
+            .map(|g| {
+                let size = snapshot.groups.iter().filter(|&&u| in_group(u, g)).count();
+                size.to_string()
+            })
+            .collect();
+        writeln!(
+            self.w,
+            "{{\"iteration\":{},\"log_like\":{},\"num_groups\":{},\"group_sizes\":{},\"config\":{}}}",
+            snapshot.iteration,
+            snapshot.log_like,
+            snapshot.num_groups,
+            list(group_sizes),
+            list(config),
+        )?;
+        self.w.flush()
+    }
+    /// the underlying writer, e.g. to finish it
+    pub fn into_inner(self) -> W {
+        self.w
+    }
+}
+
+/// value of `key` in a snapshot line, up to the next `,` or `}` outside of brackets
+fn json_field<'a>(line: &'a str, key: &str) -> Result<&'a str, String> {
+    let start = line
+        .find(&format!("\"{}\":", key))
+        .ok_or(format!("Malformed snapshot line: {}", line))?
+        + key.len()
+        + 3;
+    let mut depth = 0;
+    for (i, c) in line[start..].char_indices() {
+        match c {
+            '[' => depth += 1,
+            ']' => depth -= 1,
+            ',' | '}' if depth == 0 => return Ok(&line[start..start + i]),
+            _ => {}
+        }
+    }
+    Err(format!("Malformed snapshot line: {}", line))
+}
+
+/// parse a line written by `SnapshotJsonWriter`
+fn parse_snapshot(line: &str) -> Result<Snapshot, String> {
+    let config = json_field(line, "config")?;
+    let groups = config
+        .strip_prefix('[')
+        .and_then(|c| c.strip_suffix(']'))
+        .ok_or(format!("Malformed snapshot line: {}", line))?
+        .split_terminator("],")
+        .map(|node| {
+            node.trim_matches(|c| c == '[' || c == ']')
+                .split_terminator(',')
+                .try_fold(0, |a: Groups, g| {
+                    let bit = (1 as Groups)
+                        .checked_shl(parse(Some(g), line)?)
+                        .ok_or(format!(
+                            "Group {} out of range in snapshot line: {}",
+                            g, line
+                        ))?;
+                    Ok(a | bit)
+                })
+        })
+        .collect::<Result<Vec<Groups>, String>>()?;
+    Ok(Snapshot {
+        iteration: parse(Some(json_field(line, "iteration")?), line)?,
+        log_like: parse(Some(json_field(line, "log_like")?), line)?,
+        num_groups: parse(Some(json_field(line, "num_groups")?), line)?,
+        groups,
+    })
+}
+
+/// reads the snapshots of a JSON Lines snapshot stream
+pub struct SnapshotJsonReader<R: BufRead> {
+    lines: io::Lines<R>,
+}
+
+impl<R: BufRead> SnapshotJsonReader<R> {
+    pub fn new(r: R) -> Self {
+        Self { lines: r.lines() }
+    }
+}
+
+impl<R: BufRead> Iterator for SnapshotJsonReader<R> {
+    type Item = Result<Snapshot, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let line = match self.lines.next()? {
+            Ok(line) => line,
+            Err(e) => return Some(Err(e.to_string())),
+        };
+        Some(parse_snapshot(&line))
+    }
+}
+
+/// write the group hierarchy of the current configuration of `hcp` as CSV
+pub fn write_hierarchy_csv<W: Write, F: Float, L: Likelihood, R: SamplerRng>(
+    mut w: W,
+    hcp: &HierarchicalModel<F, L, R>,
+) -> io::Result<()> {
+    writeln!(w, "group,parent,size,edges,pairs,density")?;
+    for (g, parent) in hcp.model().parent_groups().into_iter().enumerate() {
+        writeln!(
+            w,
+            "{},{},{},{},{},{}",
+            g,
+            parent.map_or(String::new(), |p| p.to_string()),
+            hcp.model().group_size(g),
+            hcp.hcg_edges()[g],
+            hcp.hcg_pairs()[g],
+            hcp.group_density(g)
+        )?;
+    }
+    w.flush()
+}
+
+/// write the group hierarchy of `hcp` as JSON for `d3.hierarchy`: nested groups with their
+/// size, edge and pair counts and density, each with its child groups and then its nodes
+/// (those whose deepest group it is, see `MultiGroupModel::deepest_groups`) as `children`.
+/// Nodes are leaves with `value` 1, so `.sum(d => d.value)` counts the nodes of every group.
+pub fn write_hierarchy_json<W: Write, F: Float, L: Likelihood, R: SamplerRng>(
+    mut w: W,
+    hcp: &HierarchicalModel<F, L, R>,
+) -> io::Result<()> {
+    let model = hcp.model();
+    let parents = model.parent_groups();
+    let mut nodes = vec![Vec::new(); parents.len()];
+    for (u, g) in model.deepest_groups().into_iter().enumerate() {
+        nodes[g].push(u as Node);
+    }
+
+    fn group<F: Float, L: Likelihood, R: SamplerRng>(
+        json: &mut String,
+        hcp: &HierarchicalModel<F, L, R>,
+        parents: &[Option<usize>],
+        nodes: &[Vec<Node>],
+        g: usize,
+    ) {
+        *json += &format!(
+            "{{\"name\":\"group {}\",\"group\":{},\"size\":{},\"edges\":{},\"pairs\":{},\"density\":{},\"children\":[",
+            g,
+            g,
+            hcp.model().group_size(g),
+            hcp.hcg_edges()[g],
+            hcp.hcg_pairs()[g],
+            hcp.group_density(g)
+        );
+        let mut first = true;
+        for c in (0..parents.len()).filter(|&c| parents[c] == Some(g)) {
+            if !std::mem::take(&mut first) {
+                json.push(',');
+            }
+            group(json, hcp, parents, nodes, c);
+        }
+        for &u in &nodes[g] {
+            if !std::mem::take(&mut first) {
+                json.push(',');
+            }
+            let name = match hcp.network().label(u) {
+                Some(label) => json_string(label),
+                None => format!("\"{}\"", u),
+            };
+            *json += &format!("{{\"name\":{},\"node\":{},\"value\":1}}", name, u);
+        }
+        json.push_str("]}");
+    }
+
+    let mut json = String::new();
+    group(&mut json, hcp, &parents, &nodes, 0);
+    writeln!(w, "{}", json)?;
+    w.flush()
+}
+
+/// write a node table for Gephi or Cytoscape: `Id`, `Label` (the node label, or the id), the
+/// degree, the deepest group (see `MultiGroupModel::deepest_groups`) and `level_1` to `level_<d>`,
+/// the groups on the path from the root to the deepest group, empty below it
+pub fn write_node_attributes_csv<W: Write, F: Float, L: Likelihood, R: SamplerRng>(
+    mut w: W,
+    hcp: &HierarchicalModel<F, L, R>,
+) -> io::Result<()> {
+    let (network, model) = (hcp.network(), hcp.model());
+    let parents = model.parent_groups();
+    let depths = model.group_depths();
+    let max_depth = depths.iter().copied().max().unwrap_or(0);
+    write!(w, "Id,Label,degree,deepest")?;
+    for level in 1..=max_depth {
+        write!(w, ",level_{}", level)?;
+    }
+    writeln!(w)?;
+    for (u, deepest) in model.deepest_groups().into_iter().enumerate() {
+        let u = u as Node;
+        let label = network.label(u).map_or(u.to_string(), csv_field);
+        write!(w, "{},{},{},{}", u, label, network.degree(u), deepest)?;
+        let mut path = vec![None; max_depth + 1];
+        let mut g = Some(deepest);
+        while let Some(h) = g {
+            path[depths[h]] = Some(h);
+            g = parents[h];
+        }
+        for g in &path[1..] {
+            write!(w, ",{}", g.map_or(String::new(), |g| g.to_string()))?;
+        }
+        writeln!(w)?;
+    }
+    w.flush()
+}
+
+/// `s` as a CSV field, quoted if needed
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
+
+/// `s` as a JSON string literal
+pub(crate) fn json_string(s: &str) -> String {
+    let mut json = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => json += "\\\"",
+            '\\' => json += "\\\\",
+            c if (c as u32) < 0x20 => json += &format!("\\u{:04x}", c as u32),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// write the members of each group by the fraction of their edges inside it, with their label,
+/// or the id if the network has none
+pub fn write_core_periphery_csv<W: Write, F: Float, L: Likelihood, R: SamplerRng>(
+    mut w: W,
+    hcp: &HierarchicalModel<F, L, R>,
+) -> io::Result<()> {
+    let (network, model) = (hcp.network(), hcp.model());
+    writeln!(w, "group,node,label,degree,inside,fraction")?;
+    for g in 1..model.num_groups() {
+        let mut rows: Vec<(Node, usize, usize)> = model
+            .members(g)
+            .iter()
+            .map(|&u| {
+                let inside = network
+                    .neighbors(u)
+                    .iter()
+                    .filter(|&&v| model.groups()[v as usize] >> g & 1 == 1)
+                    .count();
+                (u, network.degree(u), inside)
+            })
+            .collect();
+        let fraction = |&(_, degree, inside): &(Node, usize, usize)| match degree {
+            0 => 0.0,
+            _ => inside as f64 / degree as f64,
+        };
+        rows.sort_by(|a, b| fraction(b).total_cmp(&fraction(a)).then(a.0.cmp(&b.0)));
+        for row in &rows {
+            let label = network.label(row.0).map_or(row.0.to_string(), csv_field);
+            writeln!(
+                w,
+                "{},{},{},{},{},{}",
+                g,
+                row.0,
+                label,
+                row.1,
+                row.2,
+                fraction(row)
+            )?;
+        }
+    }
+    w.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network::NetworkData;
+    use crate::tests::{_example_model, _example_parameters};
+
+    #[test]
+    fn roundtrip() {
+        let mut model = MultiGroupModel::with_groups(vec![1, 3, 3, 5, 7], 3, 64);
+        let initial = model.clone();
+        let mut buf = Vec::new();
+        let mut trace = MoveTraceWriter::new(&mut buf, &model).unwrap();
+        let m1 = model.add_node_to_group_by_idx(1, 0);
+        trace.record(4, &m1).unwrap();
+        let after_first = model.groups().to_vec();
+        let m2 = model.add_group(3);
+        trace.record(9, &m2).unwrap();
+        trace.flush().unwrap();
+
+        assert_eq!(replay(&buf[..], 0).unwrap().groups(), initial.groups());
+        assert_eq!(replay(&buf[..], 4).unwrap().groups(), after_first);
+        let end = replay(&buf[..], u64::MAX).unwrap();
+        assert_eq!(end.groups(), model.groups());
+        assert_eq!(end.num_groups(), 4);
+
+        // moves address nodes by index, so replay needs the same node order
+        let mut model =
+            MultiGroupModel::with_groups_in_order(vec![1, 3, 1, 1], 2, 64, vec![3, 1, 0, 2]);
+        let mut buf = Vec::new();
+        let mut trace = MoveTraceWriter::new(&mut buf, &model).unwrap();
+        let m = model.add_node_to_group_by_idx(1, 0);
+        trace.record(1, &m).unwrap();
+        trace.flush().unwrap();
+        assert!(std::str::from_utf8(&buf)
+            .unwrap()
+            .contains("\norder 3 1 0 2\n"));
+        let end = replay(&buf[..], 1).unwrap();
+        assert_eq!(end.node_order(), [3, 1, 0, 2]);
+        assert_eq!(end.groups(), model.groups());
+
+        // a read error is not the end of the trace
+        buf.extend(b"\xff\n");
+        assert!(replay(&buf[..], u64::MAX).is_err());
+        assert!(replay(&b"1 64\n"[..], 0).is_err());
+    }
+
+    #[test]
+    fn invalid_trace() {
+        let replay = |trace: &str| replay(trace.as_bytes(), u64::MAX).err();
+        // moves that do not fit the configuration
+        assert_eq!(
+            replay("2 64\n1 3 1\n5 add_node 1 0 99\n").unwrap(),
+            "Invalid move at iteration 5: idx 99 beyond the 2 non-members of group 1"
+        );
+        assert_eq!(
+            replay("2 64\n1 3 1\n5 remove_group 7\n").unwrap(),
+            "Invalid move at iteration 5: group 7 is not between 1 and 1"
+        );
+        // headers that do not describe a configuration
+        assert_eq!(
+            replay("2 64\n1 9 1\n").unwrap(),
+            "Initial configuration: node 1: in group 3, beyond the 2 groups (9)"
+        );
+        assert!(replay("2 64\n2 3 1\n").is_some());
+        assert!(replay("0 64\n\n").is_some());
+        assert!(replay("65 64\n1 1\n").is_some());
+        assert!(replay("2 64\n1 3 1\norder 0 1 1\n").is_some());
+        assert!(replay("2 64\n1 3 1\n").is_none());
+    }
+
+    #[test]
+    fn proposal_roundtrip() {
+        let p = Proposal {
+            m: Move::RemoveNodeFromGroup {
+                group: 3,
+                node: 17,
+                idx: 2,
+                old_state: 0,
+            },
+            delta_log_like: -1.25,
+            accepted: true,
+        };
+        let mut buf = Vec::new();
+        let mut trace = ProposalTraceWriter::new(&mut buf, 23).unwrap();
+        trace.record(7, &p).unwrap();
+        trace.flush().unwrap();
+
+        let mut reader = ProposalTraceReader::new(&buf[..]).unwrap();
+        assert_eq!(reader.seed, 23);
+        assert_eq!(reader.next(), Some(Ok((7, p))));
+        assert_eq!(reader.next(), None);
+
+        // a record cut short is an error, not the end of the trace
+        let mut reader = ProposalTraceReader::new(&buf[..buf.len() - 1]).unwrap();
+        assert!(matches!(reader.next(), Some(Err(_))));
+    }
+
+    #[test]
+    fn snapshot_json() {
+        let hcp = _example_model(
+            b"initial_group_config: 9 3 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1\n\
+              initial_num_groups: 4\n",
+        );
+        let mut buf = Vec::new();
+        let mut trace = SnapshotJsonWriter::new(&mut buf);
+        trace.record(3, &hcp).unwrap();
+        trace.record(4, &hcp).unwrap();
+        let out = String::from_utf8(buf).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("{\"iteration\":3,\"log_like\":-"));
+        assert!(lines[0].contains("\"num_groups\":4,\"group_sizes\":[25,1,0,1],"));
+        assert!(lines[0].ends_with("\"config\":[[0,3],[0,1],[0],[0],[0],[0],[0],[0],[0],[0],[0],[0],[0],[0],[0],[0],[0],[0],[0],[0],[0],[0],[0],[0],[0]]}"));
+
+        let read: Vec<Snapshot> = SnapshotJsonReader::new(out.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(read, [Snapshot::new(3, &hcp), Snapshot::new(4, &hcp)]);
+
+        let bad = lines[0].replace("[[0,3],", "[[0,64],");
+        assert!(SnapshotJsonReader::new(bad.as_bytes())
+            .next()
+            .unwrap()
+            .is_err());
+    }
+
+    #[test]
+    fn hierarchy_csv() {
+        let hcp = _example_model(
+            b"initial_group_config: 9 3 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1\n\
+              initial_num_groups: 4\n",
+        );
+        let mut buf = Vec::new();
+        write_hierarchy_csv(&mut buf, &hcp).unwrap();
+        let out = String::from_utf8(buf).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0], "group,parent,size,edges,pairs,density");
+        assert!(lines[1].starts_with("0,,25,"));
+        assert_eq!(lines[2], "1,0,1,0,0,0");
+        assert_eq!(lines[4], "3,0,1,0,0,0");
+    }
+
+    #[test]
+    fn hierarchy_json() {
+        let hcp = _example_model(
+            b"initial_group_config: 9 3 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1\n\
+              initial_num_groups: 4\n",
+        );
+        let mut buf = Vec::new();
+        write_hierarchy_json(&mut buf, &hcp).unwrap();
+        let out = String::from_utf8(buf).unwrap();
+        let node = |u: u32| {
+            let name = hcp
+                .network()
+                .label(u)
+                .map_or(format!("\"{}\"", u), json_string);
+            format!("{{\"name\":{},\"node\":{},\"value\":1}}", name, u)
+        };
+        // group 2 is empty and under group 1, node 1 is in group 1, node 0 in group 3
+        assert!(out.starts_with("{\"name\":\"group 0\",\"group\":0,\"size\":25,"));
+        assert!(out.contains(&format!(
+            "\"children\":[{{\"name\":\"group 1\",\"group\":1,\"size\":1,\"edges\":0,\"pairs\":0,\"density\":0,\"children\":[{{\"name\":\"group 2\",\"group\":2,\"size\":0,\"edges\":0,\"pairs\":0,\"density\":0,\"children\":[]}},{}]}},{{\"name\":\"group 3\"",
+            node(1)
+        )));
+        assert!(out.contains(&format!("\"children\":[{}]}},{}", node(0), node(2))));
+        assert!(out.trim_end().ends_with(&format!("{}]}}", node(24))));
+        assert_eq!(out.matches("\"value\":1").count(), 25);
+        assert_eq!(json_string("a\"b\\c\n"), "\"a\\\"b\\\\c\\u000a\"");
+    }
+
+    #[test]
+    fn node_attributes_csv() {
+        // group 2 inside group 1, group 3 on its own
+        let hcp = _example_model(
+            b"initial_group_config: 7 7 3 9 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1\n\
+              initial_num_groups: 4\n",
+        );
+        let mut buf = Vec::new();
+        write_node_attributes_csv(&mut buf, &hcp).unwrap();
+        let out = String::from_utf8(buf).unwrap();
+        let rows: Vec<Vec<&str>> = out.lines().map(|l| l.split(',').collect()).collect();
+        assert_eq!(
+            rows[0],
+            ["Id", "Label", "degree", "deepest", "level_1", "level_2"]
+        );
+        assert_eq!(rows.len(), 26);
+        let groups = |u: usize| rows[u + 1][3..].to_vec();
+        assert_eq!(groups(0), ["2", "1", "2"]);
+        assert_eq!(groups(2), ["1", "1", ""]);
+        assert_eq!(groups(3), ["3", "3", ""]);
+        assert_eq!(groups(4), ["0", "", ""]);
+        assert_eq!(rows[5][2], hcp.network().degree(4).to_string());
+        assert_eq!(csv_field("a,\"b\""), "\"a,\"\"b\"\"\"");
+    }
+
+    #[test]
+    fn core_periphery_csv() {
+        let hcp = _example_model(
+            b"initial_group_config: 3 3 3 3 3 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1 1\n\
+              initial_num_groups: 2\n",
+        );
+        let mut buf = Vec::new();
+        write_core_periphery_csv(&mut buf, &hcp).unwrap();
+        let out = String::from_utf8(buf).unwrap();
+        let rows: Vec<Vec<&str>> = out
+            .lines()
+            .skip(1)
+            .map(|l| l.split(',').collect())
+            .collect();
+        assert_eq!(rows.len(), 5);
+        assert!(rows.iter().all(|r| r[0] == "1"));
+        let fractions: Vec<f64> = rows.iter().map(|r| r[5].parse().unwrap()).collect();
+        assert!(fractions.windows(2).all(|w| w[0] >= w[1]));
+        let node: u32 = rows[0][1].parse().unwrap();
+        let label = hcp
+            .network()
+            .label(node)
+            .map_or(node.to_string(), csv_field);
+        assert_eq!(rows[0][2], label);
+        let inside: usize = rows[0][4].parse().unwrap();
+        assert_eq!(rows[0][3], hcp.network().degree(node).to_string());
+        assert_eq!(
+            fractions[0],
+            inside as f64 / hcp.network().degree(node) as f64
+        );
+
+        // with the labels of a labeled network
+        let network = NetworkData::read_pajek(
+            &b"*Vertices 3\n1 \"a\"\n2 \"b,c\"\n3 \"d\"\n*Edges\n1 2\n2 3\n"[..],
+        )
+        .unwrap();
+        let params =
+            _example_parameters(b"initial_group_config: 3 3 1\ninitial_num_groups: 2\n").unwrap();
+        let hcp: HierarchicalModel =
+            HierarchicalModel::with_network(&params, network.into()).unwrap();
+        let mut buf = Vec::new();
+        write_core_periphery_csv(&mut buf, &hcp).unwrap();
+        let out = String::from_utf8(buf).unwrap();
+        assert_eq!(out.lines().nth(1), Some("1,0,a,1,1,1"));
+        assert_eq!(out.lines().nth(2), Some("1,1,\"b,c\",2,1,0.5"));
+    }
+}
@@ -0,0 +1,76 @@
+//! detection of stalled chains: a chain that accepts no move for many iterations is stuck in a
+//! configuration it cannot leave, or its acceptance probabilities are degenerate (e.g. NaN
+//! log-likelihoods from pathological inputs).
+use crate::checkpoint::{Decoder, Encoder};
+use std::io::{self, Read, Write};
+
+/// counts iterations since the last accepted move
+#[derive(Debug, Clone)]
+pub struct Watchdog {
+    window: u64,
+    /// iteration of the last accepted move or of the last alarm
+    last: u64,
+}
+
+impl Watchdog {
+    /// alarm after `window` iterations without an accepted move, counted from `start`
+    pub fn new(window: u64, start: u64) -> Self {
+        Self {
+            window: window.max(1),
+            last: start,
+        }
+    }
+
+    pub fn window(&self) -> u64 {
+        self.window
+    }
+
+    /// observe `iteration`; true once every `window` iterations without an accepted move
+    pub fn observe(&mut self, iteration: u64, accepted: bool) -> bool {
+        if accepted {
+            self.last = iteration;
+            return false;
+        }
+        if iteration - self.last >= self.window {
+            self.last = iteration;
+            return true;
+        }
+        false
+    }
+
+    pub fn encode<W: Write>(&self, e: &mut Encoder<W>) -> io::Result<()> {
+        e.u64(self.window)?;
+        e.u64(self.last)
+    }
+
+    pub fn decode<R: Read>(d: &mut Decoder<R>) -> io::Result<Self> {
+        Ok(Self {
+            window: d.u64()?.max(1),
+            last: d.u64()?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alarms() {
+        let mut watchdog = Watchdog::new(10, 0);
+        let alarms: Vec<u64> = (1..=35).filter(|&i| watchdog.observe(i, i == 5)).collect();
+        assert_eq!(alarms, [15, 25, 35]);
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut watchdog = Watchdog::new(10, 0);
+        assert!(!watchdog.observe(5, true));
+        let mut e = Encoder::new(Vec::new()).unwrap();
+        watchdog.encode(&mut e).unwrap();
+        let buf = e.into_inner();
+        let mut restored = Watchdog::decode(&mut Decoder::new(&buf[..]).unwrap()).unwrap();
+        let alarms: Vec<u64> = (6..=35).filter(|&i| restored.observe(i, false)).collect();
+        assert_eq!(alarms, [15, 25, 35]);
+    }
+}
@@ -0,0 +1,75 @@
+//! a run resumed from a checkpoint writes the same files as an uninterrupted run
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn run(dir: &Path, max_itr: u64, resume: bool, extra: &str) {
+    let gml = Path::new(env!("CARGO_MANIFEST_DIR")).join("examples/clique_cp.gml");
+    let parameters = dir.join("parameters.txt");
+    fs::write(
+        &parameters,
+        format!(
+            "gml_path: {}\nmax_itr: {}\nseed: 7\nsave_directory: {}\nsaved_data_name: run\n\
+             checkpoint_interval: 2000\nsave_best: true\n{}",
+            gml.display(),
+            max_itr,
+            dir.display(),
+            extra
+        ),
+    )
+    .unwrap();
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_hcp-rs"));
+    cmd.arg(&parameters);
+    if resume {
+        cmd.arg("--resume");
+    }
+    let output = cmd.output().unwrap();
+    assert!(output.status.success(), "{:?}", output);
+}
+
+fn output_files(dir: &Path) -> Vec<(PathBuf, Vec<u8>)> {
+    let mut files: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|e| e.unwrap().path())
+        .filter(|p| p.file_name().unwrap() != "parameters.txt")
+        .map(|p| (PathBuf::from(p.file_name().unwrap()), fs::read(&p).unwrap()))
+        .collect();
+    files.sort();
+    files
+}
+
+/// run for 7000 iterations in one go, and interrupted after the checkpoint at iteration 4000
+/// and resumed from it, and compare the outputs
+fn compare_resumed(name: &str, extra: &str) {
+    let base = std::env::temp_dir().join(format!("hcp_rs_{}_{}", name, std::process::id()));
+    let (whole, resumed) = (base.join("whole"), base.join("resumed"));
+    fs::create_dir_all(&whole).unwrap();
+    fs::create_dir_all(&resumed).unwrap();
+
+    run(&whole, 7000, false, extra);
+    run(&resumed, 5000, false, extra);
+    run(&resumed, 7000, true, extra);
+
+    let (a, b) = (output_files(&whole), output_files(&resumed));
+    for file in ["run_custom_num_groups.txt", "run_best.txt"] {
+        assert!(a
+            .iter()
+            .any(|(p, data)| p == Path::new(file) && !data.is_empty()));
+    }
+    assert_eq!(a, b);
+    fs::remove_dir_all(base).unwrap();
+}
+
+#[test]
+fn resume_is_deterministic() {
+    compare_resumed("checkpoint", "custom_stats: num_groups 500\n");
+}
+
+#[test]
+fn resume_keeps_watchdog() {
+    // the reheat after the stall alarm at iteration 3964 spans the checkpoint at 4000
+    compare_resumed(
+        "checkpoint_watchdog",
+        "custom_stats: num_groups 500 log_like 10\nstall_window: 40\nstall_action: reheat 0.05\n",
+    );
+}